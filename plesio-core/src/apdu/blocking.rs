@@ -0,0 +1,121 @@
+use core::{
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+#[cfg(feature = "alloc")]
+use crate::apdu::owned::OwnedResponse;
+use crate::apdu::{
+    command::ApduCommand,
+    iso_7816::{
+        operation::{Iso7816Operation, Iso7816StreamingOperation},
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    response::ApduResponse,
+    sink::ResponseSink,
+    transport::{ApduTransport, TransportError},
+};
+
+/// The synchronous counterpart of [`ApduTransport`], for backends such as
+/// PC/SC whose calls block until the card has replied.
+pub trait BlockingTransport {
+    type TransportError: TransportError;
+
+    /// Sends `command` and writes the response data followed by the two
+    /// status bytes to the start of `reply_buffer`.
+    ///
+    /// The expected response length is `reply_buffer.len() - 2`.
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, Self::TransportError>;
+
+    fn max_payload_size(&self) -> usize;
+}
+
+/// Exposes a [`BlockingTransport`] as an [`ApduTransport`] whose futures are
+/// already complete when returned.
+pub struct BlockingAdapter<T: BlockingTransport> {
+    transport: T,
+}
+
+impl<T: BlockingTransport> BlockingAdapter<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+}
+
+impl<T: BlockingTransport> ApduTransport for BlockingAdapter<T> {
+    type TransportError = T::TransportError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, Self::TransportError>> {
+        core::future::ready(self.transport.execute(command, reply_buffer))
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.transport.max_payload_size()
+    }
+}
+
+/// Drives `future` to completion on the current thread without an executor.
+///
+/// This is intended for futures that complete immediately, such as those of
+/// a [`BlockingAdapter`]. A future that is pending is polled again in a busy
+/// loop, so one that relies on being woken will spin forever.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+
+        core::hint::spin_loop();
+    }
+}
+
+impl<T: BlockingTransport> Iso7816Transport<BlockingAdapter<T>> {
+    /// Blocking version of [`Iso7816Transport::execute`].
+    pub fn execute_blocking<'a, O: Iso7816Operation<'a>>(
+        &mut self,
+        operation: O,
+    ) -> Result<O::Result, Iso7816TransportError<T::TransportError>> {
+        block_on(self.execute(operation))
+    }
+
+    /// Blocking version of [`Iso7816Transport::execute_streaming`].
+    pub fn execute_streaming_blocking<'s, O: Iso7816StreamingOperation, S: ResponseSink>(
+        &mut self,
+        operation: O,
+        sink: &'s mut S,
+    ) -> Result<O::Result<'s>, Iso7816TransportError<T::TransportError>> {
+        block_on(self.execute_streaming(operation, sink))
+    }
+
+    /// Blocking version of [`Iso7816Transport::execute_owned`].
+    #[cfg(feature = "alloc")]
+    pub fn execute_owned_blocking<O: Iso7816StreamingOperation>(
+        &mut self,
+        operation: O,
+    ) -> Result<OwnedResponse, Iso7816TransportError<T::TransportError>> {
+        block_on(self.execute_owned(operation))
+    }
+}
@@ -1,3 +1,10 @@
 pub trait ApduClass {
     fn to_u8(&self) -> u8;
 }
+
+/// A raw class byte, for commands relayed or replayed as they were encoded.
+impl ApduClass for u8 {
+    fn to_u8(&self) -> u8 {
+        *self
+    }
+}
@@ -0,0 +1,454 @@
+//! Offline data authentication as EMV Book 2 has it: recovering the issuer
+//! public key with a certification authority key, then the ICC public key
+//! with the issuer's, and verifying the signed static data of SDA, the
+//! INTERNAL AUTHENTICATE signature of DDA or the GENERATE AC signature of
+//! CDA. The RSA and SHA-1 arithmetic is left to an [`OdaCrypto`].
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    emv::{
+        data::{EmvData, digits},
+        element::tag,
+        object::{ApplicationCryptogram, Record},
+    },
+    iso_7816::tlv::ber::BerTlv,
+};
+
+/// The DDOL a terminal uses when the card has none: the unpredictable
+/// number.
+pub const DEFAULT_DDOL: &[u8] = &[0x9F, 0x37, 0x04];
+
+const HEADER: u8 = 0x6A;
+const TRAILER: u8 = 0xBC;
+const SHA_1: u8 = 0x01;
+const RSA: u8 = 0x01;
+const HASH_LENGTH: usize = 20;
+
+/// The public key arithmetic offline data authentication needs.
+pub trait OdaCrypto {
+    /// The RSA public key operation: `data` raised to `exponent` modulo
+    /// `modulus`, as many bytes long as the modulus. Returns `None` if
+    /// `data` is not less than the modulus.
+    fn rsa_recover(&self, modulus: &[u8], exponent: &[u8], data: &[u8]) -> Option<Vec<u8>>;
+
+    fn sha1(&self, data: &[u8]) -> [u8; 20];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OdaError {
+    /// A data element authentication needs is missing.
+    Missing(u32),
+    /// There is no CA public key with the card's RID and index.
+    UnknownCaKey,
+    /// A certificate or signature does not recover to the expected format.
+    Malformed,
+    /// A certificate or signature's hash does not match the data it
+    /// covers.
+    HashMismatch,
+    /// A certificate expired before the transaction date.
+    Expired,
+    /// A certificate is for another PAN.
+    PanMismatch,
+    /// The CDA signature covers another cryptogram than the card returned.
+    CryptogramMismatch,
+}
+
+impl core::fmt::Display for OdaError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Missing(tag) => write!(f, "data element {tag:02X} missing"),
+            Self::UnknownCaKey => write!(f, "unknown certification authority public key"),
+            Self::Malformed => write!(f, "malformed certificate or signature"),
+            Self::HashMismatch => write!(f, "certificate or signature hash mismatch"),
+            Self::Expired => write!(f, "certificate expired"),
+            Self::PanMismatch => write!(f, "certificate issued for another PAN"),
+            Self::CryptogramMismatch => write!(f, "signature covers another cryptogram"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OdaError {}
+
+/// An RSA public key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    pub modulus: Vec<u8>,
+    pub exponent: Vec<u8>,
+}
+
+/// A certification authority public key, named by the RID of the payment
+/// system and an index the card gives in `8F`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaPublicKey {
+    pub rid: [u8; 5],
+    pub index: u8,
+    pub key: PublicKey,
+}
+
+impl CaPublicKey {
+    pub fn new(rid: [u8; 5], index: u8, modulus: &[u8], exponent: &[u8]) -> Self {
+        Self {
+            rid,
+            index,
+            key: PublicKey {
+                modulus: modulus.to_vec(),
+                exponent: exponent.to_vec(),
+            },
+        }
+    }
+
+    /// The checksum payment systems publish with their keys, to check a
+    /// key was copied correctly.
+    pub fn checksum(&self, crypto: &impl OdaCrypto) -> [u8; 20] {
+        let mut data = self.rid.to_vec();
+        data.push(self.index);
+        data.extend_from_slice(&self.key.modulus);
+        data.extend_from_slice(&self.key.exponent);
+        crypto.sha1(&data)
+    }
+}
+
+/// The CA public keys a terminal trusts.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CaKeyStore {
+    keys: Vec<CaPublicKey>,
+}
+
+impl CaKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_key(mut self, key: CaPublicKey) -> Self {
+        self.insert(key);
+        self
+    }
+
+    /// Adds `key`, replacing any key with the same RID and index.
+    pub fn insert(&mut self, key: CaPublicKey) {
+        self.keys
+            .retain(|known| (known.rid, known.index) != (key.rid, key.index));
+        self.keys.push(key);
+    }
+
+    pub fn find(&self, rid: &[u8], index: u8) -> Option<&CaPublicKey> {
+        self.keys
+            .iter()
+            .find(|key| key.rid == rid && key.index == index)
+    }
+}
+
+/// The static data to be authenticated: the records offline data
+/// authentication covers, then the AIP if the static data authentication
+/// tag list asks for it. Records of files 1 to 10 count without their
+/// `70` tag and length, those of files 11 to 30 whole.
+pub fn static_data(records: &[Record], data: &EmvData) -> Result<Vec<u8>, OdaError> {
+    let mut static_data = Vec::new();
+
+    for record in records.iter().filter(|record| record.authenticated) {
+        match record.sfi {
+            1..=10 => {
+                let (template, _) = BerTlv::next(&record.data)
+                    .filter(|(object, _)| object.tag() == tag::RECORD_TEMPLATE)
+                    .ok_or(OdaError::Malformed)?;
+                static_data.extend_from_slice(template.value());
+            }
+            _ => static_data.extend_from_slice(&record.data),
+        }
+    }
+
+    match data.get(tag::STATIC_DATA_AUTHENTICATION_TAG_LIST) {
+        None | Some([]) => {}
+        Some([0x82]) => static_data.extend_from_slice(
+            data.get(tag::APPLICATION_INTERCHANGE_PROFILE)
+                .ok_or(OdaError::Missing(tag::APPLICATION_INTERCHANGE_PROFILE))?,
+        ),
+        Some(_) => return Err(OdaError::Malformed),
+    }
+
+    Ok(static_data)
+}
+
+/// The year of a two-digit BCD year, taking `50` to `99` as the 1900s.
+fn year(bcd: u8) -> u16 {
+    let year = (bcd >> 4) as u16 * 10 + (bcd & 0x0F) as u16;
+    match year {
+        0..50 => 2000 + year,
+        _ => 1900 + year,
+    }
+}
+
+/// The recovered data of a certificate or signature.
+struct Recovered {
+    data: Vec<u8>,
+}
+
+impl Recovered {
+    /// Recovers `data` with `key`, and checks its header, trailer, format
+    /// and algorithm at `algorithm`.
+    fn new(
+        crypto: &impl OdaCrypto,
+        key: &PublicKey,
+        data: &[u8],
+        format: u8,
+        algorithm: usize,
+    ) -> Result<Self, OdaError> {
+        if data.len() != key.modulus.len() || data.len() < algorithm + HASH_LENGTH + 2 {
+            return Err(OdaError::Malformed);
+        }
+
+        let data = crypto
+            .rsa_recover(&key.modulus, &key.exponent, data)
+            .filter(|recovered| recovered.len() == key.modulus.len())
+            .ok_or(OdaError::Malformed)?;
+
+        let valid = data[0] == HEADER
+            && data[data.len() - 1] == TRAILER
+            && data[1] == format
+            && data[algorithm] == SHA_1;
+
+        match valid {
+            true => Ok(Self { data }),
+            false => Err(OdaError::Malformed),
+        }
+    }
+
+    /// What the hash covers of the recovered data, from the format to the
+    /// hash.
+    fn signed(&self) -> &[u8] {
+        &self.data[1..self.data.len() - 1 - HASH_LENGTH]
+    }
+
+    /// Checks the hash over the signed part of the recovered data and then
+    /// `parts`.
+    fn check_hash(&self, crypto: &impl OdaCrypto, parts: &[&[u8]]) -> Result<(), OdaError> {
+        let mut input = self.signed().to_vec();
+        for part in parts {
+            input.extend_from_slice(part);
+        }
+
+        let hash = &self.data[self.data.len() - 1 - HASH_LENGTH..self.data.len() - 1];
+        match crypto.sha1(&input)[..] == *hash {
+            true => Ok(()),
+            false => Err(OdaError::HashMismatch),
+        }
+    }
+
+    /// A public key whose leftmost digits start at `offset`, with its
+    /// length and exponent length just before them.
+    fn public_key(
+        &self,
+        offset: usize,
+        remainder: Option<&[u8]>,
+        exponent: &[u8],
+    ) -> Result<PublicKey, OdaError> {
+        let length = self.data[offset - 2] as usize;
+        let leftmost = self.signed().get(offset - 1..).ok_or(OdaError::Malformed)?;
+
+        let modulus = match length.checked_sub(leftmost.len()) {
+            None | Some(0) => leftmost[..length].to_vec(),
+            Some(missing) => {
+                let remainder = remainder.filter(|remainder| remainder.len() == missing);
+                [leftmost, remainder.ok_or(OdaError::Malformed)?].concat()
+            }
+        };
+
+        if self.data[offset - 1] as usize != exponent.len() || self.data[offset - 3] != RSA {
+            return Err(OdaError::Malformed);
+        }
+
+        Ok(PublicKey {
+            modulus,
+            exponent: exponent.to_vec(),
+        })
+    }
+
+    /// Checks the expiration date, `MMYY` at `offset`, against the
+    /// transaction date `YYMMDD`.
+    fn check_expiration(&self, offset: usize, transaction_date: &[u8; 3]) -> Result<(), OdaError> {
+        let [month, expiry_year] = [self.data[offset], self.data[offset + 1]];
+        let expiry = (year(expiry_year), month);
+        let today = (year(transaction_date[0]), transaction_date[1]);
+
+        match expiry >= today {
+            true => Ok(()),
+            false => Err(OdaError::Expired),
+        }
+    }
+
+    /// The dynamic data of a signed dynamic application data block.
+    fn dynamic_data(&self) -> Result<&[u8], OdaError> {
+        let length = self.data[3] as usize;
+        self.signed().get(3..3 + length).ok_or(OdaError::Malformed)
+    }
+}
+
+fn get(data: &EmvData, tag: u32) -> Result<&[u8], OdaError> {
+    data.get(tag).ok_or(OdaError::Missing(tag))
+}
+
+/// Verifies a card's certificates and signatures with the CA keys a
+/// terminal trusts.
+pub struct Verifier<'a, C: OdaCrypto> {
+    crypto: &'a C,
+    ca_keys: &'a CaKeyStore,
+    transaction_date: [u8; 3],
+}
+
+impl<'a, C: OdaCrypto> Verifier<'a, C> {
+    /// `transaction_date` is `YYMMDD` in BCD, as in `9A`.
+    pub fn new(crypto: &'a C, ca_keys: &'a CaKeyStore, transaction_date: [u8; 3]) -> Self {
+        Self {
+            crypto,
+            ca_keys,
+            transaction_date,
+        }
+    }
+
+    /// Recovers the issuer public key from its certificate `90` with the
+    /// CA key the RID of `aid` and the index `8F` name.
+    pub fn issuer_key(&self, aid: &[u8], data: &EmvData) -> Result<PublicKey, OdaError> {
+        let index = *get(data, tag::CA_PUBLIC_KEY_INDEX)?
+            .first()
+            .ok_or(OdaError::Malformed)?;
+        let ca_key = aid
+            .get(..5)
+            .and_then(|rid| self.ca_keys.find(rid, index))
+            .ok_or(OdaError::UnknownCaKey)?;
+
+        let certificate = get(data, tag::ISSUER_PUBLIC_KEY_CERTIFICATE)?;
+        let exponent = get(data, tag::ISSUER_PUBLIC_KEY_EXPONENT)?;
+        let remainder = data.get(tag::ISSUER_PUBLIC_KEY_REMAINDER);
+
+        let recovered = Recovered::new(self.crypto, &ca_key.key, certificate, 0x02, 11)?;
+        recovered.check_hash(self.crypto, &[remainder.unwrap_or_default(), exponent])?;
+
+        let pan = data.pan().ok_or(OdaError::Missing(tag::PAN))?;
+        let issuer = digits(&recovered.data[2..6]);
+        if issuer.len() < 3 || !pan.starts_with(&issuer) {
+            return Err(OdaError::PanMismatch);
+        }
+
+        recovered.check_expiration(6, &self.transaction_date)?;
+        recovered.public_key(15, remainder, exponent)
+    }
+
+    /// Recovers the ICC public key from its certificate `9F46` with the
+    /// issuer key. The certificate also signs the static data.
+    pub fn icc_key(
+        &self,
+        issuer: &PublicKey,
+        data: &EmvData,
+        static_data: &[u8],
+    ) -> Result<PublicKey, OdaError> {
+        let certificate = get(data, tag::ICC_PUBLIC_KEY_CERTIFICATE)?;
+        let exponent = get(data, tag::ICC_PUBLIC_KEY_EXPONENT)?;
+        let remainder = data.get(tag::ICC_PUBLIC_KEY_REMAINDER);
+
+        let recovered = Recovered::new(self.crypto, issuer, certificate, 0x04, 17)?;
+        recovered.check_hash(
+            self.crypto,
+            &[remainder.unwrap_or_default(), exponent, static_data],
+        )?;
+
+        let pan = data.pan().ok_or(OdaError::Missing(tag::PAN))?;
+        if digits(&recovered.data[2..12]) != pan {
+            return Err(OdaError::PanMismatch);
+        }
+
+        recovered.check_expiration(12, &self.transaction_date)?;
+        recovered.public_key(21, remainder, exponent)
+    }
+
+    /// Verifies the signed static application data `93` of SDA, and
+    /// returns the data authentication code it holds.
+    pub fn verify_sda(
+        &self,
+        issuer: &PublicKey,
+        data: &EmvData,
+        static_data: &[u8],
+    ) -> Result<[u8; 2], OdaError> {
+        let signed = get(data, tag::SIGNED_STATIC_APPLICATION_DATA)?;
+
+        let recovered = Recovered::new(self.crypto, issuer, signed, 0x03, 2)?;
+        recovered.check_hash(self.crypto, &[static_data])?;
+
+        Ok([recovered.data[3], recovered.data[4]])
+    }
+
+    /// Verifies the signed dynamic application data INTERNAL AUTHENTICATE
+    /// returns for DDA over the filled DDOL, and returns the ICC dynamic
+    /// number.
+    pub fn verify_dda(
+        &self,
+        icc: &PublicKey,
+        signed: &[u8],
+        ddol_data: &[u8],
+    ) -> Result<Vec<u8>, OdaError> {
+        let recovered = Recovered::new(self.crypto, icc, signed, 0x05, 2)?;
+        recovered.check_hash(self.crypto, &[ddol_data])?;
+
+        let (&length, rest) = recovered
+            .dynamic_data()?
+            .split_first()
+            .ok_or(OdaError::Malformed)?;
+        rest.get(..length as usize)
+            .map(<[u8]>::to_vec)
+            .ok_or(OdaError::Malformed)
+    }
+
+    /// Verifies the CDA signature of a GENERATE AC response, and returns
+    /// the cryptogram it signs. `pdol_data` and `cdol_data` are what the
+    /// terminal sent with GET PROCESSING OPTIONS and GENERATE AC.
+    pub fn verify_cda(
+        &self,
+        icc: &PublicKey,
+        cryptogram: &ApplicationCryptogram,
+        unpredictable_number: &[u8; 4],
+        pdol_data: &[u8],
+        cdol_data: &[u8],
+    ) -> Result<[u8; 8], OdaError> {
+        let signed = cryptogram
+            .signed_dynamic_data
+            .as_deref()
+            .ok_or(OdaError::Missing(tag::SIGNED_DYNAMIC_APPLICATION_DATA))?;
+
+        let recovered = Recovered::new(self.crypto, icc, signed, 0x05, 2)?;
+        recovered.check_hash(self.crypto, &[unpredictable_number])?;
+
+        let (&length, rest) = recovered
+            .dynamic_data()?
+            .split_first()
+            .ok_or(OdaError::Malformed)?;
+        let rest = rest.get(length as usize..).ok_or(OdaError::Malformed)?;
+        let [cid, rest @ ..] = rest else {
+            return Err(OdaError::Malformed);
+        };
+        let (ac, rest) = rest.split_first_chunk::<8>().ok_or(OdaError::Malformed)?;
+        let hash = rest.get(..HASH_LENGTH).ok_or(OdaError::Malformed)?;
+
+        if *cid != cryptogram.cid {
+            return Err(OdaError::CryptogramMismatch);
+        }
+
+        let (response, _) = BerTlv::next(&cryptogram.response)
+            .filter(|(object, _)| object.tag() == tag::RESPONSE_FORMAT_2)
+            .ok_or(OdaError::Malformed)?;
+
+        let mut transaction_data = [pdol_data, cdol_data].concat();
+        for object in response.children() {
+            if object.tag() != tag::SIGNED_DYNAMIC_APPLICATION_DATA {
+                object
+                    .write(&mut transaction_data)
+                    .expect("vector sink is unbounded");
+            }
+        }
+
+        match self.crypto.sha1(&transaction_data)[..] == *hash {
+            true => Ok(*ac),
+            false => Err(OdaError::HashMismatch),
+        }
+    }
+}
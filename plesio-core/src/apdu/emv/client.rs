@@ -0,0 +1,320 @@
+//! A client for EMV payment applications, running the commands of a
+//! transaction up to the first GENERATE AC.
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    emv::{
+        PPSE, PSE,
+        data::EmvData,
+        dol::TerminalData,
+        element::tag,
+        object::{
+            ApplicationCryptogram, ApplicationEntry, CryptogramType, Fci, ProcessingOptions, Record,
+        },
+        operation::{GenerateAc, GetData, GetProcessingOptions, InternalAuthenticate},
+    },
+    iso_7816::{
+        operation::{
+            Iso7816StreamingOperation,
+            read_record::ReadRecord,
+            select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        },
+        status,
+        tlv::ber::BerTlv,
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::ApduTransport,
+};
+
+#[derive(Debug)]
+pub enum EmvError<E> {
+    Transport(Iso7816TransportError<E>),
+    Rejected(ApduStatus),
+    /// The application, file or record does not exist.
+    NotFound,
+    /// The card will not run the command in this state, such as GET
+    /// PROCESSING OPTIONS with terminal data it refuses.
+    ConditionsNotSatisfied,
+    MalformedResponse,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for EmvError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Rejected(status) => write!(f, "card rejected the command: {status}"),
+            Self::NotFound => write!(f, "application, file or record not found"),
+            Self::ConditionsNotSatisfied => write!(f, "conditions of use not satisfied"),
+            Self::MalformedResponse => write!(f, "malformed EMV response"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for EmvError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E> From<Iso7816TransportError<E>> for EmvError<E> {
+    fn from(e: Iso7816TransportError<E>) -> Self {
+        Self::Transport(e)
+    }
+}
+
+impl<E> From<ApduResponse<'_>> for EmvError<E> {
+    fn from(response: ApduResponse<'_>) -> Self {
+        match response.status() {
+            status::FILE_NOT_FOUND | status::RECORD_NOT_FOUND => Self::NotFound,
+            status::CONDITIONS_OF_USE_NOT_SATISFIED => Self::ConditionsNotSatisfied,
+            status => Self::Rejected(status),
+        }
+    }
+}
+
+/// An application read up to its first GENERATE AC.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Application {
+    pub fci: Fci,
+    /// The PDOL as filled for GET PROCESSING OPTIONS.
+    pub pdol_data: Vec<u8>,
+    pub processing_options: ProcessingOptions,
+    pub records: Vec<Record>,
+    /// Every data element of the FCI, the processing options and the
+    /// records.
+    pub data: EmvData,
+}
+
+/// An EMV card, contact or contactless.
+pub struct Emv<T: ApduTransport> {
+    transport: Iso7816Transport<T>,
+}
+
+impl<T: ApduTransport> Emv<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport: Iso7816Transport::new(transport),
+        }
+    }
+
+    pub fn inner(&self) -> &Iso7816Transport<T> {
+        &self.transport
+    }
+
+    pub fn inner_mut(&mut self) -> &mut Iso7816Transport<T> {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport.into_inner()
+    }
+
+    /// Runs an operation and returns its response data.
+    async fn fetch<O>(&mut self, operation: O) -> Result<Vec<u8>, EmvError<T::TransportError>>
+    where
+        O: for<'s> Iso7816StreamingOperation<Result<'s> = Result<&'s [u8], ApduResponse<'s>>>,
+    {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        Ok(response)
+    }
+
+    /// Selects the PSE, PPSE or application called `name` and returns its
+    /// FCI.
+    pub async fn select(&mut self, name: &[u8]) -> Result<Fci, EmvError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(
+                Iso7816Select::new(
+                    Iso7816SelectResolution::ByApplicationIdentifier(name),
+                    &mut [],
+                ),
+                &mut response,
+            )
+            .await??;
+
+        Fci::parse(&response).ok_or(EmvError::MalformedResponse)
+    }
+
+    /// Lists the applications of a contact card from the records of its
+    /// PSE directory, highest priority first.
+    pub async fn select_pse(
+        &mut self,
+    ) -> Result<Vec<ApplicationEntry>, EmvError<T::TransportError>> {
+        let sfi = self
+            .select(PSE)
+            .await?
+            .directory_sfi
+            .filter(|sfi| (1..=30).contains(sfi))
+            .ok_or(EmvError::MalformedResponse)?;
+
+        let mut entries = Vec::new();
+
+        for number in 1..=u8::MAX {
+            let record = match self.read_record(sfi, number).await {
+                Err(EmvError::NotFound) => break,
+                result => result?,
+            };
+
+            let template = BerTlv::next(&record)
+                .filter(|(object, _)| object.tag() == tag::RECORD_TEMPLATE)
+                .ok_or(EmvError::MalformedResponse)?
+                .0;
+            entries.extend(
+                ApplicationEntry::parse_all(template.children())
+                    .ok_or(EmvError::MalformedResponse)?,
+            );
+        }
+
+        ApplicationEntry::sort(&mut entries);
+        Ok(entries)
+    }
+
+    /// Lists the applications of a contactless card from the FCI of its
+    /// PPSE, highest priority first.
+    pub async fn select_ppse(
+        &mut self,
+    ) -> Result<Vec<ApplicationEntry>, EmvError<T::TransportError>> {
+        let mut entries = self
+            .select(PPSE)
+            .await?
+            .applications()
+            .ok_or(EmvError::MalformedResponse)?;
+
+        ApplicationEntry::sort(&mut entries);
+        Ok(entries)
+    }
+
+    /// Starts a transaction with the filled PDOL.
+    pub async fn get_processing_options(
+        &mut self,
+        pdol_data: &[u8],
+    ) -> Result<ProcessingOptions, EmvError<T::TransportError>> {
+        let data = self.fetch(GetProcessingOptions::new(pdol_data)).await?;
+        ProcessingOptions::parse(&data).ok_or(EmvError::MalformedResponse)
+    }
+
+    pub async fn read_record(
+        &mut self,
+        sfi: u8,
+        number: u8,
+    ) -> Result<Vec<u8>, EmvError<T::TransportError>> {
+        self.fetch(ReadRecord::new(sfi, number)).await
+    }
+
+    /// Reads every record the application file locator names.
+    pub async fn read_records(
+        &mut self,
+        processing_options: &ProcessingOptions,
+    ) -> Result<Vec<Record>, EmvError<T::TransportError>> {
+        let mut records = Vec::new();
+
+        for entry in &processing_options.afl {
+            for number in entry.records() {
+                records.push(Record {
+                    sfi: entry.sfi,
+                    number,
+                    data: self.read_record(entry.sfi, number).await?,
+                    authenticated: entry.is_authenticated(number),
+                });
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Selects the application `aid`, starts a transaction with
+    /// `terminal`'s data and reads its records.
+    pub async fn read_application(
+        &mut self,
+        aid: &[u8],
+        terminal: &TerminalData,
+    ) -> Result<Application, EmvError<T::TransportError>> {
+        let fci = self.select(aid).await?;
+
+        let pdol_data = match &fci.pdol {
+            Some(pdol) => terminal.fill(pdol).ok_or(EmvError::MalformedResponse)?,
+            None => Vec::new(),
+        };
+
+        let processing_options = self.get_processing_options(&pdol_data).await?;
+        let records = self.read_records(&processing_options).await?;
+
+        let mut data = EmvData::new();
+        let parsed = [&fci.response, &processing_options.response]
+            .into_iter()
+            .chain(records.iter().map(|record| &record.data))
+            .all(|template| data.merge(template));
+
+        if !parsed {
+            return Err(EmvError::MalformedResponse);
+        }
+
+        Ok(Application {
+            fci,
+            pdol_data,
+            processing_options,
+            records,
+            data,
+        })
+    }
+
+    /// Asks for a cryptogram of `cryptogram_type` over the filled CDOL,
+    /// signed for combined dynamic data authentication if `cda` is set.
+    pub async fn generate_ac(
+        &mut self,
+        cryptogram_type: CryptogramType,
+        cda: bool,
+        cdol_data: &[u8],
+    ) -> Result<ApplicationCryptogram, EmvError<T::TransportError>> {
+        let mut operation = GenerateAc::new(cryptogram_type, cdol_data);
+        if cda {
+            operation = operation.with_combined_data_authentication();
+        }
+
+        let data = self.fetch(operation).await?;
+        ApplicationCryptogram::parse(&data).ok_or(EmvError::MalformedResponse)
+    }
+
+    /// Runs INTERNAL AUTHENTICATE with the filled DDOL, and returns the
+    /// signed dynamic application data for
+    /// [`Verifier::verify_dda`](crate::apdu::emv::authentication::Verifier::verify_dda).
+    pub async fn internal_authenticate(
+        &mut self,
+        ddol_data: &[u8],
+    ) -> Result<Vec<u8>, EmvError<T::TransportError>> {
+        let data = self.fetch(InternalAuthenticate(ddol_data)).await?;
+
+        let (object, _) = BerTlv::next(&data).ok_or(EmvError::MalformedResponse)?;
+        let signed = match object.tag() {
+            tag::RESPONSE_FORMAT_1 => Some(object),
+            tag::RESPONSE_FORMAT_2 => object.children().get(tag::SIGNED_DYNAMIC_APPLICATION_DATA),
+            _ => None,
+        };
+        signed
+            .map(|signed| signed.value().to_vec())
+            .ok_or(EmvError::MalformedResponse)
+    }
+
+    /// Reads a data element that is not in the records, such as the ATC
+    /// `9F36` or the PIN try counter `9F17`, without its tag and length.
+    pub async fn get_data(&mut self, tag: u16) -> Result<Vec<u8>, EmvError<T::TransportError>> {
+        let data = self.fetch(GetData(tag)).await?;
+
+        let (object, _) = BerTlv::next(&data).ok_or(EmvError::MalformedResponse)?;
+        match object.tag() == tag as u32 {
+            true => Ok(object.value().to_vec()),
+            false => Err(EmvError::MalformedResponse),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! The data elements a card returns, collected from its templates into
+//! one dictionary and decoded by the formats of the element database.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::apdu::{
+    emv::element::{Element, Format, tag},
+    iso_7816::tlv::ber::BerTlv,
+};
+
+/// A data element's value decoded by its format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Field<'a> {
+    /// The digits of a numeric or compressed numeric element.
+    Digits(String),
+    Text(String),
+    Binary(&'a [u8]),
+}
+
+impl<'a> Field<'a> {
+    /// Decodes `value` by `format`. Text is read as ISO 8859-1, and digits
+    /// stop at the first nibble that is not a decimal digit.
+    pub fn decode(format: Format, value: &'a [u8]) -> Self {
+        match format {
+            Format::Numeric | Format::CompressedNumeric => Self::Digits(digits(value)),
+            Format::Text => Self::Text(value.iter().map(|&byte| byte as char).collect()),
+            Format::Binary | Format::Template => Self::Binary(value),
+        }
+    }
+}
+
+/// The BCD digits of `value`, up to the first padding nibble.
+pub(crate) fn digits(value: &[u8]) -> String {
+    value
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0F])
+        .take_while(|&nibble| nibble <= 9)
+        .map(|nibble| (b'0' + nibble) as char)
+        .collect()
+}
+
+/// Data elements by tag, in the order they were first seen.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EmvData {
+    elements: Vec<(u32, Vec<u8>)>,
+}
+
+impl EmvData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `tag` to `value`, replacing any value it had.
+    pub fn insert(&mut self, tag: u32, value: &[u8]) {
+        match self.elements.iter_mut().find(|(known, _)| *known == tag) {
+            Some((_, existing)) => *existing = value.to_vec(),
+            None => self.elements.push((tag, value.to_vec())),
+        }
+    }
+
+    /// Adds the primitive data objects of `data`, descending into the
+    /// templates among them. Returns `false` if `data` is malformed, after
+    /// adding what comes before the malformed object.
+    pub fn merge(&mut self, data: &[u8]) -> bool {
+        let mut rest = data;
+
+        while let Some(start) = rest.iter().position(|&byte| byte != 0x00 && byte != 0xFF) {
+            let Some((object, next)) = BerTlv::next(&rest[start..]) else {
+                return false;
+            };
+
+            match object.is_constructed() {
+                true if !self.merge(object.value()) => return false,
+                true => {}
+                false => self.insert(object.tag(), object.value()),
+            }
+
+            rest = next;
+        }
+
+        true
+    }
+
+    pub fn get(&self, tag: u32) -> Option<&[u8]> {
+        self.elements
+            .iter()
+            .find(|(known, _)| *known == tag)
+            .map(|(_, value)| &value[..])
+    }
+
+    /// The value of `tag` decoded by its format, or as binary if the
+    /// element is not in the database.
+    pub fn field(&self, tag: u32) -> Option<Field<'_>> {
+        let format = Element::find(tag).map_or(Format::Binary, |element| element.format);
+        self.get(tag).map(|value| Field::decode(format, value))
+    }
+
+    /// The elements with their database entries, where known.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, Option<&'static Element>, &[u8])> {
+        self.elements
+            .iter()
+            .map(|(tag, value)| (*tag, Element::find(*tag), &value[..]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// The PAN, from its own element or else from the track 2 equivalent
+    /// data.
+    pub fn pan(&self) -> Option<String> {
+        self.get(tag::PAN)
+            .or_else(|| self.get(tag::TRACK_2_EQUIVALENT_DATA))
+            .map(digits)
+    }
+
+    /// The expiration date as `YYMMDD`.
+    pub fn expiration_date(&self) -> Option<String> {
+        self.get(tag::EXPIRATION_DATE).map(digits)
+    }
+
+    pub fn cardholder_name(&self) -> Option<String> {
+        match self.field(tag::CARDHOLDER_NAME)? {
+            Field::Text(name) => Some(String::from(name.trim_end())),
+            _ => None,
+        }
+    }
+
+    /// The preferred name if the card gives one, or else the label.
+    pub fn application_label(&self) -> Option<String> {
+        [tag::APPLICATION_PREFERRED_NAME, tag::APPLICATION_LABEL]
+            .into_iter()
+            .find_map(|tag| match self.field(tag)? {
+                Field::Text(label) => Some(label),
+                _ => None,
+            })
+    }
+}
@@ -0,0 +1,152 @@
+//! Data object lists, through which a card names the terminal data it
+//! wants with GET PROCESSING OPTIONS and GENERATE AC, and the terminal
+//! data that fills them.
+
+use alloc::vec::Vec;
+
+use crate::apdu::emv::{
+    data::EmvData,
+    element::{Element, Format, tag},
+};
+
+/// An entry of a data object list: a tag and the length its value must
+/// take.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DolEntry {
+    pub tag: u32,
+    pub length: usize,
+}
+
+/// The entries of a data object list, or `None` if it is truncated or a
+/// tag is longer than three bytes.
+pub fn parse(dol: &[u8]) -> Option<Vec<DolEntry>> {
+    let mut entries = Vec::new();
+    let mut rest = dol;
+
+    while let Some((&first, next)) = rest.split_first() {
+        let mut tag = first as u32;
+        rest = next;
+
+        if first & 0x1F == 0x1F {
+            loop {
+                let (&byte, next) = rest.split_first()?;
+                rest = next;
+                tag = (tag << 8) | byte as u32;
+
+                if byte & 0x80 == 0 {
+                    break;
+                }
+
+                if tag > 0xFFFF {
+                    return None;
+                }
+            }
+        }
+
+        let (&length, next) = rest.split_first()?;
+        rest = next;
+        entries.push(DolEntry {
+            tag,
+            length: length as usize,
+        });
+    }
+
+    Some(entries)
+}
+
+/// Fits `value` to `length` bytes as EMV Book 3 has it: numeric values
+/// keep their rightmost digits and are padded with leading zeros,
+/// compressed numeric values are padded with trailing `F`s, and anything
+/// else keeps its leftmost bytes and is padded with trailing zeros.
+fn fit(format: Format, value: &[u8], length: usize, out: &mut Vec<u8>) {
+    match format {
+        Format::Numeric if value.len() >= length => {
+            out.extend_from_slice(&value[value.len() - length..]);
+        }
+        Format::Numeric => {
+            out.resize(out.len() + length - value.len(), 0x00);
+            out.extend_from_slice(value);
+        }
+        _ => {
+            let kept = &value[..value.len().min(length)];
+            let padding = match format {
+                Format::CompressedNumeric => 0xFF,
+                _ => 0x00,
+            };
+
+            out.extend_from_slice(kept);
+            out.resize(out.len() + length - kept.len(), padding);
+        }
+    }
+}
+
+/// `value` as `length` bytes of BCD, keeping the rightmost digits if it
+/// has more.
+pub fn numeric(value: u64, length: usize) -> Vec<u8> {
+    let mut bcd = alloc::vec![0; length];
+    let mut rest = value;
+
+    for byte in bcd.iter_mut().rev() {
+        *byte = (((rest / 10 % 10) << 4) | (rest % 10)) as u8;
+        rest /= 100;
+    }
+
+    bcd
+}
+
+/// The data a terminal puts in the data object lists a card sends it.
+/// Elements the terminal does not have are sent as zeros.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TerminalData {
+    data: EmvData,
+}
+
+impl TerminalData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, tag: u32, value: &[u8]) -> Self {
+        self.data.insert(tag, value);
+        self
+    }
+
+    /// Sets the authorised amount, in the minor unit of the currency.
+    pub fn with_amount(self, amount: u64) -> Self {
+        self.with(tag::AMOUNT_AUTHORISED, &numeric(amount, 6))
+    }
+
+    /// Sets the transaction currency by its ISO 4217 numeric code.
+    pub fn with_currency(self, code: u16) -> Self {
+        self.with(tag::TRANSACTION_CURRENCY_CODE, &numeric(code as u64, 2))
+    }
+
+    /// Sets the terminal country by its ISO 3166-1 numeric code.
+    pub fn with_country(self, code: u16) -> Self {
+        self.with(tag::TERMINAL_COUNTRY_CODE, &numeric(code as u64, 2))
+    }
+
+    pub fn with_unpredictable_number(self, number: [u8; 4]) -> Self {
+        self.with(tag::UNPREDICTABLE_NUMBER, &number)
+    }
+
+    pub fn data(&self) -> &EmvData {
+        &self.data
+    }
+
+    /// Fills the data object list `dol`, or returns `None` if it is
+    /// malformed.
+    pub fn fill(&self, dol: &[u8]) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+
+        for DolEntry { tag, length } in parse(dol)? {
+            let format = Element::find(tag).map_or(Format::Binary, |element| element.format);
+            match self.data.get(tag) {
+                Some(value) => fit(format, value, length, &mut out),
+                None => out.resize(out.len() + length, 0x00),
+            }
+        }
+
+        Some(out)
+    }
+}
@@ -0,0 +1,561 @@
+//! The EMV data elements a terminal reads and sends, and a database of
+//! their names and formats for decoding and display.
+
+/// The tags of the EMV data elements.
+pub mod tag {
+    pub const ISSUER_IDENTIFICATION_NUMBER: u32 = 0x42;
+    pub const APPLICATION_IDENTIFIER: u32 = 0x4F;
+    pub const APPLICATION_LABEL: u32 = 0x50;
+    pub const TRACK_2_EQUIVALENT_DATA: u32 = 0x57;
+    pub const PAN: u32 = 0x5A;
+    pub const APPLICATION_TEMPLATE: u32 = 0x61;
+    pub const FCI_TEMPLATE: u32 = 0x6F;
+    pub const RECORD_TEMPLATE: u32 = 0x70;
+    pub const RESPONSE_FORMAT_2: u32 = 0x77;
+    pub const RESPONSE_FORMAT_1: u32 = 0x80;
+    pub const APPLICATION_INTERCHANGE_PROFILE: u32 = 0x82;
+    pub const COMMAND_TEMPLATE: u32 = 0x83;
+    pub const DF_NAME: u32 = 0x84;
+    pub const APPLICATION_PRIORITY_INDICATOR: u32 = 0x87;
+    pub const SHORT_FILE_IDENTIFIER: u32 = 0x88;
+    pub const AUTHORISATION_RESPONSE_CODE: u32 = 0x8A;
+    pub const CDOL1: u32 = 0x8C;
+    pub const CDOL2: u32 = 0x8D;
+    pub const CVM_LIST: u32 = 0x8E;
+    pub const CA_PUBLIC_KEY_INDEX: u32 = 0x8F;
+    pub const ISSUER_PUBLIC_KEY_CERTIFICATE: u32 = 0x90;
+    pub const ISSUER_PUBLIC_KEY_REMAINDER: u32 = 0x92;
+    pub const SIGNED_STATIC_APPLICATION_DATA: u32 = 0x93;
+    pub const APPLICATION_FILE_LOCATOR: u32 = 0x94;
+    pub const TERMINAL_VERIFICATION_RESULTS: u32 = 0x95;
+    pub const TRANSACTION_DATE: u32 = 0x9A;
+    pub const TRANSACTION_TYPE: u32 = 0x9C;
+    pub const FCI_PROPRIETARY_TEMPLATE: u32 = 0xA5;
+    pub const CARDHOLDER_NAME: u32 = 0x5F20;
+    pub const EXPIRATION_DATE: u32 = 0x5F24;
+    pub const EFFECTIVE_DATE: u32 = 0x5F25;
+    pub const ISSUER_COUNTRY_CODE: u32 = 0x5F28;
+    pub const TRANSACTION_CURRENCY_CODE: u32 = 0x5F2A;
+    pub const LANGUAGE_PREFERENCE: u32 = 0x5F2D;
+    pub const SERVICE_CODE: u32 = 0x5F30;
+    pub const PAN_SEQUENCE_NUMBER: u32 = 0x5F34;
+    pub const TRANSACTION_CURRENCY_EXPONENT: u32 = 0x5F36;
+    pub const ISSUER_URL: u32 = 0x5F50;
+    pub const ACCOUNT_TYPE: u32 = 0x5F57;
+    pub const AMOUNT_AUTHORISED: u32 = 0x9F02;
+    pub const AMOUNT_OTHER: u32 = 0x9F03;
+    pub const TERMINAL_APPLICATION_IDENTIFIER: u32 = 0x9F06;
+    pub const APPLICATION_USAGE_CONTROL: u32 = 0x9F07;
+    pub const APPLICATION_VERSION_NUMBER: u32 = 0x9F08;
+    pub const TERMINAL_APPLICATION_VERSION_NUMBER: u32 = 0x9F09;
+    pub const CARDHOLDER_NAME_EXTENDED: u32 = 0x9F0B;
+    pub const IAC_DEFAULT: u32 = 0x9F0D;
+    pub const IAC_DENIAL: u32 = 0x9F0E;
+    pub const IAC_ONLINE: u32 = 0x9F0F;
+    pub const ISSUER_APPLICATION_DATA: u32 = 0x9F10;
+    pub const ISSUER_CODE_TABLE_INDEX: u32 = 0x9F11;
+    pub const APPLICATION_PREFERRED_NAME: u32 = 0x9F12;
+    pub const LAST_ONLINE_ATC: u32 = 0x9F13;
+    pub const LOWER_CONSECUTIVE_OFFLINE_LIMIT: u32 = 0x9F14;
+    pub const MERCHANT_CATEGORY_CODE: u32 = 0x9F15;
+    pub const MERCHANT_IDENTIFIER: u32 = 0x9F16;
+    pub const PIN_TRY_COUNTER: u32 = 0x9F17;
+    pub const TERMINAL_COUNTRY_CODE: u32 = 0x9F1A;
+    pub const TERMINAL_IDENTIFICATION: u32 = 0x9F1C;
+    pub const IFD_SERIAL_NUMBER: u32 = 0x9F1E;
+    pub const TRACK_1_DISCRETIONARY_DATA: u32 = 0x9F1F;
+    pub const TRANSACTION_TIME: u32 = 0x9F21;
+    pub const UPPER_CONSECUTIVE_OFFLINE_LIMIT: u32 = 0x9F23;
+    pub const APPLICATION_CRYPTOGRAM: u32 = 0x9F26;
+    pub const CRYPTOGRAM_INFORMATION_DATA: u32 = 0x9F27;
+    pub const KERNEL_IDENTIFIER: u32 = 0x9F2A;
+    pub const ISSUER_PUBLIC_KEY_EXPONENT: u32 = 0x9F32;
+    pub const TERMINAL_CAPABILITIES: u32 = 0x9F33;
+    pub const CVM_RESULTS: u32 = 0x9F34;
+    pub const TERMINAL_TYPE: u32 = 0x9F35;
+    pub const APPLICATION_TRANSACTION_COUNTER: u32 = 0x9F36;
+    pub const UNPREDICTABLE_NUMBER: u32 = 0x9F37;
+    pub const PDOL: u32 = 0x9F38;
+    pub const POS_ENTRY_MODE: u32 = 0x9F39;
+    pub const ADDITIONAL_TERMINAL_CAPABILITIES: u32 = 0x9F40;
+    pub const APPLICATION_CURRENCY_CODE: u32 = 0x9F42;
+    pub const APPLICATION_CURRENCY_EXPONENT: u32 = 0x9F44;
+    pub const DATA_AUTHENTICATION_CODE: u32 = 0x9F45;
+    pub const ICC_PUBLIC_KEY_CERTIFICATE: u32 = 0x9F46;
+    pub const ICC_PUBLIC_KEY_EXPONENT: u32 = 0x9F47;
+    pub const ICC_PUBLIC_KEY_REMAINDER: u32 = 0x9F48;
+    pub const DDOL: u32 = 0x9F49;
+    pub const STATIC_DATA_AUTHENTICATION_TAG_LIST: u32 = 0x9F4A;
+    pub const SIGNED_DYNAMIC_APPLICATION_DATA: u32 = 0x9F4B;
+    pub const ICC_DYNAMIC_NUMBER: u32 = 0x9F4C;
+    pub const LOG_ENTRY: u32 = 0x9F4D;
+    pub const MERCHANT_NAME_AND_LOCATION: u32 = 0x9F4E;
+    pub const LOG_FORMAT: u32 = 0x9F4F;
+    pub const TRANSACTION_CATEGORY_CODE: u32 = 0x9F53;
+    pub const AVAILABLE_OFFLINE_SPENDING_AMOUNT: u32 = 0x9F5D;
+    pub const TERMINAL_TRANSACTION_QUALIFIERS: u32 = 0x9F66;
+    pub const CARD_AUTHENTICATION_RELATED_DATA: u32 = 0x9F69;
+    pub const CARD_TRANSACTION_QUALIFIERS: u32 = 0x9F6C;
+    pub const FORM_FACTOR_INDICATOR: u32 = 0x9F6E;
+    pub const FCI_ISSUER_DISCRETIONARY_DATA: u32 = 0xBF0C;
+}
+
+/// How a data element's value is encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Format `n`: BCD digits, right-aligned and padded with leading zeros.
+    Numeric,
+    /// Format `cn`: BCD digits, left-aligned and padded with trailing `F`s.
+    CompressedNumeric,
+    /// Formats `a`, `an` and `ans`: characters of the common character set.
+    Text,
+    /// Format `b`, or a format specific to the element.
+    Binary,
+    /// A constructed data object holding others.
+    Template,
+}
+
+/// An entry of the data element database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Element {
+    pub tag: u32,
+    pub name: &'static str,
+    pub format: Format,
+}
+
+impl Element {
+    const fn new(tag: u32, name: &'static str, format: Format) -> Self {
+        Self { tag, name, format }
+    }
+
+    /// The database entry of `tag`, if it is a known element.
+    pub fn find(tag: u32) -> Option<&'static Self> {
+        ELEMENTS
+            .binary_search_by_key(&tag, |element| element.tag)
+            .ok()
+            .map(|index| &ELEMENTS[index])
+    }
+}
+
+/// The known data elements, in tag order.
+static ELEMENTS: &[Element] = &[
+    Element::new(
+        tag::ISSUER_IDENTIFICATION_NUMBER,
+        "Issuer Identification Number",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::APPLICATION_IDENTIFIER,
+        "Application Identifier (AID)",
+        Format::Binary,
+    ),
+    Element::new(tag::APPLICATION_LABEL, "Application Label", Format::Text),
+    Element::new(
+        tag::TRACK_2_EQUIVALENT_DATA,
+        "Track 2 Equivalent Data",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::PAN,
+        "Application Primary Account Number (PAN)",
+        Format::CompressedNumeric,
+    ),
+    Element::new(
+        tag::APPLICATION_TEMPLATE,
+        "Application Template",
+        Format::Template,
+    ),
+    Element::new(
+        tag::FCI_TEMPLATE,
+        "File Control Information (FCI) Template",
+        Format::Template,
+    ),
+    Element::new(
+        tag::RECORD_TEMPLATE,
+        "READ RECORD Response Message Template",
+        Format::Template,
+    ),
+    Element::new(
+        tag::RESPONSE_FORMAT_2,
+        "Response Message Template Format 2",
+        Format::Template,
+    ),
+    Element::new(
+        tag::RESPONSE_FORMAT_1,
+        "Response Message Template Format 1",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::APPLICATION_INTERCHANGE_PROFILE,
+        "Application Interchange Profile",
+        Format::Binary,
+    ),
+    Element::new(tag::COMMAND_TEMPLATE, "Command Template", Format::Binary),
+    Element::new(tag::DF_NAME, "Dedicated File (DF) Name", Format::Binary),
+    Element::new(
+        tag::APPLICATION_PRIORITY_INDICATOR,
+        "Application Priority Indicator",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::SHORT_FILE_IDENTIFIER,
+        "Short File Identifier (SFI)",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::AUTHORISATION_RESPONSE_CODE,
+        "Authorisation Response Code",
+        Format::Text,
+    ),
+    Element::new(
+        tag::CDOL1,
+        "Card Risk Management Data Object List 1 (CDOL1)",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::CDOL2,
+        "Card Risk Management Data Object List 2 (CDOL2)",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::CVM_LIST,
+        "Cardholder Verification Method (CVM) List",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::CA_PUBLIC_KEY_INDEX,
+        "Certification Authority Public Key Index",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::ISSUER_PUBLIC_KEY_CERTIFICATE,
+        "Issuer Public Key Certificate",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::ISSUER_PUBLIC_KEY_REMAINDER,
+        "Issuer Public Key Remainder",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::SIGNED_STATIC_APPLICATION_DATA,
+        "Signed Static Application Data",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::APPLICATION_FILE_LOCATOR,
+        "Application File Locator (AFL)",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::TERMINAL_VERIFICATION_RESULTS,
+        "Terminal Verification Results",
+        Format::Binary,
+    ),
+    Element::new(tag::TRANSACTION_DATE, "Transaction Date", Format::Numeric),
+    Element::new(tag::TRANSACTION_TYPE, "Transaction Type", Format::Numeric),
+    Element::new(
+        tag::FCI_PROPRIETARY_TEMPLATE,
+        "FCI Proprietary Template",
+        Format::Template,
+    ),
+    Element::new(tag::CARDHOLDER_NAME, "Cardholder Name", Format::Text),
+    Element::new(
+        tag::EXPIRATION_DATE,
+        "Application Expiration Date",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::EFFECTIVE_DATE,
+        "Application Effective Date",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::ISSUER_COUNTRY_CODE,
+        "Issuer Country Code",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::TRANSACTION_CURRENCY_CODE,
+        "Transaction Currency Code",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::LANGUAGE_PREFERENCE,
+        "Language Preference",
+        Format::Text,
+    ),
+    Element::new(tag::SERVICE_CODE, "Service Code", Format::Numeric),
+    Element::new(
+        tag::PAN_SEQUENCE_NUMBER,
+        "Application PAN Sequence Number",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::TRANSACTION_CURRENCY_EXPONENT,
+        "Transaction Currency Exponent",
+        Format::Numeric,
+    ),
+    Element::new(tag::ISSUER_URL, "Issuer URL", Format::Text),
+    Element::new(tag::ACCOUNT_TYPE, "Account Type", Format::Numeric),
+    Element::new(
+        tag::AMOUNT_AUTHORISED,
+        "Amount, Authorised (Numeric)",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::AMOUNT_OTHER,
+        "Amount, Other (Numeric)",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::TERMINAL_APPLICATION_IDENTIFIER,
+        "Application Identifier (AID) - terminal",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::APPLICATION_USAGE_CONTROL,
+        "Application Usage Control",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::APPLICATION_VERSION_NUMBER,
+        "Application Version Number",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::TERMINAL_APPLICATION_VERSION_NUMBER,
+        "Application Version Number - terminal",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::CARDHOLDER_NAME_EXTENDED,
+        "Cardholder Name Extended",
+        Format::Text,
+    ),
+    Element::new(
+        tag::IAC_DEFAULT,
+        "Issuer Action Code - Default",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::IAC_DENIAL,
+        "Issuer Action Code - Denial",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::IAC_ONLINE,
+        "Issuer Action Code - Online",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::ISSUER_APPLICATION_DATA,
+        "Issuer Application Data",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::ISSUER_CODE_TABLE_INDEX,
+        "Issuer Code Table Index",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::APPLICATION_PREFERRED_NAME,
+        "Application Preferred Name",
+        Format::Text,
+    ),
+    Element::new(
+        tag::LAST_ONLINE_ATC,
+        "Last Online ATC Register",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::LOWER_CONSECUTIVE_OFFLINE_LIMIT,
+        "Lower Consecutive Offline Limit",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::MERCHANT_CATEGORY_CODE,
+        "Merchant Category Code",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::MERCHANT_IDENTIFIER,
+        "Merchant Identifier",
+        Format::Text,
+    ),
+    Element::new(
+        tag::PIN_TRY_COUNTER,
+        "Personal Identification Number (PIN) Try Counter",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::TERMINAL_COUNTRY_CODE,
+        "Terminal Country Code",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::TERMINAL_IDENTIFICATION,
+        "Terminal Identification",
+        Format::Text,
+    ),
+    Element::new(
+        tag::IFD_SERIAL_NUMBER,
+        "Interface Device (IFD) Serial Number",
+        Format::Text,
+    ),
+    Element::new(
+        tag::TRACK_1_DISCRETIONARY_DATA,
+        "Track 1 Discretionary Data",
+        Format::Text,
+    ),
+    Element::new(tag::TRANSACTION_TIME, "Transaction Time", Format::Numeric),
+    Element::new(
+        tag::UPPER_CONSECUTIVE_OFFLINE_LIMIT,
+        "Upper Consecutive Offline Limit",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::APPLICATION_CRYPTOGRAM,
+        "Application Cryptogram",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::CRYPTOGRAM_INFORMATION_DATA,
+        "Cryptogram Information Data",
+        Format::Binary,
+    ),
+    Element::new(tag::KERNEL_IDENTIFIER, "Kernel Identifier", Format::Binary),
+    Element::new(
+        tag::ISSUER_PUBLIC_KEY_EXPONENT,
+        "Issuer Public Key Exponent",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::TERMINAL_CAPABILITIES,
+        "Terminal Capabilities",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::CVM_RESULTS,
+        "Cardholder Verification Method (CVM) Results",
+        Format::Binary,
+    ),
+    Element::new(tag::TERMINAL_TYPE, "Terminal Type", Format::Numeric),
+    Element::new(
+        tag::APPLICATION_TRANSACTION_COUNTER,
+        "Application Transaction Counter (ATC)",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::UNPREDICTABLE_NUMBER,
+        "Unpredictable Number",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::PDOL,
+        "Processing Options Data Object List (PDOL)",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::POS_ENTRY_MODE,
+        "Point-of-Service (POS) Entry Mode",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::ADDITIONAL_TERMINAL_CAPABILITIES,
+        "Additional Terminal Capabilities",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::APPLICATION_CURRENCY_CODE,
+        "Application Currency Code",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::APPLICATION_CURRENCY_EXPONENT,
+        "Application Currency Exponent",
+        Format::Numeric,
+    ),
+    Element::new(
+        tag::DATA_AUTHENTICATION_CODE,
+        "Data Authentication Code",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::ICC_PUBLIC_KEY_CERTIFICATE,
+        "ICC Public Key Certificate",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::ICC_PUBLIC_KEY_EXPONENT,
+        "ICC Public Key Exponent",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::ICC_PUBLIC_KEY_REMAINDER,
+        "ICC Public Key Remainder",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::DDOL,
+        "Dynamic Data Authentication Data Object List (DDOL)",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::STATIC_DATA_AUTHENTICATION_TAG_LIST,
+        "Static Data Authentication Tag List",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::SIGNED_DYNAMIC_APPLICATION_DATA,
+        "Signed Dynamic Application Data",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::ICC_DYNAMIC_NUMBER,
+        "ICC Dynamic Number",
+        Format::Binary,
+    ),
+    Element::new(tag::LOG_ENTRY, "Log Entry", Format::Binary),
+    Element::new(
+        tag::MERCHANT_NAME_AND_LOCATION,
+        "Merchant Name and Location",
+        Format::Text,
+    ),
+    Element::new(tag::LOG_FORMAT, "Log Format", Format::Binary),
+    Element::new(
+        tag::TRANSACTION_CATEGORY_CODE,
+        "Transaction Category Code",
+        Format::Text,
+    ),
+    Element::new(
+        tag::AVAILABLE_OFFLINE_SPENDING_AMOUNT,
+        "Available Offline Spending Amount",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::TERMINAL_TRANSACTION_QUALIFIERS,
+        "Terminal Transaction Qualifiers",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::CARD_AUTHENTICATION_RELATED_DATA,
+        "Card Authentication Related Data",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::CARD_TRANSACTION_QUALIFIERS,
+        "Card Transaction Qualifiers",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::FORM_FACTOR_INDICATOR,
+        "Form Factor Indicator",
+        Format::Binary,
+    ),
+    Element::new(
+        tag::FCI_ISSUER_DISCRETIONARY_DATA,
+        "FCI Issuer Discretionary Data",
+        Format::Template,
+    ),
+];
@@ -0,0 +1,20 @@
+//! EMV payment applications: application selection through the payment
+//! system environment, GET PROCESSING OPTIONS with the processing options
+//! data object list, reading the records the application file locator
+//! names, GENERATE AC, and offline data authentication.
+
+pub mod authentication;
+pub mod client;
+pub mod data;
+pub mod dol;
+pub mod element;
+pub mod object;
+pub mod operation;
+
+/// The name of the payment system environment of contact cards, whose
+/// directory lists the payment applications in records.
+pub const PSE: &[u8] = b"1PAY.SYS.DDF01";
+
+/// The name of the proximity payment system environment of contactless
+/// cards, whose FCI lists the payment applications.
+pub const PPSE: &[u8] = b"2PAY.SYS.DDF01";
@@ -0,0 +1,339 @@
+//! What selection, GET PROCESSING OPTIONS, READ RECORD and GENERATE AC
+//! return.
+
+use alloc::{string::String, vec::Vec};
+use core::ops::RangeInclusive;
+
+use crate::apdu::{
+    emv::element::tag,
+    iso_7816::tlv::ber::{BerTlv, BerTlvIterator},
+};
+
+fn text(value: &[u8]) -> String {
+    value.iter().map(|&byte| byte as char).collect()
+}
+
+/// The template `tag` at the start of `data`.
+fn template(data: &[u8], tag: u32) -> Option<BerTlv<'_>> {
+    let (object, _) = BerTlv::next(data)?;
+    (object.tag() == tag).then_some(object)
+}
+
+/// A payment application a directory lists.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApplicationEntry {
+    pub aid: Vec<u8>,
+    pub label: Option<String>,
+    pub preferred_name: Option<String>,
+    /// The priority, 1 being the highest; `None` if there is none.
+    pub priority: Option<u8>,
+    /// Whether the application asks the cardholder to confirm it.
+    pub requires_confirmation: bool,
+    /// The contactless kernel the application runs on.
+    pub kernel_identifier: Option<Vec<u8>>,
+}
+
+impl ApplicationEntry {
+    /// Parses the value of an application template `61`.
+    pub fn parse(template: &[u8]) -> Option<Self> {
+        let objects = BerTlvIterator::new(template);
+        let indicator = objects
+            .get(tag::APPLICATION_PRIORITY_INDICATOR)
+            .and_then(|object| object.value().first().copied())
+            .unwrap_or(0);
+
+        Some(Self {
+            aid: objects.get(tag::APPLICATION_IDENTIFIER)?.value().to_vec(),
+            label: objects
+                .get(tag::APPLICATION_LABEL)
+                .map(|object| text(object.value())),
+            preferred_name: objects
+                .get(tag::APPLICATION_PREFERRED_NAME)
+                .map(|object| text(object.value())),
+            priority: Some(indicator & 0x0F).filter(|&priority| priority != 0),
+            requires_confirmation: indicator & 0x80 != 0,
+            kernel_identifier: objects
+                .get(tag::KERNEL_IDENTIFIER)
+                .map(|object| object.value().to_vec()),
+        })
+    }
+
+    /// The application templates among `objects`, such as the records of a
+    /// PSE directory or the issuer discretionary data of a PPSE.
+    pub fn parse_all(objects: BerTlvIterator<'_>) -> Option<Vec<Self>> {
+        objects
+            .filter(|object| object.tag() == tag::APPLICATION_TEMPLATE)
+            .map(|object| Self::parse(object.value()))
+            .collect()
+    }
+
+    /// Sorts `entries` by priority, those without one last.
+    pub fn sort(entries: &mut [Self]) {
+        entries.sort_by_key(|entry| entry.priority.unwrap_or(u8::MAX));
+    }
+}
+
+/// The file control information selecting a PSE, PPSE or application
+/// returns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fci {
+    pub df_name: Vec<u8>,
+    pub label: Option<String>,
+    pub preferred_name: Option<String>,
+    pub priority: Option<u8>,
+    /// The processing options data object list.
+    pub pdol: Option<Vec<u8>>,
+    pub language_preference: Option<String>,
+    /// The short file identifier of a PSE directory.
+    pub directory_sfi: Option<u8>,
+    /// The issuer discretionary data `BF0C`, which lists the applications
+    /// of a PPSE.
+    pub issuer_discretionary_data: Option<Vec<u8>>,
+    /// The FCI as the card sent it.
+    pub response: Vec<u8>,
+}
+
+impl Fci {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let fci = template(data, tag::FCI_TEMPLATE)?;
+        let proprietary = fci
+            .children()
+            .get(tag::FCI_PROPRIETARY_TEMPLATE)?
+            .children();
+        let value = |tag| proprietary.get(tag).map(|object| object.value());
+
+        Some(Self {
+            df_name: fci.children().get(tag::DF_NAME)?.value().to_vec(),
+            label: value(tag::APPLICATION_LABEL).map(text),
+            preferred_name: value(tag::APPLICATION_PREFERRED_NAME).map(text),
+            priority: value(tag::APPLICATION_PRIORITY_INDICATOR)
+                .and_then(|value| value.first())
+                .map(|indicator| indicator & 0x0F)
+                .filter(|&priority| priority != 0),
+            pdol: value(tag::PDOL).map(<[u8]>::to_vec),
+            language_preference: value(tag::LANGUAGE_PREFERENCE).map(text),
+            directory_sfi: value(tag::SHORT_FILE_IDENTIFIER)
+                .and_then(|value| value.first().copied()),
+            issuer_discretionary_data: value(tag::FCI_ISSUER_DISCRETIONARY_DATA)
+                .map(<[u8]>::to_vec),
+            response: data.to_vec(),
+        })
+    }
+
+    /// The applications the issuer discretionary data lists.
+    pub fn applications(&self) -> Option<Vec<ApplicationEntry>> {
+        let data = self.issuer_discretionary_data.as_deref()?;
+        ApplicationEntry::parse_all(BerTlvIterator::new(data))
+    }
+}
+
+/// An entry of the application file locator: records to read from a file,
+/// the first of which also go into offline data authentication.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AflEntry {
+    pub sfi: u8,
+    pub first_record: u8,
+    pub last_record: u8,
+    /// How many records, from the first, offline data authentication
+    /// covers.
+    pub authenticated_records: u8,
+}
+
+impl AflEntry {
+    /// Parses an application file locator, or returns `None` if it is not
+    /// a sequence of valid four-byte entries.
+    pub fn parse_all(afl: &[u8]) -> Option<Vec<Self>> {
+        let (entries, []) = afl.as_chunks::<4>() else {
+            return None;
+        };
+
+        entries
+            .iter()
+            .map(|&[sfi, first, last, authenticated]| {
+                let entry = Self {
+                    sfi: sfi >> 3,
+                    first_record: first,
+                    last_record: last,
+                    authenticated_records: authenticated,
+                };
+
+                let valid = (1..=30).contains(&entry.sfi)
+                    && first != 0
+                    && first <= last
+                    && authenticated <= last - first + 1;
+                valid.then_some(entry)
+            })
+            .collect()
+    }
+
+    pub fn records(&self) -> RangeInclusive<u8> {
+        self.first_record..=self.last_record
+    }
+
+    pub fn is_authenticated(&self, record: u8) -> bool {
+        record >= self.first_record && record - self.first_record < self.authenticated_records
+    }
+}
+
+/// What GET PROCESSING OPTIONS returns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcessingOptions {
+    /// The application interchange profile, which says what the card
+    /// supports: offline data authentication (SDA `40`, DDA `20`, CDA `01`
+    /// in the first byte), cardholder verification and the like.
+    pub aip: [u8; 2],
+    pub afl: Vec<AflEntry>,
+    /// The response as the card sent it, with any other data elements of a
+    /// format 2 response.
+    pub response: Vec<u8>,
+}
+
+impl ProcessingOptions {
+    pub const SDA: u8 = 0x40;
+    pub const DDA: u8 = 0x20;
+    pub const CDA: u8 = 0x01;
+
+    /// Parses a format 1 response, `80` with the AIP and AFL run together,
+    /// or a format 2 response, a `77` template.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (object, _) = BerTlv::next(data)?;
+
+        let (aip, afl) = match object.tag() {
+            tag::RESPONSE_FORMAT_1 => object.value().split_first_chunk::<2>()?,
+            tag::RESPONSE_FORMAT_2 => {
+                let objects = object.children();
+                let aip = objects.get(tag::APPLICATION_INTERCHANGE_PROFILE)?.value();
+                let afl = objects
+                    .get(tag::APPLICATION_FILE_LOCATOR)
+                    .map_or(&[][..], |object| object.value());
+                (aip.try_into().ok()?, afl)
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            aip: *aip,
+            afl: AflEntry::parse_all(afl)?,
+            response: data.to_vec(),
+        })
+    }
+
+    pub fn supports(&self, method: u8) -> bool {
+        self.aip[0] & method != 0
+    }
+}
+
+/// A record read for the application file locator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub sfi: u8,
+    pub number: u8,
+    /// The record as read, a `70` template for files 1 to 10.
+    pub data: Vec<u8>,
+    /// Whether offline data authentication covers it.
+    pub authenticated: bool,
+}
+
+/// The cryptogram GENERATE AC asks for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CryptogramType {
+    /// An application authentication cryptogram: the transaction is
+    /// declined.
+    Aac,
+    /// A transaction certificate: the transaction is approved offline.
+    Tc,
+    /// An authorisation request cryptogram: the transaction goes online.
+    Arqc,
+}
+
+impl CryptogramType {
+    pub fn from_u8(cid: u8) -> Option<Self> {
+        match cid & 0xC0 {
+            0x00 => Some(Self::Aac),
+            0x40 => Some(Self::Tc),
+            0x80 => Some(Self::Arqc),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::Aac => 0x00,
+            Self::Tc => 0x40,
+            Self::Arqc => 0x80,
+        }
+    }
+}
+
+/// What GENERATE AC returns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApplicationCryptogram {
+    /// The cryptogram information data, whose top bits are the type of
+    /// cryptogram the card chose.
+    pub cid: u8,
+    pub atc: u16,
+    /// The cryptogram; with CDA it is inside the signed dynamic data
+    /// instead.
+    pub cryptogram: Option<[u8; 8]>,
+    pub issuer_application_data: Option<Vec<u8>>,
+    /// The signature of combined dynamic data authentication.
+    pub signed_dynamic_data: Option<Vec<u8>>,
+    /// The response as the card sent it, which CDA verification needs.
+    pub response: Vec<u8>,
+}
+
+impl ApplicationCryptogram {
+    /// Parses a format 1 response, `80` with the CID, ATC, cryptogram and
+    /// issuer application data run together, or a format 2 `77` template.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (object, _) = BerTlv::next(data)?;
+
+        let cryptogram = match object.tag() {
+            tag::RESPONSE_FORMAT_1 => {
+                let (&[cid, a1, a2], rest) = object.value().split_first_chunk()?;
+                let (cryptogram, issuer_application_data) = rest.split_first_chunk::<8>()?;
+
+                Self {
+                    cid,
+                    atc: u16::from_be_bytes([a1, a2]),
+                    cryptogram: Some(*cryptogram),
+                    issuer_application_data: Some(issuer_application_data.to_vec())
+                        .filter(|data| !data.is_empty()),
+                    signed_dynamic_data: None,
+                    response: data.to_vec(),
+                }
+            }
+            tag::RESPONSE_FORMAT_2 => {
+                let objects = object.children();
+                let value = |tag| objects.get(tag).map(|object| object.value());
+
+                Self {
+                    cid: *value(tag::CRYPTOGRAM_INFORMATION_DATA)?.first()?,
+                    atc: u16::from_be_bytes(
+                        value(tag::APPLICATION_TRANSACTION_COUNTER)?
+                            .try_into()
+                            .ok()?,
+                    ),
+                    cryptogram: match value(tag::APPLICATION_CRYPTOGRAM) {
+                        Some(cryptogram) => Some(cryptogram.try_into().ok()?),
+                        None => None,
+                    },
+                    issuer_application_data: value(tag::ISSUER_APPLICATION_DATA)
+                        .map(<[u8]>::to_vec),
+                    signed_dynamic_data: value(tag::SIGNED_DYNAMIC_APPLICATION_DATA)
+                        .map(<[u8]>::to_vec),
+                    response: data.to_vec(),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(cryptogram)
+    }
+
+    /// The type of cryptogram the card returned, which may be lower than
+    /// the one asked for.
+    pub fn cryptogram_type(&self) -> Option<CryptogramType> {
+        CryptogramType::from_u8(self.cid)
+    }
+}
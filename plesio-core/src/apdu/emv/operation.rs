@@ -0,0 +1,125 @@
+//! The EMV commands, in the proprietary class `80` but for INTERNAL
+//! AUTHENTICATE.
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    emv::{element::tag, object::CryptogramType},
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+        tlv::ber::BerTlv,
+    },
+    response::ApduResponse,
+    status::is,
+};
+
+/// GET PROCESSING OPTIONS, which starts a transaction with the terminal
+/// data the PDOL asks for.
+pub struct GetProcessingOptions {
+    data: Vec<u8>,
+}
+
+impl GetProcessingOptions {
+    /// `pdol_data` is the filled PDOL, empty if the card has none.
+    pub fn new(pdol_data: &[u8]) -> Self {
+        let mut data = Vec::new();
+        BerTlv::from(tag::COMMAND_TEMPLATE, pdol_data)
+            .write(&mut data)
+            .expect("vector sink is unbounded");
+        Self { data }
+    }
+}
+
+impl Iso7816StreamingOperation for GetProcessingOptions {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class.with_proprietary(), 0xA8, (0x00, 0x00), &self.data)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// GENERATE AC, which asks the card for a cryptogram over the terminal
+/// data a CDOL names.
+pub struct GenerateAc<'a> {
+    cryptogram_type: CryptogramType,
+    combined_data_authentication: bool,
+    cdol_data: &'a [u8],
+}
+
+impl<'a> GenerateAc<'a> {
+    pub fn new(cryptogram_type: CryptogramType, cdol_data: &'a [u8]) -> Self {
+        Self {
+            cryptogram_type,
+            combined_data_authentication: false,
+            cdol_data,
+        }
+    }
+
+    /// Asks the card to sign the cryptogram for combined dynamic data
+    /// authentication.
+    pub fn with_combined_data_authentication(mut self) -> Self {
+        self.combined_data_authentication = true;
+        self
+    }
+}
+
+impl Iso7816StreamingOperation for GenerateAc<'_> {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let mut reference = self.cryptogram_type.to_u8();
+        if self.combined_data_authentication {
+            reference |= 0x10;
+        }
+
+        Iso7816Command::new(
+            class.with_proprietary(),
+            0xAE,
+            (reference, 0x00),
+            self.cdol_data,
+        )
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// GET DATA of a single data element, such as the ATC `9F36` or the PIN
+/// try counter `9F17`.
+pub struct GetData(pub u16);
+
+impl Iso7816StreamingOperation for GetData {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let [p1, p2] = self.0.to_be_bytes();
+        Iso7816Command::new(class.with_proprietary(), 0xCA, (p1, p2), &[])
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// INTERNAL AUTHENTICATE, which asks the card to sign the terminal data the
+/// DDOL names for dynamic data authentication.
+pub struct InternalAuthenticate<'a>(pub &'a [u8]);
+
+impl Iso7816StreamingOperation for InternalAuthenticate<'_> {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, 0x88, (0x00, 0x00), self.0)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
@@ -0,0 +1,146 @@
+use crate::apdu::{class::ApduClass, command::ApduCommand};
+
+/// Largest possible encoded command: an extended header with Lc, 65535 data
+/// bytes and a two byte Le.
+pub const MAX_ENCODED_LENGTH: usize = 4 + 3 + u16::MAX as usize + 2;
+
+/// The number of response data bytes (Ne) a transport should request for a
+/// reply buffer of the given length, which includes the two status bytes.
+pub fn expected_length(reply_buffer_length: usize) -> usize {
+    reply_buffer_length.saturating_sub(2)
+}
+
+fn is_extended(data_length: usize, expected_length: usize) -> bool {
+    data_length > u8::MAX as usize || expected_length > u8::MAX as usize + 1
+}
+
+/// The length of `command` once encoded, or `None` if it cannot be encoded.
+///
+/// Short encoding is used unless the data or expected length require the
+/// extended form.
+pub fn encoded_length(command: &impl ApduCommand, expected_length: usize) -> Option<usize> {
+    let data_length = command.data().len();
+
+    if data_length > u16::MAX as usize || expected_length > u16::MAX as usize + 1 {
+        return None;
+    }
+
+    let extended = is_extended(data_length, expected_length);
+
+    let lc_length = match (data_length, extended) {
+        (0, _) => 0,
+        (_, false) => 1,
+        (_, true) => 3,
+    };
+
+    let le_length = match (expected_length, extended, data_length) {
+        (0, _, _) => 0,
+        (_, false, _) => 1,
+        // Without Lc the extended marker byte precedes Le instead.
+        (_, true, 0) => 3,
+        (_, true, _) => 2,
+    };
+
+    Some(4 + lc_length + data_length + le_length)
+}
+
+/// Encodes `command` into `buffer` as a command APDU requesting
+/// `expected_length` response bytes, returning the encoded bytes.
+pub fn encode<'b>(
+    command: &impl ApduCommand,
+    expected_length: usize,
+    buffer: &'b mut [u8],
+) -> Option<&'b [u8]> {
+    let length = encoded_length(command, expected_length)?;
+    let buffer = buffer.get_mut(..length)?;
+
+    let data = command.data();
+    let extended = is_extended(data.len(), expected_length);
+    let (p1, p2) = command.parameters();
+
+    buffer[..4].copy_from_slice(&[command.class().to_u8(), command.instruction(), p1, p2]);
+
+    let mut offset = 4;
+
+    if extended {
+        buffer[offset] = 0x00;
+        offset += 1;
+    }
+
+    if !data.is_empty() {
+        if extended {
+            buffer[offset..offset + 2].copy_from_slice(&(data.len() as u16).to_be_bytes());
+            offset += 2;
+        } else {
+            buffer[offset] = data.len() as u8;
+            offset += 1;
+        }
+
+        buffer[offset..offset + data.len()].copy_from_slice(data);
+        offset += data.len();
+    }
+
+    if expected_length != 0 {
+        // The maximum length is encoded as zero in both forms.
+        if extended {
+            buffer[offset..offset + 2].copy_from_slice(&(expected_length as u16).to_be_bytes());
+        } else {
+            buffer[offset] = expected_length as u8;
+        }
+    }
+
+    Some(buffer)
+}
+
+/// The parts of a decoded command APDU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedCommand<'a> {
+    /// CLA, INS, P1 and P2.
+    pub header: [u8; 4],
+    pub data: &'a [u8],
+    /// Ne, with an encoded zero already expanded to 256 or 65536.
+    pub expected_length: usize,
+}
+
+/// Decodes a command APDU in short or extended form, or returns `None` if
+/// its length fields do not match its size.
+pub fn decode(encoded: &[u8]) -> Option<DecodedCommand<'_>> {
+    let (header, body) = encoded.split_first_chunk::<4>()?;
+
+    let short_le = |le: u8| if le == 0 { 256 } else { le as usize };
+    let extended_le = |le: &[u8]| match u16::from_be_bytes([le[0], le[1]]) {
+        0 => 65536,
+        le => le as usize,
+    };
+
+    let (data, expected_length) = match *body {
+        [] => (&body[..0], 0),
+        [le] => (&body[..0], short_le(le)),
+        [0x00, _, _] => (&body[..0], extended_le(&body[1..])),
+        [0x00, lc1, lc2, ref rest @ ..] if u16::from_be_bytes([lc1, lc2]) != 0 => {
+            let lc = u16::from_be_bytes([lc1, lc2]) as usize;
+
+            match rest.len().checked_sub(lc)? {
+                0 => (rest, 0),
+                2 => (&rest[..lc], extended_le(&rest[lc..])),
+                _ => return None,
+            }
+        }
+        [lc, ref rest @ ..] if lc != 0 => {
+            let lc = lc as usize;
+
+            match rest.len().checked_sub(lc)? {
+                0 => (rest, 0),
+                1 => (&rest[..lc], short_le(rest[lc])),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    Some(DecodedCommand {
+        header: *header,
+        data,
+        expected_length,
+    })
+}
@@ -0,0 +1,260 @@
+//! The subset of CBOR (RFC 8949) CTAP2 uses: integers, byte and text
+//! strings, arrays, maps, booleans and null, with definite lengths only.
+//! Maps are encoded in the canonical key order CTAP2 requires.
+
+use alloc::{string::String, vec::Vec};
+
+/// How deeply arrays and maps may nest in decoded data.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Unsigned(u64),
+    /// The negative integer `-1 - n`.
+    Negative(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    pub fn integer(value: i64) -> Self {
+        match value {
+            0.. => Self::Unsigned(value as u64),
+            _ => Self::Negative(!value as u64),
+        }
+    }
+
+    pub fn text(value: &str) -> Self {
+        Self::Text(value.into())
+    }
+
+    pub fn bytes(value: &[u8]) -> Self {
+        Self::Bytes(value.to_vec())
+    }
+
+    /// A map with integer keys, as CTAP2 parameters are.
+    pub fn map(entries: impl IntoIterator<Item = (i64, Value)>) -> Self {
+        Self::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (Self::integer(key), value))
+                .collect(),
+        )
+    }
+
+    /// Returns `None` for anything but an integer that fits an `i64`.
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            Self::Unsigned(value) => i64::try_from(value).ok(),
+            Self::Negative(value) => i64::try_from(value).ok().map(|value| -1 - value),
+            _ => None,
+        }
+    }
+
+    pub fn as_unsigned(&self) -> Option<u64> {
+        match *self {
+            Self::Unsigned(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&[(Value, Value)]> {
+        match self {
+            Self::Map(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Self::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value of the integer key `key`, if this is a map holding it.
+    pub fn get(&self, key: i64) -> Option<&Value> {
+        self.as_map()?
+            .iter()
+            .find(|(entry, _)| entry.as_integer() == Some(key))
+            .map(|(_, value)| value)
+    }
+
+    /// The value of the text key `key`, if this is a map holding it.
+    pub fn get_text(&self, key: &str) -> Option<&Value> {
+        self.as_map()?
+            .iter()
+            .find(|(entry, _)| entry.as_text() == Some(key))
+            .map(|(_, value)| value)
+    }
+
+    /// Appends the encoding to `out`. Map entries are sorted by their
+    /// encoded keys, shorter first, which is the CTAP2 canonical order.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Unsigned(value) => header(0, *value, out),
+            Self::Negative(value) => header(1, *value, out),
+            Self::Bytes(value) => {
+                header(2, value.len() as u64, out);
+                out.extend_from_slice(value);
+            }
+            Self::Text(value) => {
+                header(3, value.len() as u64, out);
+                out.extend_from_slice(value.as_bytes());
+            }
+            Self::Array(values) => {
+                header(4, values.len() as u64, out);
+                for value in values {
+                    value.encode(out);
+                }
+            }
+            Self::Map(entries) => {
+                let mut encoded: Vec<(Vec<u8>, &Value)> = entries
+                    .iter()
+                    .map(|(key, value)| (key.to_vec(), value))
+                    .collect();
+                encoded.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+                header(5, entries.len() as u64, out);
+                for (key, value) in encoded {
+                    out.extend_from_slice(&key);
+                    value.encode(out);
+                }
+            }
+            Self::Bool(false) => out.push(0xF4),
+            Self::Bool(true) => out.push(0xF5),
+            Self::Null => out.push(0xF6),
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    /// Splits the first value off `data`. Returns `None` if it is truncated
+    /// or uses what this codec leaves out: floats, tags, indefinite lengths
+    /// or nesting deeper than 16 levels.
+    pub fn decode(data: &[u8]) -> Option<(Self, &[u8])> {
+        decode(data, MAX_DEPTH)
+    }
+}
+
+fn header(major: u8, value: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xFF => out.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xFFFF => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x10000..=0xFFFF_FFFF => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+fn decode(data: &[u8], depth: usize) -> Option<(Value, &[u8])> {
+    let (&initial, rest) = data.split_first()?;
+    let major = initial >> 5;
+    let additional = initial & 0x1F;
+
+    let (argument, mut rest) = match additional {
+        0..=23 => (additional as u64, rest),
+        24..=27 => {
+            let (bytes, rest) = rest.split_at_checked(1 << (additional - 24))?;
+            let argument = bytes
+                .iter()
+                .fold(0u64, |argument, &byte| (argument << 8) | byte as u64);
+            (argument, rest)
+        }
+        _ => return None,
+    };
+
+    let value = match major {
+        0 => Value::Unsigned(argument),
+        1 => Value::Negative(argument),
+        2 | 3 => {
+            let length = usize::try_from(argument).ok()?;
+            let (bytes, remainder) = rest.split_at_checked(length)?;
+            rest = remainder;
+
+            match major {
+                2 => Value::Bytes(bytes.to_vec()),
+                _ => Value::Text(String::from(core::str::from_utf8(bytes).ok()?)),
+            }
+        }
+        4 | 5 => {
+            let depth = depth.checked_sub(1)?;
+            let count = usize::try_from(argument).ok()?;
+
+            // Every item takes at least a byte, which bounds the allocation.
+            if count > rest.len() {
+                return None;
+            }
+
+            let mut values = Vec::with_capacity(count);
+            let mut entries = Vec::with_capacity(if major == 5 { count } else { 0 });
+
+            for _ in 0..count {
+                let (first, remainder) = decode(rest, depth)?;
+                rest = remainder;
+
+                if major == 4 {
+                    values.push(first);
+                } else {
+                    let (second, remainder) = decode(rest, depth)?;
+                    rest = remainder;
+                    entries.push((first, second));
+                }
+            }
+
+            match major {
+                4 => Value::Array(values),
+                _ => Value::Map(entries),
+            }
+        }
+        7 => match additional {
+            20 => Value::Bool(false),
+            21 => Value::Bool(true),
+            22 => Value::Null,
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some((value, rest))
+}
@@ -0,0 +1,513 @@
+//! A client for CTAP2 authenticators over NFC, running their commands and
+//! polling while they wait for the user.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::ops::ControlFlow;
+
+#[cfg(feature = "ctap2-pin")]
+use crate::apdu::fido::ctap2::{
+    command::RelyingParty,
+    pin::{self, KeyAgreement, PinUvAuthProtocol, PinUvAuthToken, SharedSecret},
+};
+use crate::apdu::{
+    fido::{
+        FIDO_AID,
+        cbor::Value,
+        ctap2::{
+            command::{
+                self, Assertion, Attestation, ClientPin, ClientPinResponse, CredentialManagement,
+                CredentialManagementResponse, GetAssertion, Info, MakeCredential,
+            },
+            operation::{KeepaliveStatus, NfcCtapGetResponse, NfcCtapMsg, NfcCtapResponse},
+            status,
+        },
+    },
+    iso_7816::{
+        operation::select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::ApduTransport,
+};
+
+#[derive(Debug)]
+pub enum Ctap2Error<E> {
+    Transport(Iso7816TransportError<E>),
+    /// The authenticator refused the APDU itself.
+    Rejected(ApduStatus),
+    /// The authenticator answered with a CTAP2 error, one of
+    /// [`status`].
+    Ctap(u8),
+    MalformedResponse,
+    /// The keepalive callback stopped polling before the authenticator
+    /// replied.
+    Cancelled,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for Ctap2Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Rejected(status) => write!(f, "authenticator rejected the command: {status}"),
+            Self::Ctap(status) => write!(f, "authenticator returned CTAP2 error {status:#04X}"),
+            Self::MalformedResponse => write!(f, "malformed CTAP2 response"),
+            Self::Cancelled => write!(f, "cancelled while waiting for the authenticator"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for Ctap2Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E> From<Iso7816TransportError<E>> for Ctap2Error<E> {
+    fn from(e: Iso7816TransportError<E>) -> Self {
+        Self::Transport(e)
+    }
+}
+
+impl<E> From<ApduResponse<'_>> for Ctap2Error<E> {
+    fn from(response: ApduResponse<'_>) -> Self {
+        Self::Rejected(response.status())
+    }
+}
+
+/// A CTAP2 authenticator.
+pub struct Ctap2<T: ApduTransport> {
+    transport: Iso7816Transport<T>,
+    keepalive: Option<Box<dyn FnMut(KeepaliveStatus) -> ControlFlow<()>>>,
+}
+
+impl<T: ApduTransport> Ctap2<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport: Iso7816Transport::new(transport),
+            keepalive: None,
+        }
+    }
+
+    /// Calls `keepalive` each time the authenticator reports that it is
+    /// still working, such as to ask the user to touch it, before polling
+    /// again. Returning [`ControlFlow::Break`] stops polling and fails the
+    /// command with [`Ctap2Error::Cancelled`]; it is also the place to wait
+    /// between polls or give up after a timeout.
+    ///
+    /// Without it, polling continues until the authenticator replies.
+    pub fn with_keepalive(
+        mut self,
+        keepalive: impl FnMut(KeepaliveStatus) -> ControlFlow<()> + 'static,
+    ) -> Self {
+        self.keepalive = Some(Box::new(keepalive));
+        self
+    }
+
+    pub fn inner(&self) -> &Iso7816Transport<T> {
+        &self.transport
+    }
+
+    pub fn inner_mut(&mut self) -> &mut Iso7816Transport<T> {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport.into_inner()
+    }
+
+    /// Selects the FIDO application and returns the version it reports:
+    /// `FIDO_2_0` for CTAP2-only authenticators, `U2F_V2` for those that
+    /// speak both.
+    pub async fn select(&mut self) -> Result<Vec<u8>, Ctap2Error<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(
+                Iso7816Select::new(
+                    Iso7816SelectResolution::ByApplicationIdentifier(FIDO_AID),
+                    &mut [],
+                ),
+                &mut response,
+            )
+            .await??;
+
+        Ok(response)
+    }
+
+    /// Sends `command` and polls until the authenticator replies, then
+    /// returns the reply's CBOR, if it has any.
+    async fn call(
+        &mut self,
+        command: u8,
+        parameters: Option<&Value>,
+    ) -> Result<Option<Value>, Ctap2Error<T::TransportError>> {
+        let mut response = Vec::new();
+        let mut reply = self
+            .transport
+            .execute_streaming(NfcCtapMsg::new(command, parameters), &mut response)
+            .await??;
+
+        while let NfcCtapResponse::Keepalive(status) = reply {
+            if let Some(keepalive) = &mut self.keepalive
+                && keepalive(status).is_break()
+            {
+                return Err(Ctap2Error::Cancelled);
+            }
+
+            response.clear();
+            reply = self
+                .transport
+                .execute_streaming(NfcCtapGetResponse, &mut response)
+                .await??;
+        }
+
+        let NfcCtapResponse::Complete([code, data @ ..]) = reply else {
+            return Err(Ctap2Error::MalformedResponse);
+        };
+
+        if *code != status::OK {
+            return Err(Ctap2Error::Ctap(*code));
+        }
+        if data.is_empty() {
+            return Ok(None);
+        }
+
+        match Value::decode(data) {
+            Some((value, [])) => Ok(Some(value)),
+            _ => Err(Ctap2Error::MalformedResponse),
+        }
+    }
+
+    /// Runs `command` and parses the CBOR map it must reply with.
+    async fn request<R>(
+        &mut self,
+        command: u8,
+        parameters: Option<&Value>,
+        parse: impl FnOnce(&Value) -> Option<R>,
+    ) -> Result<R, Ctap2Error<T::TransportError>> {
+        self.call(command, parameters)
+            .await?
+            .as_ref()
+            .and_then(parse)
+            .ok_or(Ctap2Error::MalformedResponse)
+    }
+
+    pub async fn get_info(&mut self) -> Result<Info, Ctap2Error<T::TransportError>> {
+        self.request(command::GET_INFO, None, Info::parse).await
+    }
+
+    /// Creates a credential. The authenticator holds the reply until the
+    /// user touches it.
+    pub async fn make_credential(
+        &mut self,
+        request: &MakeCredential,
+    ) -> Result<Attestation, Ctap2Error<T::TransportError>> {
+        let parameters = request.to_value();
+        self.request(
+            command::MAKE_CREDENTIAL,
+            Some(&parameters),
+            Attestation::parse,
+        )
+        .await
+    }
+
+    /// Signs with the first matching credential; more are fetched with
+    /// [`Self::get_next_assertion`] if
+    /// [`Assertion::number_of_credentials`] says there are.
+    pub async fn get_assertion(
+        &mut self,
+        request: &GetAssertion,
+    ) -> Result<Assertion, Ctap2Error<T::TransportError>> {
+        let parameters = request.to_value();
+        self.request(command::GET_ASSERTION, Some(&parameters), Assertion::parse)
+            .await
+    }
+
+    pub async fn get_next_assertion(&mut self) -> Result<Assertion, Ctap2Error<T::TransportError>> {
+        self.request(command::GET_NEXT_ASSERTION, None, Assertion::parse)
+            .await
+    }
+
+    pub async fn client_pin(
+        &mut self,
+        request: &ClientPin,
+    ) -> Result<ClientPinResponse, Ctap2Error<T::TransportError>> {
+        let parameters = request.to_value();
+        match self.call(command::CLIENT_PIN, Some(&parameters)).await? {
+            Some(value) => ClientPinResponse::parse(&value).ok_or(Ctap2Error::MalformedResponse),
+            None => Ok(ClientPinResponse::default()),
+        }
+    }
+
+    /// How many PIN attempts are left.
+    pub async fn pin_retries(
+        &mut self,
+        protocol: u8,
+    ) -> Result<u64, Ctap2Error<T::TransportError>> {
+        self.client_pin(&ClientPin::get_pin_retries(protocol))
+            .await?
+            .pin_retries
+            .ok_or(Ctap2Error::MalformedResponse)
+    }
+
+    pub async fn credential_management(
+        &mut self,
+        request: &CredentialManagement,
+    ) -> Result<CredentialManagementResponse, Ctap2Error<T::TransportError>> {
+        let parameters = request.to_value();
+        match self
+            .call(command::CREDENTIAL_MANAGEMENT, Some(&parameters))
+            .await?
+        {
+            Some(value) => {
+                CredentialManagementResponse::parse(&value).ok_or(Ctap2Error::MalformedResponse)
+            }
+            None => Ok(CredentialManagementResponse::default()),
+        }
+    }
+
+    /// Deletes every credential and the PIN. Authenticators only accept it
+    /// shortly after power-up, and wait for the user to touch them.
+    pub async fn reset(&mut self) -> Result<(), Ctap2Error<T::TransportError>> {
+        self.call(command::RESET, None).await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ctap2-pin")]
+impl<T: ApduTransport> Ctap2<T> {
+    /// Agrees on a shared secret with the authenticator's key agreement
+    /// key, and returns it with the platform key as a COSE key.
+    pub async fn shared_secret(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+        key_agreement: &mut impl KeyAgreement,
+    ) -> Result<(SharedSecret, Value), Ctap2Error<T::TransportError>> {
+        let response = self
+            .client_pin(&ClientPin::get_key_agreement(protocol.to_u8()))
+            .await?;
+        let (x, y) = response
+            .key_agreement
+            .as_ref()
+            .and_then(pin::parse_cose_key)
+            .ok_or(Ctap2Error::MalformedResponse)?;
+        let z = key_agreement
+            .shared_secret(&x, &y)
+            .ok_or(Ctap2Error::MalformedResponse)?;
+
+        let (x, y) = key_agreement.public_key();
+        Ok((SharedSecret::new(protocol, &z), pin::cose_key(&x, &y)))
+    }
+
+    /// Sets the PIN of an authenticator that has none. A PIN that is not 4
+    /// to 63 bytes long fails with [`status::PIN_POLICY_VIOLATION`], as the
+    /// authenticator would.
+    pub async fn set_pin(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+        key_agreement: &mut impl KeyAgreement,
+        pin: &str,
+    ) -> Result<(), Ctap2Error<T::TransportError>> {
+        let padded = pin::pad_pin(pin).ok_or(Ctap2Error::Ctap(status::PIN_POLICY_VIOLATION))?;
+        let (secret, platform_key) = self.shared_secret(protocol, key_agreement).await?;
+
+        let new_pin_enc = secret.encrypt(&random_iv(key_agreement), &padded);
+        let param = secret.authenticate(&new_pin_enc);
+
+        let request = ClientPin::set_pin(protocol.to_u8(), platform_key, new_pin_enc, param);
+        self.client_pin(&request).await?;
+        Ok(())
+    }
+
+    /// Changes the PIN. A wrong `current` PIN fails with
+    /// [`status::PIN_INVALID`] and uses up an attempt.
+    pub async fn change_pin(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+        key_agreement: &mut impl KeyAgreement,
+        current: &str,
+        new: &str,
+    ) -> Result<(), Ctap2Error<T::TransportError>> {
+        let padded = pin::pad_pin(new).ok_or(Ctap2Error::Ctap(status::PIN_POLICY_VIOLATION))?;
+        let (secret, platform_key) = self.shared_secret(protocol, key_agreement).await?;
+
+        let pin_hash_enc = secret.encrypt(&random_iv(key_agreement), &pin::pin_hash(current));
+        let new_pin_enc = secret.encrypt(&random_iv(key_agreement), &padded);
+
+        let mut message = new_pin_enc.clone();
+        message.extend_from_slice(&pin_hash_enc);
+        let param = secret.authenticate(&message);
+
+        let request = ClientPin::change_pin(
+            protocol.to_u8(),
+            platform_key,
+            pin_hash_enc,
+            new_pin_enc,
+            param,
+        );
+        self.client_pin(&request).await?;
+        Ok(())
+    }
+
+    /// Exchanges the PIN for a token with every permission, the CTAP 2.0
+    /// way.
+    pub async fn pin_token(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+        key_agreement: &mut impl KeyAgreement,
+        pin: &str,
+    ) -> Result<PinUvAuthToken, Ctap2Error<T::TransportError>> {
+        let (secret, platform_key) = self.shared_secret(protocol, key_agreement).await?;
+        let pin_hash_enc = secret.encrypt(&random_iv(key_agreement), &pin::pin_hash(pin));
+
+        let request = ClientPin::get_pin_token(protocol.to_u8(), platform_key, pin_hash_enc);
+        self.decrypt_token(&secret, &request).await
+    }
+
+    /// Exchanges the PIN for a token limited to `permissions`, one or more
+    /// of [`command::permission`], and to `relying_party_id` if given.
+    pub async fn pin_uv_auth_token(
+        &mut self,
+        protocol: PinUvAuthProtocol,
+        key_agreement: &mut impl KeyAgreement,
+        pin: &str,
+        permissions: u8,
+        relying_party_id: Option<&str>,
+    ) -> Result<PinUvAuthToken, Ctap2Error<T::TransportError>> {
+        let (secret, platform_key) = self.shared_secret(protocol, key_agreement).await?;
+        let pin_hash_enc = secret.encrypt(&random_iv(key_agreement), &pin::pin_hash(pin));
+
+        let request = ClientPin::get_pin_uv_auth_token(
+            protocol.to_u8(),
+            platform_key,
+            pin_hash_enc,
+            permissions,
+            relying_party_id,
+        );
+        self.decrypt_token(&secret, &request).await
+    }
+
+    async fn decrypt_token(
+        &mut self,
+        secret: &SharedSecret,
+        request: &ClientPin,
+    ) -> Result<PinUvAuthToken, Ctap2Error<T::TransportError>> {
+        let token = self
+            .client_pin(request)
+            .await?
+            .pin_uv_auth_token
+            .and_then(|token| secret.decrypt(&token))
+            .ok_or(Ctap2Error::MalformedResponse)?;
+
+        Ok(PinUvAuthToken::new(secret.protocol(), token))
+    }
+
+    /// Runs a credential management subcommand authenticated with `token`.
+    async fn manage_credentials(
+        &mut self,
+        token: &PinUvAuthToken,
+        request: CredentialManagement,
+    ) -> Result<CredentialManagementResponse, Ctap2Error<T::TransportError>> {
+        let param = token.authenticate(&request.auth_message());
+        let request = request.with_pin_uv_auth(token.protocol().to_u8(), param);
+        self.credential_management(&request).await
+    }
+
+    /// How many discoverable credentials the authenticator holds, and how
+    /// many more it has room for.
+    pub async fn credentials_metadata(
+        &mut self,
+        token: &PinUvAuthToken,
+    ) -> Result<(u64, u64), Ctap2Error<T::TransportError>> {
+        let response = self
+            .manage_credentials(token, CredentialManagement::get_creds_metadata())
+            .await?;
+
+        response
+            .existing_resident_credentials_count
+            .zip(response.max_possible_remaining_resident_credentials_count)
+            .ok_or(Ctap2Error::MalformedResponse)
+    }
+
+    /// The relying parties with discoverable credentials, and the hashes of
+    /// their identifiers.
+    pub async fn relying_parties(
+        &mut self,
+        token: &PinUvAuthToken,
+    ) -> Result<Vec<(RelyingParty, [u8; 32])>, Ctap2Error<T::TransportError>> {
+        let first = match self
+            .manage_credentials(token, CredentialManagement::enumerate_rps_begin())
+            .await
+        {
+            Err(Ctap2Error::Ctap(status::NO_CREDENTIALS)) => return Ok(Vec::new()),
+            result => result?,
+        };
+
+        let total = first.total_rps.ok_or(Ctap2Error::MalformedResponse)?;
+        let mut relying_parties = Vec::new();
+        let mut response = first;
+
+        loop {
+            let relying_party = response
+                .relying_party
+                .zip(response.rp_id_hash)
+                .ok_or(Ctap2Error::MalformedResponse)?;
+            relying_parties.push(relying_party);
+
+            if relying_parties.len() as u64 >= total {
+                return Ok(relying_parties);
+            }
+
+            response = self
+                .credential_management(&CredentialManagement::enumerate_rps_get_next_rp())
+                .await?;
+        }
+    }
+
+    /// The discoverable credentials of the relying party whose identifier
+    /// hashes to `rp_id_hash`.
+    pub async fn credentials(
+        &mut self,
+        token: &PinUvAuthToken,
+        rp_id_hash: &[u8; 32],
+    ) -> Result<Vec<CredentialManagementResponse>, Ctap2Error<T::TransportError>> {
+        let request = CredentialManagement::enumerate_credentials_begin(rp_id_hash);
+        let first = match self.manage_credentials(token, request).await {
+            Err(Ctap2Error::Ctap(status::NO_CREDENTIALS)) => return Ok(Vec::new()),
+            result => result?,
+        };
+
+        let total = first
+            .total_credentials
+            .ok_or(Ctap2Error::MalformedResponse)?;
+        let mut credentials = Vec::from([first]);
+
+        while (credentials.len() as u64) < total {
+            let request = CredentialManagement::enumerate_credentials_get_next_credential();
+            credentials.push(self.credential_management(&request).await?);
+        }
+
+        Ok(credentials)
+    }
+
+    pub async fn delete_credential(
+        &mut self,
+        token: &PinUvAuthToken,
+        credential_id: &[u8],
+    ) -> Result<(), Ctap2Error<T::TransportError>> {
+        let request = CredentialManagement::delete_credential(credential_id);
+        self.manage_credentials(token, request).await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ctap2-pin")]
+fn random_iv(key_agreement: &mut impl KeyAgreement) -> [u8; 16] {
+    let mut iv = [0; 16];
+    key_agreement.random(&mut iv);
+    iv
+}
@@ -0,0 +1,816 @@
+//! The CTAP2 authenticator commands: their parameters as CBOR maps and
+//! the responses they decode to.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::apdu::fido::cbor::Value;
+
+pub const MAKE_CREDENTIAL: u8 = 0x01;
+pub const GET_ASSERTION: u8 = 0x02;
+pub const GET_INFO: u8 = 0x04;
+pub const CLIENT_PIN: u8 = 0x06;
+pub const RESET: u8 = 0x07;
+pub const GET_NEXT_ASSERTION: u8 = 0x08;
+pub const CREDENTIAL_MANAGEMENT: u8 = 0x0A;
+
+/// The COSE identifier of ES256, ECDSA with P-256 and SHA-256.
+pub const ES256: i64 = -7;
+/// The COSE identifier of EdDSA.
+pub const EDDSA: i64 = -8;
+
+fn text(value: Option<&Value>) -> Option<String> {
+    value?.as_text().map(String::from)
+}
+
+fn bytes(value: Option<&Value>) -> Option<Vec<u8>> {
+    value?.as_bytes().map(<[u8]>::to_vec)
+}
+
+fn credential_descriptor(id: &[u8]) -> Value {
+    Value::Map(Vec::from([
+        (Value::text("id"), Value::bytes(id)),
+        (Value::text("type"), Value::text("public-key")),
+    ]))
+}
+
+/// Appends the `uv` or `rk` options map if any option is set.
+fn options(rk: bool, up: Option<bool>, uv: bool) -> Option<Value> {
+    let mut options = Vec::new();
+
+    if rk {
+        options.push((Value::text("rk"), Value::Bool(true)));
+    }
+    if let Some(up) = up {
+        options.push((Value::text("up"), Value::Bool(up)));
+    }
+    if uv {
+        options.push((Value::text("uv"), Value::Bool(true)));
+    }
+
+    (!options.is_empty()).then_some(Value::Map(options))
+}
+
+/// The relying party a credential is scoped to.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+impl RelyingParty {
+    pub fn to_value(&self) -> Value {
+        let mut entries = Vec::from([(Value::text("id"), Value::text(&self.id))]);
+        if let Some(name) = &self.name {
+            entries.push((Value::text("name"), Value::text(name)));
+        }
+        Value::Map(entries)
+    }
+
+    pub fn parse(value: &Value) -> Option<Self> {
+        Some(Self {
+            id: text(value.get_text("id"))?,
+            name: text(value.get_text("name")),
+        })
+    }
+}
+
+/// The user account a credential belongs to.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct User {
+    pub id: Vec<u8>,
+    pub name: Option<String>,
+    pub display_name: Option<String>,
+}
+
+impl User {
+    pub fn to_value(&self) -> Value {
+        let mut entries = Vec::from([(Value::text("id"), Value::bytes(&self.id))]);
+        if let Some(name) = &self.name {
+            entries.push((Value::text("name"), Value::text(name)));
+        }
+        if let Some(display_name) = &self.display_name {
+            entries.push((Value::text("displayName"), Value::text(display_name)));
+        }
+        Value::Map(entries)
+    }
+
+    pub fn parse(value: &Value) -> Option<Self> {
+        Some(Self {
+            id: bytes(value.get_text("id"))?,
+            name: text(value.get_text("name")),
+            display_name: text(value.get_text("displayName")),
+        })
+    }
+}
+
+/// The parameters of authenticatorMakeCredential.
+#[derive(Clone, Debug)]
+pub struct MakeCredential {
+    client_data_hash: [u8; 32],
+    relying_party: RelyingParty,
+    user: User,
+    algorithms: Vec<i64>,
+    exclude_list: Vec<Vec<u8>>,
+    extensions: Option<Value>,
+    resident_key: bool,
+    user_verification: bool,
+    pin_uv_auth: Option<(u8, Vec<u8>)>,
+}
+
+impl MakeCredential {
+    /// Asks for an ES256 credential.
+    pub fn new(client_data_hash: [u8; 32], relying_party: RelyingParty, user: User) -> Self {
+        Self {
+            client_data_hash,
+            relying_party,
+            user,
+            algorithms: Vec::from([ES256]),
+            exclude_list: Vec::new(),
+            extensions: None,
+            resident_key: false,
+            user_verification: false,
+            pin_uv_auth: None,
+        }
+    }
+
+    /// The COSE algorithms the relying party accepts, most preferred first.
+    pub fn with_algorithms(mut self, algorithms: &[i64]) -> Self {
+        self.algorithms = algorithms.to_vec();
+        self
+    }
+
+    /// Credentials the authenticator must not already hold.
+    pub fn with_exclude_list(mut self, credential_ids: Vec<Vec<u8>>) -> Self {
+        self.exclude_list = credential_ids;
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: Value) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
+    /// Stores the credential on the authenticator, as a discoverable
+    /// credential.
+    pub fn with_resident_key(mut self) -> Self {
+        self.resident_key = true;
+        self
+    }
+
+    /// Asks for built-in user verification rather than a PIN.
+    pub fn with_user_verification(mut self) -> Self {
+        self.user_verification = true;
+        self
+    }
+
+    /// Proves user verification with a PIN/UV auth token: `param` is the
+    /// token's authentication of the client data hash.
+    pub fn with_pin_uv_auth(mut self, protocol: u8, param: Vec<u8>) -> Self {
+        self.pin_uv_auth = Some((protocol, param));
+        self
+    }
+
+    pub fn client_data_hash(&self) -> &[u8; 32] {
+        &self.client_data_hash
+    }
+
+    pub fn to_value(&self) -> Value {
+        let algorithms = self
+            .algorithms
+            .iter()
+            .map(|&algorithm| {
+                Value::Map(Vec::from([
+                    (Value::text("alg"), Value::integer(algorithm)),
+                    (Value::text("type"), Value::text("public-key")),
+                ]))
+            })
+            .collect();
+
+        let mut entries = Vec::from([
+            (1, Value::bytes(&self.client_data_hash)),
+            (2, self.relying_party.to_value()),
+            (3, self.user.to_value()),
+            (4, Value::Array(algorithms)),
+        ]);
+
+        if !self.exclude_list.is_empty() {
+            let excluded = self
+                .exclude_list
+                .iter()
+                .map(|id| credential_descriptor(id))
+                .collect();
+            entries.push((5, Value::Array(excluded)));
+        }
+        if let Some(extensions) = &self.extensions {
+            entries.push((6, extensions.clone()));
+        }
+        if let Some(options) = options(self.resident_key, None, self.user_verification) {
+            entries.push((7, options));
+        }
+        if let Some((protocol, param)) = &self.pin_uv_auth {
+            entries.push((8, Value::bytes(param)));
+            entries.push((9, Value::integer(*protocol as i64)));
+        }
+
+        Value::map(entries)
+    }
+}
+
+/// The parameters of authenticatorGetAssertion.
+#[derive(Clone, Debug)]
+pub struct GetAssertion {
+    relying_party_id: String,
+    client_data_hash: [u8; 32],
+    allow_list: Vec<Vec<u8>>,
+    extensions: Option<Value>,
+    user_presence: Option<bool>,
+    user_verification: bool,
+    pin_uv_auth: Option<(u8, Vec<u8>)>,
+}
+
+impl GetAssertion {
+    pub fn new(relying_party_id: &str, client_data_hash: [u8; 32]) -> Self {
+        Self {
+            relying_party_id: relying_party_id.into(),
+            client_data_hash,
+            allow_list: Vec::new(),
+            extensions: None,
+            user_presence: None,
+            user_verification: false,
+            pin_uv_auth: None,
+        }
+    }
+
+    /// The credentials to use. Without them, the authenticator uses its
+    /// discoverable credentials for the relying party.
+    pub fn with_allow_list(mut self, credential_ids: Vec<Vec<u8>>) -> Self {
+        self.allow_list = credential_ids;
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: Value) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
+    /// Skips the user presence test, for silent credential probing.
+    pub fn without_user_presence(mut self) -> Self {
+        self.user_presence = Some(false);
+        self
+    }
+
+    pub fn with_user_verification(mut self) -> Self {
+        self.user_verification = true;
+        self
+    }
+
+    /// Proves user verification with a PIN/UV auth token: `param` is the
+    /// token's authentication of the client data hash.
+    pub fn with_pin_uv_auth(mut self, protocol: u8, param: Vec<u8>) -> Self {
+        self.pin_uv_auth = Some((protocol, param));
+        self
+    }
+
+    pub fn client_data_hash(&self) -> &[u8; 32] {
+        &self.client_data_hash
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut entries = Vec::from([
+            (1, Value::text(&self.relying_party_id)),
+            (2, Value::bytes(&self.client_data_hash)),
+        ]);
+
+        if !self.allow_list.is_empty() {
+            let allowed = self
+                .allow_list
+                .iter()
+                .map(|id| credential_descriptor(id))
+                .collect();
+            entries.push((3, Value::Array(allowed)));
+        }
+        if let Some(extensions) = &self.extensions {
+            entries.push((4, extensions.clone()));
+        }
+        if let Some(options) = options(false, self.user_presence, self.user_verification) {
+            entries.push((5, options));
+        }
+        if let Some((protocol, param)) = &self.pin_uv_auth {
+            entries.push((6, Value::bytes(param)));
+            entries.push((7, Value::integer(*protocol as i64)));
+        }
+
+        Value::map(entries)
+    }
+}
+
+/// What authenticatorGetInfo reports.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Info {
+    /// `FIDO_2_0`, `FIDO_2_1` and `U2F_V2` among others.
+    pub versions: Vec<String>,
+    pub extensions: Vec<String>,
+    pub aaguid: [u8; 16],
+    pub options: Vec<(String, bool)>,
+    pub max_message_size: Option<u64>,
+    pub pin_uv_auth_protocols: Vec<u64>,
+    pub max_credential_count_in_list: Option<u64>,
+    pub max_credential_id_length: Option<u64>,
+    /// The COSE algorithms the authenticator supports.
+    pub algorithms: Vec<i64>,
+    pub firmware_version: Option<u64>,
+}
+
+impl Info {
+    pub fn parse(value: &Value) -> Option<Self> {
+        let texts = |key| {
+            value
+                .get(key)
+                .and_then(Value::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|value| value.as_text().map(String::from))
+                .collect()
+        };
+
+        Some(Self {
+            versions: texts(1),
+            extensions: texts(2),
+            aaguid: value.get(3)?.as_bytes()?.try_into().ok()?,
+            options: value
+                .get(4)
+                .and_then(Value::as_map)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|(key, value)| Some((key.as_text()?.into(), value.as_bool()?)))
+                .collect(),
+            max_message_size: value.get(5).and_then(Value::as_unsigned),
+            pin_uv_auth_protocols: value
+                .get(6)
+                .and_then(Value::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(Value::as_unsigned)
+                .collect(),
+            max_credential_count_in_list: value.get(7).and_then(Value::as_unsigned),
+            max_credential_id_length: value.get(8).and_then(Value::as_unsigned),
+            algorithms: value
+                .get(0x0A)
+                .and_then(Value::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|algorithm| algorithm.get_text("alg")?.as_integer())
+                .collect(),
+            firmware_version: value.get(0x0E).and_then(Value::as_unsigned),
+        })
+    }
+
+    /// The option `name`, or `None` if the authenticator does not support
+    /// it.
+    pub fn option(&self, name: &str) -> Option<bool> {
+        self.options
+            .iter()
+            .find(|(option, _)| option == name)
+            .map(|&(_, value)| value)
+    }
+}
+
+/// The credential an authenticator data block attests to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttestedCredential {
+    pub aaguid: [u8; 16],
+    pub credential_id: Vec<u8>,
+    /// The public key as a COSE key.
+    pub public_key: Value,
+}
+
+/// The authenticator data makeCredential and getAssertion sign.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthenticatorData {
+    pub rp_id_hash: [u8; 32],
+    pub flags: u8,
+    pub sign_count: u32,
+    pub attested_credential: Option<AttestedCredential>,
+    pub extensions: Option<Value>,
+}
+
+impl AuthenticatorData {
+    pub const USER_PRESENT: u8 = 0x01;
+    pub const USER_VERIFIED: u8 = 0x04;
+    pub const ATTESTED_CREDENTIAL: u8 = 0x40;
+    pub const EXTENSIONS: u8 = 0x80;
+
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (rp_id_hash, rest) = data.split_first_chunk::<32>()?;
+        let (&flags, rest) = rest.split_first()?;
+        let (sign_count, mut rest) = rest.split_first_chunk::<4>()?;
+
+        let attested_credential = match flags & Self::ATTESTED_CREDENTIAL {
+            0 => None,
+            _ => {
+                let (aaguid, remainder) = rest.split_first_chunk::<16>()?;
+                let (length, remainder) = remainder.split_first_chunk::<2>()?;
+                let (credential_id, remainder) =
+                    remainder.split_at_checked(u16::from_be_bytes(*length) as usize)?;
+                let (public_key, remainder) = Value::decode(remainder)?;
+                rest = remainder;
+
+                Some(AttestedCredential {
+                    aaguid: *aaguid,
+                    credential_id: credential_id.to_vec(),
+                    public_key,
+                })
+            }
+        };
+
+        let extensions = match flags & Self::EXTENSIONS {
+            0 => None,
+            _ => {
+                let (extensions, remainder) = Value::decode(rest)?;
+                rest = remainder;
+                Some(extensions)
+            }
+        };
+
+        rest.is_empty().then_some(Self {
+            rp_id_hash: *rp_id_hash,
+            flags,
+            sign_count: u32::from_be_bytes(*sign_count),
+            attested_credential,
+            extensions,
+        })
+    }
+
+    pub fn user_present(&self) -> bool {
+        self.flags & Self::USER_PRESENT != 0
+    }
+
+    pub fn user_verified(&self) -> bool {
+        self.flags & Self::USER_VERIFIED != 0
+    }
+}
+
+/// What authenticatorMakeCredential returns: the attestation object.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attestation {
+    /// The attestation statement format, such as `packed` or `none`.
+    pub format: String,
+    /// The authenticator data as signed.
+    pub auth_data: Vec<u8>,
+    pub authenticator_data: AuthenticatorData,
+    pub statement: Value,
+}
+
+impl Attestation {
+    pub fn parse(value: &Value) -> Option<Self> {
+        let auth_data = bytes(value.get(2))?;
+
+        Some(Self {
+            format: text(value.get(1))?,
+            authenticator_data: AuthenticatorData::parse(&auth_data)?,
+            auth_data,
+            statement: value.get(3)?.clone(),
+        })
+    }
+}
+
+/// What authenticatorGetAssertion and authenticatorGetNextAssertion
+/// return.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Assertion {
+    /// The credential used, which the authenticator may leave out if the
+    /// allow list named only one.
+    pub credential_id: Option<Vec<u8>>,
+    pub auth_data: Vec<u8>,
+    pub authenticator_data: AuthenticatorData,
+    /// The signature over the authenticator data and the client data
+    /// hash.
+    pub signature: Vec<u8>,
+    /// The user of a discoverable credential.
+    pub user: Option<User>,
+    /// How many credentials matched, if there is more than one; the rest
+    /// are fetched with getNextAssertion.
+    pub number_of_credentials: Option<u64>,
+}
+
+impl Assertion {
+    pub fn parse(value: &Value) -> Option<Self> {
+        let auth_data = bytes(value.get(2))?;
+
+        Some(Self {
+            credential_id: bytes(
+                value
+                    .get(1)
+                    .and_then(|credential| credential.get_text("id")),
+            ),
+            authenticator_data: AuthenticatorData::parse(&auth_data)?,
+            auth_data,
+            signature: bytes(value.get(3))?,
+            user: value.get(4).and_then(User::parse),
+            number_of_credentials: value.get(5).and_then(Value::as_unsigned),
+        })
+    }
+}
+
+/// The parameters of authenticatorClientPIN. Building the encrypted and
+/// authenticated fields takes a PIN/UV auth protocol.
+#[derive(Clone, Debug)]
+pub struct ClientPin {
+    protocol: u8,
+    sub_command: u8,
+    key_agreement: Option<Value>,
+    pin_uv_auth_param: Option<Vec<u8>>,
+    new_pin_enc: Option<Vec<u8>>,
+    pin_hash_enc: Option<Vec<u8>>,
+    permissions: Option<u8>,
+    relying_party_id: Option<String>,
+}
+
+impl ClientPin {
+    pub const GET_PIN_RETRIES: u8 = 0x01;
+    pub const GET_KEY_AGREEMENT: u8 = 0x02;
+    pub const SET_PIN: u8 = 0x03;
+    pub const CHANGE_PIN: u8 = 0x04;
+    pub const GET_PIN_TOKEN: u8 = 0x05;
+    pub const GET_PIN_UV_AUTH_TOKEN_USING_PIN_WITH_PERMISSIONS: u8 = 0x09;
+
+    fn new(protocol: u8, sub_command: u8) -> Self {
+        Self {
+            protocol,
+            sub_command,
+            key_agreement: None,
+            pin_uv_auth_param: None,
+            new_pin_enc: None,
+            pin_hash_enc: None,
+            permissions: None,
+            relying_party_id: None,
+        }
+    }
+
+    pub fn get_pin_retries(protocol: u8) -> Self {
+        Self::new(protocol, Self::GET_PIN_RETRIES)
+    }
+
+    /// Asks for the authenticator's key agreement key.
+    pub fn get_key_agreement(protocol: u8) -> Self {
+        Self::new(protocol, Self::GET_KEY_AGREEMENT)
+    }
+
+    /// Sets the first PIN: `key_agreement` is the platform's COSE key,
+    /// `param` authenticates `new_pin_enc`.
+    pub fn set_pin(
+        protocol: u8,
+        key_agreement: Value,
+        new_pin_enc: Vec<u8>,
+        param: Vec<u8>,
+    ) -> Self {
+        Self {
+            key_agreement: Some(key_agreement),
+            new_pin_enc: Some(new_pin_enc),
+            pin_uv_auth_param: Some(param),
+            ..Self::new(protocol, Self::SET_PIN)
+        }
+    }
+
+    /// Changes the PIN: `param` authenticates `new_pin_enc` followed by
+    /// `pin_hash_enc`.
+    pub fn change_pin(
+        protocol: u8,
+        key_agreement: Value,
+        pin_hash_enc: Vec<u8>,
+        new_pin_enc: Vec<u8>,
+        param: Vec<u8>,
+    ) -> Self {
+        Self {
+            key_agreement: Some(key_agreement),
+            pin_hash_enc: Some(pin_hash_enc),
+            new_pin_enc: Some(new_pin_enc),
+            pin_uv_auth_param: Some(param),
+            ..Self::new(protocol, Self::CHANGE_PIN)
+        }
+    }
+
+    /// Exchanges the PIN for a token with every permission, as CTAP 2.0
+    /// authenticators do.
+    pub fn get_pin_token(protocol: u8, key_agreement: Value, pin_hash_enc: Vec<u8>) -> Self {
+        Self {
+            key_agreement: Some(key_agreement),
+            pin_hash_enc: Some(pin_hash_enc),
+            ..Self::new(protocol, Self::GET_PIN_TOKEN)
+        }
+    }
+
+    /// Exchanges the PIN for a token limited to `permissions`, and to a
+    /// relying party for those that need one.
+    pub fn get_pin_uv_auth_token(
+        protocol: u8,
+        key_agreement: Value,
+        pin_hash_enc: Vec<u8>,
+        permissions: u8,
+        relying_party_id: Option<&str>,
+    ) -> Self {
+        Self {
+            key_agreement: Some(key_agreement),
+            pin_hash_enc: Some(pin_hash_enc),
+            permissions: Some(permissions),
+            relying_party_id: relying_party_id.map(String::from),
+            ..Self::new(
+                protocol,
+                Self::GET_PIN_UV_AUTH_TOKEN_USING_PIN_WITH_PERMISSIONS,
+            )
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut entries = Vec::from([
+            (1, Value::integer(self.protocol as i64)),
+            (2, Value::integer(self.sub_command as i64)),
+        ]);
+
+        if let Some(key_agreement) = &self.key_agreement {
+            entries.push((3, key_agreement.clone()));
+        }
+        for (key, value) in [
+            (4, &self.pin_uv_auth_param),
+            (5, &self.new_pin_enc),
+            (6, &self.pin_hash_enc),
+        ] {
+            if let Some(value) = value {
+                entries.push((key, Value::bytes(value)));
+            }
+        }
+        if let Some(permissions) = self.permissions {
+            entries.push((9, Value::integer(permissions as i64)));
+        }
+        if let Some(relying_party_id) = &self.relying_party_id {
+            entries.push((0x0A, Value::text(relying_party_id)));
+        }
+
+        Value::map(entries)
+    }
+}
+
+/// The permissions of a PIN/UV auth token.
+pub mod permission {
+    pub const MAKE_CREDENTIAL: u8 = 0x01;
+    pub const GET_ASSERTION: u8 = 0x02;
+    pub const CREDENTIAL_MANAGEMENT: u8 = 0x04;
+    pub const BIO_ENROLLMENT: u8 = 0x08;
+    pub const LARGE_BLOB_WRITE: u8 = 0x10;
+    pub const AUTHENTICATOR_CONFIGURATION: u8 = 0x20;
+}
+
+/// What authenticatorClientPIN returns, depending on the subcommand.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct ClientPinResponse {
+    /// The authenticator's COSE key agreement key.
+    pub key_agreement: Option<Value>,
+    /// The token, encrypted with the shared secret.
+    pub pin_uv_auth_token: Option<Vec<u8>>,
+    pub pin_retries: Option<u64>,
+    /// Whether the authenticator must be power cycled before the PIN can
+    /// be tried again.
+    pub power_cycle_state: Option<bool>,
+    pub uv_retries: Option<u64>,
+}
+
+impl ClientPinResponse {
+    pub fn parse(value: &Value) -> Option<Self> {
+        value.as_map()?;
+
+        Some(Self {
+            key_agreement: value.get(1).cloned(),
+            pin_uv_auth_token: bytes(value.get(2)),
+            pin_retries: value.get(3).and_then(Value::as_unsigned),
+            power_cycle_state: value.get(4).and_then(Value::as_bool),
+            uv_retries: value.get(5).and_then(Value::as_unsigned),
+        })
+    }
+}
+
+/// The parameters of authenticatorCredentialManagement, which manages the
+/// discoverable credentials with a token that has the credential
+/// management permission.
+#[derive(Clone, Debug)]
+pub struct CredentialManagement {
+    sub_command: u8,
+    parameters: Option<Value>,
+    pin_uv_auth: Option<(u8, Vec<u8>)>,
+}
+
+impl CredentialManagement {
+    pub const GET_CREDS_METADATA: u8 = 0x01;
+    pub const ENUMERATE_RPS_BEGIN: u8 = 0x02;
+    pub const ENUMERATE_RPS_GET_NEXT_RP: u8 = 0x03;
+    pub const ENUMERATE_CREDENTIALS_BEGIN: u8 = 0x04;
+    pub const ENUMERATE_CREDENTIALS_GET_NEXT_CREDENTIAL: u8 = 0x05;
+    pub const DELETE_CREDENTIAL: u8 = 0x06;
+
+    fn new(sub_command: u8, parameters: Option<Value>) -> Self {
+        Self {
+            sub_command,
+            parameters,
+            pin_uv_auth: None,
+        }
+    }
+
+    pub fn get_creds_metadata() -> Self {
+        Self::new(Self::GET_CREDS_METADATA, None)
+    }
+
+    pub fn enumerate_rps_begin() -> Self {
+        Self::new(Self::ENUMERATE_RPS_BEGIN, None)
+    }
+
+    pub fn enumerate_rps_get_next_rp() -> Self {
+        Self::new(Self::ENUMERATE_RPS_GET_NEXT_RP, None)
+    }
+
+    pub fn enumerate_credentials_begin(rp_id_hash: &[u8; 32]) -> Self {
+        let parameters = Value::map([(1, Value::bytes(rp_id_hash))]);
+        Self::new(Self::ENUMERATE_CREDENTIALS_BEGIN, Some(parameters))
+    }
+
+    pub fn enumerate_credentials_get_next_credential() -> Self {
+        Self::new(Self::ENUMERATE_CREDENTIALS_GET_NEXT_CREDENTIAL, None)
+    }
+
+    pub fn delete_credential(credential_id: &[u8]) -> Self {
+        let parameters = Value::map([(2, credential_descriptor(credential_id))]);
+        Self::new(Self::DELETE_CREDENTIAL, Some(parameters))
+    }
+
+    /// What the token authenticates for `param`: the subcommand followed by
+    /// the encoded subcommand parameters.
+    pub fn auth_message(&self) -> Vec<u8> {
+        let mut message = Vec::from([self.sub_command]);
+        if let Some(parameters) = &self.parameters {
+            parameters.encode(&mut message);
+        }
+        message
+    }
+
+    /// Authenticates the command with a token: `param` is its
+    /// authentication of [`Self::auth_message`]. The get-next subcommands
+    /// need none.
+    pub fn with_pin_uv_auth(mut self, protocol: u8, param: Vec<u8>) -> Self {
+        self.pin_uv_auth = Some((protocol, param));
+        self
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut entries = Vec::from([(1, Value::integer(self.sub_command as i64))]);
+
+        if let Some(parameters) = &self.parameters {
+            entries.push((2, parameters.clone()));
+        }
+        if let Some((protocol, param)) = &self.pin_uv_auth {
+            entries.push((3, Value::integer(*protocol as i64)));
+            entries.push((4, Value::bytes(param)));
+        }
+
+        Value::map(entries)
+    }
+}
+
+/// What authenticatorCredentialManagement returns, depending on the
+/// subcommand.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct CredentialManagementResponse {
+    pub existing_resident_credentials_count: Option<u64>,
+    pub max_possible_remaining_resident_credentials_count: Option<u64>,
+    pub relying_party: Option<RelyingParty>,
+    pub rp_id_hash: Option<[u8; 32]>,
+    pub total_rps: Option<u64>,
+    pub user: Option<User>,
+    pub credential_id: Option<Vec<u8>>,
+    pub public_key: Option<Value>,
+    pub total_credentials: Option<u64>,
+}
+
+impl CredentialManagementResponse {
+    pub fn parse(value: &Value) -> Option<Self> {
+        value.as_map()?;
+
+        Some(Self {
+            existing_resident_credentials_count: value.get(1).and_then(Value::as_unsigned),
+            max_possible_remaining_resident_credentials_count: value
+                .get(2)
+                .and_then(Value::as_unsigned),
+            relying_party: value.get(3).and_then(RelyingParty::parse),
+            rp_id_hash: value
+                .get(4)
+                .and_then(Value::as_bytes)
+                .and_then(|hash| hash.try_into().ok()),
+            total_rps: value.get(5).and_then(Value::as_unsigned),
+            user: value.get(6).and_then(User::parse),
+            credential_id: bytes(
+                value
+                    .get(7)
+                    .and_then(|credential| credential.get_text("id")),
+            ),
+            public_key: value.get(8).cloned(),
+            total_credentials: value.get(9).and_then(Value::as_unsigned),
+        })
+    }
+}
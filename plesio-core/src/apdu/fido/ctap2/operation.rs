@@ -0,0 +1,104 @@
+//! NFCCTAP_MSG and NFCCTAP_GETRESPONSE, which carry CTAP2 messages in
+//! proprietary-class APDUs.
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    fido::cbor::Value,
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+    },
+    response::ApduResponse,
+    status::{ApduStatus, is},
+};
+
+/// The status an authenticator answers with while it is still working on
+/// a message; its data is the keepalive status.
+pub const KEEPALIVE: ApduStatus = ApduStatus::new(0x91, 0x00);
+
+/// What the authenticator reports while it works.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeepaliveStatus {
+    Processing,
+    /// The authenticator is waiting for the user to touch it.
+    UserPresenceNeeded,
+    Other(u8),
+}
+
+impl KeepaliveStatus {
+    pub fn from_u8(status: u8) -> Self {
+        match status {
+            0x01 => Self::Processing,
+            0x02 => Self::UserPresenceNeeded,
+            status => Self::Other(status),
+        }
+    }
+}
+
+/// What an NFCCTAP command gets back: either the authenticator's reply,
+/// starting with the CTAP status byte, or word that it is still working.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NfcCtapResponse<'s> {
+    Keepalive(KeepaliveStatus),
+    Complete(&'s [u8]),
+}
+
+fn parse<'s>(response: &ApduResponse<'s>) -> Result<NfcCtapResponse<'s>, ApduResponse<'s>> {
+    if response.status() == KEEPALIVE {
+        let status = response.data().first().copied().unwrap_or(0x01);
+        return Ok(NfcCtapResponse::Keepalive(KeepaliveStatus::from_u8(status)));
+    }
+
+    match response.expect_status(is(status::OK))? {
+        [] => Err(*response),
+        data => Ok(NfcCtapResponse::Complete(data)),
+    }
+}
+
+/// NFCCTAP_MSG, which sends a CTAP2 command and its CBOR parameters. It
+/// tells the authenticator that the reader polls with
+/// [`NfcCtapGetResponse`], so the authenticator may answer with
+/// [`KEEPALIVE`] rather than hold the field.
+pub struct NfcCtapMsg {
+    data: Vec<u8>,
+}
+
+impl NfcCtapMsg {
+    pub fn new(command: u8, parameters: Option<&Value>) -> Self {
+        let mut data = Vec::from([command]);
+        if let Some(parameters) = parameters {
+            parameters.encode(&mut data);
+        }
+        Self { data }
+    }
+}
+
+impl Iso7816StreamingOperation for NfcCtapMsg {
+    type Result<'s> = Result<NfcCtapResponse<'s>, ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class.with_proprietary(), 0x10, (0x80, 0x00), &self.data)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        parse(response)
+    }
+}
+
+/// NFCCTAP_GETRESPONSE, which polls for the reply to the last
+/// NFCCTAP_MSG.
+pub struct NfcCtapGetResponse;
+
+impl Iso7816StreamingOperation for NfcCtapGetResponse {
+    type Result<'s> = Result<NfcCtapResponse<'s>, ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class.with_proprietary(), 0x11, (0x00, 0x00), &[])
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        parse(response)
+    }
+}
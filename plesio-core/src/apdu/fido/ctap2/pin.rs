@@ -0,0 +1,262 @@
+//! PIN/UV auth protocols one and two: the shared secret an ECDH key
+//! agreement with the authenticator yields, the AES-256-CBC encryption of
+//! PINs and tokens under it, and the HMAC-SHA-256 authentication of
+//! commands.
+
+use aes::{
+    Aes256,
+    cipher::{BlockDecrypt, BlockEncrypt, KeyInit, generic_array::GenericArray},
+};
+use alloc::vec::Vec;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::apdu::{fido::cbor::Value, iso_7816::secure_messaging::constant_time_eq};
+
+/// How long a padded PIN is.
+const PADDED_PIN_LENGTH: usize = 64;
+
+/// The platform side of the P-256 key agreement, left to the caller along
+/// with the randomness protocol two needs.
+pub trait KeyAgreement {
+    /// The platform's public key as affine coordinates.
+    fn public_key(&self) -> ([u8; 32], [u8; 32]);
+
+    /// The x coordinate of the product of the platform's private key and
+    /// the authenticator's public key, or `None` if that is not a point on
+    /// the curve.
+    fn shared_secret(&mut self, x: &[u8; 32], y: &[u8; 32]) -> Option<[u8; 32]>;
+
+    /// Fills `buffer` with random bytes.
+    fn random(&mut self, buffer: &mut [u8]);
+}
+
+/// A P-256 key agreement key as a COSE key.
+pub fn cose_key(x: &[u8; 32], y: &[u8; 32]) -> Value {
+    Value::map([
+        (1, Value::integer(2)),
+        (3, Value::integer(-25)),
+        (-1, Value::integer(1)),
+        (-2, Value::bytes(x)),
+        (-3, Value::bytes(y)),
+    ])
+}
+
+/// The coordinates of a P-256 COSE key.
+pub fn parse_cose_key(key: &Value) -> Option<([u8; 32], [u8; 32])> {
+    if key.get(1)?.as_integer()? != 2 || key.get(-1)?.as_integer()? != 1 {
+        return None;
+    }
+
+    Some((
+        key.get(-2)?.as_bytes()?.try_into().ok()?,
+        key.get(-3)?.as_bytes()?.try_into().ok()?,
+    ))
+}
+
+/// The PIN padded with zeros to 64 bytes, or `None` if it is not between
+/// 4 and 63 bytes long.
+pub fn pad_pin(pin: &str) -> Option<[u8; PADDED_PIN_LENGTH]> {
+    if !(4..PADDED_PIN_LENGTH).contains(&pin.len()) {
+        return None;
+    }
+
+    let mut padded = [0; PADDED_PIN_LENGTH];
+    padded[..pin.len()].copy_from_slice(pin.as_bytes());
+    Some(padded)
+}
+
+/// The left half of the SHA-256 of the PIN, which the authenticator keeps.
+pub fn pin_hash(pin: &str) -> [u8; 16] {
+    let digest = Sha256::digest(pin.as_bytes());
+    digest[..16].try_into().expect("digest is 32 bytes")
+}
+
+fn hmac(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// HKDF-SHA-256 with a zero salt, for one block of output.
+fn hkdf(secret: &[u8], info: &[u8]) -> [u8; 32] {
+    let key = hmac(&[0; 32], &[secret]);
+    hmac(&key, &[info, &[0x01]])
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinUvAuthProtocol {
+    One,
+    Two,
+}
+
+impl PinUvAuthProtocol {
+    pub fn from_u8(protocol: u8) -> Option<Self> {
+        match protocol {
+            1 => Some(Self::One),
+            2 => Some(Self::Two),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::One => 1,
+            Self::Two => 2,
+        }
+    }
+
+    /// Authenticates `message` with `key`: the first 16 bytes of the HMAC
+    /// for protocol one, all of it for protocol two.
+    pub fn authenticate(self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        match self {
+            Self::One => hmac(key, &[message])[..16].to_vec(),
+            Self::Two => hmac(&key[..key.len().min(32)], &[message]).to_vec(),
+        }
+    }
+
+    pub fn verify(self, key: &[u8], message: &[u8], param: &[u8]) -> bool {
+        constant_time_eq(&self.authenticate(key, message), param)
+    }
+}
+
+/// The keys derived from a key agreement.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SharedSecret {
+    protocol: PinUvAuthProtocol,
+    hmac_key: [u8; 32],
+    aes_key: [u8; 32],
+}
+
+impl SharedSecret {
+    /// Derives the keys from `z`, the x coordinate of the shared point.
+    pub fn new(protocol: PinUvAuthProtocol, z: &[u8; 32]) -> Self {
+        match protocol {
+            PinUvAuthProtocol::One => {
+                let key = Sha256::digest(z).into();
+                Self {
+                    protocol,
+                    hmac_key: key,
+                    aes_key: key,
+                }
+            }
+            PinUvAuthProtocol::Two => Self {
+                protocol,
+                hmac_key: hkdf(z, b"CTAP2 HMAC key"),
+                aes_key: hkdf(z, b"CTAP2 AES key"),
+            },
+        }
+    }
+
+    pub fn protocol(&self) -> PinUvAuthProtocol {
+        self.protocol
+    }
+
+    /// Encrypts `data`, a multiple of 16 bytes long. Protocol one uses a
+    /// zero IV; protocol two uses `iv` and puts it in front of the
+    /// ciphertext.
+    pub fn encrypt(&self, iv: &[u8; 16], data: &[u8]) -> Vec<u8> {
+        assert!(
+            data.len().is_multiple_of(16),
+            "data is not a multiple of the block size"
+        );
+
+        let cipher = Aes256::new(GenericArray::from_slice(&self.aes_key));
+        let (mut chain, mut ciphertext) = match self.protocol {
+            PinUvAuthProtocol::One => ([0; 16], Vec::with_capacity(data.len())),
+            PinUvAuthProtocol::Two => (*iv, iv.to_vec()),
+        };
+
+        for block in data.chunks_exact(16) {
+            for (chain, byte) in chain.iter_mut().zip(block) {
+                *chain ^= byte;
+            }
+            cipher.encrypt_block(GenericArray::from_mut_slice(&mut chain));
+            ciphertext.extend_from_slice(&chain);
+        }
+
+        ciphertext
+    }
+
+    /// Returns `None` if `data` is not whole blocks, after the IV for
+    /// protocol two.
+    pub fn decrypt(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let (mut chain, data) = match self.protocol {
+            PinUvAuthProtocol::One => ([0; 16], data),
+            PinUvAuthProtocol::Two => {
+                let (iv, data) = data.split_first_chunk::<16>()?;
+                (*iv, data)
+            }
+        };
+
+        if !data.len().is_multiple_of(16) {
+            return None;
+        }
+
+        let cipher = Aes256::new(GenericArray::from_slice(&self.aes_key));
+        let mut plaintext = Vec::with_capacity(data.len());
+
+        for block in data.chunks_exact(16) {
+            let mut decrypted = [0; 16];
+            decrypted.copy_from_slice(block);
+            cipher.decrypt_block(GenericArray::from_mut_slice(&mut decrypted));
+            for (byte, chain) in decrypted.iter_mut().zip(&chain) {
+                *byte ^= chain;
+            }
+            plaintext.extend_from_slice(&decrypted);
+            chain.copy_from_slice(block);
+        }
+
+        Some(plaintext)
+    }
+
+    pub fn authenticate(&self, message: &[u8]) -> Vec<u8> {
+        self.protocol.authenticate(&self.hmac_key, message)
+    }
+
+    pub fn verify(&self, message: &[u8], param: &[u8]) -> bool {
+        self.protocol.verify(&self.hmac_key, message, param)
+    }
+}
+
+impl core::fmt::Debug for SharedSecret {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SharedSecret")
+            .field("protocol", &self.protocol)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A token that proves user verification, which authenticates commands
+/// such as makeCredential and credential management.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PinUvAuthToken {
+    protocol: PinUvAuthProtocol,
+    token: Vec<u8>,
+}
+
+impl PinUvAuthToken {
+    pub fn new(protocol: PinUvAuthProtocol, token: Vec<u8>) -> Self {
+        Self { protocol, token }
+    }
+
+    pub fn protocol(&self) -> PinUvAuthProtocol {
+        self.protocol
+    }
+
+    /// The `pinUvAuthParam` for `message`: the client data hash for
+    /// makeCredential and getAssertion.
+    pub fn authenticate(&self, message: &[u8]) -> Vec<u8> {
+        self.protocol.authenticate(&self.token, message)
+    }
+}
+
+impl core::fmt::Debug for PinUvAuthToken {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PinUvAuthToken")
+            .field("protocol", &self.protocol)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,9 @@
+//! FIDO authenticators over ISO 7816: security keys on NFC, or contact
+//! cards, selected by the FIDO AID.
+
+pub mod cbor;
+pub mod ctap2;
+pub mod u2f;
+
+/// The AID of the FIDO application, which U2F and CTAP2 share.
+pub const FIDO_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x06, 0x47, 0x2F, 0x00, 0x01];
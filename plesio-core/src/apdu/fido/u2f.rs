@@ -0,0 +1,383 @@
+//! FIDO U2F, also known as CTAP1: raw messages sent as command APDUs, with
+//! extended lengths or chaining for messages over 255 bytes, such as
+//! AUTHENTICATE with a long key handle, and GET RESPONSE for the
+//! registration response.
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    fido::FIDO_AID,
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{
+            Iso7816Command, Iso7816StreamingOperation,
+            select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        },
+        status,
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    response::ApduResponse,
+    status::{ApduStatus, is},
+    transport::ApduTransport,
+};
+
+/// The version U2F authenticators report.
+pub const U2F_V2: &[u8] = b"U2F_V2";
+
+/// The length of an uncompressed P-256 point.
+const PUBLIC_KEY_LENGTH: usize = 65;
+
+/// How AUTHENTICATE treats user presence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthenticateMode {
+    /// Only checks whether the key handle is the authenticator's, which it
+    /// answers with `6985` if it is.
+    CheckOnly,
+    EnforceUserPresence,
+    DontEnforceUserPresence,
+}
+
+impl AuthenticateMode {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::CheckOnly => 0x07,
+            Self::EnforceUserPresence => 0x03,
+            Self::DontEnforceUserPresence => 0x08,
+        }
+    }
+}
+
+/// REGISTER, which creates a key pair for an application.
+pub struct Register {
+    data: [u8; 64],
+}
+
+impl Register {
+    /// `challenge` is the hash of the client data, `application` the hash
+    /// of the application identity.
+    pub fn new(challenge: &[u8; 32], application: &[u8; 32]) -> Self {
+        let mut data = [0; 64];
+        data[..32].copy_from_slice(challenge);
+        data[32..].copy_from_slice(application);
+        Self { data }
+    }
+}
+
+impl Iso7816StreamingOperation for Register {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, 0x01, (0x00, 0x00), &self.data)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// AUTHENTICATE, which signs with the key a key handle names.
+pub struct Authenticate {
+    mode: AuthenticateMode,
+    data: Vec<u8>,
+}
+
+impl Authenticate {
+    /// Returns `None` if `key_handle` is longer than the 255 bytes its
+    /// length byte allows.
+    pub fn new(
+        mode: AuthenticateMode,
+        challenge: &[u8; 32],
+        application: &[u8; 32],
+        key_handle: &[u8],
+    ) -> Option<Self> {
+        let length = u8::try_from(key_handle.len()).ok()?;
+
+        let mut data = Vec::with_capacity(65 + key_handle.len());
+        data.extend_from_slice(challenge);
+        data.extend_from_slice(application);
+        data.push(length);
+        data.extend_from_slice(key_handle);
+
+        Some(Self { mode, data })
+    }
+}
+
+impl Iso7816StreamingOperation for Authenticate {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, 0x02, (self.mode.to_u8(), 0x00), &self.data)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// VERSION, which returns [`U2F_V2`].
+pub struct Version;
+
+impl Iso7816StreamingOperation for Version {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, 0x03, (0x00, 0x00), &[])
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// The length of the DER object at the start of `data`, with its header.
+fn der_length(data: &[u8]) -> Option<usize> {
+    match data {
+        [_, length @ 0x00..=0x7F, ..] => Some(2 + *length as usize),
+        [_, 0x81, length, ..] => Some(3 + *length as usize),
+        [_, 0x82, high, low, ..] => Some(4 + u16::from_be_bytes([*high, *low]) as usize),
+        _ => None,
+    }
+}
+
+/// What REGISTER returns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistrationResponse {
+    /// The uncompressed P-256 public key.
+    pub public_key: [u8; PUBLIC_KEY_LENGTH],
+    pub key_handle: Vec<u8>,
+    /// The DER X.509 certificate of the attestation key.
+    pub attestation_certificate: Vec<u8>,
+    /// The DER ECDSA signature of the attestation key over
+    /// [`Self::signed_data`].
+    pub signature: Vec<u8>,
+}
+
+impl RegistrationResponse {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let [0x05, rest @ ..] = data else {
+            return None;
+        };
+
+        let (public_key, rest) = rest.split_first_chunk::<PUBLIC_KEY_LENGTH>()?;
+        let (&length, rest) = rest.split_first()?;
+        let (key_handle, rest) = rest.split_at_checked(length as usize)?;
+        let (certificate, signature) = rest.split_at_checked(der_length(rest)?)?;
+
+        if signature.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            public_key: *public_key,
+            key_handle: key_handle.to_vec(),
+            attestation_certificate: certificate.to_vec(),
+            signature: signature.to_vec(),
+        })
+    }
+
+    /// What the attestation signature covers.
+    pub fn signed_data(&self, challenge: &[u8; 32], application: &[u8; 32]) -> Vec<u8> {
+        let mut data = Vec::from([0x00]);
+        data.extend_from_slice(application);
+        data.extend_from_slice(challenge);
+        data.extend_from_slice(&self.key_handle);
+        data.extend_from_slice(&self.public_key);
+        data
+    }
+}
+
+/// What AUTHENTICATE returns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthenticationResponse {
+    pub user_present: bool,
+    pub counter: u32,
+    /// The DER ECDSA signature over [`Self::signed_data`].
+    pub signature: Vec<u8>,
+}
+
+impl AuthenticationResponse {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (&[flags, c1, c2, c3, c4], signature) = data.split_first_chunk()?;
+
+        if signature.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            user_present: flags & 0x01 != 0,
+            counter: u32::from_be_bytes([c1, c2, c3, c4]),
+            signature: signature.to_vec(),
+        })
+    }
+
+    /// What the signature covers.
+    pub fn signed_data(&self, challenge: &[u8; 32], application: &[u8; 32]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(69);
+        data.extend_from_slice(application);
+        data.push(self.user_present as u8);
+        data.extend_from_slice(&self.counter.to_be_bytes());
+        data.extend_from_slice(challenge);
+        data
+    }
+}
+
+#[derive(Debug)]
+pub enum U2fError<E> {
+    Transport(Iso7816TransportError<E>),
+    Rejected(ApduStatus),
+    /// The user has not touched the authenticator yet; retry.
+    UserPresenceRequired,
+    /// The key handle is not the authenticator's.
+    InvalidKeyHandle,
+    /// The key handle is longer than the 255 bytes U2F allows.
+    KeyHandleTooLong,
+    MalformedResponse,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for U2fError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Rejected(status) => write!(f, "authenticator rejected the command: {status}"),
+            Self::UserPresenceRequired => write!(f, "user presence required"),
+            Self::InvalidKeyHandle => write!(f, "key handle not recognised"),
+            Self::KeyHandleTooLong => write!(f, "key handle longer than 255 bytes"),
+            Self::MalformedResponse => write!(f, "malformed U2F response"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for U2fError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E> From<Iso7816TransportError<E>> for U2fError<E> {
+    fn from(e: Iso7816TransportError<E>) -> Self {
+        Self::Transport(e)
+    }
+}
+
+impl<E> From<ApduResponse<'_>> for U2fError<E> {
+    fn from(response: ApduResponse<'_>) -> Self {
+        match response.status() {
+            status::CONDITIONS_OF_USE_NOT_SATISFIED => Self::UserPresenceRequired,
+            status::WRONG_DATA => Self::InvalidKeyHandle,
+            status => Self::Rejected(status),
+        }
+    }
+}
+
+/// A U2F authenticator.
+pub struct U2f<T: ApduTransport> {
+    transport: Iso7816Transport<T>,
+}
+
+impl<T: ApduTransport> U2f<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport: Iso7816Transport::new(transport),
+        }
+    }
+
+    pub fn inner(&self) -> &Iso7816Transport<T> {
+        &self.transport
+    }
+
+    pub fn inner_mut(&mut self) -> &mut Iso7816Transport<T> {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport.into_inner()
+    }
+
+    /// Runs an operation and returns its response data.
+    async fn fetch<O>(&mut self, operation: O) -> Result<Vec<u8>, U2fError<T::TransportError>>
+    where
+        O: for<'s> Iso7816StreamingOperation<Result<'s> = Result<&'s [u8], ApduResponse<'s>>>,
+    {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        Ok(response)
+    }
+
+    /// Selects the FIDO application and returns the version it reports.
+    pub async fn select(&mut self) -> Result<Vec<u8>, U2fError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(
+                Iso7816Select::new(
+                    Iso7816SelectResolution::ByApplicationIdentifier(FIDO_AID),
+                    &mut [],
+                ),
+                &mut response,
+            )
+            .await??;
+
+        Ok(response)
+    }
+
+    pub async fn version(&mut self) -> Result<Vec<u8>, U2fError<T::TransportError>> {
+        self.fetch(Version).await
+    }
+
+    /// Registers with the application whose identity hashes to
+    /// `application`. Fails with [`U2fError::UserPresenceRequired`] until
+    /// the user touches the authenticator.
+    pub async fn register(
+        &mut self,
+        challenge: &[u8; 32],
+        application: &[u8; 32],
+    ) -> Result<RegistrationResponse, U2fError<T::TransportError>> {
+        let data = self.fetch(Register::new(challenge, application)).await?;
+        RegistrationResponse::parse(&data).ok_or(U2fError::MalformedResponse)
+    }
+
+    /// Signs `challenge` with the key `key_handle` names.
+    pub async fn authenticate(
+        &mut self,
+        mode: AuthenticateMode,
+        challenge: &[u8; 32],
+        application: &[u8; 32],
+        key_handle: &[u8],
+    ) -> Result<AuthenticationResponse, U2fError<T::TransportError>> {
+        let operation = Authenticate::new(mode, challenge, application, key_handle)
+            .ok_or(U2fError::KeyHandleTooLong)?;
+        let data = self.fetch(operation).await?;
+        AuthenticationResponse::parse(&data).ok_or(U2fError::MalformedResponse)
+    }
+
+    /// Whether `key_handle` was registered with this authenticator for
+    /// `application`, without asking for user presence.
+    pub async fn is_registered(
+        &mut self,
+        challenge: &[u8; 32],
+        application: &[u8; 32],
+        key_handle: &[u8],
+    ) -> Result<bool, U2fError<T::TransportError>> {
+        let result = self
+            .authenticate(
+                AuthenticateMode::CheckOnly,
+                challenge,
+                application,
+                key_handle,
+            )
+            .await;
+
+        match result {
+            Err(U2fError::UserPresenceRequired) => Ok(true),
+            Err(U2fError::InvalidKeyHandle) => Ok(false),
+            Err(e) => Err(e),
+            Ok(_) => Err(U2fError::MalformedResponse),
+        }
+    }
+}
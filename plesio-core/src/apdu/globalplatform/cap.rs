@@ -0,0 +1,333 @@
+//! Java Card CAP files: the zip archive the converter produces for a
+//! package, and the load file GlobalPlatform LOAD sends from it.
+
+use std::{
+    io::{Read, Seek},
+    vec::Vec,
+};
+
+use digest::Digest;
+
+/// The components of a package, with their tags as the Java Card Virtual
+/// Machine Specification numbers them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Component {
+    Header = 1,
+    Directory = 2,
+    Applet = 3,
+    Import = 4,
+    ConstantPool = 5,
+    Class = 6,
+    Method = 7,
+    StaticField = 8,
+    RefLocation = 9,
+    Export = 10,
+    Descriptor = 11,
+    Debug = 12,
+}
+
+impl Component {
+    const ALL: [Self; 12] = [
+        Self::Header,
+        Self::Directory,
+        Self::Applet,
+        Self::Import,
+        Self::ConstantPool,
+        Self::Class,
+        Self::Method,
+        Self::StaticField,
+        Self::RefLocation,
+        Self::Export,
+        Self::Descriptor,
+        Self::Debug,
+    ];
+
+    /// The order of the components in the load file, which is not the
+    /// order of their tags.
+    const LOAD_ORDER: [Self; 10] = [
+        Self::Header,
+        Self::Directory,
+        Self::Import,
+        Self::Applet,
+        Self::Class,
+        Self::Method,
+        Self::StaticField,
+        Self::Export,
+        Self::ConstantPool,
+        Self::RefLocation,
+    ];
+
+    pub fn tag(self) -> u8 {
+        self as u8
+    }
+
+    /// The name of the component's file in the archive, under
+    /// `<package path>/javacard/`.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Header => "Header.cap",
+            Self::Directory => "Directory.cap",
+            Self::Applet => "Applet.cap",
+            Self::Import => "Import.cap",
+            Self::ConstantPool => "ConstantPool.cap",
+            Self::Class => "Class.cap",
+            Self::Method => "Method.cap",
+            Self::StaticField => "StaticField.cap",
+            Self::RefLocation => "RefLocation.cap",
+            Self::Export => "Export.cap",
+            Self::Descriptor => "Descriptor.cap",
+            Self::Debug => "Debug.cap",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CapError {
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    MissingComponent(Component),
+    /// The component's tag or size does not match its file, or its
+    /// content does not parse.
+    MalformedComponent(Component),
+}
+
+impl core::fmt::Display for CapError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "reading CAP file failed: {error}"),
+            Self::Zip(error) => write!(f, "invalid CAP archive: {error}"),
+            Self::MissingComponent(component) => {
+                write!(f, "CAP file has no {}", component.file_name())
+            }
+            Self::MalformedComponent(component) => {
+                write!(f, "malformed {} in CAP file", component.file_name())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Zip(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for CapError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<zip::result::ZipError> for CapError {
+    fn from(error: zip::result::ZipError) -> Self {
+        Self::Zip(error)
+    }
+}
+
+/// A package a CAP file imports or declares: its AID and version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackageInfo {
+    pub aid: Vec<u8>,
+    pub major_version: u8,
+    pub minor_version: u8,
+}
+
+/// A parsed CAP file.
+#[derive(Clone, Debug)]
+pub struct CapFile {
+    components: [Option<Vec<u8>>; 12],
+    package: PackageInfo,
+    applets: Vec<Vec<u8>>,
+    imports: Vec<PackageInfo>,
+}
+
+impl CapFile {
+    /// Reads the components from the archive in `reader`.
+    pub fn read(reader: impl Read + Seek) -> Result<Self, CapError> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut components: [Option<Vec<u8>>; 12] = Default::default();
+
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            let Some((directory, name)) = file.name().rsplit_once('/') else {
+                continue;
+            };
+
+            if !directory.ends_with("javacard") {
+                continue;
+            }
+
+            let Some(component) = Component::ALL
+                .into_iter()
+                .find(|component| component.file_name() == name)
+            else {
+                continue;
+            };
+
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+
+            let valid = match data.as_slice() {
+                [tag, high, low, info @ ..] => {
+                    *tag == component.tag()
+                        && u16::from_be_bytes([*high, *low]) as usize == info.len()
+                }
+                _ => false,
+            };
+
+            if !valid {
+                return Err(CapError::MalformedComponent(component));
+            }
+
+            components[component as usize - 1] = Some(data);
+        }
+
+        let mut cap = Self {
+            components,
+            package: PackageInfo {
+                aid: Vec::new(),
+                major_version: 0,
+                minor_version: 0,
+            },
+            applets: Vec::new(),
+            imports: Vec::new(),
+        };
+
+        for component in Component::LOAD_ORDER {
+            if component != Component::Applet
+                && component != Component::Export
+                && cap.component(component).is_none()
+            {
+                return Err(CapError::MissingComponent(component));
+            }
+        }
+
+        cap.package = cap
+            .parse_header()
+            .ok_or(CapError::MalformedComponent(Component::Header))?;
+        cap.applets = cap
+            .parse_applets()
+            .ok_or(CapError::MalformedComponent(Component::Applet))?;
+        cap.imports = cap
+            .parse_imports()
+            .ok_or(CapError::MalformedComponent(Component::Import))?;
+
+        Ok(cap)
+    }
+
+    /// Reads the CAP file at `path`.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, CapError> {
+        Self::read(std::fs::File::open(path)?)
+    }
+
+    /// The component as stored, with its tag and size.
+    pub fn component(&self, component: Component) -> Option<&[u8]> {
+        self.components[component as usize - 1].as_deref()
+    }
+
+    /// The package the file declares.
+    pub fn package(&self) -> &PackageInfo {
+        &self.package
+    }
+
+    /// The AIDs of the applets the package defines, which INSTALL [for
+    /// install] takes as module AIDs.
+    pub fn applets(&self) -> &[Vec<u8>] {
+        &self.applets
+    }
+
+    pub fn imports(&self) -> &[PackageInfo] {
+        &self.imports
+    }
+
+    /// The components in load order, as LOAD expects them inside the load
+    /// file data block. The Descriptor component is only needed by cards
+    /// that verify the code on the card, and Debug is never sent.
+    pub fn load_file(&self, include_descriptor: bool) -> Vec<u8> {
+        let descriptor = include_descriptor.then_some(Component::Descriptor);
+
+        Component::LOAD_ORDER
+            .into_iter()
+            .chain(descriptor)
+            .filter_map(|component| self.component(component))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// The load file data block hash of `load_file`, which INSTALL [for
+    /// load] carries and DAP signatures cover: SHA-1 on older cards,
+    /// SHA-256 or larger on those following GlobalPlatform 2.3.
+    pub fn hash<D: Digest>(load_file: &[u8]) -> Vec<u8> {
+        D::digest(load_file).to_vec()
+    }
+
+    /// Header: magic, CAP format version, flags, then the package version
+    /// and AID.
+    fn parse_header(&self) -> Option<PackageInfo> {
+        let info = &self.component(Component::Header)?[3..];
+        let (&[0xDE, 0xCA, 0xFF, 0xED, _, _, _, minor, major], mut rest) =
+            info.split_first_chunk()?
+        else {
+            return None;
+        };
+
+        Some(PackageInfo {
+            aid: Self::aid(&mut rest)?.to_vec(),
+            major_version: major,
+            minor_version: minor,
+        })
+    }
+
+    /// Applet: a count, then each applet's AID and install method offset.
+    /// Library packages have no Applet component.
+    fn parse_applets(&self) -> Option<Vec<Vec<u8>>> {
+        let Some(component) = self.component(Component::Applet) else {
+            return Some(Vec::new());
+        };
+
+        let (&count, mut rest) = component[3..].split_first()?;
+        let mut applets = Vec::new();
+
+        for _ in 0..count {
+            applets.push(Self::aid(&mut rest)?.to_vec());
+            rest = rest.get(2..)?;
+        }
+
+        Some(applets)
+    }
+
+    /// Import: a count, then the version and AID of each package.
+    fn parse_imports(&self) -> Option<Vec<PackageInfo>> {
+        let Some(component) = self.component(Component::Import) else {
+            return Some(Vec::new());
+        };
+
+        let (&count, mut rest) = component[3..].split_first()?;
+        let mut imports = Vec::new();
+
+        for _ in 0..count {
+            let (&[minor, major], next) = rest.split_first_chunk()?;
+            rest = next;
+            imports.push(PackageInfo {
+                aid: Self::aid(&mut rest)?.to_vec(),
+                major_version: major,
+                minor_version: minor,
+            });
+        }
+
+        Some(imports)
+    }
+
+    /// Splits a length-prefixed AID off `data`.
+    fn aid<'d>(data: &mut &'d [u8]) -> Option<&'d [u8]> {
+        let (&length, rest) = data.split_first()?;
+        let (aid, rest) = rest.split_at_checked(length as usize)?;
+        *data = rest;
+        Some(aid)
+    }
+}
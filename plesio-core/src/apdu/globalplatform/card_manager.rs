@@ -0,0 +1,306 @@
+//! A client for the card manager, the issuer security domain, running the
+//! management operations and collecting their typed results.
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    globalplatform::operation::{
+        ResponseError,
+        delete::Delete,
+        get_data::{CardRecognitionData, Cplc, GetData, KeyInformation},
+        get_status::{GetStatus, RegistryEntry},
+        install::Install,
+        load::{self, DapBlock, Load},
+        put_key::PutKey,
+        set_status::SetStatus,
+    },
+    iso_7816::{
+        operation::select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        tlv::ber::BerTlvIterator,
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::ApduTransport,
+};
+
+/// The AID of the issuer security domain on most cards.
+pub const ISSUER_SECURITY_DOMAIN: &[u8] = &[0xA0, 0x00, 0x00, 0x01, 0x51, 0x00, 0x00, 0x00];
+
+#[derive(Debug)]
+pub enum CardManagerError<E> {
+    Transport(Iso7816TransportError<E>),
+    Rejected(ApduStatus),
+    MalformedResponse,
+    /// The load file needs more than 256 LOAD commands of the blocks the
+    /// transport can carry, or the transport has no room for a block.
+    LoadFileTooLarge,
+    /// PUT KEY succeeded but the card reported other key check values
+    /// than those sent, so it holds other keys than intended.
+    KeyCheckValueMismatch,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for CardManagerError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Rejected(status) => write!(f, "card manager rejected the command: {status}"),
+            Self::MalformedResponse => write!(f, "malformed card manager response"),
+            Self::LoadFileTooLarge => write!(f, "load file exceeds 256 blocks"),
+            Self::KeyCheckValueMismatch => write!(f, "card reported unexpected key check values"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for CardManagerError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E> From<Iso7816TransportError<E>> for CardManagerError<E> {
+    fn from(e: Iso7816TransportError<E>) -> Self {
+        Self::Transport(e)
+    }
+}
+
+impl<E> From<ApduResponse<'_>> for CardManagerError<E> {
+    fn from(response: ApduResponse<'_>) -> Self {
+        Self::Rejected(response.status())
+    }
+}
+
+impl<E> From<ResponseError<'_>> for CardManagerError<E> {
+    fn from(e: ResponseError<'_>) -> Self {
+        match e {
+            ResponseError::Rejected(response) => response.into(),
+            ResponseError::Malformed => Self::MalformedResponse,
+        }
+    }
+}
+
+/// The file control information of a selected security domain.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct SecurityDomainInfo {
+    pub aid: Vec<u8>,
+    /// The security domain management data, `73`, which holds the same
+    /// objects as the card recognition data.
+    pub management_data: Option<Vec<u8>>,
+    /// The largest command data the security domain accepts.
+    pub max_command_length: Option<usize>,
+}
+
+impl SecurityDomainInfo {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let fci = BerTlvIterator::new(data).get(0x6F)?;
+        let mut info = Self {
+            aid: fci.children().get(0x84)?.value().to_vec(),
+            ..Self::default()
+        };
+
+        if let Some(proprietary) = fci.children().get(0xA5) {
+            info.management_data = proprietary
+                .children()
+                .get(0x73)
+                .map(|object| object.value().to_vec());
+            info.max_command_length = proprietary.children().get(0x9F65).map(|object| {
+                object
+                    .value()
+                    .iter()
+                    .fold(0, |length, &byte| (length << 8) | byte as usize)
+            });
+        }
+
+        Some(info)
+    }
+}
+
+/// Card content management through a security domain. Operations go to
+/// whatever the transport carries them to, so `T` is usually a secure
+/// channel opened after [`Self::select`].
+pub struct CardManager<T: ApduTransport> {
+    transport: Iso7816Transport<T>,
+}
+
+impl<T: ApduTransport> CardManager<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport: Iso7816Transport::new(transport),
+        }
+    }
+
+    pub fn inner(&self) -> &Iso7816Transport<T> {
+        &self.transport
+    }
+
+    pub fn inner_mut(&mut self) -> &mut Iso7816Transport<T> {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport.into_inner()
+    }
+
+    /// Selects the security domain `aid`, usually [`ISSUER_SECURITY_DOMAIN`].
+    pub async fn select(
+        &mut self,
+        aid: &[u8],
+    ) -> Result<SecurityDomainInfo, CardManagerError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(
+                Iso7816Select::new(
+                    Iso7816SelectResolution::ByApplicationIdentifier(aid),
+                    &mut [],
+                ),
+                &mut response,
+            )
+            .await??;
+
+        SecurityDomainInfo::parse(&response).ok_or(CardManagerError::MalformedResponse)
+    }
+
+    /// Runs GET STATUS until the card has reported every entry.
+    pub async fn get_status(
+        &mut self,
+        mut operation: GetStatus,
+    ) -> Result<Vec<RegistryEntry>, CardManagerError<T::TransportError>> {
+        let mut entries = Vec::new();
+
+        loop {
+            let mut response = Vec::new();
+            let page = self
+                .transport
+                .execute_streaming(operation.clone(), &mut response)
+                .await??;
+            entries.extend(page.entries);
+
+            if !page.more {
+                return Ok(entries);
+            }
+
+            operation = operation.next();
+        }
+    }
+
+    pub async fn install(
+        &mut self,
+        operation: Install<'_>,
+    ) -> Result<Vec<u8>, CardManagerError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        Ok(response)
+    }
+
+    /// Sends `load_file` with LOAD, after INSTALL [for load] announced it,
+    /// in blocks as large as the transport carries.
+    pub async fn load(
+        &mut self,
+        load_file: &[u8],
+    ) -> Result<(), CardManagerError<T::TransportError>> {
+        self.load_with_dap(load_file, &[]).await
+    }
+
+    /// Sends `load_file` like [`Self::load`], preceded by the DAP blocks
+    /// of the security domains that verify it.
+    pub async fn load_with_dap(
+        &mut self,
+        load_file: &[u8],
+        dap_blocks: &[DapBlock<'_>],
+    ) -> Result<(), CardManagerError<T::TransportError>> {
+        let payload = load::load_payload(load_file, dap_blocks);
+        let block_size = self.transport.inner().max_payload_size().min(0xFF);
+        let blocks =
+            Load::blocks(&payload, block_size).ok_or(CardManagerError::LoadFileTooLarge)?;
+
+        for block in blocks {
+            let mut response = Vec::new();
+            self.transport
+                .execute_streaming(block, &mut response)
+                .await??;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete(
+        &mut self,
+        operation: Delete,
+    ) -> Result<(), CardManagerError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        Ok(())
+    }
+
+    pub async fn set_status(
+        &mut self,
+        operation: SetStatus<'_>,
+    ) -> Result<(), CardManagerError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        Ok(())
+    }
+
+    /// Runs PUT KEY and checks the key check values the card answers with.
+    pub async fn put_key(
+        &mut self,
+        operation: PutKey,
+    ) -> Result<(), CardManagerError<T::TransportError>> {
+        let expected = operation.expected_response().to_vec();
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        if response != expected {
+            return Err(CardManagerError::KeyCheckValueMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Runs GET DATA and returns the data object it answers with.
+    pub async fn get_data(
+        &mut self,
+        tag: u16,
+    ) -> Result<Vec<u8>, CardManagerError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(GetData::new(tag), &mut response)
+            .await??;
+
+        Ok(response)
+    }
+
+    pub async fn cplc(&mut self) -> Result<Cplc, CardManagerError<T::TransportError>> {
+        let data = self.get_data(GetData::CARD_PRODUCTION_LIFE_CYCLE).await?;
+        Cplc::parse(&data).ok_or(CardManagerError::MalformedResponse)
+    }
+
+    pub async fn card_recognition_data(
+        &mut self,
+    ) -> Result<CardRecognitionData, CardManagerError<T::TransportError>> {
+        let data = self.get_data(GetData::CARD_RECOGNITION_DATA).await?;
+        CardRecognitionData::parse(&data).ok_or(CardManagerError::MalformedResponse)
+    }
+
+    pub async fn key_information(
+        &mut self,
+    ) -> Result<Vec<KeyInformation>, CardManagerError<T::TransportError>> {
+        let data = self.get_data(GetData::KEY_INFORMATION_TEMPLATE).await?;
+        KeyInformation::parse_template(&data).ok_or(CardManagerError::MalformedResponse)
+    }
+}
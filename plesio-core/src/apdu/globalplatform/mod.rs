@@ -0,0 +1,70 @@
+//! GlobalPlatform card management: secure channels to a security domain
+//! and the commands that manage the card content through it.
+
+#[cfg(any(feature = "scp02", feature = "scp03"))]
+use alloc::vec::Vec;
+
+#[cfg(feature = "cap")]
+pub mod cap;
+pub mod card_manager;
+pub mod operation;
+#[cfg(feature = "scp02")]
+pub mod scp02;
+#[cfg(feature = "scp03")]
+pub mod scp03;
+
+/// The protection applied to commands and responses after EXTERNAL
+/// AUTHENTICATE, combined with `|`.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SecurityLevel(u8);
+
+impl SecurityLevel {
+    /// Authentication only; later commands are sent as they are.
+    pub const NONE: Self = Self(0x00);
+    pub const C_MAC: Self = Self(0x01);
+    /// Command data encryption, which implies C-MAC.
+    pub const C_DECRYPTION: Self = Self(0x03);
+    /// Response MACs, which require C-MAC as well.
+    pub const R_MAC: Self = Self(0x10);
+    /// Response data encryption, which implies R-MAC.
+    pub const R_ENCRYPTION: Self = Self(0x30);
+
+    pub fn from_u8(level: u8) -> Self {
+        Self(level)
+    }
+
+    pub fn to_u8(self) -> u8 {
+        self.0
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for SecurityLevel {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// Sets the GlobalPlatform secure messaging indication: b3 for classes of
+/// the basic channels, b6 for those of the further channels.
+#[cfg(any(feature = "scp02", feature = "scp03"))]
+pub(crate) fn secured_class(class: u8) -> u8 {
+    match class & 0x40 {
+        0 => class | 0x04,
+        _ => class | 0x20,
+    }
+}
+
+/// The Lc of a command with `length` bytes of data, as MACed.
+#[cfg(any(feature = "scp02", feature = "scp03"))]
+pub(crate) fn encoded_lc(length: usize) -> Vec<u8> {
+    match length {
+        0..=0xFF => [length as u8].to_vec(),
+        _ => [0x00, (length >> 8) as u8, length as u8].to_vec(),
+    }
+}
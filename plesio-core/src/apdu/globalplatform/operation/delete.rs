@@ -0,0 +1,51 @@
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    globalplatform::operation::aid_object,
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+    },
+    response::ApduResponse,
+    status::is,
+};
+
+/// DELETE of a load file or application.
+pub struct Delete {
+    data: Vec<u8>,
+    related_objects: bool,
+}
+
+impl Delete {
+    /// Returns `None` if `aid` is longer than 255 bytes.
+    pub fn new(aid: &[u8]) -> Option<Self> {
+        Some(Self {
+            data: aid_object(aid)?,
+            related_objects: false,
+        })
+    }
+
+    /// Deletes the applications installed from the load file along with it.
+    pub fn with_related_objects(mut self) -> Self {
+        self.related_objects = true;
+        self
+    }
+}
+
+impl Iso7816StreamingOperation for Delete {
+    type Result<'s> = Result<(), ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command {
+            class: class.with_proprietary(),
+            instruction: 0xE4,
+            parameters: (0x00, if self.related_objects { 0x80 } else { 0x00 }),
+            data: &self.data,
+        }
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(|_| ())
+    }
+}
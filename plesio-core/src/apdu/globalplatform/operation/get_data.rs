@@ -0,0 +1,211 @@
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+        tlv::ber::BerTlv,
+    },
+    response::ApduResponse,
+    status::is,
+};
+
+/// The prefix of the GlobalPlatform object identifiers, `1.2.840.114283`.
+const GLOBALPLATFORM_OID: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xFC, 0x6B];
+
+/// GET DATA of the data object `tag` of the security domain. The response
+/// is the data object itself, tag and length included.
+pub struct GetData {
+    tag: u16,
+}
+
+impl GetData {
+    pub const CARD_PRODUCTION_LIFE_CYCLE: u16 = 0x9F7F;
+    pub const CARD_RECOGNITION_DATA: u16 = 0x0066;
+    pub const KEY_INFORMATION_TEMPLATE: u16 = 0x00E0;
+
+    pub fn new(tag: u16) -> Self {
+        Self { tag }
+    }
+}
+
+impl Iso7816StreamingOperation for GetData {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let [p1, p2] = self.tag.to_be_bytes();
+
+        Iso7816Command {
+            class: class.with_proprietary(),
+            instruction: 0xCA,
+            parameters: (p1, p2),
+            data: &[],
+        }
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// The card production life cycle data: who made and prepared the chip
+/// and when. Dates are `YDDD`, the last digit of the year and the day of
+/// the year, in BCD.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Cplc {
+    pub ic_fabricator: [u8; 2],
+    pub ic_type: [u8; 2],
+    pub operating_system_identifier: [u8; 2],
+    pub operating_system_release_date: [u8; 2],
+    pub operating_system_release_level: [u8; 2],
+    pub ic_fabrication_date: [u8; 2],
+    pub ic_serial_number: [u8; 4],
+    pub ic_batch_identifier: [u8; 2],
+    pub ic_module_fabricator: [u8; 2],
+    pub ic_module_packaging_date: [u8; 2],
+    pub icc_manufacturer: [u8; 2],
+    pub ic_embedding_date: [u8; 2],
+    pub ic_pre_personalizer: [u8; 2],
+    pub ic_pre_personalization_date: [u8; 2],
+    pub ic_pre_personalization_equipment: [u8; 4],
+    pub ic_personalizer: [u8; 2],
+    pub ic_personalization_date: [u8; 2],
+    pub ic_personalization_equipment: [u8; 4],
+}
+
+impl Cplc {
+    /// Parses the `9F7F` data object GET DATA returns.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (object, _) = BerTlv::next(data)?;
+
+        if object.tag() != 0x9F7F {
+            return None;
+        }
+
+        fn take<const N: usize>(value: &mut &[u8]) -> Option<[u8; N]> {
+            let (field, rest) = value.split_first_chunk()?;
+            *value = rest;
+            Some(*field)
+        }
+
+        let value = &mut object.value();
+
+        Some(Self {
+            ic_fabricator: take(value)?,
+            ic_type: take(value)?,
+            operating_system_identifier: take(value)?,
+            operating_system_release_date: take(value)?,
+            operating_system_release_level: take(value)?,
+            ic_fabrication_date: take(value)?,
+            ic_serial_number: take(value)?,
+            ic_batch_identifier: take(value)?,
+            ic_module_fabricator: take(value)?,
+            ic_module_packaging_date: take(value)?,
+            icc_manufacturer: take(value)?,
+            ic_embedding_date: take(value)?,
+            ic_pre_personalizer: take(value)?,
+            ic_pre_personalization_date: take(value)?,
+            ic_pre_personalization_equipment: take(value)?,
+            ic_personalizer: take(value)?,
+            ic_personalization_date: take(value)?,
+            ic_personalization_equipment: take(value)?,
+        })
+    }
+}
+
+/// What the card recognition data says about the card's management.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct CardRecognitionData {
+    /// The GlobalPlatform version, such as `[2, 2, 1]`.
+    pub version: Vec<u8>,
+    /// The secure channel protocols and their `i` parameters.
+    pub secure_channels: Vec<(u8, u8)>,
+    /// The object identifiers of the card configuration details.
+    pub card_configuration: Vec<Vec<u8>>,
+    /// The object identifiers of the card and chip details.
+    pub chip_details: Vec<Vec<u8>>,
+}
+
+impl CardRecognitionData {
+    /// Parses the `66` data object GET DATA returns.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (object, _) = BerTlv::next(data)?;
+
+        if object.tag() != 0x66 {
+            return None;
+        }
+
+        let template = object.children().get(0x73)?;
+        let mut recognition = Self::default();
+
+        for object in template.children() {
+            let oid = || object.children().get(0x06).map(|oid| oid.value());
+
+            match object.tag() {
+                0x60 => {
+                    let oid = oid()?;
+                    recognition.version = gp_arc(oid, 0x02)?.to_vec();
+                }
+                0x64 => {
+                    let oid = oid()?;
+
+                    if let [protocol, parameter] = *gp_arc(oid, 0x04)? {
+                        recognition.secure_channels.push((protocol, parameter));
+                    }
+                }
+                0x65 => recognition
+                    .card_configuration
+                    .extend(object.children().map(|oid| oid.value().to_vec())),
+                0x66 => recognition
+                    .chip_details
+                    .extend(object.children().map(|oid| oid.value().to_vec())),
+                _ => {}
+            }
+        }
+
+        Some(recognition)
+    }
+}
+
+/// The rest of `oid` after the GlobalPlatform prefix and `arc`.
+fn gp_arc(oid: &[u8], arc: u8) -> Option<&[u8]> {
+    oid.strip_prefix(GLOBALPLATFORM_OID)?.strip_prefix(&[arc])
+}
+
+/// One entry of the key information template: a key and the type and
+/// length of each of its components, such as `(0x88, 16)` for AES-128.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct KeyInformation {
+    pub identifier: u8,
+    pub version: u8,
+    pub components: Vec<(u8, u8)>,
+}
+
+impl KeyInformation {
+    /// Parses the `E0` data object GET DATA returns into its `C0` entries.
+    pub fn parse_template(data: &[u8]) -> Option<Vec<Self>> {
+        let (object, _) = BerTlv::next(data)?;
+
+        if object.tag() != 0xE0 {
+            return None;
+        }
+
+        object
+            .children()
+            .filter(|entry| entry.tag() == 0xC0)
+            .map(|entry| {
+                let (&[identifier, version], components) = entry.value().split_first_chunk()?;
+
+                Some(Self {
+                    identifier,
+                    version,
+                    components: components
+                        .chunks_exact(2)
+                        .map(|component| (component[0], component[1]))
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,218 @@
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    globalplatform::operation::{ResponseError, aid_object},
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+        tlv::ber::BerTlvIterator,
+    },
+    response::ApduResponse,
+    status::ApduStatus,
+};
+
+/// The card has more entries, which GET STATUS with the next occurrence
+/// returns.
+pub const MORE_DATA: ApduStatus = ApduStatus::new(0x63, 0x10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusScope {
+    IssuerSecurityDomain,
+    /// Applications and supplementary security domains.
+    Applications,
+    LoadFiles,
+    /// Load files with the AIDs of the modules they contain.
+    LoadFilesAndModules,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum StatusFormat {
+    /// The `E3` data objects of GlobalPlatform 2.2 and later.
+    #[default]
+    Tagged,
+    /// The fixed layout of earlier cards, which report only the first
+    /// privilege byte.
+    Legacy,
+}
+
+/// One entry of the registry of a security domain.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct RegistryEntry {
+    pub aid: Vec<u8>,
+    /// The life cycle state, for instance `07` SELECTABLE for an
+    /// application or `01` LOADED for a load file.
+    pub life_cycle: u8,
+    pub privileges: [u8; 3],
+    /// The load file an application was installed from.
+    pub load_file: Option<Vec<u8>>,
+    /// The security domain the entry is associated with.
+    pub security_domain: Option<Vec<u8>>,
+    /// The version of a load file.
+    pub version: Option<Vec<u8>>,
+    pub modules: Vec<Vec<u8>>,
+}
+
+/// The entries of one GET STATUS response.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct StatusPage {
+    pub entries: Vec<RegistryEntry>,
+    /// Whether the card answered `6310` and has more entries.
+    pub more: bool,
+}
+
+/// GET STATUS of the registry entries in `scope`, optionally only those
+/// whose AID starts with a prefix.
+#[derive(Clone, Debug)]
+pub struct GetStatus {
+    scope: StatusScope,
+    format: StatusFormat,
+    criteria: Vec<u8>,
+    next: bool,
+}
+
+impl GetStatus {
+    pub fn new(scope: StatusScope) -> Self {
+        Self {
+            scope,
+            format: StatusFormat::default(),
+            criteria: aid_object(&[]).expect("an empty AID fits"),
+            next: false,
+        }
+    }
+
+    pub fn with_format(mut self, format: StatusFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Returns `None` if `prefix` is longer than 255 bytes.
+    pub fn with_aid_prefix(mut self, prefix: &[u8]) -> Option<Self> {
+        self.criteria = aid_object(prefix)?;
+        Some(self)
+    }
+
+    /// The same request for the entries after a page that ended in `6310`.
+    pub fn next(mut self) -> Self {
+        self.next = true;
+        self
+    }
+
+    pub fn scope(&self) -> StatusScope {
+        self.scope
+    }
+
+    fn parse_tagged(data: &[u8]) -> Option<Vec<RegistryEntry>> {
+        let mut objects = BerTlvIterator::new(data);
+        let mut entries = Vec::new();
+
+        for object in &mut objects {
+            if object.tag() != 0xE3 {
+                return None;
+            }
+
+            let mut entry = RegistryEntry::default();
+
+            for field in object.children() {
+                let value = field.value();
+
+                match field.tag() {
+                    0x4F => entry.aid = value.to_vec(),
+                    0x9F70 => entry.life_cycle = *value.first()?,
+                    0xC5 => {
+                        let length = value.len().min(3);
+                        entry.privileges[..length].copy_from_slice(&value[..length]);
+                    }
+                    0xC4 => entry.load_file = Some(value.to_vec()),
+                    0xCC => entry.security_domain = Some(value.to_vec()),
+                    0xCE => entry.version = Some(value.to_vec()),
+                    0x84 => entry.modules.push(value.to_vec()),
+                    _ => {}
+                }
+            }
+
+            entries.push(entry);
+        }
+
+        objects.remainder().is_empty().then_some(entries)
+    }
+
+    fn parse_legacy(&self, mut data: &[u8]) -> Option<Vec<RegistryEntry>> {
+        fn field<'d>(data: &mut &'d [u8]) -> Option<&'d [u8]> {
+            let (&length, rest) = data.split_first()?;
+            let (value, rest) = rest.split_at_checked(length as usize)?;
+            *data = rest;
+            Some(value)
+        }
+
+        let mut entries = Vec::new();
+
+        while !data.is_empty() {
+            let aid = field(&mut data)?.to_vec();
+            let (&[life_cycle, privilege], rest) = data.split_first_chunk()?;
+            data = rest;
+
+            let mut entry = RegistryEntry {
+                aid,
+                life_cycle,
+                privileges: [privilege, 0, 0],
+                ..RegistryEntry::default()
+            };
+
+            if self.scope == StatusScope::LoadFilesAndModules {
+                let (&count, rest) = data.split_first()?;
+                data = rest;
+
+                for _ in 0..count {
+                    entry.modules.push(field(&mut data)?.to_vec());
+                }
+            }
+
+            entries.push(entry);
+        }
+
+        Some(entries)
+    }
+}
+
+impl Iso7816StreamingOperation for GetStatus {
+    type Result<'s> = Result<StatusPage, ResponseError<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let scope = match self.scope {
+            StatusScope::IssuerSecurityDomain => 0x80,
+            StatusScope::Applications => 0x40,
+            StatusScope::LoadFiles => 0x20,
+            StatusScope::LoadFilesAndModules => 0x10,
+        };
+
+        let format = match self.format {
+            StatusFormat::Tagged => 0x02,
+            StatusFormat::Legacy => 0x00,
+        };
+
+        Iso7816Command {
+            class: class.with_proprietary(),
+            instruction: 0xF2,
+            parameters: (scope, format | self.next as u8),
+            data: &self.criteria,
+        }
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        let more = match response.status() {
+            status::OK => false,
+            MORE_DATA => true,
+            _ => return Err(ResponseError::Rejected(*response)),
+        };
+
+        let entries = match self.format {
+            StatusFormat::Tagged => Self::parse_tagged(response.data()),
+            StatusFormat::Legacy => self.parse_legacy(response.data()),
+        };
+
+        entries
+            .map(|entries| StatusPage { entries, more })
+            .ok_or(ResponseError::Malformed)
+    }
+}
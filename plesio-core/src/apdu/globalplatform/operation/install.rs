@@ -0,0 +1,195 @@
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    globalplatform::operation::push_field,
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+    },
+    response::ApduResponse,
+    status::is,
+};
+
+const FOR_LOAD: u8 = 0x02;
+const FOR_INSTALL: u8 = 0x04;
+const FOR_MAKE_SELECTABLE: u8 = 0x08;
+const FOR_PERSONALIZATION: u8 = 0x20;
+
+/// INSTALL, in one of its roles. Fields a role does not use are sent
+/// empty.
+///
+/// Each field is preceded by a one byte length, so the constructors and
+/// builders that set one return `None` if it is longer than 255 bytes, or
+/// the parameters longer than 253.
+pub struct Install<'a> {
+    role: u8,
+    load_file: &'a [u8],
+    module: &'a [u8],
+    application: &'a [u8],
+    security_domain: &'a [u8],
+    hash: &'a [u8],
+    privileges: &'a [u8],
+    parameters: &'a [u8],
+    token: &'a [u8],
+    data: Vec<u8>,
+}
+
+impl<'a> Install<'a> {
+    fn new(role: u8) -> Self {
+        Self {
+            role,
+            load_file: &[],
+            module: &[],
+            application: &[],
+            security_domain: &[],
+            hash: &[],
+            privileges: &[],
+            parameters: &[],
+            token: &[],
+            data: Vec::new(),
+        }
+    }
+
+    /// INSTALL [for load], which announces the load file LOAD delivers next.
+    /// `security_domain` is left empty to associate it with the issuer
+    /// security domain.
+    pub fn for_load(load_file: &'a [u8], security_domain: &'a [u8]) -> Option<Self> {
+        Self {
+            load_file,
+            security_domain,
+            ..Self::new(FOR_LOAD)
+        }
+        .encode()
+    }
+
+    /// INSTALL [for install and make selectable] of `module` from
+    /// `load_file` as `application`, with no privileges and no parameters
+    /// unless given.
+    pub fn for_install(
+        load_file: &'a [u8],
+        module: &'a [u8],
+        application: &'a [u8],
+    ) -> Option<Self> {
+        Self {
+            load_file,
+            module,
+            application,
+            ..Self::new(FOR_INSTALL | FOR_MAKE_SELECTABLE)
+        }
+        .encode()
+    }
+
+    /// INSTALL [for make selectable] of an application installed without
+    /// it.
+    pub fn for_make_selectable(application: &'a [u8]) -> Option<Self> {
+        Self {
+            application,
+            ..Self::new(FOR_MAKE_SELECTABLE)
+        }
+        .encode()
+    }
+
+    /// INSTALL [for personalization], after which STORE DATA goes to
+    /// `application` through the security domain.
+    pub fn for_personalization(application: &'a [u8]) -> Option<Self> {
+        Self {
+            application,
+            ..Self::new(FOR_PERSONALIZATION)
+        }
+        .encode()
+    }
+
+    /// Leaves the application installed but not selectable.
+    pub fn without_make_selectable(mut self) -> Self {
+        self.role &= !FOR_MAKE_SELECTABLE;
+        self.encode().expect("fields were checked when set")
+    }
+
+    /// The privileges of the application: one byte, or the three of
+    /// GlobalPlatform 2.2 and later.
+    pub fn with_privileges(mut self, privileges: &'a [u8]) -> Option<Self> {
+        self.privileges = privileges;
+        self.encode()
+    }
+
+    /// The application specific parameters, sent in the `C9` data object.
+    pub fn with_parameters(mut self, parameters: &'a [u8]) -> Option<Self> {
+        self.parameters = parameters;
+        self.encode()
+    }
+
+    /// The hash of the load file data block, for cards that check it.
+    pub fn with_hash(mut self, hash: &'a [u8]) -> Option<Self> {
+        self.hash = hash;
+        self.encode()
+    }
+
+    /// The token of delegated management.
+    pub fn with_token(mut self, token: &'a [u8]) -> Option<Self> {
+        self.token = token;
+        self.encode()
+    }
+
+    fn encode(mut self) -> Option<Self> {
+        let mut data = Vec::new();
+
+        match self.role {
+            FOR_LOAD => {
+                push_field(&mut data, self.load_file)?;
+                push_field(&mut data, self.security_domain)?;
+                push_field(&mut data, self.hash)?;
+                push_field(&mut data, &[])?;
+            }
+            FOR_PERSONALIZATION => {
+                push_field(&mut data, &[])?;
+                push_field(&mut data, &[])?;
+                push_field(&mut data, self.application)?;
+                push_field(&mut data, &[])?;
+                push_field(&mut data, &[])?;
+            }
+            _ => {
+                let mut parameters = Vec::new();
+
+                if self.role & FOR_INSTALL != 0 {
+                    parameters.push(0xC9);
+                    push_field(&mut parameters, self.parameters)?;
+                }
+
+                push_field(&mut data, self.load_file)?;
+                push_field(&mut data, self.module)?;
+                push_field(&mut data, self.application)?;
+                push_field(&mut data, self.privileges_or_none())?;
+                push_field(&mut data, &parameters)?;
+            }
+        }
+
+        push_field(&mut data, self.token)?;
+        self.data = data;
+        Some(self)
+    }
+
+    fn privileges_or_none(&self) -> &'a [u8] {
+        match self.privileges {
+            [] => &[0x00],
+            privileges => privileges,
+        }
+    }
+}
+
+impl Iso7816StreamingOperation for Install<'_> {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command {
+            class: class.with_proprietary(),
+            instruction: 0xE6,
+            parameters: (self.role, 0x00),
+            data: &self.data,
+        }
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
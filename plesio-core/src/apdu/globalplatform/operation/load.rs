@@ -0,0 +1,113 @@
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+        tlv::ber::BerTlv,
+    },
+    response::ApduResponse,
+    status::is,
+};
+
+/// The most blocks a load file can be split into, as P2 numbers them in a
+/// single byte.
+pub const MAX_BLOCKS: usize = 256;
+
+/// A data authentication pattern: the signature of a security domain with
+/// the DAP verification privilege over the load file data block hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DapBlock<'a> {
+    pub security_domain: &'a [u8],
+    pub signature: &'a [u8],
+}
+
+/// Wraps the load file, the concatenated components of a CAP file, in the
+/// `C4` data object LOAD delivers.
+pub fn load_file_data_block(load_file: &[u8]) -> Vec<u8> {
+    load_payload(load_file, &[])
+}
+
+/// The whole LOAD payload: the `E2` DAP blocks, if the card asks for
+/// any, followed by the load file data block.
+pub fn load_payload(load_file: &[u8], dap_blocks: &[DapBlock<'_>]) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    for dap in dap_blocks {
+        let mut block = Vec::new();
+        for object in [
+            BerTlv::from(0x4F, dap.security_domain),
+            BerTlv::from(0xC3, dap.signature),
+        ] {
+            object.write(&mut block).expect("vector sink is unbounded");
+        }
+
+        BerTlv::from(0xE2, &block)
+            .write(&mut data)
+            .expect("vector sink is unbounded");
+    }
+
+    BerTlv::from(0xC4, load_file)
+        .write(&mut data)
+        .expect("vector sink is unbounded");
+    data
+}
+
+/// One LOAD command carrying a block of the load file data block.
+pub struct Load<'a> {
+    block: &'a [u8],
+    number: u8,
+    last: bool,
+}
+
+impl<'a> Load<'a> {
+    pub fn new(block: &'a [u8], number: u8, last: bool) -> Self {
+        Self {
+            block,
+            number,
+            last,
+        }
+    }
+
+    /// Splits `payload` into numbered LOAD commands of at most `block_size`
+    /// bytes. Returns `None` if `block_size` is zero or that takes more
+    /// than [`MAX_BLOCKS`].
+    pub fn blocks(
+        payload: &'a [u8],
+        block_size: usize,
+    ) -> Option<impl ExactSizeIterator<Item = Self> + 'a> {
+        if block_size == 0 {
+            return None;
+        }
+
+        let count = payload.len().div_ceil(block_size).max(1);
+
+        if count > MAX_BLOCKS {
+            return None;
+        }
+
+        Some((0..count).map(move |number| {
+            let start = number * block_size;
+            let end = (start + block_size).min(payload.len());
+            Self::new(&payload[start..end], number as u8, number + 1 == count)
+        }))
+    }
+}
+
+impl Iso7816StreamingOperation for Load<'_> {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command {
+            class: class.with_proprietary(),
+            instruction: 0xE8,
+            parameters: (if self.last { 0x80 } else { 0x00 }, self.number),
+            data: self.block,
+        }
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
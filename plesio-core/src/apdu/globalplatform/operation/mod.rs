@@ -0,0 +1,40 @@
+//! The card management commands of a security domain. They are sent with
+//! the proprietary class on the transport's current channel, and through a
+//! secure channel if the card requires one.
+
+use alloc::vec::Vec;
+
+use crate::apdu::response::ApduResponse;
+
+pub mod delete;
+pub mod get_data;
+pub mod get_status;
+pub mod install;
+pub mod load;
+pub mod put_key;
+pub mod set_status;
+
+/// The failure of an operation whose response data is parsed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseError<'s> {
+    Rejected(ApduResponse<'s>),
+    /// The card accepted the command but its response data does not parse.
+    Malformed,
+}
+
+/// Appends `value` preceded by its one byte length, the layout of the
+/// fields of INSTALL. Returns `None` if `value` is longer than 255 bytes.
+pub(crate) fn push_field(data: &mut Vec<u8>, value: &[u8]) -> Option<()> {
+    data.push(u8::try_from(value.len()).ok()?);
+    data.extend_from_slice(value);
+    Some(())
+}
+
+/// An AID as the `4F` data object DELETE and GET STATUS take, or `None`
+/// if it is longer than 255 bytes.
+pub(crate) fn aid_object(aid: &[u8]) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(aid.len() + 2);
+    data.push(0x4F);
+    push_field(&mut data, aid)?;
+    Some(data)
+}
@@ -0,0 +1,161 @@
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+    },
+    response::ApduResponse,
+    status::is,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyType {
+    /// Two-key 3DES, as SCP02 uses.
+    Des,
+    Aes,
+}
+
+impl KeyType {
+    pub fn from_u8(key_type: u8) -> Option<Self> {
+        match key_type {
+            0x80 => Some(Self::Des),
+            0x88 => Some(Self::Aes),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::Des => 0x80,
+            Self::Aes => 0x88,
+        }
+    }
+}
+
+/// A key as PUT KEY carries it: encrypted under the DEK, with the check
+/// value the card verifies after decrypting it.
+#[derive(Clone, PartialEq, Eq)]
+pub struct KeyData {
+    key_type: KeyType,
+    length: usize,
+    encrypted: Vec<u8>,
+    check_value: [u8; 3],
+}
+
+impl KeyData {
+    /// `length` is that of the plain key, which AES keys carry separately
+    /// from the encrypted data.
+    pub fn new(key_type: KeyType, length: usize, encrypted: Vec<u8>, check_value: [u8; 3]) -> Self {
+        Self {
+            key_type,
+            length,
+            encrypted,
+            check_value,
+        }
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        self.key_type
+    }
+
+    pub fn check_value(&self) -> [u8; 3] {
+        self.check_value
+    }
+
+    fn encode(&self, data: &mut Vec<u8>) {
+        data.push(self.key_type.to_u8());
+
+        match self.key_type {
+            KeyType::Des => data.push(self.encrypted.len() as u8),
+            KeyType::Aes => {
+                data.push(self.encrypted.len() as u8 + 1);
+                data.push(self.length as u8);
+            }
+        }
+
+        data.extend_from_slice(&self.encrypted);
+        data.push(self.check_value.len() as u8);
+        data.extend_from_slice(&self.check_value);
+    }
+}
+
+impl core::fmt::Debug for KeyData {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("KeyData")
+            .field("key_type", &self.key_type)
+            .field("length", &self.length)
+            .field("check_value", &self.check_value)
+            .finish_non_exhaustive()
+    }
+}
+
+/// PUT KEY of a key set, usually the ENC, MAC and DEK keys of a secure
+/// channel with identifiers 1 to 3.
+pub struct PutKey {
+    replaced: u8,
+    identifier: u8,
+    count: usize,
+    data: Vec<u8>,
+    expected: Vec<u8>,
+}
+
+impl PutKey {
+    /// Adds the key set `version` made of `keys`.
+    pub fn new(version: u8, keys: &[KeyData]) -> Self {
+        let mut data = [version].to_vec();
+        let mut expected = [version].to_vec();
+
+        for key in keys {
+            key.encode(&mut data);
+            expected.extend_from_slice(&key.check_value);
+        }
+
+        Self {
+            replaced: 0x00,
+            identifier: 0x01,
+            count: keys.len(),
+            data,
+            expected,
+        }
+    }
+
+    /// Replaces the key set `version` rather than adding one, for instance
+    /// the factory keys, which often have version `FF`.
+    pub fn replacing(mut self, version: u8) -> Self {
+        self.replaced = version;
+        self
+    }
+
+    /// The identifier of the first key, `01` unless given.
+    pub fn with_identifier(mut self, identifier: u8) -> Self {
+        self.identifier = identifier;
+        self
+    }
+
+    /// What the card answers if it accepted every key: the key version
+    /// followed by the check values.
+    pub fn expected_response(&self) -> &[u8] {
+        &self.expected
+    }
+}
+
+impl Iso7816StreamingOperation for PutKey {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let multiple = if self.count > 1 { 0x80 } else { 0x00 };
+
+        Iso7816Command {
+            class: class.with_proprietary(),
+            instruction: 0xD8,
+            parameters: (self.replaced, multiple | self.identifier),
+            data: &self.data,
+        }
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
@@ -0,0 +1,56 @@
+use crate::apdu::{
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+    },
+    response::ApduResponse,
+    status::is,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetStatusScope {
+    IssuerSecurityDomain,
+    Application,
+    /// A security domain and the applications associated with it.
+    SecurityDomainAndApplications,
+}
+
+/// SET STATUS, which moves the card, a security domain or an application
+/// to another life cycle state, such as `0F` SECURED for the card or `83`
+/// LOCKED for an application.
+pub struct SetStatus<'a> {
+    scope: SetStatusScope,
+    state: u8,
+    aid: &'a [u8],
+}
+
+impl<'a> SetStatus<'a> {
+    /// `aid` is left empty for the issuer security domain.
+    pub fn new(scope: SetStatusScope, aid: &'a [u8], state: u8) -> Self {
+        Self { scope, state, aid }
+    }
+}
+
+impl Iso7816StreamingOperation for SetStatus<'_> {
+    type Result<'s> = Result<(), ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let scope = match self.scope {
+            SetStatusScope::IssuerSecurityDomain => 0x80,
+            SetStatusScope::Application => 0x40,
+            SetStatusScope::SecurityDomainAndApplications => 0x60,
+        };
+
+        Iso7816Command {
+            class: class.with_proprietary(),
+            instruction: 0xF0,
+            parameters: (scope, self.state),
+            data: self.aid,
+        }
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(|_| ())
+    }
+}
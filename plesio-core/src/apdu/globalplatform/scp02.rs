@@ -0,0 +1,566 @@
+//! GlobalPlatform Secure Channel Protocol '02' (Card Specification v2.2,
+//! Appendix E), the two-key 3DES secure channel of older security domains.
+//!
+//! Session keys are derived from the static keys and the card's sequence
+//! counter. Commands carry a retail MAC whose ICV is the previous C-MAC,
+//! optionally encrypted, and with C-DECRYPTION their data is encrypted under
+//! the session ENC key. Only explicit initiation with three static keys is
+//! supported, as with the common `i` parameters `15` and `55`.
+
+use des::{
+    Des, TdesEde2,
+    cipher::{BlockEncrypt, KeyInit, generic_array::GenericArray},
+};
+
+use crate::apdu::{
+    class::ApduClass,
+    command::ApduCommand,
+    globalplatform::{
+        SecurityLevel, encoded_lc,
+        operation::put_key::{KeyData, KeyType},
+        secured_class,
+    },
+    iso_7816::{
+        pending,
+        secure_messaging::{constant_time_eq, pad},
+        status,
+    },
+    owned::OwnedCommand,
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::{ApduTransport, PayloadTooLarge, TransportError},
+};
+
+const BLOCK_SIZE: usize = 8;
+const MAC_LENGTH: usize = 8;
+const KEY_LENGTH: usize = 16;
+
+/// The SCP identifier in the key information of INITIALIZE UPDATE.
+const PROTOCOL: u8 = 0x02;
+
+const C_MAC: [u8; 2] = [0x01, 0x01];
+const S_ENC: [u8; 2] = [0x01, 0x82];
+const DEK: [u8; 2] = [0x01, 0x81];
+
+/// The options of the `i` parameter this implementation requires, and all
+/// it accepts.
+const REQUIRED_OPTIONS: u8 = 0x05;
+const SUPPORTED_OPTIONS: u8 = 0x77;
+
+/// The `i` parameter of the security domain, which SCP02 cards do not
+/// report and which has to be known in advance.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Scp02Options(u8);
+
+impl Scp02Options {
+    pub const THREE_KEYS: Self = Self(0x01);
+    /// The C-MAC covers the command as it was before wrapping.
+    pub const UNMODIFIED_APDU_MAC: Self = Self(0x02);
+    pub const EXPLICIT: Self = Self(0x04);
+    pub const ICV_MAC_OVER_AID: Self = Self(0x08);
+    /// The ICV of every C-MAC after the first is the previous C-MAC
+    /// encrypted with the first half of the session MAC key.
+    pub const ICV_ENCRYPTION: Self = Self(0x10);
+    pub const R_MAC: Self = Self(0x20);
+    pub const PSEUDO_RANDOM_CHALLENGE: Self = Self(0x40);
+
+    /// `i=15`: explicit initiation, three keys and ICV encryption.
+    pub const I_15: Self = Self(0x15);
+    /// `i=55`: as `i=15`, with a pseudo-random card challenge.
+    pub const I_55: Self = Self(0x55);
+
+    pub fn from_u8(options: u8) -> Self {
+        Self(options)
+    }
+
+    pub fn to_u8(self) -> u8 {
+        self.0
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for Scp02Options {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+/// The static keys of a security domain key set, each a two-key 3DES key.
+#[derive(Clone)]
+pub struct Scp02Keys {
+    version: u8,
+    enc: [u8; KEY_LENGTH],
+    mac: [u8; KEY_LENGTH],
+    dek: [u8; KEY_LENGTH],
+}
+
+impl Scp02Keys {
+    /// The key version defaults to 0, which lets the card choose.
+    pub fn new(enc: [u8; KEY_LENGTH], mac: [u8; KEY_LENGTH], dek: [u8; KEY_LENGTH]) -> Self {
+        Self {
+            version: 0,
+            enc,
+            mac,
+            dek,
+        }
+    }
+
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The session keys of a channel opened with these keys while the
+    /// card's sequence counter is `sequence_counter`.
+    pub fn session_keys(&self, sequence_counter: [u8; 2]) -> Scp02SessionKeys {
+        Scp02SessionKeys {
+            enc: derive(&self.enc, S_ENC, &sequence_counter),
+            mac: derive(&self.mac, C_MAC, &sequence_counter),
+            dek: derive(&self.dek, DEK, &sequence_counter),
+        }
+    }
+}
+
+impl core::fmt::Debug for Scp02Keys {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Scp02Keys")
+            .field("version", &self.version)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The keys of a secure channel session: S-ENC for cryptograms and
+/// command data, C-MAC for command MACs and DEK for keys sent with PUT KEY.
+#[derive(Clone)]
+pub struct Scp02SessionKeys {
+    pub enc: [u8; KEY_LENGTH],
+    pub mac: [u8; KEY_LENGTH],
+    pub dek: [u8; KEY_LENGTH],
+}
+
+impl core::fmt::Debug for Scp02SessionKeys {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Scp02SessionKeys").finish_non_exhaustive()
+    }
+}
+
+fn encrypt_cbc(key: &[u8; KEY_LENGTH], data: &mut [u8]) {
+    let cipher = TdesEde2::new_from_slice(key).expect("3DES key length");
+    let mut previous = [0u8; BLOCK_SIZE];
+
+    for block in data.chunks_exact_mut(BLOCK_SIZE) {
+        block
+            .iter_mut()
+            .zip(previous)
+            .for_each(|(byte, iv)| *byte ^= iv);
+        cipher.encrypt_block(GenericArray::from_mut_slice(block));
+        previous.copy_from_slice(block);
+    }
+}
+
+/// The key check value of a 3DES key: the start of a zero block
+/// encrypted under it.
+pub fn key_check_value(key: &[u8; KEY_LENGTH]) -> [u8; 3] {
+    let mut block = [0u8; BLOCK_SIZE];
+    encrypt_cbc(key, &mut block);
+    [block[0], block[1], block[2]]
+}
+
+/// Derives a session key from a static key, the derivation constant and
+/// the sequence counter.
+fn derive(key: &[u8; KEY_LENGTH], constant: [u8; 2], sequence_counter: &[u8]) -> [u8; KEY_LENGTH] {
+    let mut derivation = [0u8; KEY_LENGTH];
+    derivation[..2].copy_from_slice(&constant);
+    derivation[2..4].copy_from_slice(sequence_counter);
+
+    encrypt_cbc(key, &mut derivation);
+    derivation
+}
+
+/// The full 3DES MAC of the card and host cryptograms: the last block of
+/// the padded data encrypted with 3DES in CBC mode.
+fn cryptogram(key: &[u8; KEY_LENGTH], parts: &[&[u8]]) -> [u8; MAC_LENGTH] {
+    let mut data = parts.concat();
+    pad(&mut data, BLOCK_SIZE);
+    encrypt_cbc(key, &mut data);
+
+    let mut mac = [0; MAC_LENGTH];
+    mac.copy_from_slice(&data[data.len() - BLOCK_SIZE..]);
+    mac
+}
+
+/// The ISO 9797-1 MAC algorithm 3 of padded `data`: single DES in CBC
+/// mode with the first half of the key, finished with 3DES.
+fn retail_mac(key: &[u8; KEY_LENGTH], icv: [u8; MAC_LENGTH], data: &[u8]) -> [u8; MAC_LENGTH] {
+    let single = Des::new_from_slice(&key[..8]).expect("DES key length");
+    let triple = TdesEde2::new_from_slice(key).expect("3DES key length");
+    let blocks = data.len() / BLOCK_SIZE;
+    let mut chain = GenericArray::from(icv);
+
+    for (index, block) in data.chunks_exact(BLOCK_SIZE).enumerate() {
+        chain
+            .iter_mut()
+            .zip(block)
+            .for_each(|(byte, data)| *byte ^= data);
+
+        if index + 1 < blocks {
+            single.encrypt_block(&mut chain);
+        } else {
+            triple.encrypt_block(&mut chain);
+        }
+    }
+
+    chain.into()
+}
+
+#[derive(Debug)]
+pub enum Scp02Error<E> {
+    Transport(E),
+    /// INITIALIZE UPDATE or EXTERNAL AUTHENTICATE failed with this status.
+    Rejected(ApduStatus),
+    /// The card answered INITIALIZE UPDATE for another secure channel
+    /// protocol, or the options ask for implicit initiation or a single
+    /// static key.
+    UnsupportedProtocol {
+        identifier: u8,
+        options: u8,
+    },
+    /// Response MACs and encryption are not supported over SCP02.
+    UnsupportedSecurityLevel(SecurityLevel),
+    /// The card cryptogram does not match, so the card does not hold the
+    /// static keys.
+    InvalidCardCryptogram,
+    MalformedResponse,
+    /// The secured command would not fit the wrapped transport.
+    /// `max_size` is the largest command data that does.
+    PayloadTooLarge {
+        max_size: usize,
+    },
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for Scp02Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Rejected(status) => write!(f, "card rejected the secure channel: {status}"),
+            Self::UnsupportedProtocol {
+                identifier,
+                options,
+            } => write!(
+                f,
+                "unsupported secure channel protocol {identifier:02X} with i={options:02X}"
+            ),
+            Self::UnsupportedSecurityLevel(level) => {
+                write!(f, "unsupported SCP02 security level {:02X}", level.to_u8())
+            }
+            Self::InvalidCardCryptogram => write!(f, "card cryptogram is invalid"),
+            Self::MalformedResponse => write!(f, "malformed secure channel response"),
+            Self::PayloadTooLarge { max_size } => write!(
+                f,
+                "payload exceeds the maximum of {max_size} bytes in the secure channel"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for Scp02Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E: TransportError> TransportError for Scp02Error<E> {
+    fn is_payload_too_large(&self) -> Option<PayloadTooLarge> {
+        match *self {
+            Self::PayloadTooLarge { max_size } => Some(PayloadTooLarge { max_size }),
+            _ => None,
+        }
+    }
+}
+
+/// An [`ApduTransport`] that sends every command through an SCP02 secure
+/// channel to the currently selected security domain.
+///
+/// Commands are wrapped according to the security level and responses are
+/// passed on as they are. Wrapped in an [`Iso7816Transport`], commands too
+/// large for the channel are chained, each link wrapped on its own.
+///
+/// [`Iso7816Transport`]: crate::apdu::iso_7816::transport::Iso7816Transport
+pub struct Scp02Transport<T> {
+    inner: T,
+    options: Scp02Options,
+    level: SecurityLevel,
+    enc: [u8; KEY_LENGTH],
+    mac: [u8; KEY_LENGTH],
+    dek: [u8; KEY_LENGTH],
+    /// The last C-MAC, or `None` before the first command.
+    chaining: Option<[u8; MAC_LENGTH]>,
+}
+
+impl<T: ApduTransport> Scp02Transport<T> {
+    /// Opens a secure channel to the security domain selected on `inner`.
+    ///
+    /// The host challenge has to be unpredictable to the card.
+    pub async fn open(
+        mut inner: T,
+        keys: &Scp02Keys,
+        options: Scp02Options,
+        level: SecurityLevel,
+        host_challenge: [u8; 8],
+    ) -> Result<Self, Scp02Error<T::TransportError>> {
+        if options.0 & REQUIRED_OPTIONS != REQUIRED_OPTIONS || options.0 & !SUPPORTED_OPTIONS != 0 {
+            return Err(Scp02Error::UnsupportedProtocol {
+                identifier: PROTOCOL,
+                options: options.0,
+            });
+        }
+
+        if !SecurityLevel::C_DECRYPTION.contains(level) {
+            return Err(Scp02Error::UnsupportedSecurityLevel(level));
+        }
+
+        let initialize_update =
+            OwnedCommand::new(0x80, 0x50, (keys.version, 0x00), host_challenge.to_vec());
+        let (response, status) = pending::collect(&mut inner, initialize_update)
+            .await
+            .map_err(Scp02Error::Transport)?;
+
+        if status != status::OK {
+            return Err(Scp02Error::Rejected(status));
+        }
+
+        if response.len() != 28 {
+            return Err(Scp02Error::MalformedResponse);
+        }
+
+        if response[11] != PROTOCOL {
+            return Err(Scp02Error::UnsupportedProtocol {
+                identifier: response[11],
+                options: options.0,
+            });
+        }
+
+        let sequence_counter = &response[12..14];
+        let card_challenge = &response[14..20];
+        let card_cryptogram = &response[20..28];
+
+        let session_keys = keys.session_keys([sequence_counter[0], sequence_counter[1]]);
+
+        let mut transport = Self {
+            inner,
+            options,
+            level: SecurityLevel::NONE,
+            enc: session_keys.enc,
+            mac: session_keys.mac,
+            dek: session_keys.dek,
+            chaining: None,
+        };
+
+        let expected = cryptogram(
+            &transport.enc,
+            &[&host_challenge, sequence_counter, card_challenge],
+        );
+
+        if !constant_time_eq(&expected, card_cryptogram) {
+            return Err(Scp02Error::InvalidCardCryptogram);
+        }
+
+        let host_cryptogram = cryptogram(
+            &transport.enc,
+            &[sequence_counter, card_challenge, &host_challenge],
+        );
+        let external_authenticate =
+            OwnedCommand::new(0x80, 0x82, (level.to_u8(), 0x00), host_cryptogram.to_vec());
+        let command = transport.wrap(&external_authenticate);
+
+        let mut reply = [0u8; 2];
+        let status = transport
+            .inner
+            .execute(command, &mut reply)
+            .await
+            .map_err(Scp02Error::Transport)?
+            .status();
+
+        if status != status::OK {
+            return Err(Scp02Error::Rejected(status));
+        }
+
+        transport.level = level;
+        Ok(transport)
+    }
+
+    pub fn security_level(&self) -> SecurityLevel {
+        self.level
+    }
+
+    pub fn options(&self) -> Scp02Options {
+        self.options
+    }
+
+    /// The session key that encrypts keys sent with PUT KEY.
+    pub fn session_dek(&self) -> &[u8; KEY_LENGTH] {
+        &self.dek
+    }
+
+    /// `key` encrypted under the session DEK in ECB mode, as PUT KEY sends
+    /// it.
+    pub fn encrypt_key(&self, key: &[u8; KEY_LENGTH]) -> KeyData {
+        let mut encrypted = key.to_vec();
+        encrypted
+            .chunks_exact_mut(BLOCK_SIZE)
+            .for_each(|block| encrypt_cbc(&self.dek, block));
+
+        KeyData::new(KeyType::Des, KEY_LENGTH, encrypted, key_check_value(key))
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn is_encrypting(&self) -> bool {
+        self.level.contains(SecurityLevel::C_DECRYPTION)
+    }
+
+    /// The length of the data of a wrapped command with `length` bytes of
+    /// data.
+    fn wrapped_length(&self, length: usize) -> usize {
+        match length {
+            0 => MAC_LENGTH,
+            _ if self.is_encrypting() => (length + 1).next_multiple_of(BLOCK_SIZE) + MAC_LENGTH,
+            _ => length + MAC_LENGTH,
+        }
+    }
+
+    /// The largest command data whose wrapped form fits in `max_size`
+    /// bytes.
+    fn capacity(&self, max_size: usize) -> usize {
+        let room = max_size.saturating_sub(MAC_LENGTH);
+
+        match self.is_encrypting() {
+            true => (room / BLOCK_SIZE * BLOCK_SIZE).saturating_sub(1),
+            false => room,
+        }
+    }
+
+    /// The ICV of the next C-MAC.
+    fn icv(&self) -> [u8; MAC_LENGTH] {
+        let Some(mut icv) = self.chaining else {
+            return [0; MAC_LENGTH];
+        };
+
+        if self.options.contains(Scp02Options::ICV_ENCRYPTION) {
+            let cipher = Des::new_from_slice(&self.mac[..8]).expect("DES key length");
+            cipher.encrypt_block(GenericArray::from_mut_slice(&mut icv));
+        }
+
+        icv
+    }
+
+    /// Appends the C-MAC to `command` and encrypts its data if C-DECRYPTION
+    /// is on. The MAC covers the plain data.
+    fn wrap(&mut self, command: &impl ApduCommand) -> OwnedCommand<u8> {
+        let original_class = command.class().to_u8();
+        let class = secured_class(original_class);
+        let instruction = command.instruction();
+        let (p1, p2) = command.parameters();
+        let mut data = command.data().to_vec();
+
+        let mut authenticated = match self.options.contains(Scp02Options::UNMODIFIED_APDU_MAC) {
+            true => [original_class, instruction, p1, p2].to_vec(),
+            false => [class, instruction, p1, p2].to_vec(),
+        };
+
+        match self.options.contains(Scp02Options::UNMODIFIED_APDU_MAC) {
+            true => authenticated.extend_from_slice(&encoded_lc(data.len())),
+            false => authenticated.extend_from_slice(&encoded_lc(data.len() + MAC_LENGTH)),
+        }
+
+        authenticated.extend_from_slice(&data);
+        pad(&mut authenticated, BLOCK_SIZE);
+
+        let mac = retail_mac(&self.mac, self.icv(), &authenticated);
+        self.chaining = Some(mac);
+
+        if self.is_encrypting() && !data.is_empty() {
+            pad(&mut data, BLOCK_SIZE);
+            encrypt_cbc(&self.enc, &mut data);
+        }
+
+        data.extend_from_slice(&mac);
+        OwnedCommand::new(class, instruction, (p1, p2), data)
+    }
+}
+
+impl<T: ApduTransport> ApduTransport for Scp02Transport<T> {
+    type TransportError = Scp02Error<T::TransportError>;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, Self::TransportError>> {
+        // GET RESPONSE belongs to the transmission of the previous response,
+        // not to the secure channel.
+        let get_response = command.instruction() == 0xC0 && command.class().to_u8() < 0x80;
+
+        async move {
+            if get_response || !self.level.contains(SecurityLevel::C_MAC) {
+                return self
+                    .inner
+                    .execute(command, reply_buffer)
+                    .await
+                    .map_err(Scp02Error::Transport);
+            }
+
+            let max_payload_size = self.inner.max_payload_size();
+
+            if self.wrapped_length(command.data().len()) > max_payload_size {
+                return Err(Scp02Error::PayloadTooLarge {
+                    max_size: self.capacity(max_payload_size),
+                });
+            }
+
+            let chaining = self.chaining;
+            let wrapped = self.wrap(&command);
+
+            self.inner
+                .execute(wrapped, reply_buffer)
+                .await
+                .map_err(|error| match error.is_payload_too_large() {
+                    // Nothing was sent, so the MAC chain stands.
+                    Some(PayloadTooLarge { max_size }) => {
+                        self.chaining = chaining;
+                        Scp02Error::PayloadTooLarge {
+                            max_size: self.capacity(max_size),
+                        }
+                    }
+                    None => Scp02Error::Transport(error),
+                })
+        }
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.capacity(self.inner.max_payload_size())
+    }
+}
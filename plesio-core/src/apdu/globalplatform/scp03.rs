@@ -0,0 +1,645 @@
+//! GlobalPlatform Secure Channel Protocol '03' (Card Specification v2.3,
+//! Amendment D), the AES based secure channel of current security domains.
+//!
+//! INITIALIZE UPDATE exchanges challenges, from which session keys are
+//! derived with the NIST SP 800-108 counter mode KDF over AES-CMAC. The card
+//! proves knowledge of the static keys with its cryptogram and the host with
+//! its own in EXTERNAL AUTHENTICATE, which also sets the security level of
+//! the commands and responses that follow.
+
+use alloc::vec::Vec;
+
+use aes::{
+    Aes128, Aes192, Aes256,
+    cipher::{BlockDecrypt, BlockEncrypt, KeyInit, generic_array::GenericArray},
+};
+use cmac::{Cmac, Mac};
+
+use crate::apdu::{
+    class::ApduClass,
+    command::ApduCommand,
+    globalplatform::{
+        SecurityLevel, encoded_lc,
+        operation::put_key::{KeyData, KeyType},
+        secured_class,
+    },
+    iso_7816::{
+        pending::{self, PendingResponse},
+        secure_messaging::{constant_time_eq, is_protected, pad, unpad},
+        status,
+    },
+    owned::OwnedCommand,
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::{ApduTransport, PayloadTooLarge, TransportError},
+};
+
+const BLOCK_SIZE: usize = 16;
+const MAC_LENGTH: usize = 8;
+const CHALLENGE_LENGTH: usize = 8;
+
+/// The SCP identifier in the key information of INITIALIZE UPDATE.
+const PROTOCOL: u8 = 0x03;
+
+/// The `i` parameter bit selecting 16 byte challenges and cryptograms.
+const S16_MODE: u8 = 0x01;
+
+const CARD_CRYPTOGRAM: u8 = 0x00;
+const HOST_CRYPTOGRAM: u8 = 0x01;
+const S_ENC: u8 = 0x04;
+const S_MAC: u8 = 0x06;
+const S_RMAC: u8 = 0x07;
+
+/// The static keys of a security domain key set. ENC and MAC are used to
+/// derive the session keys, DEK encrypts keys sent to the card.
+#[derive(Clone)]
+pub struct Scp03Keys {
+    version: u8,
+    enc: Vec<u8>,
+    mac: Vec<u8>,
+    dek: Vec<u8>,
+}
+
+impl Scp03Keys {
+    /// AES keys of 16, 24 or 32 bytes, all of the same length. The key
+    /// version defaults to 0, which lets the card choose.
+    pub fn new(enc: &[u8], mac: &[u8], dek: &[u8]) -> Option<Self> {
+        let valid =
+            matches!(enc.len(), 16 | 24 | 32) && mac.len() == enc.len() && dek.len() == enc.len();
+
+        valid.then(|| Self {
+            version: 0,
+            enc: enc.to_vec(),
+            mac: mac.to_vec(),
+            dek: dek.to_vec(),
+        })
+    }
+
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn dek(&self) -> &[u8] {
+        &self.dek
+    }
+
+    /// The session keys of a channel opened with these keys and the
+    /// challenges of both sides.
+    pub fn session_keys(
+        &self,
+        host_challenge: &[u8; CHALLENGE_LENGTH],
+        card_challenge: &[u8; CHALLENGE_LENGTH],
+    ) -> Scp03SessionKeys {
+        let mut context = host_challenge.to_vec();
+        context.extend_from_slice(card_challenge);
+
+        let length = self.enc.len();
+        let enc = AesKey(self.enc.clone());
+        let mac = AesKey(self.mac.clone());
+
+        Scp03SessionKeys {
+            enc: enc.derive(S_ENC, &context, length),
+            mac: mac.derive(S_MAC, &context, length),
+            rmac: mac.derive(S_RMAC, &context, length),
+        }
+    }
+
+    /// `key` encrypted under the DEK in CBC mode with a zero IV, as PUT
+    /// KEY sends it. AES-192 keys are padded with zeros to whole blocks.
+    /// Returns `None` unless `key` is 16, 24 or 32 bytes long.
+    pub fn encrypt_key(&self, key: &[u8]) -> Option<KeyData> {
+        let check_value = key_check_value(key)?;
+        let mut encrypted = key.to_vec();
+        encrypted.resize(key.len().next_multiple_of(BLOCK_SIZE), 0x00);
+        AesKey(self.dek.clone()).encrypt_cbc([0; BLOCK_SIZE], &mut encrypted);
+
+        Some(KeyData::new(
+            KeyType::Aes,
+            key.len(),
+            encrypted,
+            check_value,
+        ))
+    }
+}
+
+/// The key check value of an AES key: the start of a block of `01` bytes
+/// encrypted under it. Returns `None` unless `key` is 16, 24 or 32 bytes
+/// long.
+pub fn key_check_value(key: &[u8]) -> Option<[u8; 3]> {
+    if !matches!(key.len(), 16 | 24 | 32) {
+        return None;
+    }
+
+    let block = AesKey(key.to_vec()).encrypt_block([0x01; BLOCK_SIZE]);
+    Some([block[0], block[1], block[2]])
+}
+
+impl core::fmt::Debug for Scp03Keys {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Scp03Keys")
+            .field("version", &self.version)
+            .field("length", &self.enc.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The keys of a secure channel session: S-ENC encrypts command and
+/// response data, S-MAC and S-RMAC authenticate commands and responses.
+#[derive(Clone)]
+pub struct Scp03SessionKeys {
+    pub enc: Vec<u8>,
+    pub mac: Vec<u8>,
+    pub rmac: Vec<u8>,
+}
+
+impl core::fmt::Debug for Scp03SessionKeys {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Scp03SessionKeys")
+            .field("length", &self.enc.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An AES key of any length, with the operations SCP03 needs.
+struct AesKey(Vec<u8>);
+
+impl AesKey {
+    fn cmac(&self, data: &[u8]) -> [u8; BLOCK_SIZE] {
+        match self.0.len() {
+            16 => cmac::<Cmac<Aes128>>(&self.0, data),
+            24 => cmac::<Cmac<Aes192>>(&self.0, data),
+            _ => cmac::<Cmac<Aes256>>(&self.0, data),
+        }
+    }
+
+    fn encrypt_cbc(&self, iv: [u8; BLOCK_SIZE], data: &mut [u8]) {
+        match self.0.len() {
+            16 => encrypt_cbc::<Aes128>(&self.0, iv, data),
+            24 => encrypt_cbc::<Aes192>(&self.0, iv, data),
+            _ => encrypt_cbc::<Aes256>(&self.0, iv, data),
+        }
+    }
+
+    fn decrypt_cbc(&self, iv: [u8; BLOCK_SIZE], data: &mut [u8]) {
+        match self.0.len() {
+            16 => decrypt_cbc::<Aes128>(&self.0, iv, data),
+            24 => decrypt_cbc::<Aes192>(&self.0, iv, data),
+            _ => decrypt_cbc::<Aes256>(&self.0, iv, data),
+        }
+    }
+
+    fn encrypt_block(&self, mut block: [u8; BLOCK_SIZE]) -> [u8; BLOCK_SIZE] {
+        self.encrypt_cbc([0; BLOCK_SIZE], &mut block);
+        block
+    }
+
+    /// The NIST SP 800-108 KDF in counter mode with AES-CMAC as PRF, laid
+    /// out as Amendment D section 6.2.2 specifies.
+    fn derive(&self, constant: u8, context: &[u8], length: usize) -> Vec<u8> {
+        let bits = (length * 8) as u16;
+        let mut output = Vec::new();
+        let mut counter = 1u8;
+
+        while output.len() < length {
+            let mut input = [0u8; 12].to_vec();
+            input[11] = constant;
+            input.push(0x00);
+            input.extend_from_slice(&bits.to_be_bytes());
+            input.push(counter);
+            input.extend_from_slice(context);
+
+            output.extend_from_slice(&self.cmac(&input));
+            counter += 1;
+        }
+
+        output.truncate(length);
+        output
+    }
+}
+
+fn cmac<M: Mac + KeyInit>(key: &[u8], data: &[u8]) -> [u8; BLOCK_SIZE] {
+    let mut mac = <M as KeyInit>::new_from_slice(key).expect("AES key length");
+    mac.update(data);
+
+    let mut output = [0; BLOCK_SIZE];
+    output.copy_from_slice(&mac.finalize().into_bytes());
+    output
+}
+
+fn encrypt_cbc<C: KeyInit + BlockEncrypt>(key: &[u8], iv: [u8; BLOCK_SIZE], data: &mut [u8]) {
+    let cipher = C::new_from_slice(key).expect("AES key length");
+    let mut previous = iv;
+
+    for block in data.chunks_exact_mut(BLOCK_SIZE) {
+        block
+            .iter_mut()
+            .zip(previous)
+            .for_each(|(byte, iv)| *byte ^= iv);
+        cipher.encrypt_block(GenericArray::from_mut_slice(block));
+        previous.copy_from_slice(block);
+    }
+}
+
+fn decrypt_cbc<C: KeyInit + BlockDecrypt>(key: &[u8], iv: [u8; BLOCK_SIZE], data: &mut [u8]) {
+    let cipher = C::new_from_slice(key).expect("AES key length");
+    let mut previous = iv;
+
+    for block in data.chunks_exact_mut(BLOCK_SIZE) {
+        let mut next = [0; BLOCK_SIZE];
+        next.copy_from_slice(block);
+        cipher.decrypt_block(GenericArray::from_mut_slice(block));
+        block
+            .iter_mut()
+            .zip(previous)
+            .for_each(|(byte, iv)| *byte ^= iv);
+        previous = next;
+    }
+}
+
+#[derive(Debug)]
+pub enum Scp03Error<E> {
+    Transport(E),
+    /// INITIALIZE UPDATE or EXTERNAL AUTHENTICATE failed with this status.
+    Rejected(ApduStatus),
+    /// The card answered INITIALIZE UPDATE for another secure channel
+    /// protocol, or asked for the unsupported S16 mode.
+    UnsupportedProtocol {
+        identifier: u8,
+        parameter: u8,
+    },
+    /// The card cryptogram does not match, so the card does not hold the
+    /// static keys.
+    InvalidCardCryptogram,
+    /// The response MAC is missing or wrong. The session can no longer be
+    /// trusted and has to be opened again.
+    InvalidMac,
+    MalformedResponse,
+    /// The secured command would not fit the wrapped transport.
+    /// `max_size` is the largest command data that does.
+    PayloadTooLarge {
+        max_size: usize,
+    },
+    /// The reply buffer has no room for the status bytes.
+    ReplyBufferTooSmall {
+        expected: usize,
+    },
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for Scp03Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Rejected(status) => write!(f, "card rejected the secure channel: {status}"),
+            Self::UnsupportedProtocol {
+                identifier,
+                parameter,
+            } => write!(
+                f,
+                "unsupported secure channel protocol {identifier:02X} with i={parameter:02X}"
+            ),
+            Self::InvalidCardCryptogram => write!(f, "card cryptogram is invalid"),
+            Self::InvalidMac => write!(f, "response MAC is missing or invalid"),
+            Self::MalformedResponse => write!(f, "malformed secure channel response"),
+            Self::PayloadTooLarge { max_size } => write!(
+                f,
+                "payload exceeds the maximum of {max_size} bytes in the secure channel"
+            ),
+            Self::ReplyBufferTooSmall { expected } => {
+                write!(f, "reply buffer too small: {expected} bytes required")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for Scp03Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E: TransportError> TransportError for Scp03Error<E> {
+    fn is_payload_too_large(&self) -> Option<PayloadTooLarge> {
+        match *self {
+            Self::PayloadTooLarge { max_size } => Some(PayloadTooLarge { max_size }),
+            _ => None,
+        }
+    }
+}
+
+/// An [`ApduTransport`] that sends every command through an SCP03 secure
+/// channel to the currently selected security domain.
+///
+/// Commands are wrapped according to the security level. With R-MAC,
+/// responses are collected in full with GET RESPONSE and verified before
+/// they are passed on; data that does not fit the reply buffer is held back
+/// and served to the next GET RESPONSE, as a card would. Wrapped in an
+/// [`Iso7816Transport`], commands too large for the channel are chained,
+/// each link wrapped on its own.
+///
+/// [`Iso7816Transport`]: crate::apdu::iso_7816::transport::Iso7816Transport
+pub struct Scp03Transport<T> {
+    inner: T,
+    level: SecurityLevel,
+    enc: AesKey,
+    mac: AesKey,
+    rmac: AesKey,
+    /// The full C-MAC of the last command, which every MAC continues from.
+    chaining: [u8; BLOCK_SIZE],
+    /// The encryption counter of the next command.
+    counter: u128,
+    pending: PendingResponse,
+}
+
+impl<T: ApduTransport> Scp03Transport<T> {
+    /// Opens a secure channel to the security domain selected on `inner`.
+    ///
+    /// The host challenge has to be unpredictable to the card.
+    pub async fn open(
+        mut inner: T,
+        keys: &Scp03Keys,
+        level: SecurityLevel,
+        host_challenge: [u8; CHALLENGE_LENGTH],
+    ) -> Result<Self, Scp03Error<T::TransportError>> {
+        let initialize_update =
+            OwnedCommand::new(0x80, 0x50, (keys.version, 0x00), host_challenge.to_vec());
+        let (response, status) = pending::collect(&mut inner, initialize_update)
+            .await
+            .map_err(Scp03Error::Transport)?;
+
+        if status != status::OK {
+            return Err(Scp03Error::Rejected(status));
+        }
+
+        if response.len() != 29 && response.len() != 32 {
+            return Err(Scp03Error::MalformedResponse);
+        }
+
+        let (identifier, parameter) = (response[11], response[12]);
+
+        if identifier != PROTOCOL || parameter & S16_MODE != 0 {
+            return Err(Scp03Error::UnsupportedProtocol {
+                identifier,
+                parameter,
+            });
+        }
+
+        let card_challenge: &[u8; CHALLENGE_LENGTH] = response[13..21]
+            .try_into()
+            .expect("eight byte card challenge");
+        let card_cryptogram = &response[21..29];
+
+        let mut context = host_challenge.to_vec();
+        context.extend_from_slice(card_challenge);
+
+        let session_keys = keys.session_keys(&host_challenge, card_challenge);
+
+        let mut transport = Self {
+            level: SecurityLevel::NONE,
+            enc: AesKey(session_keys.enc),
+            mac: AesKey(session_keys.mac),
+            rmac: AesKey(session_keys.rmac),
+            inner,
+            chaining: [0; BLOCK_SIZE],
+            counter: 1,
+            pending: PendingResponse::default(),
+        };
+
+        let expected = transport
+            .mac
+            .derive(CARD_CRYPTOGRAM, &context, CHALLENGE_LENGTH);
+
+        if !constant_time_eq(&expected, card_cryptogram) {
+            return Err(Scp03Error::InvalidCardCryptogram);
+        }
+
+        let host_cryptogram = transport
+            .mac
+            .derive(HOST_CRYPTOGRAM, &context, CHALLENGE_LENGTH);
+        let external_authenticate =
+            OwnedCommand::new(0x80, 0x82, (level.to_u8(), 0x00), host_cryptogram);
+        let command = transport.wrap(&external_authenticate);
+
+        let mut reply = [0u8; 2];
+        let status = transport
+            .inner
+            .execute(command, &mut reply)
+            .await
+            .map_err(Scp03Error::Transport)?
+            .status();
+
+        if status != status::OK {
+            return Err(Scp03Error::Rejected(status));
+        }
+
+        transport.level = level;
+        Ok(transport)
+    }
+
+    pub fn security_level(&self) -> SecurityLevel {
+        self.level
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn is_encrypting(&self) -> bool {
+        self.level.contains(SecurityLevel::C_DECRYPTION)
+    }
+
+    /// The length of the data of a wrapped command with `length` bytes of
+    /// data.
+    fn wrapped_length(&self, length: usize) -> usize {
+        match length {
+            0 => MAC_LENGTH,
+            _ if self.is_encrypting() => (length + 1).next_multiple_of(BLOCK_SIZE) + MAC_LENGTH,
+            _ => length + MAC_LENGTH,
+        }
+    }
+
+    /// The largest command data whose wrapped form fits in `max_size`
+    /// bytes.
+    fn capacity(&self, max_size: usize) -> usize {
+        let room = max_size.saturating_sub(MAC_LENGTH);
+
+        match self.is_encrypting() {
+            true => (room / BLOCK_SIZE * BLOCK_SIZE).saturating_sub(1),
+            false => room,
+        }
+    }
+
+    /// Encrypts the data of `command` if C-DECRYPTION is on and appends its
+    /// C-MAC.
+    fn wrap(&mut self, command: &impl ApduCommand) -> OwnedCommand<u8> {
+        let class = secured_class(command.class().to_u8());
+        let instruction = command.instruction();
+        let (p1, p2) = command.parameters();
+        let mut data = command.data().to_vec();
+
+        if self.is_encrypting() && !data.is_empty() {
+            pad(&mut data, BLOCK_SIZE);
+            let icv = self.enc.encrypt_block(self.counter.to_be_bytes());
+            self.enc.encrypt_cbc(icv, &mut data);
+        }
+
+        let mut authenticated = self.chaining.to_vec();
+        authenticated.extend_from_slice(&[class, instruction, p1, p2]);
+        authenticated.extend_from_slice(&encoded_lc(data.len() + MAC_LENGTH));
+        authenticated.extend_from_slice(&data);
+
+        self.chaining = self.mac.cmac(&authenticated);
+        data.extend_from_slice(&self.chaining[..MAC_LENGTH]);
+
+        OwnedCommand::new(class, instruction, (p1, p2), data)
+    }
+
+    /// Verifies the R-MAC of a response to the command with encryption
+    /// counter `counter` and decrypts its data.
+    fn unwrap(
+        &self,
+        mut data: Vec<u8>,
+        status: ApduStatus,
+        counter: u128,
+    ) -> Result<Vec<u8>, Scp03Error<T::TransportError>> {
+        if data.is_empty() && !is_protected(status) {
+            return Ok(data);
+        }
+
+        let length = data
+            .len()
+            .checked_sub(MAC_LENGTH)
+            .ok_or(Scp03Error::InvalidMac)?;
+
+        let mut authenticated = self.chaining.to_vec();
+        authenticated.extend_from_slice(&data[..length]);
+        authenticated.extend_from_slice(&[status.code1(), status.code2()]);
+
+        let expected = self.rmac.cmac(&authenticated);
+
+        if !constant_time_eq(&expected[..MAC_LENGTH], &data[length..]) {
+            return Err(Scp03Error::InvalidMac);
+        }
+
+        data.truncate(length);
+
+        if self.level.contains(SecurityLevel::R_ENCRYPTION) && !data.is_empty() {
+            if !data.len().is_multiple_of(BLOCK_SIZE) {
+                return Err(Scp03Error::MalformedResponse);
+            }
+
+            let mut block = counter.to_be_bytes();
+            block[0] = 0x80;
+            let icv = self.enc.encrypt_block(block);
+            self.enc.decrypt_cbc(icv, &mut data);
+
+            let length = unpad(&data).ok_or(Scp03Error::MalformedResponse)?.len();
+            data.truncate(length);
+        }
+
+        Ok(data)
+    }
+
+    /// Translates the error of a wrapped command. A payload too large was
+    /// not sent, so the MAC chain and counter are restored.
+    fn failed(
+        &mut self,
+        error: T::TransportError,
+        chaining: [u8; BLOCK_SIZE],
+        counter: u128,
+    ) -> Scp03Error<T::TransportError> {
+        match error.is_payload_too_large() {
+            Some(PayloadTooLarge { max_size }) => {
+                (self.chaining, self.counter) = (chaining, counter);
+                Scp03Error::PayloadTooLarge {
+                    max_size: self.capacity(max_size),
+                }
+            }
+            None => Scp03Error::Transport(error),
+        }
+    }
+
+    fn deliver<'r>(
+        &mut self,
+        data: Vec<u8>,
+        status: ApduStatus,
+        reply_buffer: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, Scp03Error<T::TransportError>> {
+        self.pending
+            .deliver(data, status, reply_buffer)
+            .ok_or(Scp03Error::ReplyBufferTooSmall { expected: 2 })
+    }
+}
+
+impl<T: ApduTransport> ApduTransport for Scp03Transport<T> {
+    type TransportError = Scp03Error<T::TransportError>;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, Self::TransportError>> {
+        let instruction = command.instruction();
+        // GET RESPONSE belongs to the transmission of the previous response,
+        // not to the secure channel.
+        let get_response = instruction == 0xC0 && command.class().to_u8() < 0x80;
+
+        async move {
+            if let Some((data, status)) = self.pending.take(instruction) {
+                return self.deliver(data, status, reply_buffer);
+            }
+
+            if get_response || !self.level.contains(SecurityLevel::C_MAC) {
+                return self
+                    .inner
+                    .execute(command, reply_buffer)
+                    .await
+                    .map_err(Scp03Error::Transport);
+            }
+
+            let max_payload_size = self.inner.max_payload_size();
+
+            if self.wrapped_length(command.data().len()) > max_payload_size {
+                return Err(Scp03Error::PayloadTooLarge {
+                    max_size: self.capacity(max_payload_size),
+                });
+            }
+
+            let (chaining, counter) = (self.chaining, self.counter);
+            let wrapped = self.wrap(&command);
+            self.counter += 1;
+
+            if !self.level.contains(SecurityLevel::R_MAC) {
+                let result = self.inner.execute(wrapped, reply_buffer).await;
+                return result.map_err(|error| self.failed(error, chaining, counter));
+            }
+
+            let (data, status) = pending::collect(&mut self.inner, wrapped)
+                .await
+                .map_err(|error| self.failed(error, chaining, counter))?;
+
+            let data = self.unwrap(data, status, counter)?;
+            self.deliver(data, status, reply_buffer)
+        }
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.capacity(self.inner.max_payload_size())
+    }
+}
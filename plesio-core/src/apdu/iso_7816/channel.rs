@@ -9,3 +9,11 @@ impl Default for Iso7816Channel {
         Iso7816Channel::Basic(0)
     }
 }
+
+impl Iso7816Channel {
+    pub fn number(&self) -> u8 {
+        match *self {
+            Iso7816Channel::Basic(number) | Iso7816Channel::Extended(number) => number,
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::apdu::class::ApduClass;
+use crate::apdu::{class::ApduClass, iso_7816::channel::Iso7816Channel};
 
 #[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum SecureMessaging {
@@ -59,6 +59,7 @@ impl Iso7816ClassState {
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Iso7816Class {
     state: Iso7816ClassState,
+    proprietary: bool,
 }
 
 impl Default for Iso7816Class {
@@ -69,14 +70,50 @@ impl Default for Iso7816Class {
                 secure_messaging: SecureMessaging::None,
                 basic_channel: 0,
             },
+            proprietary: false,
         }
     }
 }
 
 impl Iso7816Class {
+    /// The class for interindustry commands on `channel`, without chaining
+    /// or secure messaging.
+    ///
+    /// Basic channels are 0 to 3 and extended channels 4 to 19.
+    pub fn for_channel(channel: Iso7816Channel) -> Option<Self> {
+        let state = match channel {
+            Iso7816Channel::Basic(basic_channel @ 0..=3) => Iso7816ClassState::Basic {
+                chaining: false,
+                secure_messaging: SecureMessaging::None,
+                basic_channel,
+            },
+            Iso7816Channel::Extended(extended_channel @ 4..=19) => Iso7816ClassState::Extended {
+                chaining: false,
+                is_secure_messaging: false,
+                extended_channel,
+            },
+            _ => return None,
+        };
+
+        Some(Self {
+            state,
+            proprietary: false,
+        })
+    }
+
+    pub fn channel(&self) -> Iso7816Channel {
+        match self.state {
+            Iso7816ClassState::Basic { basic_channel, .. } => Iso7816Channel::Basic(basic_channel),
+            Iso7816ClassState::Extended {
+                extended_channel, ..
+            } => Iso7816Channel::Extended(extended_channel),
+        }
+    }
+
     pub fn with_chaining(&self) -> Iso7816Class {
         Iso7816Class {
             state: self.state.with_chaining(),
+            ..*self
         }
     }
 
@@ -84,6 +121,62 @@ impl Iso7816Class {
         self.state.is_chaining()
     }
 
+    /// Extended classes only indicate whether secure messaging is used, with
+    /// the header not authenticated.
+    pub fn secure_messaging(&self) -> SecureMessaging {
+        match self.state {
+            Iso7816ClassState::Basic {
+                secure_messaging, ..
+            } => secure_messaging,
+            Iso7816ClassState::Extended {
+                is_secure_messaging: true,
+                ..
+            } => SecureMessaging::Authenticated,
+            Iso7816ClassState::Extended { .. } => SecureMessaging::None,
+        }
+    }
+
+    /// The same class with the secure messaging indication replaced.
+    /// Extended classes cannot indicate an authenticated header, so
+    /// [`SecureMessaging::HeaderAuthenticated`] sets only their SM bit.
+    pub fn with_secure_messaging(&self, secure_messaging: SecureMessaging) -> Iso7816Class {
+        let state = match self.state {
+            Iso7816ClassState::Basic {
+                chaining,
+                basic_channel,
+                ..
+            } => Iso7816ClassState::Basic {
+                chaining,
+                secure_messaging,
+                basic_channel,
+            },
+            Iso7816ClassState::Extended {
+                chaining,
+                extended_channel,
+                ..
+            } => Iso7816ClassState::Extended {
+                chaining,
+                is_secure_messaging: secure_messaging != SecureMessaging::None,
+                extended_channel,
+            },
+        };
+
+        Iso7816Class { state, ..*self }
+    }
+
+    /// The same class with b8 set, for proprietary commands such as those
+    /// of GlobalPlatform that otherwise follow the interindustry coding.
+    pub fn with_proprietary(&self) -> Iso7816Class {
+        Iso7816Class {
+            proprietary: true,
+            ..*self
+        }
+    }
+
+    pub fn is_proprietary(&self) -> bool {
+        self.proprietary
+    }
+
     pub fn from_u8(class: u8) -> Option<Self> {
         if class >= 0x80 {
             return None;
@@ -99,6 +192,7 @@ impl Iso7816Class {
                     is_secure_messaging: (class & 0x20) != 0,
                     extended_channel: (class & 0x0F) + 4,
                 },
+                proprietary: false,
             })
         } else {
             if (class & 0x20) != 0 {
@@ -120,6 +214,7 @@ impl Iso7816Class {
                     secure_messaging,
                     basic_channel: class & 0x03,
                 },
+                proprietary: false,
             })
         }
     }
@@ -127,7 +222,7 @@ impl Iso7816Class {
 
 impl ApduClass for Iso7816Class {
     fn to_u8(&self) -> u8 {
-        match &self.state {
+        let class = match &self.state {
             Iso7816ClassState::Basic {
                 chaining,
                 secure_messaging,
@@ -157,6 +252,9 @@ impl ApduClass for Iso7816Class {
                     | extended_channel_bits
                     | is_extended_range_bit
             }
-        }
+        };
+
+        let proprietary_bit = if self.proprietary { 0x80 } else { 0 };
+        class | proprietary_bit
     }
 }
@@ -1,6 +1,10 @@
 pub mod channel;
 pub mod class;
 pub mod operation;
+#[cfg(feature = "alloc")]
+pub(crate) mod pending;
+#[cfg(feature = "alloc")]
+pub mod secure_messaging;
 pub mod status;
 pub mod tlv;
 pub mod transport;
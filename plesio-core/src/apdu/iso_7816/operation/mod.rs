@@ -1,5 +1,6 @@
 pub mod chunking;
 pub mod get_response;
+pub mod read_record;
 pub mod select;
 
 use crate::apdu::{
@@ -15,6 +16,17 @@ pub trait Iso7816Operation<'a> {
     fn parse(self, response: &ApduResponse<'a>) -> Self::Result;
 }
 
+/// An operation whose response is streamed into a [`ResponseSink`] rather
+/// than a buffer the operation provides itself.
+///
+/// [`ResponseSink`]: crate::apdu::sink::ResponseSink
+pub trait Iso7816StreamingOperation {
+    type Result<'s>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_>;
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s>;
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Iso7816Command<'a> {
     pub(crate) class: Iso7816Class,
@@ -24,6 +36,15 @@ pub struct Iso7816Command<'a> {
 }
 
 impl<'a> Iso7816Command<'a> {
+    pub fn new(class: Iso7816Class, instruction: u8, parameters: (u8, u8), data: &'a [u8]) -> Self {
+        Self {
+            class,
+            instruction,
+            parameters,
+            data,
+        }
+    }
+
     pub fn chunk(self, max_size: usize) -> CommandChunker<'a> {
         CommandChunker {
             base_command: self,
@@ -34,6 +55,20 @@ impl<'a> Iso7816Command<'a> {
     }
 }
 
+/// A raw command streams its response back unparsed. Its own class byte is
+/// sent as-is rather than the transport's current class state.
+impl<'a> Iso7816StreamingOperation for Iso7816Command<'a> {
+    type Result<'s> = ApduResponse<'s>;
+
+    fn command(&self, _class: Iso7816Class) -> Iso7816Command<'_> {
+        *self
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        *response
+    }
+}
+
 impl<'a> ApduCommand for Iso7816Command<'a> {
     type Class = Iso7816Class;
 
@@ -0,0 +1,44 @@
+use crate::apdu::{
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+    },
+    response::ApduResponse,
+    status::is,
+};
+
+/// READ RECORD of one record, by number, from the file with a short file
+/// identifier.
+pub struct ReadRecord {
+    record: u8,
+    short_file_identifier: u8,
+}
+
+impl ReadRecord {
+    /// Panics unless `short_file_identifier` is between 1 and 30.
+    pub fn new(short_file_identifier: u8, record: u8) -> Self {
+        assert!(
+            (1..=30).contains(&short_file_identifier),
+            "short file identifier out of range"
+        );
+
+        Self {
+            record,
+            short_file_identifier,
+        }
+    }
+}
+
+impl Iso7816StreamingOperation for ReadRecord {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let reference = (self.short_file_identifier << 3) | 0b100;
+        Iso7816Command::new(class, 0xB2, (self.record, reference), &[])
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
@@ -4,7 +4,8 @@ use crate::apdu::{
     iso_7816::{
         class::Iso7816Class,
         operation::{
-            Iso7816Command, Iso7816Operation, select::resolution::Iso7816SelectResolution,
+            Iso7816Command, Iso7816Operation, Iso7816StreamingOperation,
+            select::resolution::Iso7816SelectResolution,
         },
         status,
         tlv::iter::TlvIterator,
@@ -57,10 +58,8 @@ impl<'aid, 'res> Iso7816Select<'aid, 'res> {
     }
 }
 
-impl<'aid, 'res> Iso7816Operation<'res> for Iso7816Select<'aid, 'res> {
-    type Result = Result<TlvIterator<'res>, ApduResponse<'res>>;
-
-    fn build<'b>(&'b mut self, class: Iso7816Class) -> (Iso7816Command<'b>, &'res mut [u8]) {
+impl<'aid, 'res> Iso7816Select<'aid, 'res> {
+    fn build_command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
         let occurrence = match self.occurrence {
             SelectOccurrence::First => 0b00,
             SelectOccurrence::Last => 0b01,
@@ -75,7 +74,7 @@ impl<'aid, 'res> Iso7816Operation<'res> for Iso7816Select<'aid, 'res> {
             FileControlFlag::WithFileManagementData => 0b1100,
         };
 
-        let command = Iso7816Command::<'b> {
+        Iso7816Command {
             class,
             instruction: 0xA4,
             parameters: (
@@ -83,12 +82,32 @@ impl<'aid, 'res> Iso7816Operation<'res> for Iso7816Select<'aid, 'res> {
                 file_control_flag | occurrence,
             ),
             data: self.resolution.data(),
-        };
+        }
+    }
+}
 
-        (command, self.response.take().unwrap_or(&mut []))
+impl<'aid, 'res> Iso7816Operation<'res> for Iso7816Select<'aid, 'res> {
+    type Result = Result<TlvIterator<'res>, ApduResponse<'res>>;
+
+    fn build<'b>(&'b mut self, class: Iso7816Class) -> (Iso7816Command<'b>, &'res mut [u8]) {
+        let response = self.response.take().unwrap_or(&mut []);
+
+        (self.build_command(class), response)
     }
 
     fn parse(self, response: &ApduResponse<'res>) -> Self::Result {
         response.expect_status(is(status::OK)).map(TlvIterator::new)
     }
 }
+
+impl<'aid, 'res> Iso7816StreamingOperation for Iso7816Select<'aid, 'res> {
+    type Result<'s> = Result<TlvIterator<'s>, ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        self.build_command(class)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(TlvIterator::new)
+    }
+}
@@ -18,7 +18,7 @@ impl<'a> Iso7816SelectResolution<'a> {
 
     pub fn data(&self) -> &[u8] {
         match self {
-            Iso7816SelectResolution::ByApplicationIdentifier(data) => *data,
+            Iso7816SelectResolution::ByApplicationIdentifier(data) => data,
             Iso7816SelectResolution::ByFileId(data) => data,
         }
     }
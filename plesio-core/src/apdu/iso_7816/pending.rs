@@ -0,0 +1,92 @@
+//! Response handling shared by the transports that protect exchanges and so
+//! have to see a whole response before passing it on.
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    class::ApduClass,
+    command::ApduCommand,
+    iso_7816::{class::Iso7816Class, status},
+    owned::OwnedCommand,
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::ApduTransport,
+};
+
+/// A full short response plus the status bytes.
+const ROUND_SIZE: usize = 256 + 2;
+
+/// The class for GET RESPONSE on the channel of `class`.
+pub(crate) fn get_response_class(class: u8) -> u8 {
+    Iso7816Class::from_u8(class & 0x7F)
+        .and_then(|class| Iso7816Class::for_channel(class.channel()))
+        .unwrap_or_default()
+        .to_u8()
+}
+
+/// Sends `command` with Le=00 and collects the response over as many GET
+/// RESPONSE rounds as the card asks for.
+pub(crate) async fn collect<T: ApduTransport>(
+    transport: &mut T,
+    command: impl ApduCommand,
+) -> Result<(Vec<u8>, ApduStatus), T::TransportError> {
+    let get_response_class = get_response_class(command.class().to_u8());
+    let mut round = [0u8; ROUND_SIZE];
+    let mut response = transport.execute(command, &mut round).await?;
+    let mut data = Vec::new();
+
+    loop {
+        data.extend_from_slice(response.data());
+
+        let status = response.status();
+        let Ok(size) = status.matches_if(status::has_more_data) else {
+            return Ok((data, status));
+        };
+
+        let get_response = OwnedCommand::new(get_response_class, 0xC0, (0x00, 0x00), Vec::new());
+        response = transport
+            .execute(get_response, &mut round[..size + 2])
+            .await?;
+    }
+}
+
+/// Response data held back from a reply buffer too small for it, served to
+/// the next GET RESPONSE as a card would.
+#[derive(Default)]
+pub(crate) struct PendingResponse(Option<(Vec<u8>, ApduStatus)>);
+
+impl PendingResponse {
+    /// Takes the held response for a GET RESPONSE command. Any other
+    /// command discards it.
+    pub(crate) fn take(&mut self, instruction: u8) -> Option<(Vec<u8>, ApduStatus)> {
+        let pending = self.0.take();
+        pending.filter(|_| instruction == 0xC0)
+    }
+
+    /// Writes `data` and `status` to `reply_buffer`, holding back what does
+    /// not fit and reporting it with `61xx`. Returns `None` if the buffer
+    /// cannot hold the status bytes.
+    pub(crate) fn deliver<'r>(
+        &mut self,
+        mut data: Vec<u8>,
+        status: ApduStatus,
+        reply_buffer: &'r mut [u8],
+    ) -> Option<ApduResponse<'r>> {
+        let capacity = reply_buffer.len().checked_sub(2)?;
+
+        let status = if data.len() > capacity {
+            let rest = data.split_off(capacity);
+            let more_data = status::more_data(rest.len());
+            self.0 = Some((rest, status));
+            more_data
+        } else {
+            status
+        };
+
+        let (reply, trailer) = reply_buffer.split_at_mut(data.len());
+        reply.copy_from_slice(&data);
+        trailer[..2].copy_from_slice(&[status.code1(), status.code2()]);
+
+        Some(ApduResponse::new(reply, status))
+    }
+}
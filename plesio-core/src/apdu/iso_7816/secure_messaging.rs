@@ -0,0 +1,517 @@
+//! ISO 7816-4 secure messaging, as used by ICAO 9303 travel documents and
+//! many national eID and signature cards.
+//!
+//! Command data is encrypted into DO 87, or DO 85 for odd instructions, the
+//! expected length moves into DO 97, and a MAC over these and the header
+//! follows in DO 8E. Responses carry their data in DO 87, their status in
+//! DO 99 and a MAC in DO 8E. The cryptography is left to an [`SmCipher`].
+
+use alloc::{vec, vec::Vec};
+
+use crate::apdu::{
+    class::ApduClass,
+    command::ApduCommand,
+    encoding,
+    iso_7816::{
+        class::{Iso7816Class, SecureMessaging},
+        pending::{self, PendingResponse},
+    },
+    owned::OwnedCommand,
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::{ApduTransport, PayloadTooLarge, TransportError},
+};
+
+const TAG_PLAIN: u8 = 0x81;
+const TAG_CRYPTOGRAM: u8 = 0x85;
+const TAG_PADDED_CRYPTOGRAM: u8 = 0x87;
+const TAG_EXPECTED_LENGTH: u8 = 0x97;
+const TAG_STATUS: u8 = 0x99;
+const TAG_MAC: u8 = 0x8E;
+
+/// The first byte of DO 87, indicating ISO 7816-4 padding.
+const PADDING_INDICATOR: u8 = 0x01;
+
+const MAC_LENGTH: usize = 8;
+
+/// The cryptography behind secure messaging, so that session keys can be
+/// held in software, e.g. with the RustCrypto block ciphers, or in an HSM.
+pub trait SmCipher {
+    type Error: core::fmt::Debug;
+
+    /// The block size data is padded to, which is also the length of the
+    /// send sequence counter: 8 for DES, 16 for AES.
+    fn block_size(&self) -> usize;
+
+    /// Encrypts padded `data` in place. ICAO 9303 AES ciphers derive their
+    /// IV from the send sequence counter; DES ciphers use a zero IV.
+    fn encrypt(&mut self, counter: &[u8], data: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Decrypts padded `data` in place.
+    fn decrypt(&mut self, counter: &[u8], data: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Computes the MAC of padded `data`. ICAO 9303 ciphers MAC the send
+    /// sequence counter followed by the data.
+    fn mac(&mut self, counter: &[u8], data: &[u8]) -> Result<[u8; MAC_LENGTH], Self::Error>;
+}
+
+/// Appends ISO 7816-4 padding: `80` followed by zeros up to a multiple of
+/// `block_size`.
+pub fn pad(data: &mut Vec<u8>, block_size: usize) {
+    data.push(0x80);
+    data.resize(data.len().next_multiple_of(block_size), 0);
+}
+
+/// Strips ISO 7816-4 padding.
+pub fn unpad(data: &[u8]) -> Option<&[u8]> {
+    let end = data.iter().rposition(|&byte| byte != 0)?;
+    (data[end] == 0x80).then(|| &data[..end])
+}
+
+fn object_length(value_length: usize) -> usize {
+    let length_length = match value_length {
+        0..=0x7F => 1,
+        0x80..=0xFF => 2,
+        _ => 3,
+    };
+
+    1 + length_length + value_length
+}
+
+fn push_object(objects: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    objects.push(tag);
+
+    match value.len() {
+        length @ 0..=0x7F => objects.push(length as u8),
+        length @ 0x80..=0xFF => objects.extend_from_slice(&[0x81, length as u8]),
+        length => {
+            objects.push(0x82);
+            objects.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+    }
+
+    objects.extend_from_slice(value);
+}
+
+/// Splits the next data object off `data` into its tag, value and the
+/// rest.
+fn next_object(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (length, rest) = match first {
+        0x00..=0x7F => (first as usize, rest),
+        0x81 => {
+            let (&length, rest) = rest.split_first()?;
+            (length as usize, rest)
+        }
+        0x82 => {
+            let (length, rest) = rest.split_at_checked(2)?;
+            (u16::from_be_bytes([length[0], length[1]]) as usize, rest)
+        }
+        _ => return None,
+    };
+
+    let (value, rest) = rest.split_at_checked(length)?;
+    Some((tag, value, rest))
+}
+
+/// The length of a protected command's data, assuming an even instruction
+/// and the longest encoding of DO 97.
+fn protected_length(data_length: usize, block_size: usize) -> usize {
+    let cryptogram = match data_length {
+        0 => 0,
+        _ => object_length(1 + (data_length + 1).next_multiple_of(block_size)),
+    };
+
+    cryptogram + object_length(2) + object_length(MAC_LENGTH)
+}
+
+/// Sets the secure messaging bits of an interindustry class byte, or of a
+/// proprietary one following the interindustry layout, and reports whether
+/// the header is then authenticated.
+fn protected_class(class: u8) -> (u8, bool) {
+    let Some(iso) = Iso7816Class::from_u8(class & 0x7F) else {
+        return (class, false);
+    };
+
+    let protected = iso.with_secure_messaging(SecureMessaging::HeaderAuthenticated);
+
+    (
+        (class & 0x80) | protected.to_u8(),
+        protected.secure_messaging() == SecureMessaging::HeaderAuthenticated,
+    )
+}
+
+/// Whether a status comes with response data objects. Cards report other
+/// errors, such as missing or incorrect SM data objects, unprotected.
+pub(crate) fn is_protected(status: ApduStatus) -> bool {
+    matches!(status.code1(), 0x90 | 0x62 | 0x63)
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// The data and status of a verified response.
+type Unprotected = (Vec<u8>, ApduStatus);
+
+#[derive(Debug)]
+pub enum SecureMessagingError<E, C> {
+    Transport(E),
+    Cipher(C),
+    /// The protected command would not fit the wrapped transport.
+    /// `max_size` is the largest unprotected data that does.
+    PayloadTooLarge {
+        max_size: usize,
+    },
+    /// The response MAC is missing or wrong. The session keys can no longer
+    /// be trusted and secure messaging has to be re-established.
+    InvalidMac,
+    /// The response data objects or their padding are malformed.
+    MalformedResponse,
+    /// The reply buffer has no room for the status bytes.
+    ReplyBufferTooSmall {
+        expected: usize,
+    },
+}
+
+impl<E: core::fmt::Display, C: core::fmt::Debug> core::fmt::Display for SecureMessagingError<E, C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Cipher(e) => write!(f, "secure messaging cipher failed: {e:?}"),
+            Self::PayloadTooLarge { max_size } => write!(
+                f,
+                "payload exceeds the maximum of {max_size} bytes under secure messaging"
+            ),
+            Self::InvalidMac => write!(f, "response MAC is missing or invalid"),
+            Self::MalformedResponse => write!(f, "malformed secure messaging response"),
+            Self::ReplyBufferTooSmall { expected } => {
+                write!(f, "reply buffer too small: {expected} bytes required")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static, C: core::fmt::Debug> std::error::Error
+    for SecureMessagingError<E, C>
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E: TransportError, C: core::fmt::Debug> TransportError for SecureMessagingError<E, C> {
+    fn is_payload_too_large(&self) -> Option<PayloadTooLarge> {
+        match *self {
+            Self::PayloadTooLarge { max_size } => Some(PayloadTooLarge { max_size }),
+            _ => None,
+        }
+    }
+}
+
+/// An [`ApduTransport`] that protects every command with ISO 7816-4 secure
+/// messaging and verifies and unwraps every response.
+///
+/// The send sequence counter is incremented before each command and each
+/// response. Wrapped in an [`Iso7816Transport`], commands too large for the
+/// protected payload are chained, each link protected on its own, and
+/// protected responses longer than a short response are collected with
+/// unprotected GET RESPONSE commands before they are verified. Unprotected
+/// data that does not fit the reply buffer is held back and served to the
+/// next GET RESPONSE, as a card would.
+///
+/// [`Iso7816Transport`]: crate::apdu::iso_7816::transport::Iso7816Transport
+pub struct SecureMessagingTransport<T, C> {
+    inner: T,
+    cipher: C,
+    counter: Vec<u8>,
+    pending: PendingResponse,
+}
+
+impl<T: ApduTransport, C: SmCipher> SecureMessagingTransport<T, C> {
+    /// Starts with a send sequence counter of zero.
+    pub fn new(inner: T, cipher: C) -> Self {
+        let counter = vec![0; cipher.block_size()];
+
+        Self {
+            inner,
+            cipher,
+            counter,
+            pending: PendingResponse::default(),
+        }
+    }
+
+    /// Sets the send sequence counter, e.g. to the one derived from the
+    /// challenges of BAC.
+    ///
+    /// Panics if `counter` is not one block long.
+    pub fn with_counter(mut self, counter: &[u8]) -> Self {
+        assert_eq!(counter.len(), self.cipher.block_size());
+        self.counter.copy_from_slice(counter);
+        self
+    }
+
+    pub fn counter(&self) -> &[u8] {
+        &self.counter
+    }
+
+    pub fn cipher(&self) -> &C {
+        &self.cipher
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn increment_counter(&mut self) {
+        for byte in self.counter.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+
+            if *byte != 0 {
+                break;
+            }
+        }
+    }
+
+    /// The largest unprotected data whose protected form fits in
+    /// `max_size` bytes.
+    fn unprotected_capacity(&self, max_size: usize) -> usize {
+        let block_size = self.cipher.block_size();
+
+        (0..=max_size)
+            .rev()
+            .find(|&length| protected_length(length, block_size) <= max_size)
+            .unwrap_or(0)
+    }
+
+    fn protect(
+        &mut self,
+        command: &impl ApduCommand,
+        expected_length: usize,
+    ) -> Result<OwnedCommand<u8>, SecureMessagingError<T::TransportError, C::Error>> {
+        let block_size = self.cipher.block_size();
+        let data = command.data();
+        let max_payload_size = self.inner.max_payload_size();
+
+        if protected_length(data.len(), block_size) > max_payload_size {
+            return Err(SecureMessagingError::PayloadTooLarge {
+                max_size: self.unprotected_capacity(max_payload_size),
+            });
+        }
+
+        self.increment_counter();
+
+        let (class, header_authenticated) = protected_class(command.class().to_u8());
+        let instruction = command.instruction();
+        let (p1, p2) = command.parameters();
+        let mut objects = Vec::new();
+
+        if !data.is_empty() {
+            let mut cryptogram = data.to_vec();
+            pad(&mut cryptogram, block_size);
+            self.cipher
+                .encrypt(&self.counter, &mut cryptogram)
+                .map_err(SecureMessagingError::Cipher)?;
+
+            if instruction & 0x01 != 0 {
+                push_object(&mut objects, TAG_CRYPTOGRAM, &cryptogram);
+            } else {
+                cryptogram.insert(0, PADDING_INDICATOR);
+                push_object(&mut objects, TAG_PADDED_CRYPTOGRAM, &cryptogram);
+            }
+        }
+
+        match expected_length {
+            0 => {}
+            1..=256 => push_object(&mut objects, TAG_EXPECTED_LENGTH, &[expected_length as u8]),
+            _ => push_object(
+                &mut objects,
+                TAG_EXPECTED_LENGTH,
+                &(expected_length as u16).to_be_bytes(),
+            ),
+        }
+
+        let mut authenticated = Vec::new();
+
+        if header_authenticated {
+            authenticated.extend_from_slice(&[class, instruction, p1, p2]);
+            pad(&mut authenticated, block_size);
+        }
+
+        authenticated.extend_from_slice(&objects);
+        pad(&mut authenticated, block_size);
+
+        let mac = self
+            .cipher
+            .mac(&self.counter, &authenticated)
+            .map_err(SecureMessagingError::Cipher)?;
+        push_object(&mut objects, TAG_MAC, &mac);
+
+        Ok(OwnedCommand::new(class, instruction, (p1, p2), objects))
+    }
+
+    fn unprotect<'r>(
+        &mut self,
+        protected: &[u8],
+        status: ApduStatus,
+        reply_buffer: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, SecureMessagingError<T::TransportError, C::Error>> {
+        self.increment_counter();
+
+        let (data, status) = if protected.is_empty() && !is_protected(status) {
+            (Vec::new(), status)
+        } else {
+            self.verify(protected, status)?
+        };
+
+        self.pending
+            .deliver(data, status, reply_buffer)
+            .ok_or(SecureMessagingError::ReplyBufferTooSmall { expected: 2 })
+    }
+
+    /// Verifies the MAC of a protected response and returns its unprotected
+    /// data and status.
+    fn verify(
+        &mut self,
+        protected: &[u8],
+        status: ApduStatus,
+    ) -> Result<Unprotected, SecureMessagingError<T::TransportError, C::Error>> {
+        let mut rest = protected;
+        let mut mac = None;
+        let mut cryptogram = None;
+        let mut plain = None;
+        let mut secured_status = None;
+
+        while !rest.is_empty() {
+            // The MAC only covers the objects before it, so nothing may
+            // follow it, and no object may be given twice.
+            if mac.is_some() {
+                return Err(SecureMessagingError::MalformedResponse);
+            }
+
+            let (tag, value, next) =
+                next_object(rest).ok_or(SecureMessagingError::MalformedResponse)?;
+
+            let duplicate = match tag {
+                TAG_MAC => mac.replace((value, protected.len() - rest.len())).is_some(),
+                TAG_PADDED_CRYPTOGRAM => {
+                    let value = value
+                        .strip_prefix(&[PADDING_INDICATOR])
+                        .ok_or(SecureMessagingError::MalformedResponse)?;
+                    cryptogram.replace(value).is_some()
+                }
+                TAG_CRYPTOGRAM => cryptogram.replace(value).is_some(),
+                TAG_PLAIN => plain.replace(value).is_some(),
+                TAG_STATUS => secured_status.replace(value).is_some(),
+                _ => return Err(SecureMessagingError::MalformedResponse),
+            };
+
+            if duplicate {
+                return Err(SecureMessagingError::MalformedResponse);
+            }
+
+            rest = next;
+        }
+
+        let (mac, authenticated_length) = mac.ok_or(SecureMessagingError::InvalidMac)?;
+        let mut authenticated = protected[..authenticated_length].to_vec();
+        pad(&mut authenticated, self.cipher.block_size());
+
+        let expected = self
+            .cipher
+            .mac(&self.counter, &authenticated)
+            .map_err(SecureMessagingError::Cipher)?;
+
+        if !constant_time_eq(&expected, mac) {
+            return Err(SecureMessagingError::InvalidMac);
+        }
+
+        let status = match secured_status {
+            Some(&[code1, code2]) => ApduStatus::new(code1, code2),
+            Some(_) => return Err(SecureMessagingError::MalformedResponse),
+            None => status,
+        };
+
+        let data = match (cryptogram, plain) {
+            (Some(cryptogram), None) => {
+                if !cryptogram.len().is_multiple_of(self.cipher.block_size()) {
+                    return Err(SecureMessagingError::MalformedResponse);
+                }
+
+                let mut data = cryptogram.to_vec();
+                self.cipher
+                    .decrypt(&self.counter, &mut data)
+                    .map_err(SecureMessagingError::Cipher)?;
+
+                let length = unpad(&data)
+                    .ok_or(SecureMessagingError::MalformedResponse)?
+                    .len();
+                data.truncate(length);
+                data
+            }
+            (None, Some(plain)) => plain.to_vec(),
+            (None, None) => Vec::new(),
+            (Some(_), Some(_)) => return Err(SecureMessagingError::MalformedResponse),
+        };
+
+        Ok((data, status))
+    }
+}
+
+impl<T: ApduTransport, C: SmCipher> ApduTransport for SecureMessagingTransport<T, C> {
+    type TransportError = SecureMessagingError<T::TransportError, C::Error>;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, Self::TransportError>> {
+        let expected_length = encoding::expected_length(reply_buffer.len());
+
+        async move {
+            if let Some((data, status)) = self.pending.take(command.instruction()) {
+                return self
+                    .pending
+                    .deliver(data, status, reply_buffer)
+                    .ok_or(SecureMessagingError::ReplyBufferTooSmall { expected: 2 });
+            }
+
+            let counter = self.counter.clone();
+            let protected = self.protect(&command, expected_length)?;
+
+            let (data, status) = match pending::collect(&mut self.inner, protected).await {
+                Ok(response) => response,
+                Err(error) => {
+                    // Nothing was sent, so the counter is not spent.
+                    if let Some(PayloadTooLarge { max_size }) = error.is_payload_too_large() {
+                        self.counter = counter;
+                        let max_size = self.unprotected_capacity(max_size);
+                        return Err(SecureMessagingError::PayloadTooLarge { max_size });
+                    }
+
+                    return Err(SecureMessagingError::Transport(error));
+                }
+            };
+
+            self.unprotect(&data, status, reply_buffer)
+        }
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.unprotected_capacity(self.inner.max_payload_size())
+    }
+}
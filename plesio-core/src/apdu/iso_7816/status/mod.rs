@@ -2,9 +2,40 @@ use crate::apdu::status::ApduStatus;
 
 pub const OK: ApduStatus = ApduStatus::new(0x90, 0x00);
 
+pub const END_OF_FILE: ApduStatus = ApduStatus::new(0x62, 0x82);
+pub const WRONG_LENGTH: ApduStatus = ApduStatus::new(0x67, 0x00);
+pub const LOGICAL_CHANNEL_NOT_SUPPORTED: ApduStatus = ApduStatus::new(0x68, 0x81);
+pub const COMMAND_INCOMPATIBLE_WITH_FILE_STRUCTURE: ApduStatus = ApduStatus::new(0x69, 0x81);
+pub const SECURITY_STATUS_NOT_SATISFIED: ApduStatus = ApduStatus::new(0x69, 0x82);
+pub const AUTHENTICATION_METHOD_BLOCKED: ApduStatus = ApduStatus::new(0x69, 0x83);
+pub const CONDITIONS_OF_USE_NOT_SATISFIED: ApduStatus = ApduStatus::new(0x69, 0x85);
+pub const COMMAND_NOT_ALLOWED: ApduStatus = ApduStatus::new(0x69, 0x86);
+pub const WRONG_DATA: ApduStatus = ApduStatus::new(0x6A, 0x80);
+pub const FUNCTION_NOT_SUPPORTED: ApduStatus = ApduStatus::new(0x6A, 0x81);
+pub const FILE_NOT_FOUND: ApduStatus = ApduStatus::new(0x6A, 0x82);
+pub const RECORD_NOT_FOUND: ApduStatus = ApduStatus::new(0x6A, 0x83);
+pub const NOT_ENOUGH_MEMORY: ApduStatus = ApduStatus::new(0x6A, 0x84);
+pub const INCORRECT_PARAMETERS: ApduStatus = ApduStatus::new(0x6A, 0x86);
+pub const REFERENCED_DATA_NOT_FOUND: ApduStatus = ApduStatus::new(0x6A, 0x88);
+pub const WRONG_PARAMETERS: ApduStatus = ApduStatus::new(0x6B, 0x00);
+pub const INSTRUCTION_NOT_SUPPORTED: ApduStatus = ApduStatus::new(0x6D, 0x00);
+pub const CLASS_NOT_SUPPORTED: ApduStatus = ApduStatus::new(0x6E, 0x00);
+pub const NO_PRECISE_DIAGNOSIS: ApduStatus = ApduStatus::new(0x6F, 0x00);
+
+/// The 61xx status telling the terminal to fetch `remaining` more bytes
+/// with GET RESPONSE. Counts of 256 or more are encoded as `6100`.
+pub const fn more_data(remaining: usize) -> ApduStatus {
+    ApduStatus::new(0x61, if remaining > 0xFF { 0 } else { remaining as u8 })
+}
+
+/// The 63Cx status reporting a failed verification with `retries` left.
+pub const fn verification_failed(retries: u8) -> ApduStatus {
+    ApduStatus::new(0x63, 0xC0 | (retries & 0x0F))
+}
+
 pub fn has_more_data(status: &ApduStatus) -> Option<usize> {
-    (status.as_u16() & 0xFF00 >> 8 == 0x61).then(|| {
-        let size = status.as_u16() & 0x00FF;
+    (status.code1() == 0x61).then(|| {
+        let size = status.code2();
 
         if size == 0 {
             u8::MAX as usize + 1
@@ -15,8 +46,8 @@ pub fn has_more_data(status: &ApduStatus) -> Option<usize> {
 }
 
 pub fn has_wrong_length(status: &ApduStatus) -> Option<usize> {
-    (status.as_u16() & 0xFF00 >> 8 == 0x6C).then(|| {
-        let size = status.as_u16() & 0x00FF;
+    (status.code1() == 0x6C).then(|| {
+        let size = status.code2();
 
         if size == 0 {
             u8::MAX as usize + 1
@@ -25,3 +56,56 @@ pub fn has_wrong_length(status: &ApduStatus) -> Option<usize> {
         }
     })
 }
+
+pub fn has_retries_remaining(status: &ApduStatus) -> Option<u8> {
+    (status.code1() == 0x63 && status.code2() & 0xF0 == 0xC0).then(|| status.code2() & 0x0F)
+}
+
+/// A short description of an interindustry status, for logs.
+pub fn describe(status: &ApduStatus) -> Option<&'static str> {
+    let description = match (status.code1(), status.code2()) {
+        (0x90, 0x00) => "success",
+        (0x61, _) => "more data available",
+        (0x62, 0x81) => "returned data may be corrupted",
+        (0x62, 0x82) => "end of file reached before reading Ne bytes",
+        (0x62, 0x83) => "selected file deactivated",
+        (0x62, _) => "warning, state unchanged",
+        (0x63, 0xC0..=0xCF) => "verification failed",
+        (0x63, _) => "warning, state changed",
+        (0x64, _) => "execution error, state unchanged",
+        (0x65, 0x81) => "memory failure",
+        (0x65, _) => "execution error, state changed",
+        (0x66, _) => "security-related issue",
+        (0x67, 0x00) => "wrong length",
+        (0x68, 0x81) => "logical channel not supported",
+        (0x68, 0x82) => "secure messaging not supported",
+        (0x68, 0x83) => "last command of the chain expected",
+        (0x68, 0x84) => "command chaining not supported",
+        (0x68, _) => "functions in CLA not supported",
+        (0x69, 0x81) => "command incompatible with file structure",
+        (0x69, 0x82) => "security status not satisfied",
+        (0x69, 0x83) => "authentication method blocked",
+        (0x69, 0x84) => "reference data not usable",
+        (0x69, 0x85) => "conditions of use not satisfied",
+        (0x69, 0x86) => "command not allowed, no current EF",
+        (0x69, 0x87) => "expected secure messaging data objects missing",
+        (0x69, 0x88) => "incorrect secure messaging data objects",
+        (0x69, _) => "command not allowed",
+        (0x6A, 0x80) => "incorrect parameters in the data field",
+        (0x6A, 0x81) => "function not supported",
+        (0x6A, 0x82) => "file or application not found",
+        (0x6A, 0x83) => "record not found",
+        (0x6A, 0x84) => "not enough memory space in the file",
+        (0x6A, 0x86) => "incorrect parameters P1-P2",
+        (0x6A, 0x88) => "referenced data not found",
+        (0x6A, _) => "wrong parameters P1-P2",
+        (0x6B, 0x00) => "wrong parameters P1-P2",
+        (0x6C, _) => "wrong Le field",
+        (0x6D, 0x00) => "instruction not supported",
+        (0x6E, 0x00) => "class not supported",
+        (0x6F, 0x00) => "no precise diagnosis",
+        _ => return None,
+    };
+
+    Some(description)
+}
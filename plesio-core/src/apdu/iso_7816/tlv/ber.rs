@@ -0,0 +1,165 @@
+//! BER-TLV data objects with tags and lengths of more than one byte, as
+//! used by GlobalPlatform, PIV, OpenPGP and EMV.
+
+use crate::apdu::sink::{ResponseSink, SinkFull};
+
+/// A data object with a tag of up to three bytes, held as a big-endian
+/// number: `9F7F` is `0x9F7F`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BerTlv<'a> {
+    tag: u32,
+    value: &'a [u8],
+}
+
+/// The shifts of the bytes of `tag`, without leading zeros.
+fn tag_shifts(tag: u32) -> &'static [usize] {
+    match tag {
+        0..=0xFF => &[0],
+        0x100..=0xFFFF => &[8, 0],
+        _ => &[16, 8, 0],
+    }
+}
+
+impl<'a> BerTlv<'a> {
+    /// Splits the first data object off `data`. Returns `None` if it is
+    /// truncated or its tag or length takes more than three bytes.
+    pub fn next(data: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let (&first, mut rest) = data.split_first()?;
+        let mut tag = first as u32;
+
+        if first & 0x1F == 0x1F {
+            loop {
+                let (&byte, next) = rest.split_first()?;
+                rest = next;
+                tag = (tag << 8) | byte as u32;
+
+                if byte & 0x80 == 0 {
+                    break;
+                }
+
+                if tag > 0xFFFF {
+                    return None;
+                }
+            }
+        }
+
+        let (&first, rest) = rest.split_first()?;
+
+        let (length, rest) = match first {
+            0x00..=0x7F => (first as usize, rest),
+            0x81..=0x83 => {
+                let (length, rest) = rest.split_at_checked((first & 0x7F) as usize)?;
+                let length = length
+                    .iter()
+                    .fold(0, |length, &byte| (length << 8) | byte as usize);
+                (length, rest)
+            }
+            _ => return None,
+        };
+
+        let (value, rest) = rest.split_at_checked(length)?;
+        Some((Self { tag, value }, rest))
+    }
+
+    pub fn from(tag: u32, value: &'a [u8]) -> Self {
+        Self { tag, value }
+    }
+
+    pub fn tag(&self) -> u32 {
+        self.tag
+    }
+
+    pub fn value(&self) -> &'a [u8] {
+        self.value
+    }
+
+    /// Whether the value is itself a sequence of data objects.
+    pub fn is_constructed(&self) -> bool {
+        let first = tag_shifts(self.tag)[0];
+        (self.tag >> first) & 0x20 != 0
+    }
+
+    /// The data objects in the value.
+    pub fn children(&self) -> BerTlvIterator<'a> {
+        BerTlvIterator::new(self.value)
+    }
+
+    /// The number of bytes [`Self::write`] produces.
+    pub fn encoded_length(&self) -> usize {
+        let length_length = match self.value.len() {
+            0..=0x7F => 1,
+            0x80..=0xFF => 2,
+            0x100..=0xFFFF => 3,
+            _ => 4,
+        };
+
+        tag_shifts(self.tag).len() + length_length + self.value.len()
+    }
+
+    /// Appends the tag, length and value to `sink`.
+    ///
+    /// Panics if the value is longer than the 16 MiB three length bytes can
+    /// describe.
+    pub fn write(&self, sink: &mut impl ResponseSink) -> Result<(), SinkFull> {
+        for shift in tag_shifts(self.tag) {
+            sink.extend(&[(self.tag >> shift) as u8])?;
+        }
+
+        let length = self.value.len();
+
+        match length {
+            0..=0x7F => sink.extend(&[length as u8])?,
+            0x80..=0xFF => sink.extend(&[0x81, length as u8])?,
+            0x100..=0xFFFF => sink.extend(&[0x82, (length >> 8) as u8, length as u8])?,
+            0x10000..=0xFFFFFF => sink.extend(&[
+                0x83,
+                (length >> 16) as u8,
+                (length >> 8) as u8,
+                length as u8,
+            ])?,
+            _ => panic!("BER-TLV value longer than 16 MiB"),
+        }
+
+        sink.extend(self.value)
+    }
+}
+
+/// The data objects of a sequence. Bytes `00` and `FF` between them are
+/// skipped as padding, and iteration stops at the first malformed object.
+#[derive(Clone, Copy, Debug)]
+pub struct BerTlvIterator<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BerTlvIterator<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn get(mut self, tag: u32) -> Option<BerTlv<'a>> {
+        self.find(|object| object.tag() == tag)
+    }
+
+    /// What is left of the data, which is not empty if iteration stopped at
+    /// a malformed object.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl<'a> Iterator for BerTlvIterator<'a> {
+    type Item = BerTlv<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self
+            .data
+            .iter()
+            .position(|&byte| byte != 0x00 && byte != 0xFF)
+            .unwrap_or(self.data.len());
+        self.data = &self.data[start..];
+
+        let (object, rest) = BerTlv::next(self.data)?;
+        self.data = rest;
+        Some(object)
+    }
+}
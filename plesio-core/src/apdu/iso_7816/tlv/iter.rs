@@ -1,6 +1,6 @@
 use crate::apdu::iso_7816::tlv::TaggedSlice;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub struct TlvIterator<'a> {
     data: &'a [u8],
 }
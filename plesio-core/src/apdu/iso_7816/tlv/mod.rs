@@ -1,5 +1,51 @@
+use crate::apdu::sink::{ResponseSink, SinkFull};
+
+pub mod ber;
 pub mod iter;
 
+/// The longest value a SIMPLE-TLV length can describe.
+const MAX_LENGTH: usize = u16::MAX as usize;
+
+/// Why a [`TaggedSlice`] could not be written.
+#[derive(Clone, Copy, Debug)]
+pub enum TlvWriteError {
+    /// The value is longer than the 65535 bytes SIMPLE-TLV allows.
+    ValueTooLong {
+        length: usize,
+    },
+    SinkFull(SinkFull),
+}
+
+impl core::fmt::Display for TlvWriteError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::ValueTooLong { length } => {
+                write!(f, "TLV value of {length} bytes exceeds {MAX_LENGTH} bytes")
+            }
+            Self::SinkFull(e) => e.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TlvWriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::SinkFull(e) => Some(e),
+            Self::ValueTooLong { .. } => None,
+        }
+    }
+}
+
+impl From<SinkFull> for TlvWriteError {
+    fn from(e: SinkFull) -> Self {
+        Self::SinkFull(e)
+    }
+}
+
+/// An ISO 7816-4 SIMPLE-TLV data object: a one byte tag, then a one byte
+/// length up to `FE`, or `FF` and two length bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct TaggedSlice<'a> {
     tag: u8,
     value: &'a [u8],
@@ -7,19 +53,16 @@ pub struct TaggedSlice<'a> {
 
 impl<'a> TaggedSlice<'a> {
     pub fn next(slice: &'a [u8]) -> Option<(Self, &'a [u8])> {
-        if slice.len() < 2 {
-            return None;
-        }
+        let (tag, length, rest) = match *slice {
+            [tag, 0xFF, high, low, ref rest @ ..] => {
+                (tag, u16::from_be_bytes([high, low]) as usize, rest)
+            }
+            [_, 0xFF, ..] => return None,
+            [tag, length, ref rest @ ..] => (tag, length as usize, rest),
+            _ => return None,
+        };
 
-        let tag = slice[0];
-        let length = slice[1] as usize;
-
-        if slice.len() < 2 + length {
-            return None;
-        }
-
-        let value = &slice[2..2 + length];
-        let rest = &slice[2 + length..];
+        let (value, rest) = rest.split_at_checked(length)?;
 
         Some((Self { tag, value }, rest))
     }
@@ -35,4 +78,27 @@ impl<'a> TaggedSlice<'a> {
     pub fn value(&self) -> &'a [u8] {
         self.value
     }
+
+    /// The number of bytes [`Self::write`] produces.
+    pub fn encoded_length(&self) -> usize {
+        match self.value.len() {
+            length @ 0..0xFF => 2 + length,
+            length => 4 + length,
+        }
+    }
+
+    /// Appends the tag, length and value to `sink`. Nothing is written if
+    /// the value is longer than 65535 bytes.
+    pub fn write(&self, sink: &mut impl ResponseSink) -> Result<(), TlvWriteError> {
+        match self.value.len() {
+            length @ 0..0xFF => sink.extend(&[self.tag, length as u8])?,
+            length @ 0xFF..=MAX_LENGTH => {
+                let [high, low] = (length as u16).to_be_bytes();
+                sink.extend(&[self.tag, 0xFF, high, low])?
+            }
+            length => return Err(TlvWriteError::ValueTooLong { length }),
+        }
+
+        Ok(sink.extend(self.value)?)
+    }
 }
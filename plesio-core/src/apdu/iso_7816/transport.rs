@@ -1,14 +1,27 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::apdu::owned::OwnedResponse;
 use crate::apdu::{
+    command::ApduCommand,
     iso_7816::{
         class::Iso7816Class,
-        operation::{Iso7816Command, Iso7816Operation, get_response::GetResponse},
+        operation::{
+            Iso7816Command, Iso7816Operation, Iso7816StreamingOperation, get_response::GetResponse,
+        },
         status,
     },
     response::ApduResponse,
+    sink::{ResponseSink, SinkFull},
     status::is,
     transport::{ApduTransport, PayloadTooLarge, TransportError},
 };
 
+/// Size of the per-round buffer used when streaming into a [`ResponseSink`]:
+/// a full short response plus the status bytes.
+const STREAMING_ROUND_SIZE: usize = 256 + 2;
+
 /// The error type for ISO 7816 transport operations.
 /// Wraps the underlying transport error or indicates protocol-specific issues.
 #[derive(Debug)]
@@ -17,12 +30,81 @@ pub enum Iso7816TransportError<E> {
     ResponseBufferTooSmall { expected: usize, hint: usize },
 }
 
+impl<E: core::fmt::Display> core::fmt::Display for Iso7816TransportError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::ResponseBufferTooSmall { expected, hint } => write!(
+                f,
+                "response buffer too small: {expected} bytes required, {hint} available"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for Iso7816TransportError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            Self::ResponseBufferTooSmall { .. } => None,
+        }
+    }
+}
+
 pub struct Iso7816Transport<T: ApduTransport> {
     transport: T,
     state: Iso7816Class,
 }
 
 impl<T: ApduTransport> Iso7816Transport<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            state: Iso7816Class::default(),
+        }
+    }
+
+    /// Sets the class used for subsequent operations, e.g. to address a
+    /// logical channel other than the basic one.
+    pub fn with_class(mut self, class: Iso7816Class) -> Self {
+        self.state = class;
+        self
+    }
+
+    pub fn class(&self) -> Iso7816Class {
+        self.state
+    }
+
+    pub fn set_class(&mut self, class: Iso7816Class) {
+        self.state = class;
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+
+    /// Sends `command` as-is, without chaining, GET RESPONSE handling or
+    /// applying the current class state.
+    pub async fn transmit<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, Iso7816TransportError<T::TransportError>> {
+        self.transport
+            .execute(command, reply)
+            .await
+            .map_err(Iso7816TransportError::Transport)
+    }
+
     async fn execute_single<'a, O: Iso7816Operation<'a>>(
         &mut self,
         mut operation: O,
@@ -49,12 +131,13 @@ impl<T: ApduTransport> Iso7816Transport<T> {
     {
         let result = self
             .transport
-            .execute(command, reply)
+            .execute(command, &mut *reply)
             .await
+            .map(|response| response.data().len())
             .map_err(|e| (e.is_payload_too_large(), e));
 
         match result {
-            Ok(_) => return Ok(ApduResponse::parse(reply).unwrap()),
+            Ok(length) => return Ok(ApduResponse::parse(&reply[..length + 2]).unwrap()),
             // Wrap the transport error
             Err((None, e)) => return Err(Iso7816TransportError::Transport(e)),
             Err((Some(PayloadTooLarge { max_size }), _)) => {
@@ -97,40 +180,126 @@ impl<T: ApduTransport> Iso7816Transport<T> {
 
         let reply_length = reply.len();
 
+        // Without room for the status bytes no response data is expected, so
+        // only the status is kept.
+        if reply_length < 2 {
+            let status = self
+                .execute_command_chunked(command, &mut [0u8; 2], &mut chunk_reply_buffer)
+                .await?
+                .status();
+
+            if let Ok(size) = status
+                .matches_if(status::has_wrong_length)
+                .or_else(|status| status.matches_if(status::has_more_data))
+            {
+                return Err(Iso7816TransportError::ResponseBufferTooSmall {
+                    expected: size + 2,
+                    hint: reply_length,
+                });
+            }
+
+            return Ok(operation.parse(&ApduResponse::empty(status)));
+        }
+
         let mut response = self
             .execute_command_chunked(command, reply, &mut chunk_reply_buffer)
             .await?;
 
         if let Ok(size) = response.status().matches_if(status::has_wrong_length) {
-            if size > reply_length {
+            if size + 2 > reply_length {
                 return Err(Iso7816TransportError::ResponseBufferTooSmall {
-                    expected: size,
+                    expected: size + 2,
                     hint: reply_length,
                 });
             }
 
             response = self
-                .execute_command_chunked(command, &mut reply[0..size], &mut chunk_reply_buffer)
+                .execute_command_chunked(command, &mut reply[0..size + 2], &mut chunk_reply_buffer)
                 .await?;
         }
 
         let mut offset = response.data().len();
 
         while let Ok(size) = response.status().matches_if(status::has_more_data) {
-            if (offset + size) > reply_length {
+            if (offset + size + 2) > reply_length {
                 return Err(Iso7816TransportError::ResponseBufferTooSmall {
-                    expected: offset + size,
+                    expected: offset + size + 2,
                     hint: reply_length,
                 });
             }
 
+            // Each round overwrites the status bytes of the previous one.
             response = self
-                .execute_single(GetResponse::new(&mut reply[offset..offset + size]))
+                .execute_single(GetResponse::new(&mut reply[offset..offset + size + 2]))
                 .await?;
 
             offset += response.data().len();
         }
 
-        Ok(operation.parse(&ApduResponse::parse(reply).unwrap()))
+        Ok(operation.parse(&ApduResponse::parse(&reply[..offset + 2]).unwrap()))
+    }
+
+    /// Executes `operation`, appending the response data to `sink` one round
+    /// at a time so the total response size does not need to be known.
+    pub async fn execute_streaming<'s, O: Iso7816StreamingOperation, S: ResponseSink>(
+        &mut self,
+        operation: O,
+        sink: &'s mut S,
+    ) -> Result<O::Result<'s>, Iso7816TransportError<T::TransportError>> {
+        let mut chunk_reply_buffer = [0u8; 2];
+        let mut round = [0u8; STREAMING_ROUND_SIZE];
+
+        let command = operation.command(self.state);
+
+        let mut response = self
+            .execute_command_chunked(command, &mut round, &mut chunk_reply_buffer)
+            .await?;
+
+        if let Ok(size) = response.status().matches_if(status::has_wrong_length) {
+            response = self
+                .execute_command_chunked(command, &mut round[..size + 2], &mut chunk_reply_buffer)
+                .await?;
+        }
+
+        loop {
+            sink.extend(response.data())
+                .map_err(
+                    |SinkFull { capacity }| Iso7816TransportError::ResponseBufferTooSmall {
+                        expected: sink.data().len() + response.data().len(),
+                        hint: capacity,
+                    },
+                )?;
+
+            let Ok(size) = response.status().matches_if(status::has_more_data) else {
+                break;
+            };
+
+            response = self
+                .execute_single(GetResponse::new(&mut round[..size + 2]))
+                .await?;
+        }
+
+        let status = response.status();
+        let sink: &'s S = sink;
+
+        Ok(operation.parse(&ApduResponse::new(sink.data(), status)))
+    }
+
+    /// Executes the command of `operation`, allocating as much space for the
+    /// response as the card delivers.
+    ///
+    /// The response is returned unparsed, as [`OwnedResponse`], so that it
+    /// does not borrow from the call; typed operations such as SELECT only
+    /// contribute their command.
+    #[cfg(feature = "alloc")]
+    pub async fn execute_owned<O: Iso7816StreamingOperation>(
+        &mut self,
+        operation: O,
+    ) -> Result<OwnedResponse, Iso7816TransportError<T::TransportError>> {
+        let mut data = Vec::new();
+        let command = operation.command(self.state);
+        let status = self.execute_streaming(command, &mut data).await?.status();
+
+        Ok(OwnedResponse::new(data, status))
     }
 }
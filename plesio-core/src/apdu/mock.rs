@@ -0,0 +1,192 @@
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use crate::apdu::{
+    blocking::BlockingTransport,
+    command::ApduCommand,
+    encoding,
+    hex::Hex,
+    response::ApduResponse,
+    transport::{ApduTransport, PayloadTooLarge, TransportError},
+};
+
+/// The error a [`MockTransport`] has been scripted to fail with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockError {
+    PayloadTooLarge { max_size: usize },
+    Failure(&'static str),
+}
+
+impl core::fmt::Display for MockError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::PayloadTooLarge { max_size } => {
+                write!(f, "payload exceeds the mock maximum of {max_size} bytes")
+            }
+            Self::Failure(message) => write!(f, "scripted transport failure: {message}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MockError {}
+
+impl TransportError for MockError {
+    fn is_payload_too_large(&self) -> Option<PayloadTooLarge> {
+        match *self {
+            Self::PayloadTooLarge { max_size } => Some(PayloadTooLarge { max_size }),
+            Self::Failure(_) => None,
+        }
+    }
+}
+
+/// A command the transport received and what it replied with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockExchange {
+    /// The encoded command, including Lc and Le.
+    pub command: Vec<u8>,
+    /// The raw response including status bytes, or the scripted error.
+    pub reply: Result<Vec<u8>, MockError>,
+}
+
+/// An [`ApduTransport`] that plays back a script of expected commands and
+/// canned replies.
+///
+/// Commands are compared byte for byte in their encoded form, so the script
+/// also pins down Lc and Le. Any deviation from the script panics with both
+/// commands in hex.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    script: VecDeque<MockExchange>,
+    transcript: Vec<MockExchange>,
+    max_payload_size: Option<usize>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expects `command` next and replies with `response`, which includes the
+    /// status bytes.
+    pub fn expect(mut self, command: &[u8], response: &[u8]) -> Self {
+        self.script.push_back(MockExchange {
+            command: command.to_vec(),
+            reply: Ok(response.to_vec()),
+        });
+        self
+    }
+
+    /// Expects `command` next and rejects it as too large for the transport.
+    pub fn expect_payload_too_large(self, command: &[u8], max_size: usize) -> Self {
+        self.expect_error(command, MockError::PayloadTooLarge { max_size })
+    }
+
+    /// Expects `command` next and fails it with `error`.
+    pub fn expect_error(mut self, command: &[u8], error: MockError) -> Self {
+        self.script.push_back(MockExchange {
+            command: command.to_vec(),
+            reply: Err(error),
+        });
+        self
+    }
+
+    /// Sets the value reported by [`ApduTransport::max_payload_size`].
+    ///
+    /// Defaults to a short APDU payload of 255 bytes.
+    pub fn with_max_payload_size(mut self, max_payload_size: usize) -> Self {
+        self.max_payload_size = Some(max_payload_size);
+        self
+    }
+
+    /// Every exchange performed so far, in order.
+    pub fn transcript(&self) -> &[MockExchange] {
+        &self.transcript
+    }
+
+    /// Whether every scripted exchange has been performed.
+    pub fn is_finished(&self) -> bool {
+        self.script.is_empty()
+    }
+
+    /// Panics if any scripted exchange has not been performed.
+    pub fn assert_finished(&self) {
+        if let Some(next) = self.script.front() {
+            panic!(
+                "mock transport finished with {} exchange(s) remaining, next expected {}",
+                self.script.len(),
+                Hex(&next.command),
+            );
+        }
+    }
+}
+
+impl BlockingTransport for MockTransport {
+    type TransportError = MockError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, MockError> {
+        let expected_length = encoding::expected_length(reply_buffer.len());
+        let mut encoded = vec![0u8; encoding::encoded_length(&command, expected_length).unwrap()];
+        encoding::encode(&command, expected_length, &mut encoded).unwrap();
+
+        let Some(exchange) = self.script.pop_front() else {
+            panic!(
+                "mock transport received unexpected command {}",
+                Hex(&encoded)
+            );
+        };
+
+        if exchange.command != encoded {
+            panic!(
+                "mock transport expected command {} but received {}",
+                Hex(&exchange.command),
+                Hex(&encoded),
+            );
+        }
+
+        let result = match &exchange.reply {
+            Ok(response) => {
+                if response.len() > reply_buffer.len() {
+                    panic!(
+                        "mock response {} does not fit in a {} byte reply buffer",
+                        Hex(response),
+                        reply_buffer.len(),
+                    );
+                }
+
+                reply_buffer[..response.len()].copy_from_slice(response);
+
+                Ok(ApduResponse::parse(&reply_buffer[..response.len()])
+                    .expect("mock responses must include the status bytes"))
+            }
+            Err(error) => Err(*error),
+        };
+
+        self.transcript.push(exchange);
+
+        result
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.max_payload_size.unwrap_or(u8::MAX as usize)
+    }
+}
+
+impl ApduTransport for MockTransport {
+    type TransportError = MockError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, Self::TransportError>> {
+        core::future::ready(BlockingTransport::execute(self, command, reply_buffer))
+    }
+
+    fn max_payload_size(&self) -> usize {
+        BlockingTransport::max_payload_size(self)
+    }
+}
@@ -1,6 +1,34 @@
+pub mod blocking;
 pub mod class;
 pub mod command;
+#[cfg(feature = "alloc")]
+pub mod emv;
+pub mod encoding;
+#[cfg(feature = "alloc")]
+pub mod fido;
+#[cfg(feature = "alloc")]
+pub mod globalplatform;
+#[cfg(any(
+    feature = "testing",
+    feature = "log",
+    feature = "tracing",
+    feature = "record"
+))]
+mod hex;
 pub mod iso_7816;
+#[cfg(feature = "testing")]
+pub mod mock;
+#[cfg(feature = "alloc")]
+pub mod openpgp;
+#[cfg(feature = "alloc")]
+pub mod owned;
+#[cfg(feature = "alloc")]
+pub mod piv;
+#[cfg(feature = "record")]
+pub mod record;
 pub mod response;
+pub mod sink;
 pub mod status;
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub mod trace;
 pub mod transport;
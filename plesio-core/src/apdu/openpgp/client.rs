@@ -0,0 +1,412 @@
+//! A client for the OpenPGP application, running its commands and parsing
+//! what they return.
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    iso_7816::{
+        operation::{
+            Iso7816StreamingOperation,
+            select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        },
+        status,
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    openpgp::{
+        KeySlot, OPENPGP_AID, Password,
+        object::{
+            AlgorithmAttributes, ApplicationRelatedData, CardholderData, Kdf, PublicKey,
+            signature_counter, tag,
+        },
+        operation::{
+            ChangeReferenceData, GenerateKeyPair, GetData, LifeCycle, PrivateKeyOperation, PutData,
+            Verify,
+        },
+    },
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::ApduTransport,
+};
+
+/// What [`OpenPgp::factory_reset`] presents to block the passwords, as
+/// GnuPG does.
+const WRONG_PASSWORD: &[u8] = &[0x40; 8];
+
+#[derive(Debug)]
+pub enum OpenPgpError<E> {
+    Transport(Iso7816TransportError<E>),
+    Rejected(ApduStatus),
+    /// The data object or key does not exist on the card.
+    NotFound,
+    /// The password is wrong; `retries` attempts are left.
+    WrongPassword {
+        retries: u8,
+    },
+    /// The password has no retries left.
+    Blocked,
+    MalformedResponse,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for OpenPgpError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Rejected(status) => {
+                write!(f, "OpenPGP application rejected the command: {status}")
+            }
+            Self::NotFound => write!(f, "data object or key not found"),
+            Self::WrongPassword { retries } => {
+                write!(f, "wrong password, {retries} retries left")
+            }
+            Self::Blocked => write!(f, "password blocked"),
+            Self::MalformedResponse => write!(f, "malformed OpenPGP response"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for OpenPgpError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E> From<Iso7816TransportError<E>> for OpenPgpError<E> {
+    fn from(e: Iso7816TransportError<E>) -> Self {
+        Self::Transport(e)
+    }
+}
+
+impl<E> From<ApduResponse<'_>> for OpenPgpError<E> {
+    fn from(response: ApduResponse<'_>) -> Self {
+        let status = response.status();
+
+        if let Some(retries) = status::has_retries_remaining(&status) {
+            return Self::WrongPassword { retries };
+        }
+
+        match status {
+            status::FILE_NOT_FOUND | status::REFERENCED_DATA_NOT_FOUND => Self::NotFound,
+            status::AUTHENTICATION_METHOD_BLOCKED => Self::Blocked,
+            _ => Self::Rejected(status),
+        }
+    }
+}
+
+/// The OpenPGP application on a card.
+pub struct OpenPgp<T: ApduTransport> {
+    transport: Iso7816Transport<T>,
+}
+
+impl<T: ApduTransport> OpenPgp<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport: Iso7816Transport::new(transport),
+        }
+    }
+
+    pub fn inner(&self) -> &Iso7816Transport<T> {
+        &self.transport
+    }
+
+    pub fn inner_mut(&mut self) -> &mut Iso7816Transport<T> {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport.into_inner()
+    }
+
+    /// Runs an operation that returns nothing but its status.
+    async fn run<O>(&mut self, operation: O) -> Result<(), OpenPgpError<T::TransportError>>
+    where
+        O: for<'s> Iso7816StreamingOperation<Result<'s> = Result<(), ApduResponse<'s>>>,
+    {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        Ok(())
+    }
+
+    /// Runs an operation and returns its response data.
+    async fn fetch<O>(&mut self, operation: O) -> Result<Vec<u8>, OpenPgpError<T::TransportError>>
+    where
+        O: for<'s> Iso7816StreamingOperation<Result<'s> = Result<&'s [u8], ApduResponse<'s>>>,
+    {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        Ok(response)
+    }
+
+    pub async fn select(&mut self) -> Result<(), OpenPgpError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(
+                Iso7816Select::new(
+                    Iso7816SelectResolution::ByApplicationIdentifier(OPENPGP_AID),
+                    &mut [],
+                ),
+                &mut response,
+            )
+            .await??;
+
+        Ok(())
+    }
+
+    /// Reads the data object `tag`, as the card returns it.
+    pub async fn get_data(&mut self, tag: u16) -> Result<Vec<u8>, OpenPgpError<T::TransportError>> {
+        self.fetch(GetData::new(tag)).await
+    }
+
+    /// Writes the data object `tag`, which most objects only accept with
+    /// PW3 verified.
+    pub async fn put_data(
+        &mut self,
+        tag: u16,
+        data: &[u8],
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.run(PutData::new(tag, data)).await
+    }
+
+    pub async fn application_related_data(
+        &mut self,
+    ) -> Result<ApplicationRelatedData, OpenPgpError<T::TransportError>> {
+        let data = self.get_data(tag::APPLICATION_RELATED_DATA).await?;
+        ApplicationRelatedData::parse(&data).ok_or(OpenPgpError::MalformedResponse)
+    }
+
+    pub async fn cardholder_data(
+        &mut self,
+    ) -> Result<CardholderData, OpenPgpError<T::TransportError>> {
+        let data = self.get_data(tag::CARDHOLDER_RELATED_DATA).await?;
+        CardholderData::parse(&data).ok_or(OpenPgpError::MalformedResponse)
+    }
+
+    /// Sets the name, as `surname<<given names` with `<` for spaces.
+    pub async fn set_name(&mut self, name: &[u8]) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.put_data(tag::NAME, name).await
+    }
+
+    pub async fn set_language(
+        &mut self,
+        language: &[u8],
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.put_data(tag::LANGUAGE, language).await
+    }
+
+    pub async fn set_sex(&mut self, sex: u8) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.put_data(tag::SEX, &[sex]).await
+    }
+
+    /// The URL the public keys can be fetched from.
+    pub async fn url(&mut self) -> Result<Vec<u8>, OpenPgpError<T::TransportError>> {
+        self.get_data(tag::URL).await
+    }
+
+    pub async fn set_url(&mut self, url: &[u8]) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.put_data(tag::URL, url).await
+    }
+
+    /// The number of signatures the card computed.
+    pub async fn signature_counter(&mut self) -> Result<u32, OpenPgpError<T::TransportError>> {
+        let data = self.get_data(tag::SECURITY_SUPPORT_TEMPLATE).await?;
+        signature_counter(&data).ok_or(OpenPgpError::MalformedResponse)
+    }
+
+    pub async fn kdf(&mut self) -> Result<Kdf, OpenPgpError<T::TransportError>> {
+        let data = self.get_data(tag::KDF).await?;
+        Kdf::parse(&data).ok_or(OpenPgpError::MalformedResponse)
+    }
+
+    /// Sets the KDF, with PW3 verified. The card resets the passwords to
+    /// those whose hashes `kdf` carries.
+    pub async fn set_kdf(&mut self, kdf: &Kdf) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.put_data(tag::KDF, &kdf.to_bytes()).await
+    }
+
+    /// Presents `value`: the password, or its derived hash if a KDF is set.
+    pub async fn verify(
+        &mut self,
+        password: Password,
+        value: &[u8],
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.run(Verify::new(password, value)).await
+    }
+
+    /// The retries left for `password`, or `None` if it is already
+    /// verified.
+    pub async fn retries(
+        &mut self,
+        password: Password,
+    ) -> Result<Option<u8>, OpenPgpError<T::TransportError>> {
+        match self.run(Verify::status(password)).await {
+            Ok(()) => Ok(None),
+            Err(OpenPgpError::WrongPassword { retries }) => Ok(Some(retries)),
+            Err(OpenPgpError::Blocked) => Ok(Some(0)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Changes PW1 or PW3 from `old` to `new`.
+    pub async fn change_password(
+        &mut self,
+        password: Password,
+        old: &[u8],
+        new: &[u8],
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.run(ChangeReferenceData::new(password, old, new)).await
+    }
+
+    /// Sets the reset code, with PW3 verified.
+    pub async fn set_reset_code(
+        &mut self,
+        reset_code: &[u8],
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.put_data(tag::RESET_CODE, reset_code).await
+    }
+
+    /// Unblocks PW1 with the reset code, setting it to `new`.
+    pub async fn reset_retry_counter(
+        &mut self,
+        reset_code: &[u8],
+        new: &[u8],
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.run(ChangeReferenceData::reset_with_code(reset_code, new))
+            .await
+    }
+
+    /// Unblocks PW1 with PW3 verified, setting it to `new`.
+    pub async fn reset_retry_counter_as_admin(
+        &mut self,
+        new: &[u8],
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.run(ChangeReferenceData::reset_as_admin(new)).await
+    }
+
+    /// Signs with the signature key, after PW1 was verified for signing.
+    /// `input` is the DigestInfo for RSA, the digest for ECDSA and the
+    /// message for EdDSA.
+    pub async fn sign(&mut self, input: &[u8]) -> Result<Vec<u8>, OpenPgpError<T::TransportError>> {
+        self.fetch(PrivateKeyOperation::compute_signature(input))
+            .await
+    }
+
+    /// Decrypts an RSA cryptogram with the decryption key, after PW1 was
+    /// verified.
+    pub async fn decipher_rsa(
+        &mut self,
+        cryptogram: &[u8],
+    ) -> Result<Vec<u8>, OpenPgpError<T::TransportError>> {
+        self.fetch(PrivateKeyOperation::decipher_rsa(cryptogram))
+            .await
+    }
+
+    /// Derives the ECDH shared secret of the decryption key and the
+    /// sender's public point, after PW1 was verified.
+    pub async fn decipher_ecdh(
+        &mut self,
+        public_point: &[u8],
+    ) -> Result<Vec<u8>, OpenPgpError<T::TransportError>> {
+        self.fetch(PrivateKeyOperation::decipher_ecdh(public_point))
+            .await
+    }
+
+    /// Signs `input` with the authentication key, after PW1 was verified.
+    pub async fn internal_authenticate(
+        &mut self,
+        input: &[u8],
+    ) -> Result<Vec<u8>, OpenPgpError<T::TransportError>> {
+        self.fetch(PrivateKeyOperation::internal_authenticate(input))
+            .await
+    }
+
+    /// Generates the key of `slot`, with PW3 verified. The card does not
+    /// compute the OpenPGP fingerprint: store it and the creation time with
+    /// [`Self::set_fingerprint`] and [`Self::set_generation_date`].
+    pub async fn generate_key(
+        &mut self,
+        slot: KeySlot,
+    ) -> Result<PublicKey, OpenPgpError<T::TransportError>> {
+        let data = self.fetch(GenerateKeyPair::generate(slot)).await?;
+        PublicKey::parse(&data).ok_or(OpenPgpError::MalformedResponse)
+    }
+
+    pub async fn public_key(
+        &mut self,
+        slot: KeySlot,
+    ) -> Result<PublicKey, OpenPgpError<T::TransportError>> {
+        let data = self.fetch(GenerateKeyPair::read(slot)).await?;
+        PublicKey::parse(&data).ok_or(OpenPgpError::MalformedResponse)
+    }
+
+    /// Changes the algorithm of `slot`, with PW3 verified, before a key is
+    /// generated or imported.
+    pub async fn set_algorithm_attributes(
+        &mut self,
+        slot: KeySlot,
+        attributes: &AlgorithmAttributes,
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.put_data(slot.algorithm_attributes_tag(), &attributes.to_bytes())
+            .await
+    }
+
+    pub async fn set_fingerprint(
+        &mut self,
+        slot: KeySlot,
+        fingerprint: &[u8; 20],
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.put_data(slot.fingerprint_tag(), fingerprint).await
+    }
+
+    /// Sets the creation time of the key, in seconds since the epoch.
+    pub async fn set_generation_date(
+        &mut self,
+        slot: KeySlot,
+        timestamp: u32,
+    ) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.put_data(slot.generation_date_tag(), &timestamp.to_be_bytes())
+            .await
+    }
+
+    /// Puts the application in its termination state, with PW3 verified or
+    /// both passwords blocked.
+    pub async fn terminate(&mut self) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.run(LifeCycle::terminate()).await
+    }
+
+    /// Resets a terminated application to its factory state.
+    pub async fn activate(&mut self) -> Result<(), OpenPgpError<T::TransportError>> {
+        self.run(LifeCycle::activate()).await
+    }
+
+    /// Erases the keys and data and restores the default passwords, without
+    /// knowing PW3: both passwords are blocked by presenting a wrong one
+    /// until no retries are left, then the application is terminated and
+    /// activated.
+    pub async fn factory_reset(&mut self) -> Result<(), OpenPgpError<T::TransportError>> {
+        let status = self.application_related_data().await?.password_status;
+
+        for (password, retries) in [
+            (Password::User, status.pw1_retries),
+            (Password::Admin, status.pw3_retries),
+        ] {
+            for _ in 0..retries {
+                if let Err(OpenPgpError::Transport(e)) = self.verify(password, WRONG_PASSWORD).await
+                {
+                    return Err(OpenPgpError::Transport(e));
+                }
+            }
+        }
+
+        self.terminate().await?;
+        self.activate().await
+    }
+}
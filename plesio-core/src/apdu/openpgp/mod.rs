@@ -0,0 +1,78 @@
+//! The OpenPGP card application, version 3.4: its application related data,
+//! cardholder data and passwords, and the signing, decryption and
+//! authentication keys.
+
+pub mod client;
+pub mod object;
+pub mod operation;
+
+/// The OpenPGP application AID, without the version, manufacturer and
+/// serial number cards add to it.
+pub const OPENPGP_AID: &[u8] = &[0xD2, 0x76, 0x00, 0x01, 0x24, 0x01];
+
+/// The three keys of the card.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySlot {
+    Signature,
+    Decryption,
+    Authentication,
+}
+
+impl KeySlot {
+    pub const ALL: [Self; 3] = [Self::Signature, Self::Decryption, Self::Authentication];
+
+    /// The control reference template naming the key in GENERATE ASYMMETRIC
+    /// KEY PAIR.
+    pub fn control_reference(self) -> u8 {
+        match self {
+            Self::Signature => 0xB6,
+            Self::Decryption => 0xB8,
+            Self::Authentication => 0xA4,
+        }
+    }
+
+    /// The position of the key in the fingerprint, generation date and
+    /// algorithm attribute lists.
+    pub fn index(self) -> usize {
+        match self {
+            Self::Signature => 0,
+            Self::Decryption => 1,
+            Self::Authentication => 2,
+        }
+    }
+
+    pub fn algorithm_attributes_tag(self) -> u16 {
+        0xC1 + self.index() as u16
+    }
+
+    pub fn fingerprint_tag(self) -> u16 {
+        0xC7 + self.index() as u16
+    }
+
+    pub fn generation_date_tag(self) -> u16 {
+        0xCE + self.index() as u16
+    }
+}
+
+/// The passwords VERIFY and CHANGE REFERENCE DATA refer to. PW1 has two
+/// references: one valid for a single signature, one for the other
+/// operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Password {
+    /// PW1 for PSO:COMPUTE DIGITAL SIGNATURE.
+    Signing,
+    /// PW1 for decryption, authentication and private data.
+    User,
+    /// PW3, the admin password.
+    Admin,
+}
+
+impl Password {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::Signing => 0x81,
+            Self::User => 0x82,
+            Self::Admin => 0x83,
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! The data objects of the OpenPGP application and their content.
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "openpgp-kdf")]
+use digest::Digest;
+
+#[cfg(feature = "openpgp-kdf")]
+use crate::apdu::openpgp::Password;
+use crate::apdu::{iso_7816::tlv::ber::BerTlv, openpgp::KeySlot};
+
+/// The tags GET DATA and PUT DATA take.
+pub mod tag {
+    pub const APPLICATION_RELATED_DATA: u16 = 0x6E;
+    pub const CARDHOLDER_RELATED_DATA: u16 = 0x65;
+    pub const NAME: u16 = 0x5B;
+    pub const LANGUAGE: u16 = 0x5F2D;
+    pub const SEX: u16 = 0x5F35;
+    pub const LOGIN: u16 = 0x5E;
+    pub const URL: u16 = 0x5F50;
+    pub const SECURITY_SUPPORT_TEMPLATE: u16 = 0x7A;
+    pub const CARDHOLDER_CERTIFICATE: u16 = 0x7F21;
+    pub const PW_STATUS: u16 = 0xC4;
+    pub const RESET_CODE: u16 = 0xD3;
+    pub const KDF: u16 = 0xF9;
+}
+
+/// The data objects of `template`, with those of a `73` discretionary data
+/// template among them inlined: version 3 cards nest most of the
+/// application related data in one, older cards do not.
+fn flatten<'a>(template: BerTlv<'a>) -> impl Iterator<Item = BerTlv<'a>> {
+    template.children().flat_map(|object| {
+        let (nested, own) = match object.tag() {
+            0x73 => (Some(object.children()), None),
+            _ => (None, Some(object)),
+        };
+        nested.into_iter().flatten().chain(own)
+    })
+}
+
+/// The template `tag` at the start of `data`, as GET DATA returns it.
+fn template(data: &[u8], tag: u32) -> Option<BerTlv<'_>> {
+    let (object, _) = BerTlv::next(data)?;
+    (object.tag() == tag).then_some(object)
+}
+
+/// The algorithm and parameters of a key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AlgorithmAttributes {
+    Rsa {
+        modulus_bits: u16,
+        exponent_bits: u16,
+        /// How an imported private key is laid out: `00` standard, `01`
+        /// with modulus, `02` CRT, `03` CRT with modulus.
+        import_format: u8,
+    },
+    /// ECDH (`12`), ECDSA (`13`) or EdDSA (`16`) over the curve `oid`.
+    Ecc {
+        algorithm: u8,
+        /// The curve OID, without tag and length.
+        oid: Vec<u8>,
+        /// Whether an imported private key comes with its public key.
+        with_public_key: bool,
+    },
+}
+
+impl AlgorithmAttributes {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x01, m1, m2, e1, e2, rest @ ..] => Some(Self::Rsa {
+                modulus_bits: u16::from_be_bytes([*m1, *m2]),
+                exponent_bits: u16::from_be_bytes([*e1, *e2]),
+                import_format: rest.first().copied().unwrap_or(0x00),
+            }),
+            [algorithm @ (0x12 | 0x13 | 0x16), oid @ ..] => {
+                let (oid, with_public_key) = match oid {
+                    [oid @ .., 0xFF] => (oid, true),
+                    [oid @ .., 0x00] => (oid, false),
+                    oid => (oid, false),
+                };
+
+                Some(Self::Ecc {
+                    algorithm: *algorithm,
+                    oid: oid.to_vec(),
+                    with_public_key,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The encoding PUT DATA of `C1` to `C3` takes to change a key's
+    /// algorithm.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Rsa {
+                modulus_bits,
+                exponent_bits,
+                import_format,
+            } => {
+                let mut data = Vec::from([0x01]);
+                data.extend_from_slice(&modulus_bits.to_be_bytes());
+                data.extend_from_slice(&exponent_bits.to_be_bytes());
+                data.push(*import_format);
+                data
+            }
+            Self::Ecc {
+                algorithm,
+                oid,
+                with_public_key,
+            } => {
+                let mut data = Vec::from([*algorithm]);
+                data.extend_from_slice(oid);
+                if *with_public_key {
+                    data.push(0xFF);
+                }
+                data
+            }
+        }
+    }
+}
+
+/// The optional features the card supports, from the `C0` data object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct ExtendedCapabilities {
+    pub flags: u8,
+    pub secure_messaging_algorithm: u8,
+    pub max_challenge_length: u16,
+    pub max_certificate_length: u16,
+    pub max_special_object_length: u16,
+}
+
+impl ExtendedCapabilities {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let &[
+            flags,
+            secure_messaging_algorithm,
+            c1,
+            c2,
+            m1,
+            m2,
+            s1,
+            s2,
+            ..,
+        ] = data
+        else {
+            return None;
+        };
+
+        Some(Self {
+            flags,
+            secure_messaging_algorithm,
+            max_challenge_length: u16::from_be_bytes([c1, c2]),
+            max_certificate_length: u16::from_be_bytes([m1, m2]),
+            max_special_object_length: u16::from_be_bytes([s1, s2]),
+        })
+    }
+
+    pub fn supports_key_import(&self) -> bool {
+        self.flags & 0x20 != 0
+    }
+
+    /// Whether the algorithm attributes can be changed with PUT DATA.
+    pub fn changeable_algorithm_attributes(&self) -> bool {
+        self.flags & 0x04 != 0
+    }
+
+    pub fn supports_kdf(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+}
+
+/// The `C4` data object: the password lengths and the retries left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct PasswordStatus {
+    /// Whether a verified PW1 stays valid for more than one signature.
+    pub pw1_valid_for_multiple_signatures: bool,
+    pub max_pw1_length: u8,
+    pub max_reset_code_length: u8,
+    pub max_pw3_length: u8,
+    pub pw1_retries: u8,
+    pub reset_code_retries: u8,
+    pub pw3_retries: u8,
+}
+
+impl PasswordStatus {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let &[
+            validity,
+            pw1,
+            reset_code,
+            pw3,
+            pw1_retries,
+            reset_code_retries,
+            pw3_retries,
+            ..,
+        ] = data
+        else {
+            return None;
+        };
+
+        Some(Self {
+            pw1_valid_for_multiple_signatures: validity == 0x01,
+            max_pw1_length: pw1 & 0x7F,
+            max_reset_code_length: reset_code,
+            max_pw3_length: pw3,
+            pw1_retries,
+            reset_code_retries,
+            pw3_retries,
+        })
+    }
+}
+
+/// The application related data, `6E`, which holds what a client needs to
+/// know about the card and its keys.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct ApplicationRelatedData {
+    /// The full AID, with version, manufacturer and serial number.
+    pub aid: Vec<u8>,
+    pub historical_bytes: Vec<u8>,
+    pub extended_capabilities: ExtendedCapabilities,
+    /// The attributes of the signature, decryption and authentication
+    /// keys.
+    pub algorithm_attributes: [Option<AlgorithmAttributes>; 3],
+    pub password_status: PasswordStatus,
+    /// The fingerprints of the three keys, zero if a slot is empty.
+    pub fingerprints: [[u8; 20]; 3],
+    pub ca_fingerprints: [[u8; 20]; 3],
+    /// The creation times of the three keys, in seconds since the epoch.
+    pub generation_dates: [u32; 3],
+    /// The key references and whether each key is absent (`00`),
+    /// generated on the card (`01`) or imported (`02`), if the card lists
+    /// them.
+    pub key_information: Vec<(u8, u8)>,
+}
+
+impl ApplicationRelatedData {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let template = template(data, 0x6E)?;
+        let mut related = Self::default();
+
+        for object in flatten(template) {
+            let value = object.value();
+
+            match object.tag() {
+                0x4F => related.aid = value.to_vec(),
+                0x5F52 => related.historical_bytes = value.to_vec(),
+                0xC0 => related.extended_capabilities = ExtendedCapabilities::parse(value)?,
+                0xC1..=0xC3 => {
+                    related.algorithm_attributes[object.tag() as usize - 0xC1] =
+                        AlgorithmAttributes::parse(value);
+                }
+                0xC4 => related.password_status = PasswordStatus::parse(value)?,
+                0xC5 => related.fingerprints = Self::fingerprints(value)?,
+                0xC6 => related.ca_fingerprints = Self::fingerprints(value)?,
+                0xCD => {
+                    for (date, bytes) in related
+                        .generation_dates
+                        .iter_mut()
+                        .zip(value.chunks_exact(4))
+                    {
+                        *date = u32::from_be_bytes(bytes.try_into().ok()?);
+                    }
+                }
+                0xDE => {
+                    related.key_information = value
+                        .chunks_exact(2)
+                        .map(|pair| (pair[0], pair[1]))
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+
+        (related.aid.len() == 16).then_some(related)
+    }
+
+    fn fingerprints(data: &[u8]) -> Option<[[u8; 20]; 3]> {
+        let mut fingerprints = [[0; 20]; 3];
+
+        for (fingerprint, bytes) in fingerprints.iter_mut().zip(data.chunks_exact(20)) {
+            fingerprint.copy_from_slice(bytes);
+        }
+
+        (data.len() >= 60).then_some(fingerprints)
+    }
+
+    /// The version of the specification the card implements, as major and
+    /// minor numbers.
+    pub fn version(&self) -> (u8, u8) {
+        (self.aid[6], self.aid[7])
+    }
+
+    /// The manufacturer identifier OpenPGP assigns.
+    pub fn manufacturer(&self) -> u16 {
+        u16::from_be_bytes([self.aid[8], self.aid[9]])
+    }
+
+    pub fn serial_number(&self) -> u32 {
+        u32::from_be_bytes([self.aid[10], self.aid[11], self.aid[12], self.aid[13]])
+    }
+
+    pub fn fingerprint(&self, slot: KeySlot) -> Option<&[u8; 20]> {
+        let fingerprint = &self.fingerprints[slot.index()];
+        (fingerprint != &[0; 20]).then_some(fingerprint)
+    }
+}
+
+/// The cardholder related data, `65`.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct CardholderData {
+    /// The name as `surname<<given names`, with `<` for spaces.
+    pub name: Vec<u8>,
+    /// Up to four ISO 639 language codes in order of preference.
+    pub language: Vec<u8>,
+    /// ISO 5218 as an ASCII digit: `1` male, `2` female, `9` not
+    /// applicable.
+    pub sex: Option<u8>,
+}
+
+impl CardholderData {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let objects = template(data, 0x65)?.children();
+        let value = |tag| {
+            objects
+                .get(tag)
+                .map(|object| object.value().to_vec())
+                .unwrap_or_default()
+        };
+
+        Some(Self {
+            name: value(0x5B),
+            language: value(0x5F2D),
+            sex: objects
+                .get(0x5F35)
+                .and_then(|sex| sex.value().first().copied()),
+        })
+    }
+}
+
+/// The number of signatures the card computed, from the security support
+/// template `7A`.
+pub fn signature_counter(data: &[u8]) -> Option<u32> {
+    let counter = template(data, 0x7A)?.children().get(0x93)?;
+    let &[high, middle, low] = counter.value() else {
+        return None;
+    };
+
+    Some(u32::from_be_bytes([0, high, middle, low]))
+}
+
+/// The key derivation function data object `F9`. When a KDF is set, the
+/// card expects every password as its derived hash rather than in plain.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Kdf {
+    /// `00` for none, `03` for the iterated and salted S2K of RFC 4880.
+    pub algorithm: u8,
+    /// `08` for SHA-256, `0A` for SHA-512.
+    pub hash_algorithm: Option<u8>,
+    /// The number of bytes hashed.
+    pub iterations: u32,
+    pub pw1_salt: Vec<u8>,
+    pub reset_code_salt: Option<Vec<u8>>,
+    pub pw3_salt: Option<Vec<u8>>,
+    pub initial_pw1_hash: Option<Vec<u8>>,
+    pub initial_pw3_hash: Option<Vec<u8>>,
+}
+
+impl Kdf {
+    pub const NONE: u8 = 0x00;
+    pub const ITERATED_SALTED_S2K: u8 = 0x03;
+
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let objects = template(data, 0xF9)?.children();
+        let value = |tag| objects.get(tag).map(|object| object.value().to_vec());
+
+        let iterations = match objects.get(0x83) {
+            Some(iterations) => u32::from_be_bytes(*iterations.value().first_chunk()?),
+            None => 0,
+        };
+
+        Some(Self {
+            algorithm: *objects.get(0x81)?.value().first()?,
+            hash_algorithm: objects
+                .get(0x82)
+                .and_then(|hash| hash.value().first().copied()),
+            iterations,
+            pw1_salt: value(0x84).unwrap_or_default(),
+            reset_code_salt: value(0x85),
+            pw3_salt: value(0x86),
+            initial_pw1_hash: value(0x87),
+            initial_pw3_hash: value(0x88),
+        })
+    }
+
+    /// The encoding PUT DATA of `F9` takes, which the card only accepts
+    /// with PW3 verified. Setting it resets the passwords, so the initial
+    /// hashes should be those of the default passwords.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut write = |tag, value: &[u8]| {
+            BerTlv::from(tag, value)
+                .write(&mut data)
+                .expect("vector sink is unbounded");
+        };
+
+        write(0x81, &[self.algorithm]);
+
+        if self.algorithm != Self::NONE {
+            write(0x82, &[self.hash_algorithm.unwrap_or(0x08)]);
+            write(0x83, &self.iterations.to_be_bytes());
+            write(0x84, &self.pw1_salt);
+            for (tag, value) in [
+                (0x85, &self.reset_code_salt),
+                (0x86, &self.pw3_salt),
+                (0x87, &self.initial_pw1_hash),
+                (0x88, &self.initial_pw3_hash),
+            ] {
+                if let Some(value) = value {
+                    write(tag, value);
+                }
+            }
+        }
+
+        data
+    }
+
+    /// Derives what the card takes for `password` with the iterated and
+    /// salted S2K, where `D` matches [`Self::hash_algorithm`]. PW3 uses the
+    /// PW1 salt if it has none of its own. Without a KDF, returns
+    /// `password` unchanged.
+    #[cfg(feature = "openpgp-kdf")]
+    pub fn derive<D: Digest>(&self, reference: Password, password: &[u8]) -> Vec<u8> {
+        let salt = match reference {
+            Password::Admin => self.pw3_salt.as_ref(),
+            _ => None,
+        };
+
+        self.s2k::<D>(salt.unwrap_or(&self.pw1_salt), password)
+    }
+
+    /// Derives the reset code, which RESET RETRY COUNTER takes along with
+    /// the new PW1.
+    #[cfg(feature = "openpgp-kdf")]
+    pub fn derive_reset_code<D: Digest>(&self, reset_code: &[u8]) -> Vec<u8> {
+        let salt = self.reset_code_salt.as_ref().unwrap_or(&self.pw1_salt);
+        self.s2k::<D>(salt, reset_code)
+    }
+
+    /// Hashes `salt || password` repeated until [`Self::iterations`] bytes
+    /// are hashed, and at least once. With no salt and an empty password,
+    /// this is the hash of nothing.
+    #[cfg(feature = "openpgp-kdf")]
+    fn s2k<D: Digest>(&self, salt: &[u8], password: &[u8]) -> Vec<u8> {
+        if self.algorithm != Self::ITERATED_SALTED_S2K {
+            return password.to_vec();
+        }
+
+        let data = [salt, password].concat();
+        let mut remaining = (self.iterations as usize).max(data.len());
+        let mut hasher = D::new();
+
+        while remaining > 0 && !data.is_empty() {
+            let length = remaining.min(data.len());
+            hasher.update(&data[..length]);
+            remaining -= length;
+        }
+
+        hasher.finalize().to_vec()
+    }
+}
+
+/// A public key as GENERATE ASYMMETRIC KEY PAIR returns it in the `7F49`
+/// template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PublicKey {
+    Rsa {
+        modulus: Vec<u8>,
+        exponent: Vec<u8>,
+    },
+    /// The point, uncompressed for the NIST and Brainpool curves and the
+    /// native encoding for Curve25519 and Ed25519.
+    Ecc {
+        point: Vec<u8>,
+    },
+}
+
+impl PublicKey {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let objects = template(data, 0x7F49)?.children();
+
+        if let Some(point) = objects.get(0x86) {
+            return Some(Self::Ecc {
+                point: point.value().to_vec(),
+            });
+        }
+
+        Some(Self::Rsa {
+            modulus: objects.get(0x81)?.value().to_vec(),
+            exponent: objects.get(0x82)?.value().to_vec(),
+        })
+    }
+}
@@ -0,0 +1,294 @@
+//! The OpenPGP card commands, sent with the interindustry class.
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+        tlv::ber::BerTlv,
+    },
+    openpgp::{KeySlot, Password},
+    response::ApduResponse,
+    status::is,
+};
+
+fn write(object: BerTlv<'_>, data: &mut Vec<u8>) {
+    object.write(data).expect("vector sink is unbounded");
+}
+
+/// GET DATA of the data object `tag`.
+pub struct GetData {
+    tag: u16,
+}
+
+impl GetData {
+    pub fn new(tag: u16) -> Self {
+        Self { tag }
+    }
+}
+
+impl Iso7816StreamingOperation for GetData {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let [p1, p2] = self.tag.to_be_bytes();
+        Iso7816Command::new(class, 0xCA, (p1, p2), &[])
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// PUT DATA of the data object `tag`, which most objects only accept with
+/// PW3 verified.
+pub struct PutData<'a> {
+    tag: u16,
+    data: &'a [u8],
+}
+
+impl<'a> PutData<'a> {
+    pub fn new(tag: u16, data: &'a [u8]) -> Self {
+        Self { tag, data }
+    }
+}
+
+impl Iso7816StreamingOperation for PutData<'_> {
+    type Result<'s> = Result<(), ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let [p1, p2] = self.tag.to_be_bytes();
+        Iso7816Command::new(class, 0xDA, (p1, p2), self.data)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(|_| ())
+    }
+}
+
+/// VERIFY of a password, or without one, whether it is verified.
+pub struct Verify<'a> {
+    password: Password,
+    value: &'a [u8],
+}
+
+impl<'a> Verify<'a> {
+    /// `value` is the password in UTF-8, or its derived hash if a KDF is
+    /// set.
+    pub fn new(password: Password, value: &'a [u8]) -> Self {
+        Self { password, value }
+    }
+
+    /// Asks whether the password is verified, which the card answers with
+    /// `9000` or the retries left.
+    pub fn status(password: Password) -> Self {
+        Self {
+            password,
+            value: &[],
+        }
+    }
+}
+
+impl Iso7816StreamingOperation for Verify<'_> {
+    type Result<'s> = Result<(), ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, 0x20, (0x00, self.password.to_u8()), self.value)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(|_| ())
+    }
+}
+
+/// CHANGE REFERENCE DATA of PW1 or PW3, or RESET RETRY COUNTER of PW1.
+pub struct ChangeReferenceData {
+    instruction: u8,
+    parameters: (u8, u8),
+    data: Vec<u8>,
+}
+
+impl ChangeReferenceData {
+    /// Changes PW1, for either of its references, or PW3.
+    pub fn new(password: Password, old: &[u8], new: &[u8]) -> Self {
+        let reference = match password {
+            Password::Admin => 0x83,
+            _ => 0x81,
+        };
+
+        Self {
+            instruction: 0x24,
+            parameters: (0x00, reference),
+            data: [old, new].concat(),
+        }
+    }
+
+    /// Unblocks PW1 with the reset code and sets it to `new`.
+    pub fn reset_with_code(reset_code: &[u8], new: &[u8]) -> Self {
+        Self {
+            instruction: 0x2C,
+            parameters: (0x00, 0x81),
+            data: [reset_code, new].concat(),
+        }
+    }
+
+    /// Unblocks PW1 and sets it to `new`, with PW3 verified.
+    pub fn reset_as_admin(new: &[u8]) -> Self {
+        Self {
+            instruction: 0x2C,
+            parameters: (0x02, 0x81),
+            data: new.to_vec(),
+        }
+    }
+}
+
+impl Iso7816StreamingOperation for ChangeReferenceData {
+    type Result<'s> = Result<(), ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, self.instruction, self.parameters, &self.data)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(|_| ())
+    }
+}
+
+/// The private key operations: PSO:COMPUTE DIGITAL SIGNATURE, PSO:DECIPHER
+/// and INTERNAL AUTHENTICATE. The result is the response data.
+pub struct PrivateKeyOperation {
+    instruction: u8,
+    parameters: (u8, u8),
+    data: Vec<u8>,
+}
+
+impl PrivateKeyOperation {
+    /// Signs with the signature key, after PW1 was verified for signing.
+    /// `input` is the DigestInfo for RSA, the digest for ECDSA and the
+    /// message for EdDSA.
+    pub fn compute_signature(input: &[u8]) -> Self {
+        Self {
+            instruction: 0x2A,
+            parameters: (0x9E, 0x9A),
+            data: input.to_vec(),
+        }
+    }
+
+    /// Decrypts an RSA cryptogram with the decryption key.
+    pub fn decipher_rsa(cryptogram: &[u8]) -> Self {
+        let mut data = Vec::from([0x00]);
+        data.extend_from_slice(cryptogram);
+
+        Self {
+            instruction: 0x2A,
+            parameters: (0x80, 0x86),
+            data,
+        }
+    }
+
+    /// Derives the ECDH shared secret of the decryption key and the
+    /// sender's public point, in a cipher data object `A6`.
+    pub fn decipher_ecdh(public_point: &[u8]) -> Self {
+        let mut point = Vec::new();
+        write(BerTlv::from(0x86, public_point), &mut point);
+        let mut public_key = Vec::new();
+        write(BerTlv::from(0x7F49, &point), &mut public_key);
+        let mut data = Vec::new();
+        write(BerTlv::from(0xA6, &public_key), &mut data);
+
+        Self {
+            instruction: 0x2A,
+            parameters: (0x80, 0x86),
+            data,
+        }
+    }
+
+    /// Signs `input` with the authentication key, after PW1 was verified.
+    pub fn internal_authenticate(input: &[u8]) -> Self {
+        Self {
+            instruction: 0x88,
+            parameters: (0x00, 0x00),
+            data: input.to_vec(),
+        }
+    }
+}
+
+impl Iso7816StreamingOperation for PrivateKeyOperation {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, self.instruction, self.parameters, &self.data)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// GENERATE ASYMMETRIC KEY PAIR, which generates a key with PW3 verified
+/// or reads the public key of an existing one. The card answers with a
+/// `7F49` template.
+pub struct GenerateKeyPair {
+    generate: bool,
+    control_reference: [u8; 2],
+}
+
+impl GenerateKeyPair {
+    pub fn generate(slot: KeySlot) -> Self {
+        Self {
+            generate: true,
+            control_reference: [slot.control_reference(), 0x00],
+        }
+    }
+
+    pub fn read(slot: KeySlot) -> Self {
+        Self {
+            generate: false,
+            control_reference: [slot.control_reference(), 0x00],
+        }
+    }
+}
+
+impl Iso7816StreamingOperation for GenerateKeyPair {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let p1 = if self.generate { 0x80 } else { 0x81 };
+        Iso7816Command::new(class, 0x47, (p1, 0x00), &self.control_reference)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// TERMINATE DF, which puts the application in its termination state, or
+/// ACTIVATE FILE, which then resets it to its factory state.
+pub struct LifeCycle {
+    instruction: u8,
+}
+
+impl LifeCycle {
+    /// Needs PW3 verified, or both PW1 and PW3 blocked.
+    pub fn terminate() -> Self {
+        Self { instruction: 0xE6 }
+    }
+
+    pub fn activate() -> Self {
+        Self { instruction: 0x44 }
+    }
+}
+
+impl Iso7816StreamingOperation for LifeCycle {
+    type Result<'s> = Result<(), ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, self.instruction, (0x00, 0x00), &[])
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(|_| ())
+    }
+}
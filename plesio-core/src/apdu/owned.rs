@@ -0,0 +1,123 @@
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    class::ApduClass,
+    command::ApduCommand,
+    iso_7816::{class::Iso7816Class, operation::Iso7816Command},
+    response::ApduResponse,
+    status::ApduStatus,
+};
+
+/// A command that owns its data, for when a borrowed command cannot outlive
+/// the buffer it was built from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedCommand<C: ApduClass = Iso7816Class> {
+    class: C,
+    instruction: u8,
+    parameters: (u8, u8),
+    data: Vec<u8>,
+}
+
+impl<C: ApduClass> OwnedCommand<C> {
+    pub fn new(class: C, instruction: u8, parameters: (u8, u8), data: Vec<u8>) -> Self {
+        Self {
+            class,
+            instruction,
+            parameters,
+            data,
+        }
+    }
+
+    pub fn from_command(command: &impl ApduCommand<Class = C>) -> Self {
+        Self {
+            class: command.class(),
+            instruction: command.instruction(),
+            parameters: command.parameters(),
+            data: command.data().to_vec(),
+        }
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl OwnedCommand<Iso7816Class> {
+    pub fn as_command(&self) -> Iso7816Command<'_> {
+        Iso7816Command {
+            class: self.class,
+            instruction: self.instruction,
+            parameters: self.parameters,
+            data: &self.data,
+        }
+    }
+}
+
+impl From<Iso7816Command<'_>> for OwnedCommand<Iso7816Class> {
+    fn from(command: Iso7816Command<'_>) -> Self {
+        Self::from_command(&command)
+    }
+}
+
+impl<C: ApduClass + Copy> ApduCommand for OwnedCommand<C> {
+    type Class = C;
+
+    fn class(&self) -> C {
+        self.class
+    }
+
+    fn instruction(&self) -> u8 {
+        self.instruction
+    }
+
+    fn parameters(&self) -> (u8, u8) {
+        self.parameters
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// A response that owns its data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedResponse {
+    data: Vec<u8>,
+    status: ApduStatus,
+}
+
+impl OwnedResponse {
+    pub fn new(data: Vec<u8>, status: ApduStatus) -> Self {
+        Self { data, status }
+    }
+
+    /// Parses a raw response consisting of data followed by the two status bytes.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        ApduResponse::parse(data).map(Self::from)
+    }
+
+    pub fn as_response(&self) -> ApduResponse<'_> {
+        ApduResponse::new(&self.data, self.status)
+    }
+
+    pub fn status(&self) -> ApduStatus {
+        self.status
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl From<ApduResponse<'_>> for OwnedResponse {
+    fn from(response: ApduResponse<'_>) -> Self {
+        Self {
+            data: response.data().to_vec(),
+            status: response.status(),
+        }
+    }
+}
@@ -0,0 +1,402 @@
+//! A client for the PIV application, running its commands and parsing what
+//! they return.
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "piv-management")]
+use crate::apdu::{iso_7816::secure_messaging::constant_time_eq, piv::management::ManagementKey};
+use crate::apdu::{
+    iso_7816::{
+        operation::{
+            Iso7816StreamingOperation,
+            select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        },
+        status,
+        tlv::ber::BerTlv,
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    piv::{
+        Algorithm, ApplicationProperty, KeyReference, PIV_AID, Slot,
+        key::PublicKey,
+        object::{self, CardCapabilityContainer, Chuid, Discovery, KeyHistory, ObjectError, tag},
+        operation::{
+            Attest, ChangeReferenceData, GeneralAuthenticate, GenerateKeyPair, GetData, ImportKey,
+            Verify,
+        },
+        pad_pin,
+    },
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::ApduTransport,
+};
+
+#[derive(Debug)]
+pub enum PivError<E> {
+    Transport(Iso7816TransportError<E>),
+    Rejected(ApduStatus),
+    /// The data object or key does not exist on the card.
+    NotFound,
+    /// The PIN or PUK is wrong; `retries` attempts are left.
+    WrongPin {
+        retries: u8,
+    },
+    /// The PIN or PUK has no retries left.
+    Blocked,
+    /// A PIN or PUK longer than eight bytes.
+    InvalidPin,
+    MalformedResponse,
+    Object(ObjectError),
+    /// The card's answer to the management key challenge is wrong: it holds
+    /// a different key.
+    AuthenticationFailed,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for PivError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {e}"),
+            Self::Rejected(status) => write!(f, "PIV application rejected the command: {status}"),
+            Self::NotFound => write!(f, "data object or key not found"),
+            Self::WrongPin { retries } => write!(f, "wrong PIN, {retries} retries left"),
+            Self::Blocked => write!(f, "PIN blocked"),
+            Self::InvalidPin => write!(f, "PIN longer than 8 bytes"),
+            Self::MalformedResponse => write!(f, "malformed PIV response"),
+            Self::Object(e) => write!(f, "{e}"),
+            Self::AuthenticationFailed => write!(f, "card failed management key authentication"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for PivError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            Self::Object(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<E> From<Iso7816TransportError<E>> for PivError<E> {
+    fn from(e: Iso7816TransportError<E>) -> Self {
+        Self::Transport(e)
+    }
+}
+
+impl<E> From<ApduResponse<'_>> for PivError<E> {
+    fn from(response: ApduResponse<'_>) -> Self {
+        let status = response.status();
+
+        if let Some(retries) = status::has_retries_remaining(&status) {
+            return Self::WrongPin { retries };
+        }
+
+        match status {
+            status::FILE_NOT_FOUND | status::REFERENCED_DATA_NOT_FOUND => Self::NotFound,
+            status::AUTHENTICATION_METHOD_BLOCKED => Self::Blocked,
+            _ => Self::Rejected(status),
+        }
+    }
+}
+
+impl<E> From<ObjectError> for PivError<E> {
+    fn from(e: ObjectError) -> Self {
+        Self::Object(e)
+    }
+}
+
+/// The PIV application on a card. Objects are read and keys used through
+/// whatever the transport carries the commands to.
+pub struct Piv<T: ApduTransport> {
+    transport: Iso7816Transport<T>,
+}
+
+impl<T: ApduTransport> Piv<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport: Iso7816Transport::new(transport),
+        }
+    }
+
+    pub fn inner(&self) -> &Iso7816Transport<T> {
+        &self.transport
+    }
+
+    pub fn inner_mut(&mut self) -> &mut Iso7816Transport<T> {
+        &mut self.transport
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport.into_inner()
+    }
+
+    /// Runs an operation that returns nothing but its status.
+    async fn run<O>(&mut self, operation: O) -> Result<(), PivError<T::TransportError>>
+    where
+        O: for<'s> Iso7816StreamingOperation<Result<'s> = Result<(), ApduResponse<'s>>>,
+    {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        Ok(())
+    }
+
+    pub async fn select(&mut self) -> Result<ApplicationProperty, PivError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(
+                Iso7816Select::new(
+                    Iso7816SelectResolution::ByApplicationIdentifier(PIV_AID),
+                    &mut [],
+                ),
+                &mut response,
+            )
+            .await??;
+
+        let (template, _) = BerTlv::next(&response).ok_or(PivError::MalformedResponse)?;
+        let objects = template.children();
+
+        if template.tag() != 0x61 {
+            return Err(PivError::MalformedResponse);
+        }
+
+        Ok(ApplicationProperty {
+            aid: objects
+                .get(0x4F)
+                .ok_or(PivError::MalformedResponse)?
+                .value()
+                .to_vec(),
+            label: objects.get(0x50).map(|label| label.value().to_vec()),
+            algorithms: objects
+                .get(0xAC)
+                .map(|algorithms| {
+                    algorithms
+                        .children()
+                        .filter(|algorithm| algorithm.tag() == 0x80)
+                        .filter_map(|algorithm| algorithm.value().first().copied())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Reads the data object `tag`, as the card returns it.
+    pub async fn read_object(&mut self, tag: u32) -> Result<Vec<u8>, PivError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(GetData::new(tag), &mut response)
+            .await??;
+
+        Ok(response)
+    }
+
+    pub async fn chuid(&mut self) -> Result<Chuid, PivError<T::TransportError>> {
+        let data = self.read_object(tag::CHUID).await?;
+        Chuid::parse(&data).ok_or(PivError::MalformedResponse)
+    }
+
+    pub async fn card_capability_container(
+        &mut self,
+    ) -> Result<CardCapabilityContainer, PivError<T::TransportError>> {
+        let data = self.read_object(tag::CARD_CAPABILITY_CONTAINER).await?;
+        CardCapabilityContainer::parse(&data).ok_or(PivError::MalformedResponse)
+    }
+
+    pub async fn discovery(&mut self) -> Result<Discovery, PivError<T::TransportError>> {
+        let data = self.read_object(tag::DISCOVERY).await?;
+        Discovery::parse(&data).ok_or(PivError::MalformedResponse)
+    }
+
+    pub async fn key_history(&mut self) -> Result<KeyHistory, PivError<T::TransportError>> {
+        let data = self.read_object(tag::KEY_HISTORY).await?;
+        KeyHistory::parse(&data).ok_or(PivError::MalformedResponse)
+    }
+
+    /// The DER certificate of `slot`.
+    pub async fn certificate(
+        &mut self,
+        slot: Slot,
+    ) -> Result<Vec<u8>, PivError<T::TransportError>> {
+        let data = self.read_object(slot.certificate_object()).await?;
+        Ok(object::certificate(&data)?)
+    }
+
+    pub async fn verify(
+        &mut self,
+        reference: KeyReference,
+        pin: &[u8],
+    ) -> Result<(), PivError<T::TransportError>> {
+        let pin = pad_pin(pin).ok_or(PivError::InvalidPin)?;
+        self.run(Verify::new(reference, pin)).await
+    }
+
+    /// The retries left for `reference`, or `None` if it is already
+    /// verified.
+    pub async fn retries(
+        &mut self,
+        reference: KeyReference,
+    ) -> Result<Option<u8>, PivError<T::TransportError>> {
+        match self.run(Verify::status(reference)).await {
+            Ok(()) => Ok(None),
+            Err(PivError::WrongPin { retries }) => Ok(Some(retries)),
+            Err(PivError::Blocked) => Ok(Some(0)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Changes the PIN or the PUK `reference` from `old` to `new`.
+    pub async fn change_reference_data(
+        &mut self,
+        reference: KeyReference,
+        old: &[u8],
+        new: &[u8],
+    ) -> Result<(), PivError<T::TransportError>> {
+        let old = pad_pin(old).ok_or(PivError::InvalidPin)?;
+        let new = pad_pin(new).ok_or(PivError::InvalidPin)?;
+        self.run(ChangeReferenceData::new(reference, old, new))
+            .await
+    }
+
+    /// Unblocks the PIN with the PUK, setting it to `new`.
+    pub async fn reset_retry_counter(
+        &mut self,
+        puk: &[u8],
+        new: &[u8],
+    ) -> Result<(), PivError<T::TransportError>> {
+        let puk = pad_pin(puk).ok_or(PivError::InvalidPin)?;
+        let new = pad_pin(new).ok_or(PivError::InvalidPin)?;
+        self.run(ChangeReferenceData::reset_retry_counter(puk, new))
+            .await
+    }
+
+    /// Runs GENERAL AUTHENTICATE and returns its response: a signature, a
+    /// shared secret or a step of management key authentication.
+    pub async fn general_authenticate(
+        &mut self,
+        operation: GeneralAuthenticate,
+    ) -> Result<Vec<u8>, PivError<T::TransportError>> {
+        let mut response = Vec::new();
+        let result = self
+            .transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        result
+            .map(|result| result.to_vec())
+            .ok_or(PivError::MalformedResponse)
+    }
+
+    /// Signs `input` with the key of `slot`, after the PIN was verified.
+    pub async fn sign(
+        &mut self,
+        algorithm: Algorithm,
+        slot: Slot,
+        input: &[u8],
+    ) -> Result<Vec<u8>, PivError<T::TransportError>> {
+        self.general_authenticate(GeneralAuthenticate::sign(algorithm, slot, input))
+            .await
+    }
+
+    /// Derives the ECDH shared secret of the key in `slot` and the peer's
+    /// public point.
+    pub async fn key_agreement(
+        &mut self,
+        algorithm: Algorithm,
+        slot: Slot,
+        public_point: &[u8],
+    ) -> Result<Vec<u8>, PivError<T::TransportError>> {
+        self.general_authenticate(GeneralAuthenticate::key_agreement(
+            algorithm,
+            slot,
+            public_point,
+        ))
+        .await
+    }
+
+    /// Has the card authentication key sign `challenge`, which proves the
+    /// card genuine without a PIN.
+    pub async fn authenticate_card(
+        &mut self,
+        algorithm: Algorithm,
+        challenge: &[u8],
+    ) -> Result<Vec<u8>, PivError<T::TransportError>> {
+        self.sign(algorithm, Slot::CardAuthentication, challenge)
+            .await
+    }
+
+    /// Authenticates the host with the management key, which key
+    /// generation and import require. The card's witness is decrypted, and
+    /// its answer to `challenge` checked. 3DES uses the first 8 bytes of
+    /// `challenge`; it should be random.
+    #[cfg(feature = "piv-management")]
+    pub async fn authenticate_management_key(
+        &mut self,
+        key: &ManagementKey,
+        challenge: &[u8; 16],
+    ) -> Result<(), PivError<T::TransportError>> {
+        let block_size = key.block_size();
+        let challenge = &challenge[..block_size];
+
+        let mut witness = self
+            .general_authenticate(GeneralAuthenticate::witness(key.algorithm()))
+            .await?;
+
+        if witness.len() != block_size {
+            return Err(PivError::MalformedResponse);
+        }
+
+        key.decrypt(&mut witness);
+
+        let response = self
+            .general_authenticate(GeneralAuthenticate::mutual(
+                key.algorithm(),
+                &witness,
+                challenge,
+            ))
+            .await?;
+
+        let mut expected = challenge.to_vec();
+        key.encrypt(&mut expected);
+
+        match constant_time_eq(&response, &expected) {
+            true => Ok(()),
+            false => Err(PivError::AuthenticationFailed),
+        }
+    }
+
+    /// Generates a key pair in a slot, after management key
+    /// authentication, and returns its public key.
+    pub async fn generate_key_pair(
+        &mut self,
+        operation: GenerateKeyPair,
+    ) -> Result<PublicKey, PivError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(operation, &mut response)
+            .await??;
+
+        PublicKey::parse(&response).ok_or(PivError::MalformedResponse)
+    }
+
+    /// Imports a private key into a slot, after management key
+    /// authentication.
+    pub async fn import_key(
+        &mut self,
+        operation: ImportKey,
+    ) -> Result<(), PivError<T::TransportError>> {
+        self.run(operation).await
+    }
+
+    /// The attestation certificate of the key in `slot`.
+    pub async fn attest(&mut self, slot: Slot) -> Result<Vec<u8>, PivError<T::TransportError>> {
+        let mut response = Vec::new();
+        self.transport
+            .execute_streaming(Attest::new(slot), &mut response)
+            .await??;
+
+        Ok(response)
+    }
+}
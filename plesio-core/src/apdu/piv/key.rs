@@ -0,0 +1,130 @@
+//! The public keys GENERATE ASYMMETRIC KEY PAIR returns and the private
+//! keys and policies of the vendor key import.
+
+use alloc::vec::Vec;
+
+use crate::apdu::iso_7816::tlv::ber::BerTlv;
+
+/// A public key as the card encodes it in the `7F49` template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PublicKey {
+    Rsa {
+        modulus: Vec<u8>,
+        exponent: Vec<u8>,
+    },
+    /// An uncompressed point, `04 || x || y`.
+    Ec {
+        point: Vec<u8>,
+    },
+}
+
+impl PublicKey {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (template, _) = BerTlv::next(data)?;
+
+        if template.tag() != 0x7F49 {
+            return None;
+        }
+
+        let objects = template.children();
+
+        if let Some(point) = objects.get(0x86) {
+            return Some(Self::Ec {
+                point: point.value().to_vec(),
+            });
+        }
+
+        Some(Self::Rsa {
+            modulus: objects.get(0x81)?.value().to_vec(),
+            exponent: objects.get(0x82)?.value().to_vec(),
+        })
+    }
+}
+
+/// A private key for the vendor IMPORT ASYMMETRIC KEY command, as big-endian
+/// integers of the length the algorithm fixes.
+#[derive(Clone, PartialEq, Eq)]
+pub enum PrivateKey {
+    /// The CRT components: the primes, their exponents and the coefficient.
+    Rsa {
+        p: Vec<u8>,
+        q: Vec<u8>,
+        dp: Vec<u8>,
+        dq: Vec<u8>,
+        qinv: Vec<u8>,
+    },
+    Ec {
+        scalar: Vec<u8>,
+    },
+}
+
+impl PrivateKey {
+    pub(crate) fn encode(&self, data: &mut Vec<u8>) {
+        let components: &[(u32, &Vec<u8>)] = match self {
+            Self::Rsa { p, q, dp, dq, qinv } => {
+                &[(0x01, p), (0x02, q), (0x03, dp), (0x04, dq), (0x05, qinv)]
+            }
+            Self::Ec { scalar } => &[(0x06, scalar)],
+        };
+
+        for (tag, value) in components {
+            BerTlv::from(*tag, value)
+                .write(data)
+                .expect("vector sink is unbounded");
+        }
+    }
+}
+
+impl core::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Rsa { p, .. } => f
+                .debug_struct("Rsa")
+                .field("bits", &(p.len() * 16))
+                .finish_non_exhaustive(),
+            Self::Ec { scalar } => f
+                .debug_struct("Ec")
+                .field("bits", &(scalar.len() * 8))
+                .finish_non_exhaustive(),
+        }
+    }
+}
+
+/// When the card asks for the PIN before using a key, a vendor extension
+/// set when the key is generated or imported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinPolicy {
+    Never,
+    Once,
+    Always,
+}
+
+impl PinPolicy {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::Never => 0x01,
+            Self::Once => 0x02,
+            Self::Always => 0x03,
+        }
+    }
+}
+
+/// When the card asks for a touch before using a key, a vendor extension
+/// set when the key is generated or imported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchPolicy {
+    Never,
+    Always,
+    /// A touch is cached for 15 seconds.
+    Cached,
+}
+
+impl TouchPolicy {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::Never => 0x01,
+            Self::Always => 0x02,
+            Self::Cached => 0x03,
+        }
+    }
+}
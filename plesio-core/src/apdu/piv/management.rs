@@ -0,0 +1,91 @@
+//! The PIV card management key, which administrative commands such as key
+//! generation and import require, and the witness and challenge
+//! encryption of its mutual authentication.
+
+use aes::{Aes128, Aes192, Aes256};
+use alloc::vec::Vec;
+use des::{
+    TdesEde3,
+    cipher::{BlockDecrypt, BlockEncrypt, KeyInit, generic_array::GenericArray},
+};
+
+use crate::apdu::piv::Algorithm;
+
+/// The three-key 3DES management key cards ship with.
+pub const DEFAULT_MANAGEMENT_KEY: [u8; 24] = [
+    0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+    0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+];
+
+/// A management key and its algorithm.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ManagementKey {
+    algorithm: Algorithm,
+    key: Vec<u8>,
+}
+
+impl ManagementKey {
+    /// Returns `None` if `algorithm` is not a block cipher or `key` is not
+    /// the length it takes: 24 bytes for 3DES, 16, 24 or 32 for AES.
+    pub fn new(algorithm: Algorithm, key: &[u8]) -> Option<Self> {
+        let length = match algorithm {
+            Algorithm::TripleDes | Algorithm::Aes192 => 24,
+            Algorithm::Aes128 => 16,
+            Algorithm::Aes256 => 32,
+            _ => return None,
+        };
+
+        (key.len() == length).then(|| Self {
+            algorithm,
+            key: key.to_vec(),
+        })
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// The length of the witness and the challenge.
+    pub fn block_size(&self) -> usize {
+        match self.algorithm {
+            Algorithm::TripleDes => 8,
+            _ => 16,
+        }
+    }
+
+    pub(crate) fn encrypt(&self, block: &mut [u8]) {
+        match self.algorithm {
+            Algorithm::TripleDes => encrypt::<TdesEde3>(&self.key, block),
+            Algorithm::Aes128 => encrypt::<Aes128>(&self.key, block),
+            Algorithm::Aes192 => encrypt::<Aes192>(&self.key, block),
+            _ => encrypt::<Aes256>(&self.key, block),
+        }
+    }
+
+    pub(crate) fn decrypt(&self, block: &mut [u8]) {
+        match self.algorithm {
+            Algorithm::TripleDes => decrypt::<TdesEde3>(&self.key, block),
+            Algorithm::Aes128 => decrypt::<Aes128>(&self.key, block),
+            Algorithm::Aes192 => decrypt::<Aes192>(&self.key, block),
+            _ => decrypt::<Aes256>(&self.key, block),
+        }
+    }
+}
+
+impl core::fmt::Debug for ManagementKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ManagementKey")
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+fn encrypt<C: KeyInit + BlockEncrypt>(key: &[u8], block: &mut [u8]) {
+    let cipher = C::new_from_slice(key).expect("management key length");
+    cipher.encrypt_block(GenericArray::from_mut_slice(block));
+}
+
+fn decrypt<C: KeyInit + BlockDecrypt>(key: &[u8], block: &mut [u8]) {
+    let cipher = C::new_from_slice(key).expect("management key length");
+    cipher.decrypt_block(GenericArray::from_mut_slice(block));
+}
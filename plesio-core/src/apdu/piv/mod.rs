@@ -0,0 +1,143 @@
+//! The Personal Identity Verification card application of NIST SP 800-73-4:
+//! its data objects, PIN management and the private key operations of its
+//! key slots.
+
+use alloc::vec::Vec;
+
+pub mod client;
+pub mod key;
+#[cfg(feature = "piv-management")]
+pub mod management;
+pub mod object;
+pub mod operation;
+
+/// The PIV application AID, without the version suffix cards add to it.
+pub const PIV_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x03, 0x08];
+
+/// The key reference of the card management key, which GENERAL
+/// AUTHENTICATE names to authenticate the host.
+pub const MANAGEMENT_KEY: u8 = 0x9B;
+
+/// The key slots and their key references.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    Authentication,
+    Signature,
+    KeyManagement,
+    CardAuthentication,
+    /// The twenty retired key management slots, numbered from 1.
+    Retired(u8),
+}
+
+impl Slot {
+    pub fn from_u8(reference: u8) -> Option<Self> {
+        match reference {
+            0x9A => Some(Self::Authentication),
+            0x9C => Some(Self::Signature),
+            0x9D => Some(Self::KeyManagement),
+            0x9E => Some(Self::CardAuthentication),
+            0x82..=0x95 => Some(Self::Retired(reference - 0x81)),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::Authentication => 0x9A,
+            Self::Signature => 0x9C,
+            Self::KeyManagement => 0x9D,
+            Self::CardAuthentication => 0x9E,
+            Self::Retired(number) => 0x81 + number,
+        }
+    }
+
+    /// The data object holding the slot's certificate.
+    pub fn certificate_object(self) -> u32 {
+        match self {
+            Self::Authentication => object::tag::AUTHENTICATION_CERTIFICATE,
+            Self::Signature => object::tag::SIGNATURE_CERTIFICATE,
+            Self::KeyManagement => object::tag::KEY_MANAGEMENT_CERTIFICATE,
+            Self::CardAuthentication => object::tag::CARD_AUTHENTICATION_CERTIFICATE,
+            Self::Retired(number) => 0x5FC10C + number as u32,
+        }
+    }
+}
+
+/// The cryptographic algorithm identifiers of SP 800-78-4.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    TripleDes,
+    Aes128,
+    Aes192,
+    Aes256,
+    Rsa1024,
+    Rsa2048,
+    EccP256,
+    EccP384,
+}
+
+impl Algorithm {
+    pub fn from_u8(identifier: u8) -> Option<Self> {
+        match identifier {
+            0x03 => Some(Self::TripleDes),
+            0x08 => Some(Self::Aes128),
+            0x0A => Some(Self::Aes192),
+            0x0C => Some(Self::Aes256),
+            0x06 => Some(Self::Rsa1024),
+            0x07 => Some(Self::Rsa2048),
+            0x11 => Some(Self::EccP256),
+            0x14 => Some(Self::EccP384),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::TripleDes => 0x03,
+            Self::Aes128 => 0x08,
+            Self::Aes192 => 0x0A,
+            Self::Aes256 => 0x0C,
+            Self::Rsa1024 => 0x06,
+            Self::Rsa2048 => 0x07,
+            Self::EccP256 => 0x11,
+            Self::EccP384 => 0x14,
+        }
+    }
+}
+
+/// The authenticators VERIFY, CHANGE REFERENCE DATA and RESET RETRY
+/// COUNTER refer to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyReference {
+    GlobalPin,
+    Pin,
+    Puk,
+}
+
+impl KeyReference {
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Self::GlobalPin => 0x00,
+            Self::Pin => 0x80,
+            Self::Puk => 0x81,
+        }
+    }
+}
+
+/// Pads a PIN or PUK to the eight bytes PIV compares, with `FF`. Returns
+/// `None` if it is longer.
+pub fn pad_pin(pin: &[u8]) -> Option<[u8; 8]> {
+    let mut padded = [0xFF; 8];
+    padded.get_mut(..pin.len())?.copy_from_slice(pin);
+    Some(padded)
+}
+
+/// The application property template SELECT returns.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct ApplicationProperty {
+    /// The full AID, with the version the card implements.
+    pub aid: Vec<u8>,
+    pub label: Option<Vec<u8>>,
+    /// The algorithm identifiers the card supports, if it lists them.
+    pub algorithms: Vec<u8>,
+}
@@ -0,0 +1,202 @@
+//! The data objects of the PIV application and their content.
+
+use alloc::vec::Vec;
+
+use crate::apdu::iso_7816::tlv::ber::{BerTlv, BerTlvIterator};
+
+/// The tags GET DATA takes in its `5C` tag list.
+pub mod tag {
+    pub const CARD_CAPABILITY_CONTAINER: u32 = 0x5FC107;
+    pub const CHUID: u32 = 0x5FC102;
+    pub const AUTHENTICATION_CERTIFICATE: u32 = 0x5FC105;
+    pub const SIGNATURE_CERTIFICATE: u32 = 0x5FC10A;
+    pub const KEY_MANAGEMENT_CERTIFICATE: u32 = 0x5FC10B;
+    pub const CARD_AUTHENTICATION_CERTIFICATE: u32 = 0x5FC101;
+    pub const PRINTED_INFORMATION: u32 = 0x5FC109;
+    pub const KEY_HISTORY: u32 = 0x5FC10C;
+    pub const DISCOVERY: u32 = 0x7E;
+}
+
+#[derive(Debug)]
+pub enum ObjectError {
+    Malformed,
+    /// The certificate is gzip compressed and the `gzip` feature is off.
+    Compressed,
+    #[cfg(feature = "gzip")]
+    Decompression(std::io::Error),
+}
+
+impl core::fmt::Display for ObjectError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "malformed PIV data object"),
+            Self::Compressed => write!(f, "certificate is compressed"),
+            #[cfg(feature = "gzip")]
+            Self::Decompression(error) => write!(f, "decompressing certificate failed: {error}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ObjectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Self::Decompression(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// The value of the `53` container every data object but the discovery
+/// object is wrapped in.
+pub fn container(data: &[u8]) -> Option<BerTlvIterator<'_>> {
+    let (object, _) = BerTlv::next(data)?;
+    (object.tag() == 0x53).then(|| object.children())
+}
+
+fn value(objects: BerTlvIterator<'_>, tag: u32) -> Option<Vec<u8>> {
+    objects.get(tag).map(|object| object.value().to_vec())
+}
+
+/// The card holder unique identifier.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Chuid {
+    /// The FASC-N, the agency, system and credential numbers in BCD.
+    pub fasc_n: Vec<u8>,
+    pub guid: Option<Vec<u8>>,
+    /// The expiration date as `YYYYMMDD` digits.
+    pub expiration: Option<Vec<u8>>,
+    pub cardholder_uuid: Option<Vec<u8>>,
+    /// The CMS signature of the issuer over the other fields.
+    pub signature: Option<Vec<u8>>,
+}
+
+impl Chuid {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let objects = container(data)?;
+
+        Some(Self {
+            fasc_n: value(objects, 0x30)?,
+            guid: value(objects, 0x34),
+            expiration: value(objects, 0x35),
+            cardholder_uuid: value(objects, 0x36),
+            signature: value(objects, 0x3E),
+        })
+    }
+}
+
+/// The card capability container, which legacy middleware reads to learn
+/// the card's data model.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct CardCapabilityContainer {
+    pub card_identifier: Vec<u8>,
+    pub container_version: Option<u8>,
+    pub grammar_version: Option<u8>,
+    pub data_model: Option<u8>,
+}
+
+impl CardCapabilityContainer {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let objects = container(data)?;
+        let byte = |tag| objects.get(tag)?.value().first().copied();
+
+        Some(Self {
+            card_identifier: value(objects, 0xF0)?,
+            container_version: byte(0xF1),
+            grammar_version: byte(0xF2),
+            data_model: byte(0xF7),
+        })
+    }
+}
+
+/// The discovery object: the PIV AID and which PINs satisfy the card's
+/// access rules.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Discovery {
+    pub aid: Vec<u8>,
+    pub pin_usage_policy: [u8; 2],
+}
+
+impl Discovery {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let (object, _) = BerTlv::next(data)?;
+
+        if object.tag() != 0x7E {
+            return None;
+        }
+
+        let policy = object.children().get(0x5F2F)?.value();
+
+        Some(Self {
+            aid: value(object.children(), 0x4F)?,
+            pin_usage_policy: *policy.first_chunk()?,
+        })
+    }
+
+    /// Whether the global PIN satisfies the PIV access rules.
+    pub fn accepts_global_pin(&self) -> bool {
+        self.pin_usage_policy[0] & 0x20 != 0
+    }
+
+    /// Whether the global PIN is the one to present, rather than the PIV
+    /// application PIN.
+    pub fn prefers_global_pin(&self) -> bool {
+        self.accepts_global_pin() && self.pin_usage_policy[1] == 0x20
+    }
+}
+
+/// How many retired key management keys the card holds, and where the
+/// certificates of those not on the card can be found.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct KeyHistory {
+    pub on_card_certificates: u8,
+    pub off_card_certificates: u8,
+    pub off_card_url: Option<Vec<u8>>,
+}
+
+impl KeyHistory {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let objects = container(data)?;
+        let byte = |tag| objects.get(tag)?.value().first().copied();
+
+        Some(Self {
+            on_card_certificates: byte(0xC1)?,
+            off_card_certificates: byte(0xC2)?,
+            off_card_url: value(objects, 0xF3).filter(|url| !url.is_empty()),
+        })
+    }
+}
+
+/// Extracts the DER certificate from a certificate object, decompressing it
+/// if the certificate info byte says it is gzipped.
+pub fn certificate(data: &[u8]) -> Result<Vec<u8>, ObjectError> {
+    let objects = container(data).ok_or(ObjectError::Malformed)?;
+    let certificate = objects.get(0x70).ok_or(ObjectError::Malformed)?.value();
+    let info = objects
+        .get(0x71)
+        .and_then(|info| info.value().first().copied())
+        .unwrap_or(0x00);
+
+    if info & 0x01 == 0 {
+        return Ok(certificate.to_vec());
+    }
+
+    decompress(certificate)
+}
+
+#[cfg(feature = "gzip")]
+fn decompress(data: &[u8]) -> Result<Vec<u8>, ObjectError> {
+    use std::io::Read;
+
+    let mut certificate = Vec::new();
+    flate2::read::GzDecoder::new(data)
+        .read_to_end(&mut certificate)
+        .map_err(ObjectError::Decompression)?;
+    Ok(certificate)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn decompress(_data: &[u8]) -> Result<Vec<u8>, ObjectError> {
+    Err(ObjectError::Compressed)
+}
@@ -0,0 +1,376 @@
+//! The PIV card commands, sent with the interindustry class.
+
+use alloc::vec::Vec;
+
+use crate::apdu::{
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{Iso7816Command, Iso7816StreamingOperation},
+        status,
+        tlv::ber::BerTlv,
+    },
+    piv::{
+        Algorithm, KeyReference, MANAGEMENT_KEY, Slot,
+        key::{PinPolicy, PrivateKey, TouchPolicy},
+    },
+    response::ApduResponse,
+    status::is,
+};
+
+fn write(object: BerTlv<'_>, data: &mut Vec<u8>) {
+    object.write(data).expect("vector sink is unbounded");
+}
+
+/// GET DATA of one data object, named by its tag in a `5C` tag list.
+pub struct GetData {
+    data: Vec<u8>,
+}
+
+impl GetData {
+    pub fn new(tag: u32) -> Self {
+        let bytes = tag.to_be_bytes();
+        let start = bytes.iter().position(|&byte| byte != 0).unwrap_or(3);
+
+        let mut data = Vec::new();
+        write(BerTlv::from(0x5C, &bytes[start..]), &mut data);
+        Self { data }
+    }
+}
+
+impl Iso7816StreamingOperation for GetData {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, 0xCB, (0x3F, 0xFF), &self.data)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// VERIFY of a PIN or PUK, or without one, a query of the retries left.
+pub struct Verify {
+    reference: KeyReference,
+    pin: Option<[u8; 8]>,
+}
+
+impl Verify {
+    /// `pin` is padded as [`super::pad_pin`] does it.
+    pub fn new(reference: KeyReference, pin: [u8; 8]) -> Self {
+        Self {
+            reference,
+            pin: Some(pin),
+        }
+    }
+
+    /// Asks for the retries left, or `9000` if the PIN is already verified.
+    pub fn status(reference: KeyReference) -> Self {
+        Self {
+            reference,
+            pin: None,
+        }
+    }
+}
+
+impl Iso7816StreamingOperation for Verify {
+    type Result<'s> = Result<(), ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        let pin = self.pin.as_ref().map_or(&[][..], |pin| &pin[..]);
+        Iso7816Command::new(class, 0x20, (0x00, self.reference.to_u8()), pin)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(|_| ())
+    }
+}
+
+/// CHANGE REFERENCE DATA of a PIN or the PUK, or with the PUK, RESET
+/// RETRY COUNTER of the PIN.
+pub struct ChangeReferenceData {
+    instruction: u8,
+    reference: KeyReference,
+    data: [u8; 16],
+}
+
+impl ChangeReferenceData {
+    pub fn new(reference: KeyReference, old: [u8; 8], new: [u8; 8]) -> Self {
+        Self::with_instruction(0x24, reference, old, new)
+    }
+
+    /// Unblocks the PIN and sets it to `new`.
+    pub fn reset_retry_counter(puk: [u8; 8], new: [u8; 8]) -> Self {
+        Self::with_instruction(0x2C, KeyReference::Pin, puk, new)
+    }
+
+    fn with_instruction(
+        instruction: u8,
+        reference: KeyReference,
+        old: [u8; 8],
+        new: [u8; 8],
+    ) -> Self {
+        let mut data = [0; 16];
+        data[..8].copy_from_slice(&old);
+        data[8..].copy_from_slice(&new);
+
+        Self {
+            instruction,
+            reference,
+            data,
+        }
+    }
+}
+
+impl Iso7816StreamingOperation for ChangeReferenceData {
+    type Result<'s> = Result<(), ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(
+            class,
+            self.instruction,
+            (0x00, self.reference.to_u8()),
+            &self.data,
+        )
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(|_| ())
+    }
+}
+
+/// GENERAL AUTHENTICATE with the private key of a slot, or a step of the
+/// mutual authentication with the management key. The card answers with a
+/// `7C` template whose `82` response is the result, or `80` for the
+/// witness.
+pub struct GeneralAuthenticate {
+    algorithm: Algorithm,
+    reference: u8,
+    data: Vec<u8>,
+    response: u32,
+}
+
+impl GeneralAuthenticate {
+    fn new(algorithm: Algorithm, reference: u8, objects: &[(u32, &[u8])], response: u32) -> Self {
+        let mut template = Vec::new();
+        for &(tag, value) in objects {
+            write(BerTlv::from(tag, value), &mut template);
+        }
+
+        let mut data = Vec::new();
+        write(BerTlv::from(0x7C, &template), &mut data);
+
+        Self {
+            algorithm,
+            reference,
+            data,
+            response,
+        }
+    }
+
+    /// Signs `input`, which for RSA is the padded DigestInfo and for ECDSA
+    /// the digest, or answers the challenge of card authentication.
+    pub fn sign(algorithm: Algorithm, slot: Slot, input: &[u8]) -> Self {
+        Self::new(algorithm, slot.to_u8(), &[(0x82, &[]), (0x81, input)], 0x82)
+    }
+
+    /// Computes the ECDH shared secret with the peer's uncompressed
+    /// public point.
+    pub fn key_agreement(algorithm: Algorithm, slot: Slot, public_point: &[u8]) -> Self {
+        Self::new(
+            algorithm,
+            slot.to_u8(),
+            &[(0x82, &[]), (0x85, public_point)],
+            0x82,
+        )
+    }
+
+    /// Asks for the witness of management key authentication: a random
+    /// block the card encrypts under the key.
+    pub fn witness(algorithm: Algorithm) -> Self {
+        Self::new(algorithm, MANAGEMENT_KEY, &[(0x80, &[])], 0x80)
+    }
+
+    /// Returns the decrypted witness with the host's challenge, which the
+    /// card answers encrypted.
+    pub fn mutual(algorithm: Algorithm, witness: &[u8], challenge: &[u8]) -> Self {
+        Self::new(
+            algorithm,
+            MANAGEMENT_KEY,
+            &[(0x80, witness), (0x81, challenge), (0x82, &[])],
+            0x82,
+        )
+    }
+}
+
+impl Iso7816StreamingOperation for GeneralAuthenticate {
+    type Result<'s> = Result<Option<&'s [u8]>, ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(
+            class,
+            0x87,
+            (self.algorithm.to_u8(), self.reference),
+            &self.data,
+        )
+    }
+
+    /// Returns `None` if the template has no response.
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        let data = response.expect_status(is(status::OK))?;
+
+        Ok(BerTlv::next(data)
+            .filter(|(template, _)| template.tag() == 0x7C)
+            .and_then(|(template, _)| template.children().get(self.response))
+            .map(|result| result.value()))
+    }
+}
+
+/// Appends the vendor policy objects `AA` and `AB`, if set.
+fn write_policies(pin: Option<PinPolicy>, touch: Option<TouchPolicy>, data: &mut Vec<u8>) {
+    if let Some(pin) = pin {
+        write(BerTlv::from(0xAA, &[pin.to_u8()]), data);
+    }
+
+    if let Some(touch) = touch {
+        write(BerTlv::from(0xAB, &[touch.to_u8()]), data);
+    }
+}
+
+/// GENERATE ASYMMETRIC KEY PAIR in a slot, with the algorithm in an `AC`
+/// control reference template. The card answers with the public key in a
+/// `7F49` template, which [`PublicKey::parse`](super::key::PublicKey::parse)
+/// reads.
+#[derive(Clone, Debug)]
+pub struct GenerateKeyPair {
+    slot: Slot,
+    algorithm: Algorithm,
+    pin_policy: Option<PinPolicy>,
+    touch_policy: Option<TouchPolicy>,
+    data: Vec<u8>,
+}
+
+impl GenerateKeyPair {
+    pub fn new(slot: Slot, algorithm: Algorithm) -> Self {
+        let mut generate = Self {
+            slot,
+            algorithm,
+            pin_policy: None,
+            touch_policy: None,
+            data: Vec::new(),
+        };
+        generate.encode();
+        generate
+    }
+
+    /// Sets the vendor PIN policy of the key.
+    pub fn with_pin_policy(mut self, policy: PinPolicy) -> Self {
+        self.pin_policy = Some(policy);
+        self.encode();
+        self
+    }
+
+    /// Sets the vendor touch policy of the key.
+    pub fn with_touch_policy(mut self, policy: TouchPolicy) -> Self {
+        self.touch_policy = Some(policy);
+        self.encode();
+        self
+    }
+
+    fn encode(&mut self) {
+        let mut template = Vec::new();
+        write(BerTlv::from(0x80, &[self.algorithm.to_u8()]), &mut template);
+        write_policies(self.pin_policy, self.touch_policy, &mut template);
+
+        self.data.clear();
+        write(BerTlv::from(0xAC, &template), &mut self.data);
+    }
+}
+
+impl Iso7816StreamingOperation for GenerateKeyPair {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, 0x47, (0x00, self.slot.to_u8()), &self.data)
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
+
+/// The vendor IMPORT ASYMMETRIC KEY of YubiKey-compatible cards, which
+/// writes a private key into a slot.
+#[derive(Clone, Debug)]
+pub struct ImportKey {
+    slot: Slot,
+    algorithm: Algorithm,
+    data: Vec<u8>,
+}
+
+impl ImportKey {
+    pub fn new(slot: Slot, algorithm: Algorithm, key: &PrivateKey) -> Self {
+        let mut data = Vec::new();
+        key.encode(&mut data);
+
+        Self {
+            slot,
+            algorithm,
+            data,
+        }
+    }
+
+    /// Sets the vendor PIN policy of the key.
+    pub fn with_pin_policy(mut self, policy: PinPolicy) -> Self {
+        write_policies(Some(policy), None, &mut self.data);
+        self
+    }
+
+    /// Sets the vendor touch policy of the key.
+    pub fn with_touch_policy(mut self, policy: TouchPolicy) -> Self {
+        write_policies(None, Some(policy), &mut self.data);
+        self
+    }
+}
+
+impl Iso7816StreamingOperation for ImportKey {
+    type Result<'s> = Result<(), ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(
+            class,
+            0xFE,
+            (self.algorithm.to_u8(), self.slot.to_u8()),
+            &self.data,
+        )
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK)).map(|_| ())
+    }
+}
+
+/// The vendor ATTEST of YubiKey-compatible cards, which returns a DER
+/// certificate for the key of a slot, signed by the card's attestation key
+/// to show it was generated on the card.
+pub struct Attest {
+    slot: Slot,
+}
+
+impl Attest {
+    pub fn new(slot: Slot) -> Self {
+        Self { slot }
+    }
+}
+
+impl Iso7816StreamingOperation for Attest {
+    type Result<'s> = Result<&'s [u8], ApduResponse<'s>>;
+
+    fn command(&self, class: Iso7816Class) -> Iso7816Command<'_> {
+        Iso7816Command::new(class, 0xF9, (self.slot.to_u8(), 0x00), &[])
+    }
+
+    fn parse<'s>(self, response: &ApduResponse<'s>) -> Self::Result<'s> {
+        response.expect_status(is(status::OK))
+    }
+}
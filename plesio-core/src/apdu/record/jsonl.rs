@@ -0,0 +1,151 @@
+//! Recordings as JSON lines: a header line followed by one line per
+//! exchange, with bytes in uppercase hex and times in microseconds.
+//!
+//! ```text
+//! {"format":"plesio-apdu","version":1,"max_payload_size":255}
+//! {"command":"00A4040007A0000000041010","response":"9000","started_us":0,"elapsed_us":1830}
+//! {"command":"00B0000000","payload_too_large":255,"started_us":1912,"elapsed_us":4}
+//! ```
+
+use std::{
+    format,
+    io::{BufRead, Write},
+    string::String,
+    time::Duration,
+    vec::Vec,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::apdu::{
+    hex::Hex,
+    record::{FormatError, RecordedError, RecordedExchange, Recording},
+};
+
+const FORMAT: &str = "plesio-apdu";
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+    max_payload_size: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payload_too_large: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    started_us: u64,
+    elapsed_us: u64,
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+impl Recording {
+    /// Writes the recording as JSON lines.
+    pub fn write_jsonl(&self, mut writer: impl Write) -> std::io::Result<()> {
+        let header = Header {
+            format: FORMAT.into(),
+            version: VERSION,
+            max_payload_size: self.max_payload_size,
+        };
+        serde_json::to_writer(&mut writer, &header)?;
+        writer.write_all(b"\n")?;
+
+        for exchange in &self.exchanges {
+            let (response, payload_too_large, error) = match &exchange.reply {
+                Ok(response) => (Some(format!("{}", Hex(response))), None, None),
+                Err(RecordedError::PayloadTooLarge { max_size }) => (None, Some(*max_size), None),
+                Err(RecordedError::Other(error)) => (None, None, Some(error.clone())),
+            };
+
+            let line = Line {
+                command: format!("{}", Hex(&exchange.command)),
+                response,
+                payload_too_large,
+                error,
+                started_us: exchange.started.as_micros() as u64,
+                elapsed_us: exchange.elapsed.as_micros() as u64,
+            };
+            serde_json::to_writer(&mut writer, &line)?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()
+    }
+
+    /// Reads a recording written by [`Self::write_jsonl`]. Blank lines are
+    /// skipped; positions in errors are one-based line numbers.
+    pub fn read_jsonl(reader: impl BufRead) -> Result<Self, FormatError> {
+        let mut lines = reader
+            .lines()
+            .enumerate()
+            .map(|(index, line)| line.map(|line| (index + 1, line)))
+            .filter(|line| !matches!(line, Ok((_, line)) if line.trim().is_empty()));
+
+        let (position, header) = lines
+            .next()
+            .ok_or(FormatError::malformed(1, "missing header"))??;
+        let header: Header = serde_json::from_str(&header)
+            .map_err(|error| FormatError::malformed(position, error))?;
+
+        if header.format != FORMAT || header.version != VERSION {
+            return Err(FormatError::malformed(
+                position,
+                format!(
+                    "unsupported format {} version {}",
+                    header.format, header.version
+                ),
+            ));
+        }
+
+        let mut recording = Recording {
+            max_payload_size: header.max_payload_size,
+            exchanges: Vec::new(),
+        };
+
+        for line in lines {
+            let (position, line) = line?;
+            let line: Line = serde_json::from_str(&line)
+                .map_err(|error| FormatError::malformed(position, error))?;
+            let hex =
+                |hex: &str| parse_hex(hex).ok_or(FormatError::malformed(position, "invalid hex"));
+
+            let reply = match (line.response, line.payload_too_large, line.error) {
+                (Some(response), None, None) => Ok(hex(&response)?),
+                (None, Some(max_size), None) => Err(RecordedError::PayloadTooLarge { max_size }),
+                (None, None, Some(error)) => Err(RecordedError::Other(error)),
+                _ => {
+                    return Err(FormatError::malformed(
+                        position,
+                        "expected exactly one of response, payload_too_large and error",
+                    ));
+                }
+            };
+
+            recording.exchanges.push(RecordedExchange {
+                command: hex(&line.command)?,
+                reply,
+                started: Duration::from_micros(line.started_us),
+                elapsed: Duration::from_micros(line.elapsed_us),
+            });
+        }
+
+        Ok(recording)
+    }
+}
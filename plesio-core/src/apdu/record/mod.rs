@@ -0,0 +1,356 @@
+use std::{
+    string::{String, ToString},
+    time::{Duration, Instant},
+    vec,
+    vec::Vec,
+};
+
+use crate::apdu::{
+    command::ApduCommand,
+    encoding,
+    hex::Hex,
+    response::ApduResponse,
+    transport::{ApduTransport, PayloadTooLarge, TransportError},
+};
+
+pub mod jsonl;
+pub mod pcapng;
+
+/// Why a saved recording could not be read.
+#[derive(Debug)]
+pub enum FormatError {
+    Io(std::io::Error),
+    /// The entry at `position`, a line or block number depending on the
+    /// format, is invalid.
+    Malformed {
+        position: usize,
+        reason: String,
+    },
+}
+
+impl FormatError {
+    pub(crate) fn malformed(position: usize, reason: impl ToString) -> Self {
+        Self::Malformed {
+            position,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl core::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "reading recording failed: {error}"),
+            Self::Malformed { position, reason } => {
+                write!(f, "malformed recording entry {position}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FormatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Malformed { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for FormatError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// How a recorded exchange failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordedError {
+    PayloadTooLarge {
+        max_size: usize,
+    },
+    /// Any other transport error, as its debug representation.
+    Other(String),
+}
+
+/// One command sent during a recording and what came back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedExchange {
+    /// The encoded command, including Lc and Le.
+    pub command: Vec<u8>,
+    /// The raw response including status bytes, or the transport error.
+    pub reply: Result<Vec<u8>, RecordedError>,
+    /// When the command was sent, relative to the start of the recording.
+    pub started: Duration,
+    pub elapsed: Duration,
+}
+
+/// A recorded card session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    /// The maximum payload size the recorded transport reported.
+    pub max_payload_size: usize,
+    pub exchanges: Vec<RecordedExchange>,
+}
+
+/// An [`ApduTransport`] that records every exchange of the transport it
+/// wraps, to be saved and played back by a [`ReplayTransport`].
+pub struct RecordingTransport<T> {
+    inner: T,
+    recording: Recording,
+    origin: Instant,
+}
+
+impl<T: ApduTransport> RecordingTransport<T> {
+    pub fn new(inner: T) -> Self {
+        let max_payload_size = inner.max_payload_size();
+
+        Self {
+            inner,
+            recording: Recording {
+                max_payload_size,
+                exchanges: Vec::new(),
+            },
+            origin: Instant::now(),
+        }
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_parts(self) -> (T, Recording) {
+        (self.inner, self.recording)
+    }
+}
+
+impl<T: ApduTransport> ApduTransport for RecordingTransport<T> {
+    type TransportError = T::TransportError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, Self::TransportError>> {
+        let expected_length = encoding::expected_length(reply_buffer.len());
+        let mut encoded = encoding::encoded_length(&command, expected_length)
+            .map(|length| vec![0u8; length])
+            .unwrap_or_default();
+        encoding::encode(&command, expected_length, &mut encoded);
+
+        async move {
+            let started = self.origin.elapsed();
+            let result = self.inner.execute(command, reply_buffer).await;
+            let elapsed = self.origin.elapsed() - started;
+
+            let reply = match &result {
+                Ok(response) => {
+                    let status = response.status();
+                    let mut reply = response.data().to_vec();
+                    reply.extend_from_slice(&[status.code1(), status.code2()]);
+                    Ok(reply)
+                }
+                Err(error) => Err(match error.is_payload_too_large() {
+                    Some(PayloadTooLarge { max_size }) => {
+                        RecordedError::PayloadTooLarge { max_size }
+                    }
+                    None => RecordedError::Other(std::format!("{error:?}")),
+                }),
+            };
+
+            self.recording.exchanges.push(RecordedExchange {
+                command: encoded,
+                reply,
+                started,
+                elapsed,
+            });
+
+            result
+        }
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.inner.max_payload_size()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    PayloadTooLarge {
+        max_size: usize,
+    },
+    /// The recorded transport failed with this error.
+    Recorded(String),
+    /// The command differs from the one recorded at `index`.
+    Mismatch {
+        index: usize,
+        expected: Vec<u8>,
+        received: Vec<u8>,
+    },
+    /// Every recorded exchange has been played back.
+    Exhausted,
+    /// The recorded response does not fit in the reply buffer.
+    ReplyBufferTooSmall,
+}
+
+impl core::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::PayloadTooLarge { max_size } => {
+                write!(
+                    f,
+                    "payload exceeds the recorded maximum of {max_size} bytes"
+                )
+            }
+            Self::Recorded(error) => write!(f, "recorded transport error: {error}"),
+            Self::Mismatch {
+                index,
+                expected,
+                received,
+            } => write!(
+                f,
+                "exchange {index} expected command {} but received {}",
+                Hex(expected),
+                Hex(received),
+            ),
+            Self::Exhausted => write!(f, "no recorded exchanges left"),
+            Self::ReplyBufferTooSmall => {
+                write!(f, "recorded response does not fit the reply buffer")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl TransportError for ReplayError {
+    fn is_payload_too_large(&self) -> Option<PayloadTooLarge> {
+        match *self {
+            Self::PayloadTooLarge { max_size } => Some(PayloadTooLarge { max_size }),
+            _ => None,
+        }
+    }
+}
+
+/// An [`ApduTransport`] that plays back a [`Recording`], checking that
+/// commands arrive in the recorded order.
+///
+/// Commands whose data is volatile, such as EXTERNAL AUTHENTICATE with a
+/// cryptogram over a random challenge, can be matched on their header and
+/// Le alone with [`Self::ignore_data`].
+pub struct ReplayTransport {
+    recording: Recording,
+    position: usize,
+    ignored_data: Vec<u8>,
+}
+
+impl ReplayTransport {
+    pub fn new(recording: Recording) -> Self {
+        Self {
+            recording,
+            position: 0,
+            ignored_data: Vec::new(),
+        }
+    }
+
+    /// Ignores the data of commands with this instruction when matching
+    /// them against the recording.
+    pub fn ignore_data(mut self, instruction: u8) -> Self {
+        self.ignored_data.push(instruction);
+        self
+    }
+
+    /// The number of exchanges played back so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position == self.recording.exchanges.len()
+    }
+
+    fn matches(&self, expected: &[u8], received: &[u8]) -> bool {
+        if expected == received {
+            return true;
+        }
+
+        match (encoding::decode(expected), encoding::decode(received)) {
+            (Some(expected), Some(received)) => {
+                self.ignored_data.contains(&expected.header[1])
+                    && expected.header == received.header
+                    && expected.expected_length == received.expected_length
+            }
+            _ => false,
+        }
+    }
+
+    fn replay<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, ReplayError> {
+        let expected_length = encoding::expected_length(reply_buffer.len());
+        let mut received = encoding::encoded_length(&command, expected_length)
+            .map(|length| vec![0u8; length])
+            .unwrap_or_default();
+        encoding::encode(&command, expected_length, &mut received);
+
+        let index = self.position;
+        let exchange = self
+            .recording
+            .exchanges
+            .get(index)
+            .ok_or(ReplayError::Exhausted)?;
+
+        if !self.matches(&exchange.command, &received) {
+            return Err(ReplayError::Mismatch {
+                index,
+                expected: exchange.command.clone(),
+                received,
+            });
+        }
+
+        self.position += 1;
+
+        match &exchange.reply {
+            Ok(reply) => {
+                let buffer = reply_buffer
+                    .get_mut(..reply.len())
+                    .ok_or(ReplayError::ReplyBufferTooSmall)?;
+                buffer.copy_from_slice(reply);
+
+                ApduResponse::parse(buffer).ok_or(ReplayError::Recorded(
+                    "response without status bytes".to_string(),
+                ))
+            }
+            Err(RecordedError::PayloadTooLarge { max_size }) => Err(ReplayError::PayloadTooLarge {
+                max_size: *max_size,
+            }),
+            Err(RecordedError::Other(error)) => Err(ReplayError::Recorded(error.clone())),
+        }
+    }
+}
+
+impl ApduTransport for ReplayTransport {
+    type TransportError = ReplayError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, Self::TransportError>> {
+        core::future::ready(self.replay(command, reply_buffer))
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.recording.max_payload_size
+    }
+}
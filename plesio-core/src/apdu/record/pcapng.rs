@@ -0,0 +1,482 @@
+//! Recordings as pcapng captures with the ISO 7816 link type, which
+//! Wireshark's ISO 7816 and GSM SIM dissectors decode.
+//!
+//! Each exchange is a command packet marked outbound, to the card, followed
+//! by a response packet marked inbound. Timestamps are in microseconds from
+//! the start of the recording. What the link type cannot carry is kept in
+//! comments: the maximum payload size on the interface, and transport
+//! errors on the command packet of an exchange without a response.
+//!
+//! Captures written by other tools are read as long as they use the ISO
+//! 7816 link type; packets on other interfaces are skipped, as are
+//! responses without a command, such as an ATR.
+
+use std::{
+    format,
+    io::{Read, Write},
+    string::{String, ToString},
+    time::Duration,
+    vec,
+    vec::Vec,
+};
+
+use crate::apdu::record::{FormatError, RecordedError, RecordedExchange, Recording};
+
+/// `LINKTYPE_ISO_7816`.
+const LINKTYPE_ISO_7816: u16 = 264;
+
+const SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPTION_END: u16 = 0;
+const OPTION_COMMENT: u16 = 1;
+const OPTION_SHB_USER_APPLICATION: u16 = 4;
+const OPTION_IF_TIMESTAMP_RESOLUTION: u16 = 9;
+const OPTION_EPB_FLAGS: u16 = 2;
+
+const FLAGS_INBOUND: u32 = 0b01;
+const FLAGS_OUTBOUND: u32 = 0b10;
+
+const MAX_PAYLOAD_SIZE_KEY: &str = "plesio.max_payload_size=";
+const PAYLOAD_TOO_LARGE_KEY: &str = "plesio.payload_too_large=";
+const ERROR_KEY: &str = "plesio.error=";
+
+/// Used for captures that do not record the maximum payload size: the
+/// largest short APDU.
+const DEFAULT_MAX_PAYLOAD_SIZE: usize = 255;
+
+/// Blocks larger than this are rejected rather than allocated.
+const MAX_BLOCK_LENGTH: usize = 1 << 20;
+
+fn padded(length: usize) -> usize {
+    length.next_multiple_of(4)
+}
+
+/// A block being built, written little-endian.
+struct BlockWriter {
+    kind: u32,
+    body: Vec<u8>,
+    has_options: bool,
+}
+
+impl BlockWriter {
+    fn new(kind: u32) -> Self {
+        Self {
+            kind,
+            body: Vec::new(),
+            has_options: false,
+        }
+    }
+
+    fn u16(&mut self, value: u16) -> &mut Self {
+        self.body.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.body.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn padded_bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.body.extend_from_slice(bytes);
+        self.body.resize(padded(self.body.len()), 0);
+        self
+    }
+
+    fn option(&mut self, code: u16, value: &[u8]) -> &mut Self {
+        self.has_options = true;
+        self.u16(code).u16(value.len() as u16).padded_bytes(value)
+    }
+
+    fn write(&mut self, writer: &mut impl Write) -> std::io::Result<()> {
+        if self.has_options {
+            self.u16(OPTION_END).u16(0);
+        }
+
+        let length = (self.body.len() + 12) as u32;
+        writer.write_all(&self.kind.to_le_bytes())?;
+        writer.write_all(&length.to_le_bytes())?;
+        writer.write_all(&self.body)?;
+        writer.write_all(&length.to_le_bytes())
+    }
+}
+
+fn write_packet(
+    writer: &mut impl Write,
+    data: &[u8],
+    timestamp: Duration,
+    flags: u32,
+    comment: Option<&str>,
+) -> std::io::Result<()> {
+    let timestamp = timestamp.as_micros() as u64;
+    let mut block = BlockWriter::new(ENHANCED_PACKET);
+
+    block
+        .u32(0)
+        .u32((timestamp >> 32) as u32)
+        .u32(timestamp as u32)
+        .u32(data.len() as u32)
+        .u32(data.len() as u32)
+        .padded_bytes(data)
+        .option(OPTION_EPB_FLAGS, &flags.to_le_bytes());
+
+    if let Some(comment) = comment {
+        block.option(OPTION_COMMENT, comment.as_bytes());
+    }
+
+    block.write(writer)
+}
+
+/// The fields of a block body, in the byte order of its section.
+struct Fields<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Fields<'a> {
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(..length)?;
+        self.data = self.data.get(padded(length)..).unwrap_or_default();
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.data.get(..2)?.try_into().ok()?;
+        self.data = &self.data[2..];
+        Some(match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.data.get(..4)?.try_into().ok()?;
+        self.data = &self.data[4..];
+        Some(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+
+    /// Reads the options that end a block, as code and value.
+    fn options(mut self) -> Option<Vec<(u16, &'a [u8])>> {
+        let mut options = Vec::new();
+
+        while !self.data.is_empty() {
+            let code = self.u16()?;
+            let length = self.u16()? as usize;
+
+            if code == OPTION_END {
+                break;
+            }
+
+            options.push((code, self.bytes(length)?));
+        }
+
+        Some(options)
+    }
+}
+
+fn comments<'a>(options: &[(u16, &'a [u8])]) -> impl Iterator<Item = &'a str> {
+    options
+        .iter()
+        .filter(|(code, _)| *code == OPTION_COMMENT)
+        .filter_map(|(_, value)| core::str::from_utf8(value).ok())
+}
+
+struct Interface {
+    iso_7816: bool,
+    /// Timestamp units per second.
+    resolution: u64,
+}
+
+impl Interface {
+    fn timestamp(&self, units: u64) -> Duration {
+        let seconds = units / self.resolution;
+        let nanos = (units % self.resolution) as u128 * 1_000_000_000 / self.resolution as u128;
+        Duration::new(seconds, nanos as u32)
+    }
+}
+
+fn resolution(value: &[u8]) -> Option<u64> {
+    let exponent = *value.first()?;
+
+    match exponent & 0x80 {
+        0 => 10u64.checked_pow(exponent as u32),
+        _ => 1u64.checked_shl((exponent & 0x7F) as u32),
+    }
+}
+
+impl Recording {
+    /// Writes the recording as a pcapng capture.
+    pub fn write_pcapng(&self, mut writer: impl Write) -> std::io::Result<()> {
+        BlockWriter::new(SECTION_HEADER)
+            .u32(BYTE_ORDER_MAGIC)
+            .u16(1)
+            .u16(0)
+            .u32(u32::MAX)
+            .u32(u32::MAX)
+            .option(OPTION_SHB_USER_APPLICATION, b"plesio")
+            .write(&mut writer)?;
+
+        BlockWriter::new(INTERFACE_DESCRIPTION)
+            .u16(LINKTYPE_ISO_7816)
+            .u16(0)
+            .u32(0)
+            .option(
+                OPTION_COMMENT,
+                format!("{MAX_PAYLOAD_SIZE_KEY}{}", self.max_payload_size).as_bytes(),
+            )
+            .write(&mut writer)?;
+
+        for exchange in &self.exchanges {
+            let comment = match &exchange.reply {
+                Ok(_) => None,
+                Err(RecordedError::PayloadTooLarge { max_size }) => {
+                    Some(format!("{PAYLOAD_TOO_LARGE_KEY}{max_size}"))
+                }
+                Err(RecordedError::Other(error)) => Some(format!("{ERROR_KEY}{error}")),
+            };
+
+            write_packet(
+                &mut writer,
+                &exchange.command,
+                exchange.started,
+                FLAGS_OUTBOUND,
+                comment.as_deref(),
+            )?;
+
+            if let Ok(response) = &exchange.reply {
+                write_packet(
+                    &mut writer,
+                    response,
+                    exchange.started + exchange.elapsed,
+                    FLAGS_INBOUND,
+                    None,
+                )?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Reads a pcapng capture with the ISO 7816 link type. Positions in
+    /// errors are one-based block numbers.
+    ///
+    /// Packets without a direction flag are taken to alternate between
+    /// command and response.
+    pub fn read_pcapng(mut reader: impl Read) -> Result<Self, FormatError> {
+        let mut recording = Recording {
+            max_payload_size: DEFAULT_MAX_PAYLOAD_SIZE,
+            exchanges: Vec::new(),
+        };
+        let mut interfaces = Vec::new();
+        let mut pending: Option<RecordedExchange> = None;
+        let mut big_endian = None;
+        let mut position = 0;
+
+        loop {
+            position += 1;
+
+            let mut head = [0u8; 8];
+            match reader.read(&mut head[..1])? {
+                0 => break,
+                _ => reader.read_exact(&mut head[1..])?,
+            }
+
+            let kind = u32::from_le_bytes(head[..4].try_into().unwrap());
+
+            if kind == SECTION_HEADER {
+                let mut magic = [0u8; 4];
+                reader.read_exact(&mut magic)?;
+                big_endian = match u32::from_le_bytes(magic) {
+                    BYTE_ORDER_MAGIC => Some(false),
+                    _ if u32::from_be_bytes(magic) == BYTE_ORDER_MAGIC => Some(true),
+                    _ => return Err(FormatError::malformed(position, "invalid byte-order magic")),
+                };
+                interfaces.clear();
+            }
+
+            let Some(big_endian) = big_endian else {
+                return Err(FormatError::malformed(position, "missing section header"));
+            };
+
+            let mut fields = Fields {
+                data: &head,
+                big_endian,
+            };
+            let kind = fields.u32().unwrap();
+            let length = fields.u32().unwrap() as usize;
+
+            let minimum = match kind {
+                SECTION_HEADER => 28,
+                _ => 12,
+            };
+
+            if length < minimum || !length.is_multiple_of(4) || length > MAX_BLOCK_LENGTH {
+                return Err(FormatError::malformed(
+                    position,
+                    format!("invalid block length {length}"),
+                ));
+            }
+
+            let mut block = vec![0u8; length - 8];
+            let body = match kind {
+                SECTION_HEADER => {
+                    reader.read_exact(&mut block[4..])?;
+                    &block[4..block.len() - 4]
+                }
+                _ => {
+                    reader.read_exact(&mut block)?;
+                    &block[..block.len() - 4]
+                }
+            };
+            let fields = Fields {
+                data: body,
+                big_endian,
+            };
+
+            let malformed =
+                |what: &str| FormatError::malformed(position, format!("truncated {what}"));
+
+            match kind {
+                INTERFACE_DESCRIPTION => {
+                    let interface = read_interface(fields, &mut recording)
+                        .ok_or_else(|| malformed("interface description"))?;
+                    interfaces.push(interface);
+                }
+                ENHANCED_PACKET => {
+                    let packet = read_packet(fields, &interfaces)
+                        .ok_or_else(|| malformed("enhanced packet"))?;
+                    let Some(packet) = packet else { continue };
+
+                    let response = match packet.flags & 0b11 {
+                        FLAGS_INBOUND => true,
+                        FLAGS_OUTBOUND => false,
+                        _ => pending.is_some(),
+                    };
+
+                    if response {
+                        // Responses without a command, such as an ATR, are
+                        // not exchanges.
+                        if let Some(mut exchange) = pending.take() {
+                            exchange.elapsed = packet.timestamp.saturating_sub(exchange.started);
+                            exchange.reply = Ok(packet.data);
+                            recording.exchanges.push(exchange);
+                        }
+                        continue;
+                    }
+
+                    if let Some(exchange) = pending.take() {
+                        recording.exchanges.push(exchange);
+                    }
+
+                    let exchange = RecordedExchange {
+                        command: packet.data,
+                        reply: Err(RecordedError::Other("no response captured".to_string())),
+                        started: packet.timestamp,
+                        elapsed: Duration::ZERO,
+                    };
+
+                    match packet.error {
+                        Some(error) => recording.exchanges.push(RecordedExchange {
+                            reply: Err(error),
+                            ..exchange
+                        }),
+                        None => pending = Some(exchange),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        recording.exchanges.extend(pending);
+        Ok(recording)
+    }
+}
+
+fn read_interface(mut fields: Fields<'_>, recording: &mut Recording) -> Option<Interface> {
+    let link_type = fields.u16()?;
+    fields.u16()?;
+    fields.u32()?;
+
+    let options = fields.options()?;
+    let mut interface = Interface {
+        iso_7816: link_type == LINKTYPE_ISO_7816,
+        resolution: 1_000_000,
+    };
+
+    for (code, value) in &options {
+        if *code == OPTION_IF_TIMESTAMP_RESOLUTION {
+            interface.resolution = resolution(value)?;
+        }
+    }
+
+    if interface.iso_7816 {
+        let max_payload_size = comments(&options)
+            .find_map(|comment| comment.strip_prefix(MAX_PAYLOAD_SIZE_KEY)?.parse().ok());
+
+        if let Some(max_payload_size) = max_payload_size {
+            recording.max_payload_size = max_payload_size;
+        }
+    }
+
+    Some(interface)
+}
+
+struct Packet {
+    data: Vec<u8>,
+    timestamp: Duration,
+    flags: u32,
+    error: Option<RecordedError>,
+}
+
+/// Reads an enhanced packet block, or `None` within it if it belongs to an
+/// interface with another link type.
+fn read_packet(mut fields: Fields<'_>, interfaces: &[Interface]) -> Option<Option<Packet>> {
+    let big_endian = fields.big_endian;
+    let interface = interfaces.get(fields.u32()? as usize)?;
+    let timestamp = (fields.u32()? as u64) << 32 | fields.u32()? as u64;
+    let captured_length = fields.u32()? as usize;
+    fields.u32()?;
+    let data = fields.bytes(captured_length)?.to_vec();
+
+    if !interface.iso_7816 {
+        return Some(None);
+    }
+
+    let options = fields.options()?;
+    let flags = options
+        .iter()
+        .find(|(code, _)| *code == OPTION_EPB_FLAGS)
+        .and_then(|(_, value)| {
+            Fields {
+                data: value,
+                big_endian,
+            }
+            .u32()
+        })
+        .unwrap_or(0);
+
+    let error = comments(&options).find_map(|comment| {
+        if let Some(max_size) = comment.strip_prefix(PAYLOAD_TOO_LARGE_KEY) {
+            return Some(RecordedError::PayloadTooLarge {
+                max_size: max_size.parse().ok()?,
+            });
+        }
+
+        Some(RecordedError::Other(String::from(
+            comment.strip_prefix(ERROR_KEY)?,
+        )))
+    });
+
+    Some(Some(Packet {
+        data,
+        timestamp: interface.timestamp(timestamp),
+        flags,
+        error,
+    }))
+}
@@ -1,6 +1,6 @@
 use crate::apdu::status::ApduStatus;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ApduResponse<'a> {
     data: &'a [u8],
     status: ApduStatus,
@@ -13,8 +13,12 @@ impl ApduResponse<'static> {
 }
 
 impl<'a> ApduResponse<'a> {
+    pub fn new(data: &'a [u8], status: ApduStatus) -> Self {
+        Self { data, status }
+    }
+
     pub fn parse(data: &'a [u8]) -> Option<Self> {
-        data.split_at_checked(data.len() - 2)
+        data.split_at_checked(data.len().checked_sub(2)?)
             .map(|(data, s)| ApduResponse {
                 data,
                 status: ApduStatus::new(s[0], s[1]),
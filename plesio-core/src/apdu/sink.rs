@@ -0,0 +1,96 @@
+/// Returned by a [`ResponseSink`] that cannot hold any more data.
+#[derive(Debug, Clone, Copy)]
+pub struct SinkFull {
+    pub capacity: usize,
+}
+
+impl core::fmt::Display for SinkFull {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "response sink is full (capacity {} bytes)",
+            self.capacity
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SinkFull {}
+
+/// Storage that response data is appended to as it arrives.
+///
+/// Unlike a caller-provided reply buffer, a sink does not need to know the
+/// total response size up front: data delivered over several GET RESPONSE
+/// rounds is streamed into it one round at a time.
+pub trait ResponseSink {
+    /// Appends `data` to the sink. Nothing is written if it does not fit.
+    fn extend(&mut self, data: &[u8]) -> Result<(), SinkFull>;
+
+    /// All data written to the sink so far.
+    fn data(&self) -> &[u8];
+}
+
+/// A [`ResponseSink`] writing into a borrowed, fixed-size slice.
+pub struct SliceSink<'a> {
+    buffer: &'a mut [u8],
+    length: usize,
+}
+
+impl<'a> SliceSink<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, length: 0 }
+    }
+
+    pub fn into_data(self) -> &'a [u8] {
+        &self.buffer[..self.length]
+    }
+}
+
+impl<'a> From<&'a mut [u8]> for SliceSink<'a> {
+    fn from(buffer: &'a mut [u8]) -> Self {
+        Self::new(buffer)
+    }
+}
+
+impl ResponseSink for SliceSink<'_> {
+    fn extend(&mut self, data: &[u8]) -> Result<(), SinkFull> {
+        let end = self.length + data.len();
+
+        if end > self.buffer.len() {
+            return Err(SinkFull {
+                capacity: self.buffer.len(),
+            });
+        }
+
+        self.buffer[self.length..end].copy_from_slice(data);
+        self.length = end;
+        Ok(())
+    }
+
+    fn data(&self) -> &[u8] {
+        &self.buffer[..self.length]
+    }
+}
+
+impl<const N: usize> ResponseSink for heapless::Vec<u8, N> {
+    fn extend(&mut self, data: &[u8]) -> Result<(), SinkFull> {
+        self.extend_from_slice(data)
+            .map_err(|_| SinkFull { capacity: N })
+    }
+
+    fn data(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ResponseSink for alloc::vec::Vec<u8> {
+    fn extend(&mut self, data: &[u8]) -> Result<(), SinkFull> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn data(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
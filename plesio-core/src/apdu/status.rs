@@ -1,4 +1,4 @@
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ApduStatus(u8, u8);
 
 impl ApduStatus {
@@ -33,6 +33,12 @@ impl ApduStatus {
     }
 }
 
+impl core::fmt::Display for ApduStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:04X}", self.as_u16())
+    }
+}
+
 pub fn is(status: ApduStatus) -> impl Fn(&ApduStatus) -> Option<()> {
     move |other| (other.as_u16() == status.as_u16()).then_some(())
 }
@@ -0,0 +1,245 @@
+use core::{fmt, time::Duration};
+
+use crate::apdu::{
+    hex::Hex,
+    iso_7816::{
+        class::{Iso7816Class, SecureMessaging},
+        status,
+        tlv::ber::BerTlv,
+    },
+    response::ApduResponse,
+};
+
+/// The name of an interindustry instruction.
+pub fn instruction_name(instruction: u8) -> Option<&'static str> {
+    let name = match instruction {
+        0x04 => "DEACTIVATE FILE",
+        0x0C => "ERASE RECORD",
+        0x0E | 0x0F => "ERASE BINARY",
+        0x20 | 0x21 => "VERIFY",
+        0x22 => "MANAGE SECURITY ENVIRONMENT",
+        0x24 => "CHANGE REFERENCE DATA",
+        0x26 => "DISABLE VERIFICATION REQUIREMENT",
+        0x28 => "ENABLE VERIFICATION REQUIREMENT",
+        0x2A => "PERFORM SECURITY OPERATION",
+        0x2C => "RESET RETRY COUNTER",
+        0x44 => "ACTIVATE FILE",
+        0x46 => "GENERATE ASYMMETRIC KEY PAIR",
+        0x70 => "MANAGE CHANNEL",
+        0x82 => "EXTERNAL AUTHENTICATE",
+        0x84 => "GET CHALLENGE",
+        0x86 | 0x87 => "GENERAL AUTHENTICATE",
+        0x88 => "INTERNAL AUTHENTICATE",
+        0xA0 | 0xA1 => "SEARCH BINARY",
+        0xA2 => "SEARCH RECORD",
+        0xA4 => "SELECT",
+        0xB0 | 0xB1 => "READ BINARY",
+        0xB2 | 0xB3 => "READ RECORD",
+        0xC0 => "GET RESPONSE",
+        0xC2 | 0xC3 => "ENVELOPE",
+        0xCA | 0xCB => "GET DATA",
+        0xD0 | 0xD1 => "WRITE BINARY",
+        0xD2 => "WRITE RECORD",
+        0xD6 | 0xD7 => "UPDATE BINARY",
+        0xDA | 0xDB => "PUT DATA",
+        0xDC | 0xDD => "UPDATE RECORD",
+        0xE0 => "CREATE FILE",
+        0xE2 => "APPEND RECORD",
+        0xE4 => "DELETE FILE",
+        0xE6 => "TERMINATE DF",
+        0xE8 => "TERMINATE EF",
+        0xFE => "TERMINATE CARD USAGE",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// What P1 and P2 select for the interindustry instructions where they are
+/// more than a reference or offset.
+pub fn parameters_meaning(instruction: u8, (p1, _p2): (u8, u8)) -> Option<&'static str> {
+    let meaning = match (instruction, p1) {
+        (0xA4, 0x00) => "MF, DF or EF by file identifier",
+        (0xA4, 0x01) => "child DF",
+        (0xA4, 0x02) => "EF under the current DF",
+        (0xA4, 0x03) => "parent DF",
+        (0xA4, 0x04) => "by DF name",
+        (0xA4, 0x08) => "path from the MF",
+        (0xA4, 0x09) => "path from the current DF",
+        (0xB0 | 0xD6, 0x80..) => "short EF identifier",
+        (0xB0 | 0xD6, _) => "offset",
+        (0x20, 0xFF) => "reset verification status",
+        (0x24, 0x00) => "current and new reference data",
+        (0x24, 0x01) => "new reference data only",
+        (0x2C, 0x00) => "resetting code and new reference data",
+        (0x2C, 0x01) => "resetting code only",
+        (0x2C, 0x02) => "new reference data only",
+        (0x2C, 0x03) => "no data",
+        (0x70, 0x00) => "open",
+        (0x70, 0x80) => "close",
+        _ => return None,
+    };
+
+    Some(meaning)
+}
+
+/// Describes a class byte: its logical channel, chaining and secure
+/// messaging.
+pub struct ClassSummary(pub u8);
+
+impl fmt::Display for ClassSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let proprietary = self.0 >= 0x80;
+
+        // Proprietary classes are assumed to follow the interindustry
+        // layout, as GlobalPlatform does.
+        let Some(class) = Iso7816Class::from_u8(self.0 & 0x7F) else {
+            return f.write_str("invalid class");
+        };
+
+        if proprietary {
+            f.write_str("proprietary, ")?;
+        }
+
+        write!(f, "channel {}", class.channel().number())?;
+
+        if class.is_chaining() {
+            f.write_str(", chaining")?;
+        }
+
+        match class.secure_messaging() {
+            SecureMessaging::None => Ok(()),
+            SecureMessaging::Authenticated => f.write_str(", SM"),
+            SecureMessaging::HeaderAuthenticated => f.write_str(", SM with header"),
+        }
+    }
+}
+
+/// Formats data that parses as a sequence of BER-TLV data objects as an
+/// indented tree, one object per line, descending into constructed ones.
+/// Anything else formats as nothing, leaving the hex dump beside it.
+pub struct TlvDump<'a>(pub &'a [u8]);
+
+impl TlvDump<'_> {
+    /// Whether the data is a non-empty sequence of BER-TLV data objects.
+    pub fn is_tlv(&self) -> bool {
+        !self.0.is_empty() && parses_fully(self.0)
+    }
+}
+
+fn parses_fully(mut data: &[u8]) -> bool {
+    while !data.is_empty() {
+        match BerTlv::next(data) {
+            Some((_, rest)) => data = rest,
+            None => return false,
+        }
+    }
+
+    true
+}
+
+fn write_tlvs(f: &mut fmt::Formatter<'_>, mut data: &[u8], depth: usize) -> fmt::Result {
+    while let Some((object, rest)) = BerTlv::next(data) {
+        let value = object.value();
+        write!(
+            f,
+            "\n{:indent$}{:02X}",
+            "",
+            object.tag(),
+            indent = depth * 2
+        )?;
+
+        if object.is_constructed() && !value.is_empty() && parses_fully(value) {
+            write_tlvs(f, value, depth + 1)?;
+        } else {
+            write!(f, " {}", Hex(value))?;
+        }
+
+        data = rest;
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for TlvDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_tlv() {
+            write_tlvs(f, self.0, 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// One line describing a command, e.g.
+/// `00A40400 SELECT (by DF name) [channel 0] Lc=7 A0000000031010 Le=256`.
+pub struct CommandTrace<'a> {
+    pub header: [u8; 4],
+    pub data: &'a [u8],
+    pub expected_length: usize,
+    /// Replaces the data with its length.
+    pub redacted: bool,
+}
+
+impl fmt::Display for CommandTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [class, instruction, p1, p2] = self.header;
+
+        write!(f, "{}", Hex(&self.header))?;
+
+        if class < 0x80 {
+            if let Some(name) = instruction_name(instruction) {
+                write!(f, " {name}")?;
+            }
+
+            if let Some(meaning) = parameters_meaning(instruction, (p1, p2)) {
+                write!(f, " ({meaning})")?;
+            }
+        }
+
+        write!(f, " [{}]", ClassSummary(class))?;
+
+        if !self.data.is_empty() {
+            write!(f, " Lc={}", self.data.len())?;
+
+            if self.redacted {
+                f.write_str(" <redacted>")?;
+            } else {
+                write!(f, " {}", Hex(self.data))?;
+            }
+        }
+
+        if self.expected_length != 0 {
+            write!(f, " Le={}", self.expected_length)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// One line describing a response, e.g. `6A82 file or application not
+/// found, 0 bytes in 1.2ms`.
+pub struct ResponseTrace<'a> {
+    pub response: &'a ApduResponse<'a>,
+    pub elapsed: Option<Duration>,
+}
+
+impl fmt::Display for ResponseTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = self.response.status();
+
+        write!(f, "{status}")?;
+
+        if let Some(description) = status::describe(&status) {
+            write!(f, " {description}")?;
+        }
+
+        write!(f, ", {} bytes", self.response.data().len())?;
+
+        if let Some(elapsed) = self.elapsed {
+            write!(f, " in {elapsed:.1?}")?;
+        }
+
+        Ok(())
+    }
+}
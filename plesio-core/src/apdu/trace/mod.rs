@@ -0,0 +1,140 @@
+use crate::apdu::{
+    class::ApduClass, command::ApduCommand, encoding, hex::Hex, response::ApduResponse,
+    transport::ApduTransport,
+};
+
+pub mod describe;
+
+use describe::{CommandTrace, ResponseTrace, TlvDump};
+
+/// Logs to whichever of the `log` and `tracing` facades are enabled, under
+/// the `plesio::apdu` target.
+macro_rules! emit {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::$level!(target: "plesio::apdu", $($arg)+);
+        #[cfg(feature = "tracing")]
+        ::tracing::$level!(target: "plesio::apdu", $($arg)+);
+    }};
+}
+
+/// Instructions whose data carries PINs or other reference data, and PIV
+/// IMPORT ASYMMETRIC KEY.
+const SECRET_INSTRUCTIONS: [u8; 5] = [0x20, 0x21, 0x24, 0x2C, 0xFE];
+
+/// Data objects whose PUT DATA carries a secret: the OpenPGP secure
+/// messaging keys, resetting code and AES key.
+const SECRET_DATA_OBJECTS: [u16; 4] = [0xD1, 0xD2, 0xD3, 0xD5];
+
+/// Whether the data of the command with `header` carries a secret.
+fn is_secret(header: [u8; 4], data: &[u8]) -> bool {
+    let [_, instruction, p1, p2] = header;
+
+    match instruction {
+        0xDA | 0xDB => match u16::from_be_bytes([p1, p2]) {
+            // An OpenPGP key import, an extended header list.
+            0x3FFF => data.first() == Some(&0x4D),
+            tag => SECRET_DATA_OBJECTS.contains(&tag),
+        },
+        instruction => SECRET_INSTRUCTIONS.contains(&instruction),
+    }
+}
+
+/// An [`ApduTransport`] that logs every exchange of the transport it wraps.
+///
+/// Commands and responses are logged at debug level with decoded class,
+/// instruction and status; response data, dumped as TLV where it parses as
+/// such, at trace level; and transport errors at warn level. Exchanges are
+/// timed when the `std` feature is enabled.
+///
+/// The data of VERIFY, CHANGE REFERENCE DATA, RESET RETRY COUNTER, key
+/// imports and PUT DATA of secret data objects is redacted unless disabled
+/// with [`Self::with_redaction`].
+pub struct TracingTransport<T> {
+    inner: T,
+    redact_secrets: bool,
+}
+
+impl<T: ApduTransport> TracingTransport<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            redact_secrets: true,
+        }
+    }
+
+    pub fn with_redaction(mut self, redact_secrets: bool) -> Self {
+        self.redact_secrets = redact_secrets;
+        self
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ApduTransport> ApduTransport for TracingTransport<T> {
+    type TransportError = T::TransportError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, Self::TransportError>> {
+        let (p1, p2) = command.parameters();
+        let header = [command.class().to_u8(), command.instruction(), p1, p2];
+
+        emit!(
+            debug,
+            "> {}",
+            CommandTrace {
+                header,
+                data: command.data(),
+                expected_length: encoding::expected_length(reply_buffer.len()),
+                redacted: self.redact_secrets && is_secret(header, command.data()),
+            }
+        );
+
+        async move {
+            #[cfg(feature = "std")]
+            let started = std::time::Instant::now();
+
+            let result = self.inner.execute(command, reply_buffer).await;
+
+            #[cfg(feature = "std")]
+            let elapsed = Some(started.elapsed());
+            #[cfg(not(feature = "std"))]
+            let elapsed = None;
+
+            match &result {
+                Ok(response) => {
+                    emit!(debug, "< {}", ResponseTrace { response, elapsed });
+
+                    if !response.data().is_empty() {
+                        emit!(
+                            trace,
+                            "< {}{}",
+                            Hex(response.data()),
+                            TlvDump(response.data())
+                        );
+                    }
+                }
+                Err(error) => emit!(warn, "< transport error {error:?} after {elapsed:?}"),
+            }
+
+            result
+        }
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.inner.max_payload_size()
+    }
+}
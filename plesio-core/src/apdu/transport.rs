@@ -5,6 +5,19 @@ pub struct PayloadTooLarge {
     pub max_size: usize,
 }
 
+impl core::fmt::Display for PayloadTooLarge {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "payload exceeds the transport maximum of {} bytes",
+            self.max_size
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PayloadTooLarge {}
+
 pub trait TransportError: core::fmt::Debug {
     /// If the error is due to the payload size being too large,
     /// returns the maximum allowed payload size for this transport.
@@ -16,6 +29,10 @@ pub trait TransportError: core::fmt::Debug {
 pub trait ApduTransport {
     type TransportError: TransportError;
 
+    /// Sends `command` and writes the response data followed by the two
+    /// status bytes to the start of `reply_buffer`.
+    ///
+    /// The expected response length is `reply_buffer.len() - 2`.
     fn execute<'r>(
         &mut self,
         command: impl ApduCommand,
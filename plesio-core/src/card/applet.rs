@@ -0,0 +1,24 @@
+use alloc::vec::Vec;
+
+use crate::{
+    apdu::{iso_7816::status, status::ApduStatus},
+    card::command::CardCommand,
+};
+
+/// Card-side application logic, selected by AID.
+pub trait Applet {
+    /// Called when the applet is selected. Data written to `response` is
+    /// returned as the SELECT response; a status other than `9000` rejects
+    /// the selection.
+    fn select(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let _ = (command, response);
+        status::OK
+    }
+
+    /// Called when another applet is selected on the same channel, the
+    /// channel is closed or the card is reset.
+    fn deselect(&mut self) {}
+
+    /// Processes a command addressed to the applet while it is selected.
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus;
+}
@@ -0,0 +1,122 @@
+use crate::apdu::{
+    class::ApduClass,
+    command::ApduCommand,
+    encoding,
+    iso_7816::{channel::Iso7816Channel, class::Iso7816Class},
+};
+
+/// A command as seen by the card: the header, the command data and the
+/// number of response bytes the terminal asked for (Ne).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardCommand<'a> {
+    class: u8,
+    instruction: u8,
+    parameters: (u8, u8),
+    data: &'a [u8],
+    expected_length: usize,
+}
+
+impl<'a> CardCommand<'a> {
+    pub fn new(
+        class: u8,
+        instruction: u8,
+        parameters: (u8, u8),
+        data: &'a [u8],
+        expected_length: usize,
+    ) -> Self {
+        Self {
+            class,
+            instruction,
+            parameters,
+            data,
+            expected_length,
+        }
+    }
+
+    pub fn from_command(command: &'a impl ApduCommand, expected_length: usize) -> Self {
+        Self::new(
+            command.class().to_u8(),
+            command.instruction(),
+            command.parameters(),
+            command.data(),
+            expected_length,
+        )
+    }
+
+    /// Parses an encoded command APDU, or returns `None` if it is malformed.
+    pub fn parse(encoded: &'a [u8]) -> Option<Self> {
+        let decoded = encoding::decode(encoded)?;
+        let [class, instruction, p1, p2] = decoded.header;
+
+        Some(Self::new(
+            class,
+            instruction,
+            (p1, p2),
+            decoded.data,
+            decoded.expected_length,
+        ))
+    }
+
+    /// The raw class byte.
+    pub fn class_byte(&self) -> u8 {
+        self.class
+    }
+
+    /// The class, if it is an interindustry one.
+    pub fn class(&self) -> Option<Iso7816Class> {
+        Iso7816Class::from_u8(self.class)
+    }
+
+    pub fn is_proprietary(&self) -> bool {
+        self.class >= 0x80
+    }
+
+    /// The logical channel the command was sent on.
+    ///
+    /// Proprietary classes are assumed to encode the channel the same way
+    /// as their interindustry counterpart, as GlobalPlatform does.
+    pub fn channel(&self) -> Option<Iso7816Channel> {
+        let mut class = self.class & 0x7F;
+
+        // Secure messaging does not change the channel, and GlobalPlatform
+        // indicates its secure channel with b3 alone, which Iso7816Class
+        // does not accept.
+        if class & 0x40 == 0 {
+            class &= !0x0C;
+        }
+
+        Iso7816Class::from_u8(class).map(|class| class.channel())
+    }
+
+    pub fn is_chaining(&self) -> bool {
+        self.class & 0x10 != 0 && self.class != 0xFF
+    }
+
+    pub fn instruction(&self) -> u8 {
+        self.instruction
+    }
+
+    pub fn parameters(&self) -> (u8, u8) {
+        self.parameters
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn expected_length(&self) -> usize {
+        self.expected_length
+    }
+
+    /// The same command carrying different data, e.g. once a chain has been
+    /// reassembled.
+    pub fn with_data<'b>(&self, data: &'b [u8]) -> CardCommand<'b> {
+        CardCommand {
+            class: self.class & !0x10,
+            instruction: self.instruction,
+            parameters: self.parameters,
+            data,
+            expected_length: self.expected_length,
+        }
+    }
+}
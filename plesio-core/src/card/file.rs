@@ -0,0 +1,236 @@
+use alloc::vec::Vec;
+
+use crate::apdu::iso_7816::tlv::TaggedSlice;
+
+/// Identifies a file within a [`FileSystem`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileHandle(usize);
+
+/// Who may access an elementary file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccessCondition {
+    #[default]
+    Always,
+    Never,
+    /// Allowed once the PIN object with this reference has been verified.
+    Pin(u8),
+}
+
+#[derive(Debug, Clone)]
+enum FileKind {
+    Dedicated {
+        name: Option<Vec<u8>>,
+    },
+    Elementary {
+        data: Vec<u8>,
+        read: AccessCondition,
+        update: AccessCondition,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct FileNode {
+    file_id: u16,
+    parent: Option<FileHandle>,
+    kind: FileKind,
+}
+
+/// A tree of dedicated (DF) and transparent elementary (EF) files rooted at
+/// the master file.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    nodes: Vec<FileNode>,
+}
+
+impl Default for FileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem {
+    pub const MASTER_FILE_ID: u16 = 0x3F00;
+
+    /// A file system containing only the master file.
+    pub fn new() -> Self {
+        Self {
+            nodes: alloc::vec![FileNode {
+                file_id: Self::MASTER_FILE_ID,
+                parent: None,
+                kind: FileKind::Dedicated { name: None },
+            }],
+        }
+    }
+
+    pub fn master_file(&self) -> FileHandle {
+        FileHandle(0)
+    }
+
+    /// Adds a DF under `parent`, optionally with a DF name (AID).
+    ///
+    /// Panics if `parent` is not a DF.
+    pub fn add_dedicated(
+        &mut self,
+        parent: FileHandle,
+        file_id: u16,
+        name: Option<&[u8]>,
+    ) -> FileHandle {
+        self.add(
+            parent,
+            file_id,
+            FileKind::Dedicated {
+                name: name.map(<[u8]>::to_vec),
+            },
+        )
+    }
+
+    /// Adds a transparent EF under `parent`, readable and updatable by anyone.
+    ///
+    /// Panics if `parent` is not a DF, or if `data` is longer than the
+    /// 65535 bytes the two byte file size in its FCP can describe.
+    pub fn add_elementary(&mut self, parent: FileHandle, file_id: u16, data: &[u8]) -> FileHandle {
+        assert!(
+            u16::try_from(data.len()).is_ok(),
+            "transparent EF larger than 65535 bytes"
+        );
+
+        self.add(
+            parent,
+            file_id,
+            FileKind::Elementary {
+                data: data.to_vec(),
+                read: AccessCondition::Always,
+                update: AccessCondition::Always,
+            },
+        )
+    }
+
+    fn add(&mut self, parent: FileHandle, file_id: u16, kind: FileKind) -> FileHandle {
+        assert!(self.is_dedicated(parent), "files can only be added to a DF");
+
+        self.nodes.push(FileNode {
+            file_id,
+            parent: Some(parent),
+            kind,
+        });
+
+        FileHandle(self.nodes.len() - 1)
+    }
+
+    /// Sets the access conditions of an EF. Does nothing for a DF.
+    pub fn set_access(&mut self, file: FileHandle, read: AccessCondition, update: AccessCondition) {
+        if let FileKind::Elementary {
+            read: file_read,
+            update: file_update,
+            ..
+        } = &mut self.nodes[file.0].kind
+        {
+            *file_read = read;
+            *file_update = update;
+        }
+    }
+
+    pub fn file_id(&self, file: FileHandle) -> u16 {
+        self.nodes[file.0].file_id
+    }
+
+    pub fn parent(&self, file: FileHandle) -> Option<FileHandle> {
+        self.nodes[file.0].parent
+    }
+
+    pub fn is_dedicated(&self, file: FileHandle) -> bool {
+        matches!(self.nodes[file.0].kind, FileKind::Dedicated { .. })
+    }
+
+    pub fn name(&self, file: FileHandle) -> Option<&[u8]> {
+        match &self.nodes[file.0].kind {
+            FileKind::Dedicated { name } => name.as_deref(),
+            FileKind::Elementary { .. } => None,
+        }
+    }
+
+    /// The contents of an EF, or `None` for a DF.
+    pub fn data(&self, file: FileHandle) -> Option<&[u8]> {
+        match &self.nodes[file.0].kind {
+            FileKind::Elementary { data, .. } => Some(data),
+            FileKind::Dedicated { .. } => None,
+        }
+    }
+
+    pub fn data_mut(&mut self, file: FileHandle) -> Option<&mut [u8]> {
+        match &mut self.nodes[file.0].kind {
+            FileKind::Elementary { data, .. } => Some(data),
+            FileKind::Dedicated { .. } => None,
+        }
+    }
+
+    /// The read and update conditions of an EF.
+    pub fn access(&self, file: FileHandle) -> Option<(AccessCondition, AccessCondition)> {
+        match &self.nodes[file.0].kind {
+            FileKind::Elementary { read, update, .. } => Some((*read, *update)),
+            FileKind::Dedicated { .. } => None,
+        }
+    }
+
+    pub fn children(&self, parent: FileHandle) -> impl Iterator<Item = FileHandle> + '_ {
+        (0..self.nodes.len())
+            .map(FileHandle)
+            .filter(move |&file| self.nodes[file.0].parent == Some(parent))
+    }
+
+    pub fn child(&self, parent: FileHandle, file_id: u16) -> Option<FileHandle> {
+        self.children(parent)
+            .find(|&file| self.file_id(file) == file_id)
+    }
+
+    /// Finds an EF under `parent` by short EF identifier, which is taken to
+    /// be the low five bits of its file ID.
+    pub fn child_by_short_id(&self, parent: FileHandle, short_id: u8) -> Option<FileHandle> {
+        self.children(parent)
+            .find(|&file| !self.is_dedicated(file) && self.file_id(file) & 0x1F == short_id as u16)
+    }
+
+    /// Finds a DF whose name starts with `name`.
+    pub fn find_by_name(&self, name: &[u8]) -> Option<FileHandle> {
+        (0..self.nodes.len()).map(FileHandle).find(|&file| {
+            self.name(file)
+                .is_some_and(|df_name| df_name.starts_with(name))
+        })
+    }
+
+    /// The file control parameters template (tag 62) of `file`.
+    pub fn fcp(&self, file: FileHandle) -> Vec<u8> {
+        let node = &self.nodes[file.0];
+        let mut content = Vec::new();
+
+        match &node.kind {
+            FileKind::Dedicated { name } => {
+                push_tlv(&mut content, 0x82, &[0x38]);
+                push_tlv(&mut content, 0x83, &node.file_id.to_be_bytes());
+
+                if let Some(name) = name {
+                    push_tlv(&mut content, 0x84, name);
+                }
+            }
+            FileKind::Elementary { data, .. } => {
+                // Sizes are checked when the file is added.
+                let size = u16::try_from(data.len()).unwrap();
+                push_tlv(&mut content, 0x80, &size.to_be_bytes());
+                push_tlv(&mut content, 0x82, &[0x01]);
+                push_tlv(&mut content, 0x83, &node.file_id.to_be_bytes());
+            }
+        }
+
+        // Life cycle status: operational, activated.
+        push_tlv(&mut content, 0x8A, &[0x05]);
+
+        let mut fcp = Vec::with_capacity(content.len() + 2);
+        push_tlv(&mut fcp, 0x62, &content);
+        fcp
+    }
+}
+
+/// Appends a TLV to a `Vec`, which always has room for it.
+fn push_tlv(out: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    TaggedSlice::from(tag, value).write(out).unwrap();
+}
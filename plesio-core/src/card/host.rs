@@ -0,0 +1,157 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{
+    apdu::{iso_7816::status, status::ApduStatus},
+    card::{
+        applet::Applet,
+        command::CardCommand,
+        response::{ChainAssembler, Chained, ResponseSplitter},
+        virtual_card::DEFAULT_ATR,
+    },
+};
+
+/// A card simulated in software that exchanges encoded APDUs, as a reader
+/// emulator or relay sees it.
+pub trait SimulatedCard {
+    fn atr(&self) -> &[u8];
+
+    /// Processes an encoded command APDU and returns the encoded response,
+    /// including the status bytes.
+    ///
+    /// Malformed commands are answered with `6700`.
+    fn transmit(&mut self, command: &[u8]) -> Vec<u8>;
+
+    /// Returns the card to its state after power on.
+    fn reset(&mut self);
+}
+
+/// Appends `status` to `data`, forming an encoded response.
+pub(crate) fn encode_response(mut data: Vec<u8>, status: ApduStatus) -> Vec<u8> {
+    data.extend_from_slice(&[status.code1(), status.code2()]);
+    data
+}
+
+/// Hosts a single [`Applet`] on the basic channel, without a file system.
+///
+/// The applet is selected on reset, so it sees commands without a prior
+/// SELECT, and selecting its AID again reselects it. The host answers GET
+/// RESPONSE and reassembles command chains on the applet's behalf; any
+/// other command is passed to the applet.
+pub struct AppletHost {
+    aid: Vec<u8>,
+    applet: Box<dyn Applet>,
+    atr: Vec<u8>,
+    selected: bool,
+    splitter: ResponseSplitter,
+    assembler: ChainAssembler,
+}
+
+impl AppletHost {
+    pub fn new(aid: &[u8], applet: impl Applet + 'static) -> Self {
+        let mut host = Self {
+            aid: aid.to_vec(),
+            applet: Box::new(applet),
+            atr: DEFAULT_ATR.to_vec(),
+            selected: false,
+            splitter: ResponseSplitter::new(),
+            assembler: ChainAssembler::new(),
+        };
+
+        host.reset();
+        host
+    }
+
+    pub fn with_atr(mut self, atr: &[u8]) -> Self {
+        self.atr = atr.to_vec();
+        self
+    }
+
+    pub fn aid(&self) -> &[u8] {
+        &self.aid
+    }
+
+    /// Whether the applet accepted the last selection.
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+
+    /// Processes a single command, returning the response data and status.
+    pub fn process(&mut self, command: &CardCommand<'_>) -> (Vec<u8>, ApduStatus) {
+        if command
+            .channel()
+            .is_none_or(|channel| channel.number() != 0)
+        {
+            return (Vec::new(), status::LOGICAL_CHANNEL_NOT_SUPPORTED);
+        }
+
+        if command.instruction() == 0xC0 && !command.is_proprietary() {
+            return self.splitter.get_response(0, command.expected_length());
+        }
+
+        let mut response = Vec::new();
+
+        let status = match self.assembler.push(0, command) {
+            Chained::Incomplete => status::OK,
+            Chained::Complete(data) => self.dispatch(&command.with_data(&data), &mut response),
+            Chained::Single => self.dispatch(command, &mut response),
+        };
+
+        self.splitter
+            .split(0, response, status, command.expected_length())
+    }
+
+    fn dispatch(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let data = command.data();
+
+        if !command.is_proprietary()
+            && command.instruction() == 0xA4
+            && command.parameters().0 == 0x04
+            && !data.is_empty()
+            && self.aid.starts_with(data)
+        {
+            return self.select(command, response);
+        }
+
+        if !self.selected {
+            return status::CONDITIONS_OF_USE_NOT_SATISFIED;
+        }
+
+        self.applet.process(command, response)
+    }
+
+    fn select(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        if self.selected {
+            self.applet.deselect();
+        }
+
+        let status = self.applet.select(command, response);
+        self.selected = status == status::OK;
+
+        status
+    }
+}
+
+impl SimulatedCard for AppletHost {
+    fn atr(&self) -> &[u8] {
+        &self.atr
+    }
+
+    fn transmit(&mut self, command: &[u8]) -> Vec<u8> {
+        let (data, status) = match CardCommand::parse(command) {
+            Some(command) => self.process(&command),
+            None => (Vec::new(), status::WRONG_LENGTH),
+        };
+
+        encode_response(data, status)
+    }
+
+    fn reset(&mut self) {
+        self.splitter.clear();
+        self.assembler.clear();
+
+        let aid = self.aid.clone();
+        let select = CardCommand::new(0x00, 0xA4, (0x04, 0x00), &aid, 256);
+
+        self.select(&select, &mut Vec::new());
+    }
+}
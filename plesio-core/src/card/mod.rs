@@ -0,0 +1,7 @@
+pub mod applet;
+pub mod command;
+pub mod file;
+pub mod host;
+pub mod pin;
+pub mod response;
+pub mod virtual_card;
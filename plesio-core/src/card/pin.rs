@@ -0,0 +1,77 @@
+use alloc::vec::Vec;
+
+/// A PIN or other reference data held by the card, with a retry counter.
+#[derive(Debug, Clone)]
+pub struct PinObject {
+    reference: u8,
+    value: Vec<u8>,
+    max_retries: u8,
+    retries: u8,
+    resetting_code: Option<u8>,
+}
+
+impl PinObject {
+    pub fn new(reference: u8, value: &[u8], max_retries: u8) -> Self {
+        Self {
+            reference,
+            value: value.to_vec(),
+            max_retries,
+            retries: max_retries,
+            resetting_code: None,
+        }
+    }
+
+    /// Allows RESET RETRY COUNTER with the PIN object `reference`, e.g. a PUK.
+    pub fn with_resetting_code(mut self, reference: u8) -> Self {
+        self.resetting_code = Some(reference);
+        self
+    }
+
+    pub fn reference(&self) -> u8 {
+        self.reference
+    }
+
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    pub fn retries(&self) -> u8 {
+        self.retries
+    }
+
+    pub fn max_retries(&self) -> u8 {
+        self.max_retries
+    }
+
+    pub fn resetting_code(&self) -> Option<u8> {
+        self.resetting_code
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.retries == 0
+    }
+
+    /// Checks `candidate`, decrementing the retry counter on a mismatch and
+    /// restoring it on a match. A blocked PIN never verifies.
+    pub fn verify(&mut self, candidate: &[u8]) -> bool {
+        if self.is_blocked() {
+            return false;
+        }
+
+        if self.value == candidate {
+            self.retries = self.max_retries;
+            true
+        } else {
+            self.retries -= 1;
+            false
+        }
+    }
+
+    pub fn set_value(&mut self, value: &[u8]) {
+        self.value = value.to_vec();
+    }
+
+    pub fn unblock(&mut self) {
+        self.retries = self.max_retries;
+    }
+}
@@ -0,0 +1,148 @@
+use alloc::vec::Vec;
+
+use crate::{
+    apdu::{iso_7816::status, status::ApduStatus},
+    card::command::CardCommand,
+};
+
+/// Response data left over for GET RESPONSE.
+struct Pending {
+    channel: u8,
+    data: Vec<u8>,
+    status: ApduStatus,
+}
+
+/// Delivers responses longer than the terminal asked for in parts, with
+/// 61xx and GET RESPONSE.
+///
+/// Only one response is kept at a time: splitting a new response discards
+/// the remainder of the previous one, as any other command would on a card.
+#[derive(Default)]
+pub struct ResponseSplitter {
+    pending: Option<Pending>,
+}
+
+impl ResponseSplitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns at most `expected_length` bytes of `data`, keeping the rest
+    /// for GET RESPONSE on `channel` and replacing `status` with 61xx if
+    /// anything is left.
+    pub fn split(
+        &mut self,
+        channel: u8,
+        mut data: Vec<u8>,
+        status: ApduStatus,
+        expected_length: usize,
+    ) -> (Vec<u8>, ApduStatus) {
+        if data.len() <= expected_length {
+            self.pending = None;
+            return (data, status);
+        }
+
+        let remainder = data.split_off(expected_length);
+        let more = status::more_data(remainder.len());
+
+        self.pending = Some(Pending {
+            channel,
+            data: remainder,
+            status,
+        });
+
+        (data, more)
+    }
+
+    /// Answers a GET RESPONSE on `channel` with the next part of the pending
+    /// response, or `6985` if there is none.
+    pub fn get_response(&mut self, channel: u8, expected_length: usize) -> (Vec<u8>, ApduStatus) {
+        match self.pending.take() {
+            Some(pending) if pending.channel == channel => {
+                self.split(channel, pending.data, pending.status, expected_length)
+            }
+            _ => (Vec::new(), status::CONDITIONS_OF_USE_NOT_SATISFIED),
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.pending = None;
+    }
+}
+
+/// What a [`ChainAssembler`] made of a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chained {
+    /// A chain was started or continued; the command should be acknowledged
+    /// with `9000`.
+    Incomplete,
+    /// The command ended a chain; this is the data of the whole chain.
+    Complete(Vec<u8>),
+    /// The command is not part of a chain.
+    Single,
+}
+
+struct Chain {
+    channel: u8,
+    instruction: u8,
+    parameters: (u8, u8),
+    data: Vec<u8>,
+}
+
+/// Reassembles command chains, as sent with the chaining bit in CLA.
+///
+/// A chain is continued by commands on the same channel with the same INS,
+/// P1 and P2. Any other command abandons it.
+#[derive(Default)]
+pub struct ChainAssembler {
+    chain: Option<Chain>,
+}
+
+impl ChainAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, channel: u8, command: &CardCommand<'_>) -> Chained {
+        let continues = self.chain.as_ref().is_some_and(|chain| {
+            chain.channel == channel
+                && chain.instruction == command.instruction()
+                && chain.parameters == command.parameters()
+        });
+
+        if !continues {
+            self.chain = None;
+        }
+
+        match (command.is_chaining(), &mut self.chain) {
+            (true, Some(chain)) => {
+                chain.data.extend_from_slice(command.data());
+                Chained::Incomplete
+            }
+            (true, chain) => {
+                *chain = Some(Chain {
+                    channel,
+                    instruction: command.instruction(),
+                    parameters: command.parameters(),
+                    data: command.data().to_vec(),
+                });
+                Chained::Incomplete
+            }
+            (false, chain) => match chain.take() {
+                Some(mut chain) => {
+                    chain.data.extend_from_slice(command.data());
+                    Chained::Complete(chain.data)
+                }
+                None => Chained::Single,
+            },
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.chain = None;
+    }
+}
@@ -0,0 +1,775 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{
+    apdu::{
+        blocking::BlockingTransport,
+        command::ApduCommand,
+        encoding,
+        iso_7816::status,
+        response::ApduResponse,
+        status::ApduStatus,
+        transport::{ApduTransport, PayloadTooLarge, TransportError},
+    },
+    card::{
+        applet::Applet,
+        command::CardCommand,
+        file::{AccessCondition, FileHandle, FileSystem},
+        host::{SimulatedCard, encode_response},
+        pin::PinObject,
+        response::{ChainAssembler, Chained, ResponseSplitter},
+    },
+};
+
+/// Basic channels 0 to 3 and extended channels 4 to 19.
+const CHANNEL_COUNT: usize = 20;
+
+/// A T=1 ATR without historical bytes.
+pub const DEFAULT_ATR: &[u8] = &[0x3B, 0x80, 0x80, 0x01, 0x01];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtualCardError {
+    PayloadTooLarge {
+        max_size: usize,
+    },
+    /// The reply buffer cannot even hold the status bytes.
+    ReplyBufferTooSmall,
+}
+
+impl core::fmt::Display for VirtualCardError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::PayloadTooLarge { max_size } => {
+                write!(f, "payload exceeds the card maximum of {max_size} bytes")
+            }
+            Self::ReplyBufferTooSmall => write!(f, "reply buffer cannot hold the status bytes"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VirtualCardError {}
+
+impl TransportError for VirtualCardError {
+    fn is_payload_too_large(&self) -> Option<PayloadTooLarge> {
+        match *self {
+            Self::PayloadTooLarge { max_size } => Some(PayloadTooLarge { max_size }),
+            Self::ReplyBufferTooSmall => None,
+        }
+    }
+}
+
+struct RegisteredApplet {
+    aid: Vec<u8>,
+    applet: Box<dyn Applet>,
+}
+
+#[derive(Clone)]
+struct ChannelState {
+    current_df: FileHandle,
+    current_ef: Option<FileHandle>,
+    applet: Option<usize>,
+    verified: Vec<u8>,
+}
+
+/// An in-process ISO 7816-4 card.
+///
+/// The card has a file system of DFs and transparent EFs, PIN objects with
+/// retry counters, applets selected by AID and up to 20 logical channels,
+/// each with its own current file, selected applet and security status.
+/// While an applet is selected on a channel it receives every command sent
+/// on that channel, except MANAGE CHANNEL and SELECT of another applet.
+///
+/// Responses longer than the terminal asked for are delivered with 61xx and
+/// GET RESPONSE, and chained commands are reassembled before processing.
+pub struct VirtualCard {
+    files: FileSystem,
+    pins: Vec<PinObject>,
+    applets: Vec<RegisteredApplet>,
+    channels: Vec<Option<ChannelState>>,
+    splitter: ResponseSplitter,
+    assembler: ChainAssembler,
+    atr: Vec<u8>,
+    max_payload_size: usize,
+}
+
+impl VirtualCard {
+    pub fn new(files: FileSystem) -> Self {
+        let mut card = Self {
+            files,
+            pins: Vec::new(),
+            applets: Vec::new(),
+            channels: Vec::new(),
+            splitter: ResponseSplitter::new(),
+            assembler: ChainAssembler::new(),
+            atr: DEFAULT_ATR.to_vec(),
+            max_payload_size: u8::MAX as usize,
+        };
+
+        card.reset();
+        card
+    }
+
+    pub fn with_pin(mut self, pin: PinObject) -> Self {
+        self.pins.push(pin);
+        self
+    }
+
+    /// Registers `applet` to be selected by `aid`, or by a prefix of it.
+    pub fn with_applet(mut self, aid: &[u8], applet: impl Applet + 'static) -> Self {
+        self.applets.push(RegisteredApplet {
+            aid: aid.to_vec(),
+            applet: Box::new(applet),
+        });
+        self
+    }
+
+    pub fn with_atr(mut self, atr: &[u8]) -> Self {
+        self.atr = atr.to_vec();
+        self
+    }
+
+    /// The largest command payload accepted without chaining. Defaults to
+    /// 255, i.e. no extended length support.
+    pub fn with_max_payload_size(mut self, max_payload_size: usize) -> Self {
+        self.max_payload_size = max_payload_size;
+        self
+    }
+
+    pub fn atr(&self) -> &[u8] {
+        &self.atr
+    }
+
+    pub fn files(&self) -> &FileSystem {
+        &self.files
+    }
+
+    pub fn files_mut(&mut self) -> &mut FileSystem {
+        &mut self.files
+    }
+
+    pub fn pin(&self, reference: u8) -> Option<&PinObject> {
+        self.pins.iter().find(|pin| pin.reference() == reference)
+    }
+
+    /// Deselects all applets, closes every channel except the basic one and
+    /// clears the security status, as a warm reset would.
+    pub fn reset(&mut self) {
+        for channel in self.channels.iter().flatten() {
+            if let Some(applet) = channel.applet {
+                self.applets[applet].applet.deselect();
+            }
+        }
+
+        self.channels = vec![None; CHANNEL_COUNT];
+        self.channels[0] = Some(ChannelState {
+            current_df: self.files.master_file(),
+            current_ef: None,
+            applet: None,
+            verified: Vec::new(),
+        });
+        self.splitter.clear();
+        self.assembler.clear();
+    }
+
+    /// Processes a single command, returning the response data and status.
+    ///
+    /// The response data never exceeds the command's expected length; the
+    /// remainder is kept for GET RESPONSE.
+    pub fn process(&mut self, command: &CardCommand<'_>) -> (Vec<u8>, ApduStatus) {
+        let Some(channel) = command.channel().map(|channel| channel.number()) else {
+            return (Vec::new(), status::CLASS_NOT_SUPPORTED);
+        };
+
+        if self.channels[channel as usize].is_none() {
+            return (Vec::new(), status::LOGICAL_CHANNEL_NOT_SUPPORTED);
+        }
+
+        if command.instruction() == 0xC0 && !command.is_proprietary() {
+            return self
+                .splitter
+                .get_response(channel, command.expected_length());
+        }
+
+        let mut response = Vec::new();
+
+        let status = match self.assembler.push(channel, command) {
+            Chained::Incomplete => status::OK,
+            Chained::Complete(data) => {
+                self.dispatch(channel, &command.with_data(&data), &mut response)
+            }
+            Chained::Single => self.dispatch(channel, command, &mut response),
+        };
+
+        self.splitter
+            .split(channel, response, status, command.expected_length())
+    }
+
+    fn dispatch(
+        &mut self,
+        channel: u8,
+        command: &CardCommand<'_>,
+        response: &mut Vec<u8>,
+    ) -> ApduStatus {
+        let interindustry = !command.is_proprietary();
+
+        if interindustry && command.instruction() == 0x70 {
+            return self.manage_channel(channel, command, response);
+        }
+
+        if interindustry && command.instruction() == 0xA4 && command.parameters().0 == 0x04 {
+            let data = command.data();
+
+            if let Some(applet) = (!data.is_empty())
+                .then(|| {
+                    self.applets
+                        .iter()
+                        .position(|applet| applet.aid.starts_with(data))
+                })
+                .flatten()
+            {
+                return self.select_applet(channel, applet, command, response);
+            }
+        }
+
+        if let Some(applet) = self.channel(channel).applet {
+            return self.applets[applet].applet.process(command, response);
+        }
+
+        if !interindustry {
+            return status::CLASS_NOT_SUPPORTED;
+        }
+
+        match command.instruction() {
+            0xA4 => self.select_file(channel, command, response),
+            0xB0 => self.read_binary(channel, command, response),
+            0xD6 => self.update_binary(channel, command),
+            0x20 => self.verify(channel, command),
+            0x24 => self.change_reference_data(channel, command),
+            0x2C => self.reset_retry_counter(command),
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+
+    fn channel(&self, channel: u8) -> &ChannelState {
+        self.channels[channel as usize].as_ref().unwrap()
+    }
+
+    fn channel_mut(&mut self, channel: u8) -> &mut ChannelState {
+        self.channels[channel as usize].as_mut().unwrap()
+    }
+
+    fn deselect_applet(&mut self, channel: u8) {
+        if let Some(applet) = self.channel_mut(channel).applet.take() {
+            self.applets[applet].applet.deselect();
+        }
+    }
+
+    fn select_applet(
+        &mut self,
+        channel: u8,
+        applet: usize,
+        command: &CardCommand<'_>,
+        response: &mut Vec<u8>,
+    ) -> ApduStatus {
+        self.deselect_applet(channel);
+
+        let status = self.applets[applet].applet.select(command, response);
+
+        if status == status::OK {
+            self.channel_mut(channel).applet = Some(applet);
+        }
+
+        status
+    }
+
+    fn manage_channel(
+        &mut self,
+        channel: u8,
+        command: &CardCommand<'_>,
+        response: &mut Vec<u8>,
+    ) -> ApduStatus {
+        match command.parameters() {
+            (0x00, requested) => {
+                let target = if requested == 0 {
+                    match (1..CHANNEL_COUNT).find(|&target| self.channels[target].is_none()) {
+                        Some(target) => target,
+                        None => return status::FUNCTION_NOT_SUPPORTED,
+                    }
+                } else if (requested as usize) < CHANNEL_COUNT {
+                    requested as usize
+                } else {
+                    return status::INCORRECT_PARAMETERS;
+                };
+
+                if self.channels[target].is_some() {
+                    return status::CONDITIONS_OF_USE_NOT_SATISFIED;
+                }
+
+                // Opened from the basic channel the MF is selected, otherwise
+                // the current DF of the originating channel.
+                let origin = self.channel(channel);
+                let current_df = if channel == 0 {
+                    self.files.master_file()
+                } else {
+                    origin.current_df
+                };
+
+                self.channels[target] = Some(ChannelState {
+                    current_df,
+                    current_ef: None,
+                    applet: None,
+                    verified: Vec::new(),
+                });
+
+                if requested == 0 {
+                    response.push(target as u8);
+                }
+
+                status::OK
+            }
+            (0x80, requested) => {
+                let target = if requested == 0 { channel } else { requested };
+
+                if target == 0 || target as usize >= CHANNEL_COUNT {
+                    return status::INCORRECT_PARAMETERS;
+                }
+
+                if self.channels[target as usize].is_none() {
+                    return status::LOGICAL_CHANNEL_NOT_SUPPORTED;
+                }
+
+                self.deselect_applet(target);
+                self.channels[target as usize] = None;
+
+                status::OK
+            }
+            _ => status::INCORRECT_PARAMETERS,
+        }
+    }
+
+    fn resolve_file(
+        &self,
+        channel: u8,
+        command: &CardCommand<'_>,
+    ) -> Result<FileHandle, ApduStatus> {
+        let state = self.channel(channel);
+        let data = command.data();
+
+        let file_id = |data: &[u8]| -> Result<u16, ApduStatus> {
+            <[u8; 2]>::try_from(data)
+                .map(u16::from_be_bytes)
+                .map_err(|_| status::WRONG_LENGTH)
+        };
+
+        let walk = |start: FileHandle, path: &[u8]| -> Result<FileHandle, ApduStatus> {
+            if !path.len().is_multiple_of(2) {
+                return Err(status::WRONG_LENGTH);
+            }
+
+            path.chunks(2).try_fold(start, |file, id| {
+                self.files
+                    .child(file, u16::from_be_bytes([id[0], id[1]]))
+                    .ok_or(status::FILE_NOT_FOUND)
+            })
+        };
+
+        let found = match command.parameters().0 {
+            0x00 => {
+                if data.is_empty() {
+                    return Ok(self.files.master_file());
+                }
+
+                let file_id = file_id(data)?;
+                let parent = self.files.parent(state.current_df);
+
+                if file_id == FileSystem::MASTER_FILE_ID {
+                    Some(self.files.master_file())
+                } else if file_id == self.files.file_id(state.current_df) {
+                    Some(state.current_df)
+                } else {
+                    self.files
+                        .child(state.current_df, file_id)
+                        .or_else(|| parent.filter(|&parent| self.files.file_id(parent) == file_id))
+                        .or_else(|| parent.and_then(|parent| self.files.child(parent, file_id)))
+                }
+            }
+            0x01 => self
+                .files
+                .child(state.current_df, file_id(data)?)
+                .filter(|&file| self.files.is_dedicated(file)),
+            0x02 => self
+                .files
+                .child(state.current_df, file_id(data)?)
+                .filter(|&file| !self.files.is_dedicated(file)),
+            0x03 => self.files.parent(state.current_df),
+            0x04 => self.files.find_by_name(data),
+            0x08 => Some(walk(self.files.master_file(), data)?),
+            0x09 => Some(walk(state.current_df, data)?),
+            _ => return Err(status::INCORRECT_PARAMETERS),
+        };
+
+        found.ok_or(status::FILE_NOT_FOUND)
+    }
+
+    fn select_file(
+        &mut self,
+        channel: u8,
+        command: &CardCommand<'_>,
+        response: &mut Vec<u8>,
+    ) -> ApduStatus {
+        let file = match self.resolve_file(channel, command) {
+            Ok(file) => file,
+            Err(status) => return status,
+        };
+
+        let is_dedicated = self.files.is_dedicated(file);
+        let parent = self.files.parent(file);
+        let state = self.channel_mut(channel);
+
+        if is_dedicated {
+            state.current_df = file;
+            state.current_ef = None;
+        } else {
+            // Every EF has a parent DF.
+            state.current_df = parent.unwrap();
+            state.current_ef = Some(file);
+        }
+
+        match command.parameters().1 & 0x0C {
+            0x00 => {
+                let mut fci = self.files.fcp(file);
+                fci[0] = 0x6F;
+                response.extend_from_slice(&fci);
+            }
+            0x04 => response.extend_from_slice(&self.files.fcp(file)),
+            0x08 => response.extend_from_slice(&[0x64, 0x00]),
+            _ => {}
+        }
+
+        status::OK
+    }
+
+    /// Resolves the EF and offset addressed by READ BINARY or UPDATE BINARY,
+    /// making a file referenced by short EF identifier the current EF.
+    fn binary_target(
+        &mut self,
+        channel: u8,
+        command: &CardCommand<'_>,
+    ) -> Result<(FileHandle, usize), ApduStatus> {
+        let (p1, p2) = command.parameters();
+
+        if p1 & 0x80 != 0 {
+            let state = self.channel(channel);
+            let file = self
+                .files
+                .child_by_short_id(state.current_df, p1 & 0x1F)
+                .ok_or(status::FILE_NOT_FOUND)?;
+
+            self.channel_mut(channel).current_ef = Some(file);
+
+            Ok((file, p2 as usize))
+        } else {
+            let file = self
+                .channel(channel)
+                .current_ef
+                .ok_or(status::COMMAND_NOT_ALLOWED)?;
+
+            Ok((file, u16::from_be_bytes([p1, p2]) as usize))
+        }
+    }
+
+    fn is_satisfied(&self, channel: u8, condition: AccessCondition) -> bool {
+        match condition {
+            AccessCondition::Always => true,
+            AccessCondition::Never => false,
+            AccessCondition::Pin(reference) => self.channel(channel).verified.contains(&reference),
+        }
+    }
+
+    fn read_binary(
+        &mut self,
+        channel: u8,
+        command: &CardCommand<'_>,
+        response: &mut Vec<u8>,
+    ) -> ApduStatus {
+        let (file, offset) = match self.binary_target(channel, command) {
+            Ok(target) => target,
+            Err(status) => return status,
+        };
+
+        let (read, _) = self.files.access(file).unwrap();
+
+        if !self.is_satisfied(channel, read) {
+            return status::SECURITY_STATUS_NOT_SATISFIED;
+        }
+
+        let data = self.files.data(file).unwrap();
+
+        let Some(available) = data.get(offset..) else {
+            return status::WRONG_PARAMETERS;
+        };
+
+        let length = available.len().min(command.expected_length());
+        response.extend_from_slice(&available[..length]);
+
+        if length < command.expected_length() {
+            status::END_OF_FILE
+        } else {
+            status::OK
+        }
+    }
+
+    fn update_binary(&mut self, channel: u8, command: &CardCommand<'_>) -> ApduStatus {
+        let (file, offset) = match self.binary_target(channel, command) {
+            Ok(target) => target,
+            Err(status) => return status,
+        };
+
+        let (_, update) = self.files.access(file).unwrap();
+
+        if !self.is_satisfied(channel, update) {
+            return status::SECURITY_STATUS_NOT_SATISFIED;
+        }
+
+        let data = command.data();
+
+        match self
+            .files
+            .data_mut(file)
+            .unwrap()
+            .get_mut(offset..offset + data.len())
+        {
+            Some(target) => {
+                target.copy_from_slice(data);
+                status::OK
+            }
+            None => status::NOT_ENOUGH_MEMORY,
+        }
+    }
+
+    fn pin_index(&self, reference: u8) -> Option<usize> {
+        self.pins
+            .iter()
+            .position(|pin| pin.reference() == reference)
+    }
+
+    fn set_verified(&mut self, channel: u8, reference: u8, verified: bool) {
+        let state = self.channel_mut(channel);
+        state.verified.retain(|&other| other != reference);
+
+        if verified {
+            state.verified.push(reference);
+        }
+    }
+
+    /// Checks `candidate` against a PIN object, updating the channel's
+    /// security status accordingly.
+    fn check_pin(&mut self, channel: u8, pin: usize, candidate: &[u8]) -> ApduStatus {
+        let reference = self.pins[pin].reference();
+
+        if self.pins[pin].is_blocked() {
+            self.set_verified(channel, reference, false);
+            return status::AUTHENTICATION_METHOD_BLOCKED;
+        }
+
+        let verified = self.pins[pin].verify(candidate);
+        self.set_verified(channel, reference, verified);
+
+        if verified {
+            status::OK
+        } else {
+            status::verification_failed(self.pins[pin].retries())
+        }
+    }
+
+    fn verify(&mut self, channel: u8, command: &CardCommand<'_>) -> ApduStatus {
+        let (p1, reference) = command.parameters();
+
+        let Some(pin) = self.pin_index(reference) else {
+            return status::REFERENCED_DATA_NOT_FOUND;
+        };
+
+        match (p1, command.data().is_empty()) {
+            (0xFF, true) => {
+                self.set_verified(channel, reference, false);
+                status::OK
+            }
+            (0x00, true) => {
+                if self.channel(channel).verified.contains(&reference) {
+                    status::OK
+                } else if self.pins[pin].is_blocked() {
+                    status::AUTHENTICATION_METHOD_BLOCKED
+                } else {
+                    status::verification_failed(self.pins[pin].retries())
+                }
+            }
+            (0x00, false) => self.check_pin(channel, pin, command.data()),
+            _ => status::INCORRECT_PARAMETERS,
+        }
+    }
+
+    fn change_reference_data(&mut self, channel: u8, command: &CardCommand<'_>) -> ApduStatus {
+        let (p1, reference) = command.parameters();
+
+        let Some(pin) = self.pin_index(reference) else {
+            return status::REFERENCED_DATA_NOT_FOUND;
+        };
+
+        let new_value = match p1 {
+            0x00 => {
+                let Some((current, new_value)) = command
+                    .data()
+                    .split_at_checked(self.pins[pin].value().len())
+                else {
+                    return status::WRONG_LENGTH;
+                };
+
+                let status = self.check_pin(channel, pin, current);
+
+                if status != status::OK {
+                    return status;
+                }
+
+                new_value
+            }
+            0x01 => {
+                if !self.channel(channel).verified.contains(&reference) {
+                    return status::SECURITY_STATUS_NOT_SATISFIED;
+                }
+
+                command.data()
+            }
+            _ => return status::INCORRECT_PARAMETERS,
+        };
+
+        if new_value.is_empty() {
+            return status::WRONG_LENGTH;
+        }
+
+        self.pins[pin].set_value(new_value);
+
+        status::OK
+    }
+
+    fn reset_retry_counter(&mut self, command: &CardCommand<'_>) -> ApduStatus {
+        let (p1, reference) = command.parameters();
+
+        let Some(pin) = self.pin_index(reference) else {
+            return status::REFERENCED_DATA_NOT_FOUND;
+        };
+
+        let Some(code) = self.pins[pin]
+            .resetting_code()
+            .and_then(|code| self.pin_index(code))
+        else {
+            return status::CONDITIONS_OF_USE_NOT_SATISFIED;
+        };
+
+        let (candidate, new_value) = match p1 {
+            0x00 => match command
+                .data()
+                .split_at_checked(self.pins[code].value().len())
+            {
+                Some((candidate, new_value)) if !new_value.is_empty() => {
+                    (candidate, Some(new_value))
+                }
+                _ => return status::WRONG_LENGTH,
+            },
+            0x01 => (command.data(), None),
+            _ => return status::INCORRECT_PARAMETERS,
+        };
+
+        if self.pins[code].is_blocked() {
+            return status::AUTHENTICATION_METHOD_BLOCKED;
+        }
+
+        if !self.pins[code].verify(candidate) {
+            return status::verification_failed(self.pins[code].retries());
+        }
+
+        self.pins[pin].unblock();
+
+        if let Some(new_value) = new_value {
+            self.pins[pin].set_value(new_value);
+        }
+
+        status::OK
+    }
+
+    fn execute_now<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, VirtualCardError> {
+        if reply_buffer.len() < 2 {
+            return Err(VirtualCardError::ReplyBufferTooSmall);
+        }
+
+        if command.data().len() > self.max_payload_size {
+            return Err(VirtualCardError::PayloadTooLarge {
+                max_size: self.max_payload_size,
+            });
+        }
+
+        let command =
+            CardCommand::from_command(&command, encoding::expected_length(reply_buffer.len()));
+        let (data, status) = self.process(&command);
+
+        let length = data.len();
+        reply_buffer[..length].copy_from_slice(&data);
+        reply_buffer[length..length + 2].copy_from_slice(&[status.code1(), status.code2()]);
+
+        Ok(ApduResponse::new(&reply_buffer[..length], status))
+    }
+}
+
+impl SimulatedCard for VirtualCard {
+    fn atr(&self) -> &[u8] {
+        &self.atr
+    }
+
+    fn transmit(&mut self, command: &[u8]) -> Vec<u8> {
+        let (data, status) = match CardCommand::parse(command) {
+            Some(command) => self.process(&command),
+            None => (Vec::new(), status::WRONG_LENGTH),
+        };
+
+        encode_response(data, status)
+    }
+
+    fn reset(&mut self) {
+        VirtualCard::reset(self);
+    }
+}
+
+impl BlockingTransport for VirtualCard {
+    type TransportError = VirtualCardError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, VirtualCardError> {
+        self.execute_now(command, reply_buffer)
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.max_payload_size
+    }
+}
+
+impl ApduTransport for VirtualCard {
+    type TransportError = VirtualCardError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, Self::TransportError>> {
+        core::future::ready(self.execute_now(command, reply_buffer))
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.max_payload_size
+    }
+}
@@ -1,3 +1,11 @@
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
 pub mod apdu;
+#[cfg(feature = "card")]
+pub mod card;
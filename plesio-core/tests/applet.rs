@@ -0,0 +1,192 @@
+use std::{cell::RefCell, rc::Rc};
+
+use plesio_core::{
+    apdu::{
+        iso_7816::{
+            status,
+            tlv::{TaggedSlice, TlvWriteError},
+        },
+        status::ApduStatus,
+    },
+    card::{
+        applet::Applet,
+        command::CardCommand,
+        file::FileSystem,
+        host::{AppletHost, SimulatedCard},
+        virtual_card::VirtualCard,
+    },
+};
+
+const AID: &[u8] = &[0xA0, 0x00, 0x00, 0x05, 0x27, 0x21, 0x01];
+
+/// Records every command it processes and answers GET DATA with a TLV
+/// holding as many bytes as P2 asks for.
+#[derive(Default)]
+struct Counter {
+    selections: Rc<RefCell<u32>>,
+    received: Rc<RefCell<Vec<Vec<u8>>>>,
+}
+
+impl Applet for Counter {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        *self.selections.borrow_mut() += 1;
+        TaggedSlice::from(0x4F, AID).write(response).unwrap();
+        status::OK
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        self.received.borrow_mut().push(command.data().to_vec());
+
+        match command.instruction() {
+            0xCA => {
+                let value = vec![0x5A; command.parameters().1 as usize];
+                TaggedSlice::from(0x53, &value).write(response).unwrap();
+                status::OK
+            }
+            0xDA => status::OK,
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+#[test]
+fn parses_short_and_extended_commands() {
+    let case_1 = CardCommand::parse(&[0x00, 0xA4, 0x04, 0x00]).unwrap();
+    assert_eq!(case_1.data(), &[] as &[u8]);
+    assert_eq!(case_1.expected_length(), 0);
+
+    let case_2 = CardCommand::parse(&[0x00, 0xCA, 0x00, 0x6E, 0x00]).unwrap();
+    assert_eq!(case_2.expected_length(), 256);
+
+    let case_4 = CardCommand::parse(&[0x0C, 0xA4, 0x04, 0x00, 0x02, 0x12, 0x34, 0x10]).unwrap();
+    assert_eq!(case_4.class_byte(), 0x0C);
+    assert_eq!(case_4.data(), &[0x12, 0x34]);
+    assert_eq!(case_4.expected_length(), 16);
+
+    let case_2e = CardCommand::parse(&[0x00, 0xB0, 0x00, 0x00, 0x00, 0x00, 0x00]).unwrap();
+    assert_eq!(case_2e.expected_length(), 65536);
+
+    let data = [0xEE; 300];
+    let encoded = [
+        &[0x00, 0xDB, 0x3F, 0xFF, 0x00, 0x01, 0x2C][..],
+        &data,
+        &[0x04, 0x00],
+    ]
+    .concat();
+    let case_4e = CardCommand::parse(&encoded).unwrap();
+    assert_eq!(case_4e.data(), &data);
+    assert_eq!(case_4e.expected_length(), 1024);
+
+    assert!(CardCommand::parse(&[0x00, 0xA4, 0x04]).is_none());
+    assert!(CardCommand::parse(&[0x00, 0xA4, 0x04, 0x00, 0x03, 0x12, 0x34]).is_none());
+    assert!(CardCommand::parse(&[0x00, 0xA4, 0x04, 0x00, 0x00, 0x00, 0x02, 0x12]).is_none());
+}
+
+#[test]
+fn host_selects_applet_on_reset() {
+    let applet = Counter::default();
+    let selections = applet.selections.clone();
+    let mut host = AppletHost::new(AID, applet);
+
+    assert!(host.is_selected());
+    assert_eq!(
+        host.transmit(&[0x00, 0xCA, 0x00, 0x02, 0x00]),
+        &[0x53, 0x02, 0x5A, 0x5A, 0x90, 0x00]
+    );
+
+    let mut select = vec![0x00, 0xA4, 0x04, 0x00, 0x05];
+    select.extend_from_slice(&AID[..5]);
+    select.push(0x00);
+    assert_eq!(
+        host.transmit(&select),
+        [&[0x4F, 0x07][..], AID, &[0x90, 0x00]].concat()
+    );
+
+    host.reset();
+    assert_eq!(*selections.borrow(), 3);
+}
+
+#[test]
+fn host_splits_long_responses() {
+    let mut host = AppletHost::new(AID, Counter::default());
+
+    let first = host.transmit(&[0x00, 0xCA, 0x00, 0xFF, 0x80]);
+    assert_eq!(first.len(), 0x80 + 2);
+    assert_eq!(&first[..4], &[0x53, 0xFF, 0x00, 0xFF]);
+    assert_eq!(&first[0x80..], &[0x61, 0x83]);
+
+    let rest = host.transmit(&[0x00, 0xC0, 0x00, 0x00, 0x00]);
+    assert_eq!(rest.len(), 0x83 + 2);
+    assert_eq!(&rest[0x83..], &[0x90, 0x00]);
+
+    let none = host.transmit(&[0x00, 0xC0, 0x00, 0x00, 0x00]);
+    assert_eq!(none, &[0x69, 0x85]);
+}
+
+#[test]
+fn writes_long_values_with_three_length_bytes() {
+    let value = vec![0x5A; 300];
+    let object = TaggedSlice::from(0x53, &value);
+    assert_eq!(object.encoded_length(), 304);
+
+    let mut encoded = Vec::new();
+    object.write(&mut encoded).unwrap();
+    assert_eq!(&encoded[..4], &[0x53, 0xFF, 0x01, 0x2C]);
+    assert_eq!(TaggedSlice::next(&encoded), Some((object, &[][..])));
+    assert_eq!(TaggedSlice::next(&encoded[..100]), None);
+
+    let mut encoded = Vec::new();
+    TaggedSlice::from(0x53, &value[..0xFE])
+        .write(&mut encoded)
+        .unwrap();
+    assert_eq!(&encoded[..2], &[0x53, 0xFE]);
+
+    let value = vec![0x5A; 0x10000];
+    let mut encoded = Vec::new();
+    assert!(matches!(
+        TaggedSlice::from(0x53, &value).write(&mut encoded),
+        Err(TlvWriteError::ValueTooLong { length: 0x10000 })
+    ));
+    assert!(encoded.is_empty());
+}
+
+#[test]
+fn host_reassembles_chains() {
+    let applet = Counter::default();
+    let received = applet.received.clone();
+    let mut host = AppletHost::new(AID, applet);
+
+    let first = [&[0x10, 0xDA, 0x01, 0x02, 0x03][..], &[1, 2, 3]].concat();
+    let last = [&[0x00, 0xDA, 0x01, 0x02, 0x02][..], &[4, 5]].concat();
+
+    assert_eq!(host.transmit(&first), &[0x90, 0x00]);
+    assert!(received.borrow().is_empty());
+    assert_eq!(host.transmit(&last), &[0x90, 0x00]);
+    assert_eq!(*received.borrow(), [vec![1, 2, 3, 4, 5]]);
+}
+
+#[test]
+fn host_rejects_malformed_commands_and_other_channels() {
+    let mut host = AppletHost::new(AID, Counter::default());
+
+    assert_eq!(host.transmit(&[0x00, 0xCA]), &[0x67, 0x00]);
+    assert_eq!(
+        host.transmit(&[0x01, 0xCA, 0x00, 0x02, 0x00]),
+        &[0x68, 0x81]
+    );
+}
+
+#[test]
+fn virtual_card_transmits_encoded_commands() {
+    let mut card: Box<dyn SimulatedCard> = Box::new(VirtualCard::new(FileSystem::new()));
+
+    assert_eq!(
+        card.transmit(&[0x00, 0xA4, 0x00, 0x0C, 0x02, 0x3F, 0x00]),
+        &[0x90, 0x00]
+    );
+    assert_eq!(
+        card.transmit(&[0x00, 0xB0, 0x00, 0x00, 0x00]),
+        &[0x69, 0x86]
+    );
+    assert_eq!(card.atr(), &[0x3B, 0x80, 0x80, 0x01, 0x01]);
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use plesio_core::apdu::{
+    blocking::{BlockingAdapter, BlockingTransport, block_on},
+    command::ApduCommand,
+    encoding::{self, MAX_ENCODED_LENGTH},
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{
+            Iso7816Command,
+            select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        },
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::{ApduTransport, TransportError},
+};
+
+const AID: &[u8] = &[0xA0, 0x00, 0x00, 0x00, 0x01];
+
+#[derive(Debug, PartialEq, Eq)]
+struct Unplugged;
+
+impl TransportError for Unplugged {}
+
+/// Replies to each encoded command with the next scripted response.
+#[derive(Default)]
+struct Card {
+    script: VecDeque<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Card {
+    fn expect(mut self, command: &[u8], response: &[u8]) -> Self {
+        self.script.push_back((command.to_vec(), response.to_vec()));
+        self
+    }
+}
+
+impl BlockingTransport for Card {
+    type TransportError = Unplugged;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, Unplugged> {
+        let (expected, response) = self.script.pop_front().ok_or(Unplugged)?;
+
+        let mut buffer = vec![0; MAX_ENCODED_LENGTH];
+        let expected_length = encoding::expected_length(reply_buffer.len());
+        let encoded = encoding::encode(&command, expected_length, &mut buffer).unwrap();
+        assert_eq!(encoded, expected);
+
+        let reply = &mut reply_buffer[..response.len()];
+        reply.copy_from_slice(&response);
+        Ok(ApduResponse::parse(reply).unwrap())
+    }
+
+    fn max_payload_size(&self) -> usize {
+        255
+    }
+}
+
+/// Returns `Pending` a fixed number of times before completing.
+struct Countdown(u32);
+
+impl Future for Countdown {
+    type Output = &'static str;
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.0 == 0 {
+            return Poll::Ready("done");
+        }
+
+        self.0 -= 1;
+        Poll::Pending
+    }
+}
+
+#[test]
+fn block_on_returns_ready_output() {
+    assert_eq!(block_on(async { 7 }), 7);
+}
+
+#[test]
+fn block_on_polls_pending_futures_again() {
+    assert_eq!(block_on(Countdown(5)), "done");
+}
+
+#[test]
+fn adapter_forwards_to_blocking_transport() {
+    let card = Card::default().expect(&[0x00, 0xCA, 0x01, 0x02, 0x04], &[1, 2, 3, 4, 0x90, 0x00]);
+    let mut adapter = BlockingAdapter::new(card);
+    let mut reply = [0u8; 6];
+
+    assert_eq!(adapter.max_payload_size(), 255);
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xCA, (0x01, 0x02), &[]);
+    let response = block_on(ApduTransport::execute(&mut adapter, command, &mut reply)).unwrap();
+
+    assert_eq!(response.data(), &[1, 2, 3, 4]);
+    assert_eq!(response.status(), ApduStatus::new(0x90, 0x00));
+    assert!(adapter.inner().script.is_empty());
+
+    adapter.inner_mut().script.push_back((vec![], vec![]));
+    assert_eq!(adapter.into_inner().script.len(), 1);
+}
+
+#[test]
+fn adapter_propagates_transport_errors() {
+    let mut adapter = BlockingAdapter::new(Card::default());
+    let mut reply = [0u8; 2];
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xCA, (0x01, 0x02), &[]);
+    let error = block_on(ApduTransport::execute(&mut adapter, command, &mut reply)).unwrap_err();
+
+    assert_eq!(error, Unplugged);
+}
+
+#[test]
+fn executes_operations_blocking() {
+    let card = Card::default()
+        .expect(
+            &[
+                0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x00, 0x01, 0x10,
+            ],
+            &[0x4F, 0x02, 0x12, 0x34, 0x90, 0x00],
+        )
+        .expect(&[0x00, 0xCA, 0x01, 0x02, 0x00], &[0x61, 0x03])
+        .expect(&[0x00, 0xC0, 0x00, 0x00, 0x03], &[5, 6, 7, 0x90, 0x00])
+        .expect(&[0x00, 0xCA, 0x01, 0x03, 0x00], &[8, 9, 0x90, 0x00]);
+    let mut transport = Iso7816Transport::new(BlockingAdapter::new(card));
+
+    let mut fci = [0u8; 18];
+    let select = Iso7816Select::new(
+        Iso7816SelectResolution::ByApplicationIdentifier(AID),
+        &mut fci,
+    );
+    let fci = transport.execute_blocking(select).unwrap().unwrap();
+    assert_eq!(fci.get(0x4F).unwrap().value(), &[0x12, 0x34]);
+
+    let mut sink = heapless::Vec::<u8, 16>::new();
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xCA, (0x01, 0x02), &[]);
+    let response = transport
+        .execute_streaming_blocking(command, &mut sink)
+        .unwrap();
+    assert_eq!(response.data(), &[5, 6, 7]);
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xCA, (0x01, 0x03), &[]);
+    let response = transport.execute_owned_blocking(command).unwrap();
+    assert_eq!(response.data(), &[8, 9]);
+
+    assert!(transport.into_inner().into_inner().script.is_empty());
+}
+
+#[test]
+fn blocking_operations_report_transport_errors() {
+    let mut transport = Iso7816Transport::new(BlockingAdapter::new(Card::default()));
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xCA, (0x01, 0x02), &[]);
+    let error = transport.execute_owned_blocking(command).unwrap_err();
+
+    assert!(matches!(error, Iso7816TransportError::Transport(Unplugged)));
+}
+
+#[test]
+fn expected_length_excludes_status_bytes() {
+    assert_eq!(encoding::expected_length(258), 256);
+    assert_eq!(encoding::expected_length(2), 0);
+    assert_eq!(encoding::expected_length(0), 0);
+}
+
+#[test]
+fn encodes_and_decodes_every_case() {
+    let short = [0xAA; 255];
+    let long = [0xBB; 256];
+    let cases: [(&[u8], usize, usize); 8] = [
+        (&[], 0, 4),
+        (&[], 256, 5),
+        (&short, 0, 4 + 1 + 255),
+        (&short, 256, 4 + 1 + 255 + 1),
+        (&[], 257, 4 + 3),
+        (&[], 65536, 4 + 3),
+        (&long, 0, 4 + 3 + 256),
+        (&long, 65536, 4 + 3 + 256 + 2),
+    ];
+
+    for (data, expected_length, length) in cases {
+        let command = Iso7816Command::new(Iso7816Class::default(), 0xDA, (0x01, 0x02), data);
+        let mut buffer = vec![0; MAX_ENCODED_LENGTH];
+
+        assert_eq!(
+            encoding::encoded_length(&command, expected_length),
+            Some(length)
+        );
+
+        let encoded = encoding::encode(&command, expected_length, &mut buffer).unwrap();
+        assert_eq!(encoded.len(), length);
+
+        let decoded = encoding::decode(encoded).unwrap();
+        assert_eq!(decoded.header, [0x00, 0xDA, 0x01, 0x02]);
+        assert_eq!(decoded.data, data);
+        assert_eq!(decoded.expected_length, expected_length);
+    }
+}
+
+#[test]
+fn encodes_maximum_lengths_as_zero() {
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xB0, (0x00, 0x00), &[]);
+    let mut buffer = [0u8; 16];
+
+    assert_eq!(
+        encoding::encode(&command, 256, &mut buffer).unwrap(),
+        &[0x00, 0xB0, 0x00, 0x00, 0x00]
+    );
+    assert_eq!(
+        encoding::encode(&command, 65536, &mut buffer).unwrap(),
+        &[0x00, 0xB0, 0x00, 0x00, 0x00, 0x00, 0x00]
+    );
+}
+
+#[test]
+fn refuses_unencodable_commands() {
+    let data = vec![0; 65536];
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xDA, (0x00, 0x00), &data);
+    assert_eq!(encoding::encoded_length(&command, 0), None);
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xB0, (0x00, 0x00), &[]);
+    assert_eq!(encoding::encoded_length(&command, 65537), None);
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xDA, (0x00, 0x00), &[1, 2, 3]);
+    let mut buffer = [0u8; 8];
+    assert!(encoding::encode(&command, 0, &mut buffer[..7]).is_none());
+    assert!(encoding::encode(&command, 0, &mut buffer).is_some());
+}
+
+#[test]
+fn rejects_mismatched_lengths() {
+    for encoded in [
+        &[0x00, 0xCA][..],
+        &[0x00, 0xDA, 0x00, 0x00, 0x03, 0x01, 0x02],
+        &[0x00, 0xDA, 0x00, 0x00, 0x02, 0x01, 0x02, 0x03, 0x04],
+        &[0x00, 0xDA, 0x00, 0x00, 0x00, 0x00, 0x03, 0x01, 0x02],
+        &[0x00, 0xDA, 0x00, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02, 0x03],
+        &[0x00, 0xDA, 0x00, 0x00, 0x00, 0x01],
+    ] {
+        assert_eq!(encoding::decode(encoded), None, "{encoded:02X?}");
+    }
+}
@@ -0,0 +1,153 @@
+use std::io::{Cursor, Write};
+
+use plesio_core::apdu::globalplatform::{
+    cap::{CapError, CapFile, Component, PackageInfo},
+    operation::load::{DapBlock, load_payload},
+};
+use sha2::Sha256;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+const PACKAGE_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x06, 0x47, 0x2F, 0x00, 0x01];
+const APPLET_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x06, 0x47, 0x2F, 0x00, 0x01, 0x01];
+const JAVA_LANG: &[u8] = &[0xA0, 0x00, 0x00, 0x00, 0x62, 0x00, 0x01];
+
+fn component(component: Component, info: &[u8]) -> Vec<u8> {
+    let mut data = vec![component.tag()];
+    data.extend_from_slice(&(info.len() as u16).to_be_bytes());
+    data.extend_from_slice(info);
+    data
+}
+
+fn components() -> Vec<(Component, Vec<u8>)> {
+    let mut header = vec![0xDE, 0xCA, 0xFF, 0xED, 0x01, 0x02, 0x04, 0x03, 0x01];
+    header.push(PACKAGE_AID.len() as u8);
+    header.extend_from_slice(PACKAGE_AID);
+
+    let mut applet = vec![0x01, APPLET_AID.len() as u8];
+    applet.extend_from_slice(APPLET_AID);
+    applet.extend_from_slice(&[0x00, 0x10]);
+
+    let mut import = vec![0x01, 0x00, 0x01, JAVA_LANG.len() as u8];
+    import.extend_from_slice(JAVA_LANG);
+
+    [
+        (Component::Header, header),
+        (Component::Directory, vec![0x02; 4]),
+        (Component::Applet, applet),
+        (Component::Import, import),
+        (Component::ConstantPool, vec![0x05; 6]),
+        (Component::Class, vec![0x06; 3]),
+        (Component::Method, vec![0x07; 200]),
+        (Component::StaticField, vec![0x08; 2]),
+        (Component::RefLocation, vec![0x09; 5]),
+        (Component::Descriptor, vec![0x0B; 7]),
+        (Component::Debug, vec![0x0C; 9]),
+    ]
+    .into_iter()
+    .map(|(kind, info)| (kind, component(kind, &info)))
+    .collect()
+}
+
+fn archive(components: &[(Component, Vec<u8>)]) -> Cursor<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+
+    zip.start_file("META-INF/MANIFEST.MF", options).unwrap();
+    zip.write_all(b"Manifest-Version: 1.0\r\n").unwrap();
+
+    for (kind, data) in components {
+        zip.start_file(
+            format!("com/example/hello/javacard/{}", kind.file_name()),
+            options,
+        )
+        .unwrap();
+        zip.write_all(data).unwrap();
+    }
+
+    let mut cursor = zip.finish().unwrap();
+    cursor.set_position(0);
+    cursor
+}
+
+#[test]
+fn reads_package_applets_and_imports() {
+    let cap = CapFile::read(archive(&components())).unwrap();
+
+    assert_eq!(
+        *cap.package(),
+        PackageInfo {
+            aid: PACKAGE_AID.to_vec(),
+            major_version: 1,
+            minor_version: 3,
+        }
+    );
+    assert_eq!(cap.applets(), [APPLET_AID.to_vec()]);
+    assert_eq!(cap.imports()[0].aid, JAVA_LANG);
+    assert_eq!(cap.imports()[0].major_version, 1);
+    assert_eq!(cap.component(Component::Debug).unwrap()[0], 0x0C);
+}
+
+#[test]
+fn orders_load_file_components() {
+    let cap = CapFile::read(archive(&components())).unwrap();
+
+    let order = |load_file: &[u8]| {
+        let mut tags = Vec::new();
+        let mut rest = load_file;
+        while let [tag, high, low, info @ ..] = rest {
+            tags.push(*tag);
+            rest = &info[u16::from_be_bytes([*high, *low]) as usize..];
+        }
+        tags
+    };
+
+    assert_eq!(order(&cap.load_file(false)), [1, 2, 4, 3, 6, 7, 8, 5, 9]);
+    assert_eq!(order(&cap.load_file(true)), [1, 2, 4, 3, 6, 7, 8, 5, 9, 11]);
+}
+
+#[test]
+fn rejects_missing_and_malformed_components() {
+    let mut without_method = components();
+    without_method.retain(|(kind, _)| *kind != Component::Method);
+    assert!(matches!(
+        CapFile::read(archive(&without_method)),
+        Err(CapError::MissingComponent(Component::Method))
+    ));
+
+    let mut truncated = components();
+    truncated[1].1.pop();
+    assert!(matches!(
+        CapFile::read(archive(&truncated)),
+        Err(CapError::MalformedComponent(Component::Directory))
+    ));
+
+    assert!(matches!(
+        CapFile::read(Cursor::new(b"not a zip".to_vec())),
+        Err(CapError::Zip(_))
+    ));
+}
+
+#[test]
+fn builds_load_payload_with_hash_and_dap() {
+    assert_eq!(
+        CapFile::hash::<Sha256>(b"abc")[..8],
+        [0xBA, 0x78, 0x16, 0xBF, 0x8F, 0x01, 0xCF, 0xEA]
+    );
+
+    let cap = CapFile::read(archive(&components())).unwrap();
+    let load_file = cap.load_file(false);
+    let security_domain = [0xA0, 0x00, 0x00, 0x01, 0x51, 0x00, 0x00, 0x00];
+    let signature = [0x5A; 16];
+
+    let payload = load_payload(
+        &load_file,
+        &[DapBlock {
+            security_domain: &security_domain,
+            signature: &signature,
+        }],
+    );
+
+    assert_eq!(payload[..4], [0xE2, 28, 0x4F, 8]);
+    assert_eq!(payload[30..34], [0xC4, 0x82, 0x01, load_file.len() as u8]);
+    assert_eq!(payload[34..], load_file);
+}
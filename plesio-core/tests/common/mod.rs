@@ -0,0 +1,20 @@
+//! Fixture helpers shared by the integration tests, each of which uses only
+//! some of them.
+#![allow(dead_code)]
+
+use plesio_core::apdu::iso_7816::tlv::ber::BerTlv;
+
+/// Decodes a string of hex digit pairs.
+pub fn hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).unwrap())
+        .collect()
+}
+
+/// Encodes a BER-TLV data object.
+pub fn tlv(tag: u32, value: &[u8]) -> Vec<u8> {
+    let mut object = Vec::new();
+    BerTlv::from(tag, value).write(&mut object).unwrap();
+    object
+}
@@ -0,0 +1,904 @@
+use std::{cell::RefCell, ops::ControlFlow, rc::Rc};
+
+use p256::{
+    EncodedPoint, PublicKey, SecretKey,
+    ecdh::diffie_hellman,
+    elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint},
+};
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        fido::{
+            FIDO_AID,
+            cbor::Value,
+            ctap2::{
+                client::{Ctap2, Ctap2Error},
+                command::{
+                    self, AuthenticatorData, ClientPin, GetAssertion, MakeCredential, RelyingParty,
+                    User, permission,
+                },
+                operation::KeepaliveStatus,
+                pin::{self, KeyAgreement, PinUvAuthProtocol, SharedSecret},
+                status as ctap_status,
+            },
+        },
+        iso_7816::status,
+        status::ApduStatus,
+    },
+    card::{applet::Applet, command::CardCommand, file::FileSystem, virtual_card::VirtualCard},
+};
+use sha2::{Digest, Sha256};
+
+const AAGUID: [u8; 16] = [0xAA; 16];
+const CLIENT_DATA_HASH: [u8; 32] = [0xCD; 32];
+const KEEPALIVE: ApduStatus = ApduStatus::new(0x91, 0x00);
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+fn secret_key(seed: u8) -> SecretKey {
+    SecretKey::from_slice(&[seed; 32]).unwrap()
+}
+
+fn coordinates(key: &SecretKey) -> ([u8; 32], [u8; 32]) {
+    let point = key.public_key().to_encoded_point(false);
+    (
+        point.x().unwrap().as_slice().try_into().unwrap(),
+        point.y().unwrap().as_slice().try_into().unwrap(),
+    )
+}
+
+fn ecdh(key: &SecretKey, x: &[u8; 32], y: &[u8; 32]) -> Option<[u8; 32]> {
+    let point = EncodedPoint::from_affine_coordinates(x.into(), y.into(), false);
+    let public: Option<PublicKey> = PublicKey::from_encoded_point(&point).into();
+    let shared = diffie_hellman(key.to_nonzero_scalar(), public?.as_affine());
+    Some(shared.raw_secret_bytes().as_slice().try_into().unwrap())
+}
+
+/// The platform side of the key agreement, with a counter for randomness.
+struct Platform {
+    key: SecretKey,
+    counter: u8,
+}
+
+impl Platform {
+    fn new() -> Self {
+        Self {
+            key: secret_key(0x21),
+            counter: 0,
+        }
+    }
+}
+
+impl KeyAgreement for Platform {
+    fn public_key(&self) -> ([u8; 32], [u8; 32]) {
+        coordinates(&self.key)
+    }
+
+    fn shared_secret(&mut self, x: &[u8; 32], y: &[u8; 32]) -> Option<[u8; 32]> {
+        ecdh(&self.key, x, y)
+    }
+
+    fn random(&mut self, buffer: &mut [u8]) {
+        self.counter += 1;
+        buffer.fill(self.counter);
+    }
+}
+
+struct Credential {
+    id: Vec<u8>,
+    relying_party: RelyingParty,
+    user: User,
+}
+
+/// The card side of a CTAP2 authenticator. It holds replies to
+/// makeCredential for `user_presence_polls` polls, as if waiting for a
+/// touch, and signs with a fixed signature.
+struct Ctap2Applet {
+    key_agreement: SecretKey,
+    pin_hash: Option<[u8; 16]>,
+    pin_retries: u64,
+    token: [u8; 32],
+    credentials: Vec<Credential>,
+    counter: u32,
+    user_presence_polls: usize,
+    pending: Option<(usize, Vec<u8>)>,
+    next_assertions: Vec<Vec<u8>>,
+    next_relying_parties: Vec<Vec<u8>>,
+    next_credentials: Vec<Vec<u8>>,
+    received: Rc<RefCell<Vec<usize>>>,
+}
+
+impl Ctap2Applet {
+    fn new(user_presence_polls: usize, received: Rc<RefCell<Vec<usize>>>) -> Self {
+        Self {
+            key_agreement: secret_key(0x42),
+            pin_hash: None,
+            pin_retries: 8,
+            token: [0x7E; 32],
+            credentials: Vec::new(),
+            counter: 0,
+            user_presence_polls,
+            pending: None,
+            next_assertions: Vec::new(),
+            next_relying_parties: Vec::new(),
+            next_credentials: Vec::new(),
+            received,
+        }
+    }
+
+    fn reply(value: Option<Value>) -> Result<Vec<u8>, u8> {
+        let mut reply = vec![ctap_status::OK];
+        if let Some(value) = value {
+            value.encode(&mut reply);
+        }
+        Ok(reply)
+    }
+
+    fn auth_data(&mut self, rp_id: &str, flags: u8, credential: Option<&[u8]>) -> Vec<u8> {
+        self.counter += 1;
+        let mut data = sha256(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend(self.counter.to_be_bytes());
+
+        if let Some(id) = credential {
+            data.extend(AAGUID);
+            data.extend((id.len() as u16).to_be_bytes());
+            data.extend(id);
+            let (x, y) = coordinates(&secret_key(id[0]));
+            Value::map([
+                (1, Value::integer(2)),
+                (3, Value::integer(command::ES256)),
+                (-1, Value::integer(1)),
+                (-2, Value::bytes(&x)),
+                (-3, Value::bytes(&y)),
+            ])
+            .encode(&mut data);
+        }
+
+        data
+    }
+
+    /// Checks the token's `param` over `message`, as commands that need
+    /// user verification do once a PIN is set.
+    fn check_token(
+        &self,
+        parameters: &Value,
+        protocol: i64,
+        param: i64,
+        message: &[u8],
+    ) -> Result<u8, u8> {
+        let Some(param) = parameters.get(param) else {
+            return match self.pin_hash {
+                Some(_) => Err(ctap_status::PUAT_REQUIRED),
+                None => Ok(0),
+            };
+        };
+
+        let protocol = parameters
+            .get(protocol)
+            .and_then(Value::as_integer)
+            .and_then(|protocol| PinUvAuthProtocol::from_u8(protocol as u8))
+            .ok_or(ctap_status::MISSING_PARAMETER)?;
+
+        match protocol.verify(&self.token, message, param.as_bytes().unwrap()) {
+            true => Ok(0x04),
+            false => Err(ctap_status::PIN_AUTH_INVALID),
+        }
+    }
+
+    fn make_credential(&mut self, parameters: &Value) -> Result<Vec<u8>, u8> {
+        let client_data_hash = parameters.get(1).and_then(Value::as_bytes).unwrap();
+        let relying_party = RelyingParty::parse(parameters.get(2).unwrap()).unwrap();
+        let user = User::parse(parameters.get(3).unwrap()).unwrap();
+        let verified = self.check_token(parameters, 9, 8, client_data_hash)?;
+
+        let excluded = parameters
+            .get(5)
+            .and_then(Value::as_array)
+            .unwrap_or_default();
+        if excluded.iter().any(|descriptor| {
+            let id = descriptor.get_text("id").and_then(Value::as_bytes);
+            self.credentials
+                .iter()
+                .any(|credential| Some(&credential.id[..]) == id)
+        }) {
+            return Err(ctap_status::CREDENTIAL_EXCLUDED);
+        }
+
+        let id = vec![self.credentials.len() as u8 + 1; 48];
+        let auth_data = self.auth_data(&relying_party.id, 0x41 | verified, Some(&id));
+
+        let resident = parameters
+            .get(7)
+            .and_then(|options| options.get_text("rk"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        if resident {
+            self.credentials.push(Credential {
+                id,
+                relying_party,
+                user,
+            });
+        }
+
+        Self::reply(Some(Value::Map(vec![
+            (Value::integer(1), Value::text("none")),
+            (Value::integer(2), Value::Bytes(auth_data)),
+            (Value::integer(3), Value::Map(Vec::new())),
+        ])))
+    }
+
+    fn get_assertion(&mut self, parameters: &Value) -> Result<Vec<u8>, u8> {
+        let rp_id = parameters
+            .get(1)
+            .and_then(Value::as_text)
+            .unwrap()
+            .to_string();
+        let client_data_hash = parameters.get(2).and_then(Value::as_bytes).unwrap();
+        let verified = self.check_token(parameters, 7, 6, client_data_hash)?;
+
+        let matching: Vec<(Vec<u8>, User)> = self
+            .credentials
+            .iter()
+            .filter(|credential| credential.relying_party.id == rp_id)
+            .map(|credential| (credential.id.clone(), credential.user.clone()))
+            .collect();
+        if matching.is_empty() {
+            return Err(ctap_status::NO_CREDENTIALS);
+        }
+
+        let mut replies = Vec::new();
+        for (index, (id, user)) in matching.iter().enumerate() {
+            let auth_data = self.auth_data(&rp_id, 0x01 | verified, None);
+            let mut entries = vec![
+                (
+                    Value::integer(1),
+                    Value::Map(vec![
+                        (Value::text("id"), Value::bytes(id)),
+                        (Value::text("type"), Value::text("public-key")),
+                    ]),
+                ),
+                (Value::integer(2), Value::Bytes(auth_data)),
+                (Value::integer(3), Value::bytes(&[0x30, 0x44, id[0]])),
+                (Value::integer(4), user.to_value()),
+            ];
+            if index == 0 && matching.len() > 1 {
+                entries.push((Value::integer(5), Value::integer(matching.len() as i64)));
+            }
+            replies.push(Self::reply(Some(Value::Map(entries)))?);
+        }
+
+        let first = replies.remove(0);
+        replies.reverse();
+        self.next_assertions = replies;
+        Ok(first)
+    }
+
+    fn shared_secret(&self, parameters: &Value) -> Result<SharedSecret, u8> {
+        let protocol = parameters
+            .get(1)
+            .and_then(Value::as_integer)
+            .and_then(|protocol| PinUvAuthProtocol::from_u8(protocol as u8))
+            .ok_or(ctap_status::INVALID_PARAMETER)?;
+        let (x, y) = parameters
+            .get(3)
+            .and_then(pin::parse_cose_key)
+            .ok_or(ctap_status::MISSING_PARAMETER)?;
+        let z = ecdh(&self.key_agreement, &x, &y).ok_or(ctap_status::INVALID_PARAMETER)?;
+
+        Ok(SharedSecret::new(protocol, &z))
+    }
+
+    fn check_pin(&mut self, secret: &SharedSecret, parameters: &Value) -> Result<(), u8> {
+        if self.pin_retries == 0 {
+            return Err(ctap_status::PIN_BLOCKED);
+        }
+
+        let pin_hash_enc = parameters.get(6).and_then(Value::as_bytes).unwrap();
+        let pin_hash = secret.decrypt(pin_hash_enc).unwrap();
+
+        if Some(&pin_hash[..]) != self.pin_hash.as_ref().map(|hash| &hash[..]) {
+            self.pin_retries -= 1;
+            return Err(ctap_status::PIN_INVALID);
+        }
+
+        self.pin_retries = 8;
+        Ok(())
+    }
+
+    fn new_pin(secret: &SharedSecret, parameters: &Value) -> [u8; 16] {
+        let padded = secret
+            .decrypt(parameters.get(5).and_then(Value::as_bytes).unwrap())
+            .unwrap();
+        let length = padded.iter().position(|&byte| byte == 0).unwrap();
+        pin::pin_hash(std::str::from_utf8(&padded[..length]).unwrap())
+    }
+
+    fn client_pin(&mut self, parameters: &Value) -> Result<Vec<u8>, u8> {
+        let sub_command = parameters.get(2).and_then(Value::as_unsigned).unwrap() as u8;
+
+        match sub_command {
+            ClientPin::GET_PIN_RETRIES => Self::reply(Some(Value::map([(
+                3,
+                Value::integer(self.pin_retries as i64),
+            )]))),
+            ClientPin::GET_KEY_AGREEMENT => {
+                let (x, y) = coordinates(&self.key_agreement);
+                Self::reply(Some(Value::map([(1, pin::cose_key(&x, &y))])))
+            }
+            ClientPin::SET_PIN => {
+                let secret = self.shared_secret(parameters)?;
+                let new_pin_enc = parameters.get(5).and_then(Value::as_bytes).unwrap();
+                let param = parameters.get(4).and_then(Value::as_bytes).unwrap();
+
+                if self.pin_hash.is_some() {
+                    return Err(ctap_status::NOT_ALLOWED);
+                }
+                if !secret.verify(new_pin_enc, param) {
+                    return Err(ctap_status::PIN_AUTH_INVALID);
+                }
+
+                self.pin_hash = Some(Self::new_pin(&secret, parameters));
+                Self::reply(None)
+            }
+            ClientPin::CHANGE_PIN => {
+                let secret = self.shared_secret(parameters)?;
+                let mut message = parameters
+                    .get(5)
+                    .and_then(Value::as_bytes)
+                    .unwrap()
+                    .to_vec();
+                message.extend(parameters.get(6).and_then(Value::as_bytes).unwrap());
+                let param = parameters.get(4).and_then(Value::as_bytes).unwrap();
+
+                if !secret.verify(&message, param) {
+                    return Err(ctap_status::PIN_AUTH_INVALID);
+                }
+                self.check_pin(&secret, parameters)?;
+
+                self.pin_hash = Some(Self::new_pin(&secret, parameters));
+                Self::reply(None)
+            }
+            ClientPin::GET_PIN_TOKEN
+            | ClientPin::GET_PIN_UV_AUTH_TOKEN_USING_PIN_WITH_PERMISSIONS => {
+                let secret = self.shared_secret(parameters)?;
+                self.check_pin(&secret, parameters)?;
+
+                if sub_command == ClientPin::GET_PIN_UV_AUTH_TOKEN_USING_PIN_WITH_PERMISSIONS
+                    && parameters.get(9).and_then(Value::as_unsigned).unwrap_or(0) == 0
+                {
+                    return Err(ctap_status::MISSING_PARAMETER);
+                }
+
+                let token = secret.encrypt(&[0x1F; 16], &self.token);
+                Self::reply(Some(Value::map([(2, Value::Bytes(token))])))
+            }
+            _ => Err(ctap_status::INVALID_PARAMETER),
+        }
+    }
+
+    fn relying_party_reply(&self, rp_id: &str, total: Option<usize>) -> Vec<u8> {
+        let mut entries = vec![
+            (
+                3,
+                RelyingParty {
+                    id: rp_id.into(),
+                    name: None,
+                }
+                .to_value(),
+            ),
+            (4, Value::bytes(&sha256(rp_id.as_bytes()))),
+        ];
+        if let Some(total) = total {
+            entries.push((5, Value::integer(total as i64)));
+        }
+        Self::reply(Some(Value::map(entries))).unwrap()
+    }
+
+    fn credential_reply(credential: &Credential, total: Option<usize>) -> Vec<u8> {
+        let mut entries = vec![
+            (6, credential.user.to_value()),
+            (
+                7,
+                Value::Map(vec![
+                    (Value::text("id"), Value::bytes(&credential.id)),
+                    (Value::text("type"), Value::text("public-key")),
+                ]),
+            ),
+        ];
+        if let Some(total) = total {
+            entries.push((9, Value::integer(total as i64)));
+        }
+        Self::reply(Some(Value::map(entries))).unwrap()
+    }
+
+    fn credential_management(&mut self, parameters: &Value) -> Result<Vec<u8>, u8> {
+        let sub_command = parameters.get(1).and_then(Value::as_unsigned).unwrap() as u8;
+        let subcommand_parameters = parameters.get(2);
+
+        if matches!(sub_command, 0x01 | 0x02 | 0x04 | 0x06) {
+            let mut message = vec![sub_command];
+            if let Some(subcommand_parameters) = subcommand_parameters {
+                subcommand_parameters.encode(&mut message);
+            }
+            if self.check_token(parameters, 3, 4, &message)? == 0 {
+                return Err(ctap_status::PUAT_REQUIRED);
+            }
+        }
+
+        let mut rp_ids: Vec<String> = Vec::new();
+        for credential in &self.credentials {
+            if !rp_ids.contains(&credential.relying_party.id) {
+                rp_ids.push(credential.relying_party.id.clone());
+            }
+        }
+
+        match sub_command {
+            0x01 => Self::reply(Some(Value::map([
+                (1, Value::integer(self.credentials.len() as i64)),
+                (2, Value::integer(25 - self.credentials.len() as i64)),
+            ]))),
+            0x02 => {
+                let Some(first) = rp_ids.first() else {
+                    return Err(ctap_status::NO_CREDENTIALS);
+                };
+                self.next_relying_parties = rp_ids[1..]
+                    .iter()
+                    .rev()
+                    .map(|rp_id| self.relying_party_reply(rp_id, None))
+                    .collect();
+                Ok(self.relying_party_reply(first, Some(rp_ids.len())))
+            }
+            0x03 => self
+                .next_relying_parties
+                .pop()
+                .ok_or(ctap_status::NOT_ALLOWED),
+            0x04 => {
+                let rp_id_hash = subcommand_parameters
+                    .and_then(|parameters| parameters.get(1))
+                    .and_then(Value::as_bytes)
+                    .unwrap();
+                let matching: Vec<&Credential> = self
+                    .credentials
+                    .iter()
+                    .filter(|credential| {
+                        sha256(credential.relying_party.id.as_bytes()) == rp_id_hash
+                    })
+                    .collect();
+                let Some(first) = matching.first() else {
+                    return Err(ctap_status::NO_CREDENTIALS);
+                };
+                let first = Self::credential_reply(first, Some(matching.len()));
+                self.next_credentials = matching[1..]
+                    .iter()
+                    .rev()
+                    .map(|credential| Self::credential_reply(credential, None))
+                    .collect();
+                Ok(first)
+            }
+            0x05 => self.next_credentials.pop().ok_or(ctap_status::NOT_ALLOWED),
+            0x06 => {
+                let id = subcommand_parameters
+                    .and_then(|parameters| parameters.get(2))
+                    .and_then(|descriptor| descriptor.get_text("id"))
+                    .and_then(Value::as_bytes)
+                    .unwrap();
+                let count = self.credentials.len();
+                self.credentials.retain(|credential| credential.id != id);
+                if self.credentials.len() == count {
+                    return Err(ctap_status::NO_CREDENTIALS);
+                }
+                Self::reply(None)
+            }
+            _ => Err(ctap_status::INVALID_PARAMETER),
+        }
+    }
+
+    fn message(&mut self, data: &[u8]) -> Result<Vec<u8>, u8> {
+        let (&command, parameters) = data.split_first().ok_or(ctap_status::INVALID_LENGTH)?;
+        let parameters = match parameters {
+            [] => Value::Null,
+            _ => match Value::decode(parameters) {
+                Some((value, [])) => value,
+                _ => return Err(ctap_status::INVALID_CBOR),
+            },
+        };
+
+        match command {
+            command::GET_INFO => Self::reply(Some(Value::Map(vec![
+                (
+                    Value::integer(1),
+                    Value::Array(vec![Value::text("FIDO_2_0"), Value::text("FIDO_2_1")]),
+                ),
+                (Value::integer(3), Value::bytes(&AAGUID)),
+                (
+                    Value::integer(4),
+                    Value::Map(vec![
+                        (Value::text("rk"), Value::Bool(true)),
+                        (
+                            Value::text("clientPin"),
+                            Value::Bool(self.pin_hash.is_some()),
+                        ),
+                    ]),
+                ),
+                (Value::integer(5), Value::integer(1200)),
+                (
+                    Value::integer(6),
+                    Value::Array(vec![Value::integer(2), Value::integer(1)]),
+                ),
+                (
+                    Value::integer(0x0A),
+                    Value::Array(vec![Value::Map(vec![
+                        (Value::text("alg"), Value::integer(command::ES256)),
+                        (Value::text("type"), Value::text("public-key")),
+                    ])]),
+                ),
+            ]))),
+            command::MAKE_CREDENTIAL => self.make_credential(&parameters),
+            command::GET_ASSERTION => self.get_assertion(&parameters),
+            command::GET_NEXT_ASSERTION => {
+                self.next_assertions.pop().ok_or(ctap_status::NOT_ALLOWED)
+            }
+            command::CLIENT_PIN => self.client_pin(&parameters),
+            command::CREDENTIAL_MANAGEMENT => self.credential_management(&parameters),
+            _ => Err(ctap_status::INVALID_COMMAND),
+        }
+    }
+}
+
+impl Applet for Ctap2Applet {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        response.extend_from_slice(b"FIDO_2_0");
+        status::OK
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        assert_eq!(command.class_byte() & 0x80, 0x80);
+
+        match (command.instruction(), command.parameters()) {
+            (0x10, (0x80, 0x00)) => {
+                self.received.borrow_mut().push(command.data().len());
+                let reply = self
+                    .message(command.data())
+                    .unwrap_or_else(|error| vec![error]);
+
+                if command.data()[0] == command::MAKE_CREDENTIAL && self.user_presence_polls > 0 {
+                    self.pending = Some((self.user_presence_polls, reply));
+                    response.push(0x01);
+                    return KEEPALIVE;
+                }
+
+                response.extend(reply);
+                status::OK
+            }
+            (0x11, (0x00, 0x00)) => match self.pending.take() {
+                Some((0, reply)) => {
+                    response.extend(reply);
+                    status::OK
+                }
+                Some((polls, reply)) => {
+                    self.pending = Some((polls - 1, reply));
+                    response.push(0x02);
+                    KEEPALIVE
+                }
+                None => status::CONDITIONS_OF_USE_NOT_SATISFIED,
+            },
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+fn ctap2(
+    user_presence_polls: usize,
+    max_payload_size: usize,
+) -> (Ctap2<VirtualCard>, Rc<RefCell<Vec<usize>>>) {
+    let received = Rc::new(RefCell::new(Vec::new()));
+    let applet = Ctap2Applet::new(user_presence_polls, received.clone());
+    let card = VirtualCard::new(FileSystem::new())
+        .with_applet(FIDO_AID, applet)
+        .with_max_payload_size(max_payload_size);
+
+    let mut ctap2 = Ctap2::new(card);
+    assert_eq!(block_on(ctap2.select()).unwrap(), b"FIDO_2_0");
+
+    (ctap2, received)
+}
+
+fn relying_party(id: &str) -> RelyingParty {
+    RelyingParty {
+        id: id.into(),
+        name: Some("Example".into()),
+    }
+}
+
+fn user(id: u8) -> User {
+    User {
+        id: vec![id; 16],
+        name: Some(format!("user{id}")),
+        display_name: None,
+    }
+}
+
+#[test]
+fn encodes_canonical_cbor() {
+    let value = Value::Map(vec![
+        (Value::text("type"), Value::text("public-key")),
+        (Value::integer(-1), Value::integer(1)),
+        (Value::text("id"), Value::bytes(&[0x01, 0x02])),
+        (Value::integer(10), Value::Bool(true)),
+        (
+            Value::integer(1),
+            Value::Array(vec![Value::Null, Value::integer(-500)]),
+        ),
+    ]);
+
+    let encoded = value.to_vec();
+    assert_eq!(
+        encoded,
+        [
+            0xA5, 0x01, 0x82, 0xF6, 0x39, 0x01, 0xF3, 0x0A, 0xF5, 0x20, 0x01, 0x62, b'i', b'd',
+            0x42, 0x01, 0x02, 0x64, b't', b'y', b'p', b'e', 0x6A, b'p', b'u', b'b', b'l', b'i',
+            b'c', b'-', b'k', b'e', b'y',
+        ]
+    );
+
+    let (decoded, rest) = Value::decode(&encoded).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(
+        decoded.get(1).unwrap().as_array().unwrap()[1].as_integer(),
+        Some(-500)
+    );
+    assert_eq!(
+        decoded.get_text("type").unwrap().as_text(),
+        Some("public-key")
+    );
+    assert_eq!(decoded.to_vec(), encoded);
+
+    assert_eq!(Value::decode(&encoded[..encoded.len() - 1]), None);
+    assert_eq!(
+        Value::decode(&[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+        None
+    );
+    assert_eq!(Value::decode(&[0x81; 64]), None);
+    assert_eq!(Value::decode(&[0xFB, 0, 0, 0, 0, 0, 0, 0, 0]), None);
+}
+
+#[test]
+fn gets_info_and_chains_long_messages() {
+    let (mut ctap2, received) = ctap2(0, 255);
+
+    let info = block_on(ctap2.get_info()).unwrap();
+    assert_eq!(info.versions, ["FIDO_2_0", "FIDO_2_1"]);
+    assert_eq!(info.aaguid, AAGUID);
+    assert_eq!(info.option("rk"), Some(true));
+    assert_eq!(info.option("clientPin"), Some(false));
+    assert_eq!(info.option("uv"), None);
+    assert_eq!(info.max_message_size, Some(1200));
+    assert_eq!(info.pin_uv_auth_protocols, [2, 1]);
+    assert_eq!(info.algorithms, [command::ES256]);
+
+    let request = MakeCredential::new(CLIENT_DATA_HASH, relying_party("example.com"), user(1))
+        .with_exclude_list(vec![vec![0xEE; 128]; 4]);
+    let attestation = block_on(ctap2.make_credential(&request)).unwrap();
+    assert!(received.borrow().last().unwrap() > &512);
+
+    assert_eq!(attestation.format, "none");
+    let data = &attestation.authenticator_data;
+    assert_eq!(data.rp_id_hash, sha256(b"example.com"));
+    assert!(data.user_present() && !data.user_verified());
+    assert_eq!(data.sign_count, 1);
+    let credential = data.attested_credential.as_ref().unwrap();
+    assert_eq!(credential.aaguid, AAGUID);
+    assert_eq!(credential.credential_id, [0x01; 48]);
+    assert_eq!(
+        credential.public_key.get(3).unwrap().as_integer(),
+        Some(command::ES256)
+    );
+    assert_eq!(
+        AuthenticatorData::parse(&attestation.auth_data).as_ref(),
+        Some(data)
+    );
+
+    let request = GetAssertion::new("example.com", CLIENT_DATA_HASH);
+    assert!(matches!(
+        block_on(ctap2.get_assertion(&request)),
+        Err(Ctap2Error::Ctap(ctap_status::NO_CREDENTIALS))
+    ));
+}
+
+#[test]
+fn polls_while_waiting_for_user_presence() {
+    let (ctap2, _) = ctap2(2, 255);
+    let keepalives = Rc::new(RefCell::new(Vec::new()));
+    let recorded = keepalives.clone();
+    let mut ctap2 = ctap2.with_keepalive(move |status| {
+        recorded.borrow_mut().push(status);
+        ControlFlow::Continue(())
+    });
+
+    let request = MakeCredential::new(CLIENT_DATA_HASH, relying_party("example.com"), user(1))
+        .with_resident_key();
+    let attestation = block_on(ctap2.make_credential(&request)).unwrap();
+    assert_eq!(
+        *keepalives.borrow(),
+        [
+            KeepaliveStatus::Processing,
+            KeepaliveStatus::UserPresenceNeeded,
+            KeepaliveStatus::UserPresenceNeeded,
+        ]
+    );
+    let id = attestation
+        .authenticator_data
+        .attested_credential
+        .unwrap()
+        .credential_id;
+
+    let request = MakeCredential::new(CLIENT_DATA_HASH, relying_party("example.com"), user(2))
+        .with_exclude_list(vec![id.clone()]);
+    assert!(matches!(
+        block_on(ctap2.make_credential(&request)),
+        Err(Ctap2Error::Ctap(ctap_status::CREDENTIAL_EXCLUDED))
+    ));
+
+    let request = GetAssertion::new("example.com", CLIENT_DATA_HASH);
+    let assertion = block_on(ctap2.get_assertion(&request)).unwrap();
+    assert_eq!(assertion.credential_id, Some(id));
+    assert_eq!(assertion.user, Some(user(1)));
+    assert_eq!(assertion.number_of_credentials, None);
+    assert_eq!(assertion.authenticator_data.sign_count, 2);
+    assert_eq!(assertion.signature, [0x30, 0x44, 0x01]);
+}
+
+#[test]
+fn stops_polling_when_cancelled() {
+    let (ctap2, _) = ctap2(usize::MAX, 255);
+    let keepalives = Rc::new(RefCell::new(0));
+    let counted = keepalives.clone();
+    let mut ctap2 = ctap2.with_keepalive(move |_| {
+        *counted.borrow_mut() += 1;
+        match *counted.borrow() {
+            3 => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        }
+    });
+
+    let request = MakeCredential::new(CLIENT_DATA_HASH, relying_party("example.com"), user(1));
+    assert!(matches!(
+        block_on(ctap2.make_credential(&request)),
+        Err(Ctap2Error::Cancelled)
+    ));
+    assert_eq!(*keepalives.borrow(), 3);
+}
+
+#[test]
+fn manages_the_pin_with_both_protocols() {
+    for protocol in [PinUvAuthProtocol::One, PinUvAuthProtocol::Two] {
+        let (mut ctap2, _) = ctap2(0, 255);
+        let mut platform = Platform::new();
+
+        assert!(matches!(
+            block_on(ctap2.set_pin(protocol, &mut platform, "123")),
+            Err(Ctap2Error::Ctap(ctap_status::PIN_POLICY_VIOLATION))
+        ));
+        block_on(ctap2.set_pin(protocol, &mut platform, "1234")).unwrap();
+        assert_eq!(
+            block_on(ctap2.get_info()).unwrap().option("clientPin"),
+            Some(true)
+        );
+
+        assert!(matches!(
+            block_on(ctap2.change_pin(protocol, &mut platform, "0000", "987654")),
+            Err(Ctap2Error::Ctap(ctap_status::PIN_INVALID))
+        ));
+        assert_eq!(block_on(ctap2.pin_retries(protocol.to_u8())).unwrap(), 7);
+        block_on(ctap2.change_pin(protocol, &mut platform, "1234", "987654")).unwrap();
+        assert_eq!(block_on(ctap2.pin_retries(protocol.to_u8())).unwrap(), 8);
+
+        let request = MakeCredential::new(CLIENT_DATA_HASH, relying_party("example.com"), user(1));
+        assert!(matches!(
+            block_on(ctap2.make_credential(&request)),
+            Err(Ctap2Error::Ctap(ctap_status::PUAT_REQUIRED))
+        ));
+
+        assert!(matches!(
+            block_on(ctap2.pin_token(protocol, &mut platform, "1234")),
+            Err(Ctap2Error::Ctap(ctap_status::PIN_INVALID))
+        ));
+        let token = block_on(ctap2.pin_uv_auth_token(
+            protocol,
+            &mut platform,
+            "987654",
+            permission::MAKE_CREDENTIAL | permission::GET_ASSERTION,
+            Some("example.com"),
+        ))
+        .unwrap();
+
+        let param = token.authenticate(&CLIENT_DATA_HASH);
+        assert_eq!(
+            param.len(),
+            if protocol == PinUvAuthProtocol::One {
+                16
+            } else {
+                32
+            }
+        );
+        let request = request.with_pin_uv_auth(protocol.to_u8(), param.clone());
+        let attestation = block_on(ctap2.make_credential(&request)).unwrap();
+        assert!(attestation.authenticator_data.user_verified());
+
+        let mut wrong = param;
+        wrong[0] ^= 0x01;
+        let request = MakeCredential::new(CLIENT_DATA_HASH, relying_party("example.com"), user(1))
+            .with_pin_uv_auth(protocol.to_u8(), wrong);
+        assert!(matches!(
+            block_on(ctap2.make_credential(&request)),
+            Err(Ctap2Error::Ctap(ctap_status::PIN_AUTH_INVALID))
+        ));
+    }
+}
+
+#[test]
+fn manages_discoverable_credentials() {
+    let (mut ctap2, _) = ctap2(0, 255);
+    let mut platform = Platform::new();
+    let protocol = PinUvAuthProtocol::Two;
+    block_on(ctap2.set_pin(protocol, &mut platform, "1234")).unwrap();
+    let token = block_on(ctap2.pin_token(protocol, &mut platform, "1234")).unwrap();
+
+    assert!(block_on(ctap2.relying_parties(&token)).unwrap().is_empty());
+
+    for (rp_id, user_id) in [("example.com", 1), ("example.org", 2), ("example.com", 3)] {
+        let param = token.authenticate(&CLIENT_DATA_HASH);
+        let request = MakeCredential::new(CLIENT_DATA_HASH, relying_party(rp_id), user(user_id))
+            .with_resident_key()
+            .with_pin_uv_auth(protocol.to_u8(), param);
+        block_on(ctap2.make_credential(&request)).unwrap();
+    }
+
+    assert_eq!(
+        block_on(ctap2.credentials_metadata(&token)).unwrap(),
+        (3, 22)
+    );
+
+    let relying_parties = block_on(ctap2.relying_parties(&token)).unwrap();
+    let ids: Vec<&str> = relying_parties
+        .iter()
+        .map(|(rp, _)| rp.id.as_str())
+        .collect();
+    assert_eq!(ids, ["example.com", "example.org"]);
+    assert_eq!(relying_parties[1].1, sha256(b"example.org"));
+
+    let credentials = block_on(ctap2.credentials(&token, &sha256(b"example.com"))).unwrap();
+    assert_eq!(credentials.len(), 2);
+    assert_eq!(credentials[0].total_credentials, Some(2));
+    assert_eq!(credentials[0].user, Some(user(1)));
+    assert_eq!(credentials[1].user, Some(user(3)));
+
+    let param = token.authenticate(&CLIENT_DATA_HASH);
+    let request = GetAssertion::new("example.com", CLIENT_DATA_HASH)
+        .with_pin_uv_auth(protocol.to_u8(), param);
+    let first = block_on(ctap2.get_assertion(&request)).unwrap();
+    assert_eq!(first.number_of_credentials, Some(2));
+    assert!(first.authenticator_data.user_verified());
+    let next = block_on(ctap2.get_next_assertion()).unwrap();
+    assert_eq!(next.user, Some(user(3)));
+
+    let id = credentials[0].credential_id.clone().unwrap();
+    block_on(ctap2.delete_credential(&token, &id)).unwrap();
+    assert!(matches!(
+        block_on(ctap2.delete_credential(&token, &id)),
+        Err(Ctap2Error::Ctap(ctap_status::NO_CREDENTIALS))
+    ));
+    assert_eq!(
+        block_on(ctap2.credentials_metadata(&token)).unwrap(),
+        (2, 23)
+    );
+
+    let request = ClientPin::get_pin_retries(protocol.to_u8());
+    assert_eq!(
+        block_on(ctap2.client_pin(&request)).unwrap().pin_retries,
+        Some(8)
+    );
+}
@@ -0,0 +1,439 @@
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        emv::{
+            PPSE, PSE,
+            client::{Emv, EmvError},
+            data::Field,
+            dol::{self, DolEntry, TerminalData},
+            element::{Element, Format, tag},
+            object::{AflEntry, ApplicationCryptogram, CryptogramType, ProcessingOptions},
+        },
+        iso_7816::status,
+        status::ApduStatus,
+    },
+    card::{applet::Applet, command::CardCommand, file::FileSystem, virtual_card::VirtualCard},
+};
+
+mod common;
+
+use common::tlv;
+
+const VISA: &[u8] = &[0xA0, 0x00, 0x00, 0x00, 0x03, 0x10, 0x10];
+const MASTERCARD: &[u8] = &[0xA0, 0x00, 0x00, 0x00, 0x04, 0x10, 0x10];
+
+/// TTQ, amount, unpredictable number, currency and date.
+const PDOL: &[u8] = &[
+    0x9F, 0x66, 0x04, 0x9F, 0x02, 0x06, 0x9F, 0x37, 0x04, 0x5F, 0x2A, 0x02, 0x9A, 0x03,
+];
+
+/// Amount, other amount, country, TVR, currency, date, type and
+/// unpredictable number.
+const CDOL1: &[u8] = &[
+    0x9F, 0x02, 0x06, 0x9F, 0x03, 0x06, 0x9F, 0x1A, 0x02, 0x95, 0x05, 0x5F, 0x2A, 0x02, 0x9A, 0x03,
+    0x9C, 0x01, 0x9F, 0x37, 0x04,
+];
+
+fn application_template(aid: &[u8], label: &str, priority: u8) -> Vec<u8> {
+    let content = [
+        tlv(0x4F, aid),
+        tlv(0x50, label.as_bytes()),
+        tlv(0x87, &[priority]),
+    ]
+    .concat();
+    tlv(0x61, &content)
+}
+
+fn terminal() -> TerminalData {
+    TerminalData::new()
+        .with(
+            tag::TERMINAL_TRANSACTION_QUALIFIERS,
+            &[0x36, 0x00, 0x40, 0x00],
+        )
+        .with_amount(1234)
+        .with_currency(978)
+        .with_country(276)
+        .with(tag::TRANSACTION_DATE, &[0x26, 0x10, 0x19])
+        .with(tag::TRANSACTION_TYPE, &[0x00])
+        .with_unpredictable_number([0xDE, 0xAD, 0xBE, 0xEF])
+}
+
+/// The contact payment system environment, whose directory in SFI 1 lists
+/// Mastercard ahead of Visa.
+struct PseApplet;
+
+impl Applet for PseApplet {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let proprietary = [tlv(0x88, &[0x01]), tlv(0x5F2D, b"en")].concat();
+        let fci = [tlv(0x84, PSE), tlv(0xA5, &proprietary)].concat();
+        response.extend(tlv(0x6F, &fci));
+        status::OK
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let entry = match (command.instruction(), command.parameters()) {
+            (0xB2, (0x01, 0x0C)) => application_template(VISA, "VISA CREDIT", 0x02),
+            (0xB2, (0x02, 0x0C)) => application_template(MASTERCARD, "MASTERCARD", 0x81),
+            (0xB2, (_, 0x0C)) => return status::RECORD_NOT_FOUND,
+            _ => return status::INSTRUCTION_NOT_SUPPORTED,
+        };
+
+        response.extend(tlv(0x70, &entry));
+        status::OK
+    }
+}
+
+/// The contactless payment system environment, which lists Visa alone.
+struct PpseApplet;
+
+impl Applet for PpseApplet {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let mut content = application_template(VISA, "VISA CREDIT", 0x01)[2..].to_vec();
+        content.extend(tlv(0x9F2A, &[0x03]));
+
+        let directory = tlv(0x61, &content);
+        let proprietary = tlv(0xBF0C, &directory);
+        let fci = [tlv(0x84, PPSE), tlv(0xA5, &proprietary)].concat();
+        response.extend(tlv(0x6F, &fci));
+        status::OK
+    }
+
+    fn process(&mut self, _command: &CardCommand<'_>, _response: &mut Vec<u8>) -> ApduStatus {
+        status::INSTRUCTION_NOT_SUPPORTED
+    }
+}
+
+/// A Visa application with a PDOL, two files of records and a CDOL1.
+struct PaymentApplet {
+    format_1: bool,
+    atc: u16,
+}
+
+impl PaymentApplet {
+    fn record(sfi: u8, number: u8) -> Option<Vec<u8>> {
+        let content = match (sfi, number) {
+            (1, 1) => [
+                tlv(
+                    0x57,
+                    &[
+                        0x47, 0x61, 0x73, 0x90, 0x01, 0x01, 0x00, 0x10, 0xD2, 0x51, 0x22, 0x01,
+                        0x00, 0x00, 0x00, 0x00, 0x0F,
+                    ],
+                ),
+                tlv(0x5F20, b"DOE/JOHN                  "),
+                tlv(0x9F1F, b"0000000000"),
+            ]
+            .concat(),
+            (2, 1) => [
+                tlv(0x5A, &[0x47, 0x61, 0x73, 0x90, 0x01, 0x01, 0x00, 0x10]),
+                tlv(0x5F24, &[0x25, 0x12, 0x31]),
+                tlv(0x5F25, &[0x21, 0x01, 0x01]),
+                tlv(0x5F28, &[0x08, 0x40]),
+                tlv(0x5F34, &[0x01]),
+                tlv(0x8C, CDOL1),
+            ]
+            .concat(),
+            (2, 2) => [
+                tlv(0x8E, &[0, 0, 0, 0, 0, 0, 0, 0, 0x42, 0x03, 0x1F, 0x03]),
+                tlv(0x9F07, &[0xFF, 0x00]),
+                tlv(0x9F0D, &[0xF0, 0x40, 0x00, 0x88, 0x00]),
+            ]
+            .concat(),
+            _ => return None,
+        };
+
+        Some(tlv(0x70, &content))
+    }
+}
+
+impl Applet for PaymentApplet {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let proprietary = [
+            tlv(0x50, b"VISA CREDIT"),
+            tlv(0x87, &[0x01]),
+            tlv(0x9F38, PDOL),
+            tlv(0x5F2D, b"en"),
+            tlv(0xBF0C, &tlv(0x9F4D, &[0x0B, 0x0A])),
+        ]
+        .concat();
+        let fci = [tlv(0x84, VISA), tlv(0xA5, &proprietary)].concat();
+        response.extend(tlv(0x6F, &fci));
+        status::OK
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let proprietary = command.class_byte() & 0x80 != 0;
+
+        match (proprietary, command.instruction(), command.parameters()) {
+            (true, 0xA8, (0x00, 0x00)) => {
+                let expected = [
+                    &[0x83, 19, 0x36, 0x00, 0x40, 0x00][..],
+                    &[0x00, 0x00, 0x00, 0x00, 0x12, 0x34],
+                    &[0xDE, 0xAD, 0xBE, 0xEF, 0x09, 0x78, 0x26, 0x10, 0x19],
+                ]
+                .concat();
+                if command.data() != expected {
+                    return status::CONDITIONS_OF_USE_NOT_SATISFIED;
+                }
+
+                let aip = [0x59, 0x00];
+                let afl = [0x08, 0x01, 0x01, 0x00, 0x10, 0x01, 0x02, 0x01];
+                match self.format_1 {
+                    true => response.extend(tlv(0x80, &[&aip[..], &afl].concat())),
+                    false => {
+                        let content = [tlv(0x82, &aip), tlv(0x94, &afl)].concat();
+                        response.extend(tlv(0x77, &content));
+                    }
+                }
+                status::OK
+            }
+            (false, 0xB2, (number, reference)) if reference & 0x07 == 0x04 => {
+                match Self::record(reference >> 3, number) {
+                    Some(record) => {
+                        response.extend(record);
+                        status::OK
+                    }
+                    None => status::RECORD_NOT_FOUND,
+                }
+            }
+            (true, 0xAE, (reference, 0x00)) => {
+                if command.data().len() != 29 {
+                    return status::WRONG_LENGTH;
+                }
+
+                self.atc += 1;
+                let cid = reference & 0xC0;
+                let mut content =
+                    [tlv(0x9F27, &[cid]), tlv(0x9F36, &self.atc.to_be_bytes())].concat();
+                match reference & 0x10 {
+                    0 => content.extend(tlv(0x9F26, &[0xAC; 8])),
+                    _ => content.extend(tlv(0x9F4B, &[0x6A; 144])),
+                }
+                content.extend(tlv(0x9F10, &[0x06, 0x01, 0x0A, 0x03, 0xA0, 0x00, 0x00]));
+                response.extend(tlv(0x77, &content));
+                status::OK
+            }
+            (true, 0xCA, (0x9F, 0x36)) => {
+                response.extend(tlv(0x9F36, &self.atc.to_be_bytes()));
+                status::OK
+            }
+            (true, 0xCA, _) => status::REFERENCED_DATA_NOT_FOUND,
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+fn emv(format_1: bool) -> Emv<VirtualCard> {
+    let card = VirtualCard::new(FileSystem::new())
+        .with_applet(PSE, PseApplet)
+        .with_applet(PPSE, PpseApplet)
+        .with_applet(VISA, PaymentApplet { format_1, atc: 0 });
+
+    Emv::new(card)
+}
+
+#[test]
+fn lists_applications_from_pse_and_ppse() {
+    let mut emv = emv(false);
+    assert!(matches!(
+        block_on(emv.select(MASTERCARD)),
+        Err(EmvError::NotFound)
+    ));
+
+    let entries = block_on(emv.select_pse()).unwrap();
+    let aids: Vec<&[u8]> = entries.iter().map(|entry| &entry.aid[..]).collect();
+    assert_eq!(aids, [MASTERCARD, VISA]);
+    assert_eq!(entries[0].label.as_deref(), Some("MASTERCARD"));
+    assert_eq!(entries[0].priority, Some(1));
+    assert!(entries[0].requires_confirmation);
+    assert!(!entries[1].requires_confirmation);
+
+    let entries = block_on(emv.select_ppse()).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].aid, VISA);
+    assert_eq!(entries[0].kernel_identifier.as_deref(), Some(&[0x03][..]));
+}
+
+#[test]
+fn reads_an_application() {
+    for format_1 in [false, true] {
+        let mut emv = emv(format_1);
+        let application = block_on(emv.read_application(VISA, &terminal())).unwrap();
+
+        assert_eq!(application.fci.df_name, VISA);
+        assert_eq!(application.fci.pdol.as_deref(), Some(PDOL));
+        assert_eq!(application.pdol_data.len(), 19);
+
+        let options = &application.processing_options;
+        assert!(options.supports(ProcessingOptions::SDA));
+        assert!(options.supports(ProcessingOptions::CDA));
+        assert!(!options.supports(ProcessingOptions::DDA));
+        assert_eq!(
+            options.afl[1],
+            AflEntry {
+                sfi: 2,
+                first_record: 1,
+                last_record: 2,
+                authenticated_records: 1,
+            }
+        );
+
+        let records: Vec<(u8, u8, bool)> = application
+            .records
+            .iter()
+            .map(|record| (record.sfi, record.number, record.authenticated))
+            .collect();
+        assert_eq!(records, [(1, 1, false), (2, 1, true), (2, 2, false)]);
+
+        let data = &application.data;
+        assert_eq!(data.pan().as_deref(), Some("4761739001010010"));
+        assert_eq!(data.expiration_date().as_deref(), Some("251231"));
+        assert_eq!(data.cardholder_name().as_deref(), Some("DOE/JOHN"));
+        assert_eq!(data.application_label().as_deref(), Some("VISA CREDIT"));
+        assert_eq!(data.get(tag::CDOL1), Some(CDOL1));
+        assert_eq!(data.get(tag::LOG_ENTRY), Some(&[0x0B, 0x0A][..]));
+        assert_eq!(
+            data.field(tag::ISSUER_COUNTRY_CODE),
+            Some(Field::Digits("0840".into()))
+        );
+        assert_eq!(
+            data.field(tag::LANGUAGE_PREFERENCE),
+            Some(Field::Text("en".into()))
+        );
+        assert_eq!(
+            data.field(tag::APPLICATION_USAGE_CONTROL),
+            Some(Field::Binary(&[0xFF, 0x00]))
+        );
+        assert_eq!(
+            data.get(tag::APPLICATION_INTERCHANGE_PROFILE).is_some(),
+            !format_1
+        );
+
+        let names: Vec<&str> = data
+            .iter()
+            .filter_map(|(_, element, _)| element.map(|element| element.name))
+            .collect();
+        assert!(names.contains(&"Application Primary Account Number (PAN)"));
+        assert!(data.iter().all(|(_, element, _)| element.is_some()));
+    }
+}
+
+#[test]
+fn generates_cryptograms_over_the_cdol() {
+    let mut emv = emv(false);
+    let terminal = terminal();
+    let application = block_on(emv.read_application(VISA, &terminal)).unwrap();
+    let cdol_data = terminal
+        .fill(application.data.get(tag::CDOL1).unwrap())
+        .unwrap();
+    assert_eq!(cdol_data[..12], [0, 0, 0, 0, 0x12, 0x34, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(cdol_data[12..14], [0x02, 0x76]);
+
+    let cryptogram = block_on(emv.generate_ac(CryptogramType::Arqc, false, &cdol_data)).unwrap();
+    assert_eq!(cryptogram.cryptogram_type(), Some(CryptogramType::Arqc));
+    assert_eq!(cryptogram.atc, 1);
+    assert_eq!(cryptogram.cryptogram, Some([0xAC; 8]));
+    assert_eq!(cryptogram.issuer_application_data.unwrap().len(), 7);
+    assert_eq!(cryptogram.signed_dynamic_data, None);
+
+    let cryptogram = block_on(emv.generate_ac(CryptogramType::Tc, true, &cdol_data)).unwrap();
+    assert_eq!(cryptogram.cryptogram_type(), Some(CryptogramType::Tc));
+    assert_eq!(cryptogram.cryptogram, None);
+    assert_eq!(cryptogram.signed_dynamic_data, Some(vec![0x6A; 144]));
+    assert_eq!(
+        ApplicationCryptogram::parse(&cryptogram.response),
+        Some(cryptogram)
+    );
+
+    assert_eq!(
+        block_on(emv.get_data(tag::APPLICATION_TRANSACTION_COUNTER as u16)).unwrap(),
+        [0x00, 0x02]
+    );
+    assert!(matches!(
+        block_on(emv.get_data(tag::PIN_TRY_COUNTER as u16)),
+        Err(EmvError::Rejected(status::REFERENCED_DATA_NOT_FOUND))
+    ));
+    assert!(matches!(
+        block_on(emv.generate_ac(CryptogramType::Aac, false, &cdol_data[1..])),
+        Err(EmvError::Rejected(status::WRONG_LENGTH))
+    ));
+}
+
+#[test]
+fn fills_data_object_lists_by_format() {
+    assert_eq!(
+        dol::parse(&[0x9F, 0x02, 0x06, 0x5A, 0x0A, 0xDF, 0x81, 0x01, 0x02]),
+        Some(vec![
+            DolEntry {
+                tag: 0x9F02,
+                length: 6
+            },
+            DolEntry {
+                tag: 0x5A,
+                length: 10
+            },
+            DolEntry {
+                tag: 0xDF8101,
+                length: 2
+            },
+        ])
+    );
+    assert_eq!(dol::parse(&[0x9F, 0x02]), None);
+    assert_eq!(dol::numeric(1234, 3), [0x00, 0x12, 0x34]);
+    assert_eq!(dol::numeric(987654, 2), [0x76, 0x54]);
+
+    let terminal = TerminalData::new()
+        .with(
+            tag::AMOUNT_AUTHORISED,
+            &[0x00, 0x00, 0x00, 0x00, 0x12, 0x34],
+        )
+        .with(tag::PAN, &[0x12, 0x34, 0x5F])
+        .with(tag::MERCHANT_NAME_AND_LOCATION, b"SHOP")
+        .with(0xDF8101, &[0x01, 0x02, 0x03]);
+
+    let dol = [
+        &[0x9F, 0x02, 0x03][..],
+        &[0x9F, 0x02, 0x08],
+        &[0x5A, 0x04],
+        &[0x9F, 0x4E, 0x06],
+        &[0x9F, 0x4E, 0x02],
+        &[0xDF, 0x81, 0x01, 0x02],
+        &[0x9F, 0x37, 0x04],
+    ]
+    .concat();
+    assert_eq!(
+        terminal.fill(&dol).unwrap(),
+        [
+            &[0x00, 0x12, 0x34][..],
+            &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x34],
+            &[0x12, 0x34, 0x5F, 0xFF],
+            b"SHOP\0\0",
+            b"SH",
+            &[0x01, 0x02],
+            &[0x00; 4],
+        ]
+        .concat()
+    );
+
+    let element = Element::find(tag::CVM_LIST).unwrap();
+    assert_eq!(element.format, Format::Binary);
+    assert_eq!(Element::find(0xDF8101), None);
+    assert_eq!(
+        Field::decode(Format::CompressedNumeric, &[0x12, 0x34, 0x5F]),
+        Field::Digits("12345".into())
+    );
+
+    assert_eq!(AflEntry::parse_all(&[0x08, 0x01, 0x01]), None);
+    assert_eq!(AflEntry::parse_all(&[0x08, 0x02, 0x01, 0x00]), None);
+    assert_eq!(AflEntry::parse_all(&[0x08, 0x01, 0x02, 0x03]), None);
+    assert_eq!(AflEntry::parse_all(&[0x00, 0x01, 0x01, 0x00]), None);
+
+    let cryptogram = ApplicationCryptogram::parse(&tlv(
+        0x80,
+        &[&[0x80, 0x00, 0x07][..], &[0x11; 8], &[0x06, 0x01]].concat(),
+    ))
+    .unwrap();
+    assert_eq!(cryptogram.atc, 7);
+    assert_eq!(cryptogram.cryptogram, Some([0x11; 8]));
+    assert_eq!(cryptogram.issuer_application_data, Some(vec![0x06, 0x01]));
+}
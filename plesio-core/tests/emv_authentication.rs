@@ -0,0 +1,615 @@
+use num_bigint::BigUint;
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        emv::{
+            authentication::{
+                self, CaKeyStore, CaPublicKey, OdaCrypto, OdaError, PublicKey, Verifier,
+            },
+            client::{Application, Emv},
+            data::EmvData,
+            dol::TerminalData,
+            element::tag,
+            object::{CryptogramType, ProcessingOptions},
+        },
+        iso_7816::status,
+        status::ApduStatus,
+    },
+    card::{applet::Applet, command::CardCommand, file::FileSystem, virtual_card::VirtualCard},
+};
+use sha1::{Digest, Sha1};
+
+mod common;
+
+use common::{hex, tlv};
+
+// Payment systems publish their test CA public keys, but not the private
+// halves, so certificates under them cannot be made here. The keys below
+// were generated for these tests instead, at the sizes of test keys in the
+// field: a 1024-bit CA key with exponent 3, an 896-bit issuer key with
+// exponent 3 and a 768-bit ICC key with exponent 65537. Both certificates
+// need a remainder at these sizes.
+
+const CA_MODULUS: &str = concat!(
+    "cdfa9a5922778614d1a0b14ce4cfbc5fc84575f8c868b88b0478bc25b4dfe932",
+    "9048879616a51ad573674326612ece2c147d9802086078522e13931b43171796",
+    "a85a328783324886f05d9272e3e79a2255613fe9b9c2e4a24178be9b2f5d0aa1",
+    "79f75c64b5a9a8890cbba99914604e446a7efd1b0a8699f11c2a8dad03b57b23",
+);
+
+const CA_PRIVATE_EXPONENT: &str = concat!(
+    "8951bc3b6c4faeb88bc076334335283fdad8f950859b25b202fb2819233ff0cc",
+    "60305a640f18bc8e4cef8219961f341d62fe6556b0405036c96262122cba0fb8",
+    "923520c42c3af7aea0042eeb6c3d002bc4a2d097e6a792c2f73a69de20209b42",
+    "47a64f13d385648a59152179778737c0147defcf4305bc1b1241d3cb7862084b",
+);
+
+const ISSUER_MODULUS: &str = concat!(
+    "f308c0f107124558d5bd6d064c8ba4cf9731f400865d1224091652a74424b76f",
+    "b91e8f751a26baa0af76f69773984c057a5d2efaf79e9401bfaa797cd1c3c093",
+    "1341d8355d33bb401e33ba2a525c886044ab2517cb98faa841785df117467746",
+    "a828f4aa600355f6f98f78ffa4f02aad",
+);
+
+const ISSUER_PRIVATE_EXPONENT: &str = concat!(
+    "a205d5f604b6d8e5e3d39e043307c3350f76a2ab043e0c18060ee1c4d8187a4a",
+    "7b69b4f8bc19d1c074f9f9ba4d1032ae519374a74fbf0d55332df31adca32b87",
+    "2808561cce0adc1988fd1a7fee394ee9b7a5b48ae1817a32241905ea3a028093",
+    "73230bf339b40984943026ca7782af6b",
+);
+
+const ICC_MODULUS: &str = concat!(
+    "be77e7319a519e1bb76038cd8293a44b6fb68d9183a4e65dd2e160745d72f8a0",
+    "4b19b9fcc3337d41d0717f0fbffa401f0d2d9e7e31e760b2648517c9d82d286c",
+    "9cf550ed8bfb7aef2d975a7936bdac3e9cbe50e3a22225d370ca834196933e8d",
+);
+
+const ICC_PRIVATE_EXPONENT: &str = concat!(
+    "f10ecf8bf7c35457bfbe0826111ccad2ae905c9bce00e58dd95764bbe189a121",
+    "3deb5178959494f2b6dcedf30105b81f65916ef058f54ab2032dd6af61aafc4b",
+    "2d10e6c452e62421ec3f099ebe65f7f9420a5cd9b6598f855ebb06c0a3a1701",
+);
+
+/// Visa's published test CA public key 92, with exponent 3.
+const VISA_TEST_CA_92: &str = concat!(
+    "996AF56F569187D09293C14810450ED8EE3357397B18A2458EFAA92DA3B6DF65",
+    "14EC060195318FD43BE9B8F0CC669E3F844057CBDDF8BDA191BB64473BC8DC9A",
+    "730DB8F6B4EDE3924186FFD9B8C7735789C23A36BA0B8AF65372EB57EA5D89E7",
+    "D14E9C7B6B557460F10885DA16AC923F15AF3758F0F03EBD3C5C2C949CBA306D",
+    "B44E6A2C076C5F67E281D7EF56785DC4D75945E491F01918800A9E2DC66F6008",
+    "0566CE0DAF8D17EAD46AD8E30A247C9F",
+);
+
+const VISA_TEST_CA_92_CHECKSUM: &str = "429C954A3859CEF91295F663C963E582ED6EB253";
+
+/// The issuer key certified by the CA key for issuer `476173`, expiring
+/// December 2030, with serial number 000001.
+const ISSUER_CERTIFICATE: &str = concat!(
+    "0C52270E1A9377F647F567D0B41213433F212D8924F1ABE311591EA2DD0CA1E1",
+    "EE3CFA5B01452EB5A92FFC848AC3AF698B59B41B7EBABDFCF16A0FAB1947D0E3",
+    "5FA36AED09FD5179689E92C398E73BDD42FFF89CE177FD3BB752E01BB5F20478",
+    "B112D1AC9F98B210446306A7D9BB1F87C50E252B40F2AA225D76046FFB9C161A",
+);
+
+/// The ICC key certified by the issuer key for PAN 4761739001010010,
+/// expiring December 2028, with serial number 000002, over
+/// [`CERTIFIED_STATIC_DATA`].
+const ICC_CERTIFICATE: &str = concat!(
+    "AC5D6906FEF75781D475F846A584DB776BBF7368D94CFA3B6D50E8E3D88575F6",
+    "5AD59D8A3230DA8ACE55F4D7B58712C5D91C291CE9621859CB524B7B85376D0C",
+    "C99447342B4B375D63B7E714E1B6E20153B37D9BD8C4628FBA687CA6339338F1",
+    "1DC73F076CF8C76D853673BC403A70BD",
+);
+
+const CERTIFIED_STATIC_DATA: &str =
+    "5A0847617390010100105F24032812318C0B9F02069A039F37049F4A01826100";
+
+const VISA: &[u8] = &[0xA0, 0x00, 0x00, 0x00, 0x03, 0x10, 0x10];
+const RID: [u8; 5] = [0xA0, 0x00, 0x00, 0x00, 0x03];
+const CA_INDEX: u8 = 0xF0;
+const PAN: &[u8] = &[0x47, 0x61, 0x73, 0x90, 0x01, 0x01, 0x00, 0x10];
+const ICC_EXPONENT: &[u8] = &[0x01, 0x00, 0x01];
+
+/// Unpredictable number and date.
+const PDOL: &[u8] = &[0x9F, 0x37, 0x04, 0x9A, 0x03];
+
+/// Amount, date and unpredictable number.
+const CDOL1: &[u8] = &[0x9F, 0x02, 0x06, 0x9A, 0x03, 0x9F, 0x37, 0x04];
+
+const TODAY: [u8; 3] = [0x26, 0x10, 0x19];
+const UNPREDICTABLE_NUMBER: [u8; 4] = [0xDE, 0xAD, 0xBE, 0xEF];
+
+struct SoftwareCrypto;
+
+impl OdaCrypto for SoftwareCrypto {
+    fn rsa_recover(&self, modulus: &[u8], exponent: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+        let modulus = BigUint::from_bytes_be(modulus);
+        let data = BigUint::from_bytes_be(data);
+        if data >= modulus {
+            return None;
+        }
+
+        let recovered = data
+            .modpow(&BigUint::from_bytes_be(exponent), &modulus)
+            .to_bytes_be();
+        let mut padded = vec![0; modulus.to_bytes_be().len() - recovered.len()];
+        padded.extend(recovered);
+        Some(padded)
+    }
+
+    fn sha1(&self, data: &[u8]) -> [u8; 20] {
+        Sha1::digest(data).into()
+    }
+}
+
+fn sha1(parts: &[&[u8]]) -> [u8; 20] {
+    SoftwareCrypto.sha1(&parts.concat())
+}
+
+fn number(value: &str) -> BigUint {
+    BigUint::parse_bytes(value.as_bytes(), 16).unwrap()
+}
+
+fn modulus(key: &str) -> Vec<u8> {
+    number(key).to_bytes_be()
+}
+
+/// Signs `data` the way EMV does: header, `data`, the hash over `data` and
+/// `extra`, trailer, raised to the private exponent.
+fn sign(modulus: &str, private_exponent: &str, data: &[u8], extra: &[u8]) -> Vec<u8> {
+    let message = [&[0x6A], data, &sha1(&[data, extra]), &[0xBC]].concat();
+    assert_eq!(message.len() * 2, modulus.len());
+
+    let signature = BigUint::from_bytes_be(&message)
+        .modpow(&number(private_exponent), &number(modulus))
+        .to_bytes_be();
+    [vec![0; message.len() - signature.len()], signature].concat()
+}
+
+fn pad(data: Vec<u8>, length: usize) -> Vec<u8> {
+    let mut data = data;
+    data.resize(length, 0xBB);
+    data
+}
+
+/// An application that supports SDA, DDA and CDA, with authenticated
+/// records in files 1 and 11 and its certificates in file 2.
+struct OdaApplet {
+    records: Vec<(u8, u8, Vec<u8>)>,
+    pdol_data: Vec<u8>,
+    atc: u16,
+}
+
+impl OdaApplet {
+    const AIP: [u8; 2] = [0x61, 0x00];
+    const AFL: [u8; 12] = [
+        0x08, 0x01, 0x01, 0x01, 0x58, 0x01, 0x01, 0x01, 0x10, 0x01, 0x03, 0x00,
+    ];
+    const DYNAMIC_NUMBER: [u8; 2] = [0x0D, 0x17];
+
+    fn new(issuer_expiry: [u8; 2]) -> Self {
+        let authenticated = [
+            tlv(
+                0x70,
+                &[
+                    tlv(0x5A, PAN),
+                    tlv(0x5F24, &[0x28, 0x12, 0x31]),
+                    tlv(0x8C, CDOL1),
+                    tlv(0x9F4A, &[0x82]),
+                ]
+                .concat(),
+            ),
+            tlv(0x70, &tlv(0x5F28, &[0x08, 0x40])),
+        ];
+        let static_data = [
+            &authenticated[0][2..],
+            &authenticated[1][..],
+            &Self::AIP[..],
+        ]
+        .concat();
+
+        let issuer_modulus = modulus(ISSUER_MODULUS);
+        let (leftmost, issuer_remainder) = issuer_modulus.split_at(128 - 36);
+        let issuer_certificate = sign(
+            CA_MODULUS,
+            CA_PRIVATE_EXPONENT,
+            &[
+                &[0x02, 0x47, 0x61, 0x73, 0xFF],
+                &issuer_expiry[..],
+                &[0x00, 0x00, 0x01, 0x01, 0x01, 112, 1],
+                leftmost,
+            ]
+            .concat(),
+            &[issuer_remainder, &[0x03]].concat(),
+        );
+
+        let icc_modulus = modulus(ICC_MODULUS);
+        let (leftmost, icc_remainder) = icc_modulus.split_at(112 - 42);
+        let icc_certificate = sign(
+            ISSUER_MODULUS,
+            ISSUER_PRIVATE_EXPONENT,
+            &[
+                &[0x04][..],
+                PAN,
+                &[0xFF, 0xFF, 0x12, 0x28, 0x00, 0x00, 0x02, 0x01, 0x01, 96, 3],
+                leftmost,
+            ]
+            .concat(),
+            &[icc_remainder, ICC_EXPONENT, &static_data].concat(),
+        );
+
+        let signed_static_data = sign(
+            ISSUER_MODULUS,
+            ISSUER_PRIVATE_EXPONENT,
+            &pad(vec![0x03, 0x01, 0xDA, 0xC0], 112 - 22),
+            &static_data,
+        );
+
+        let certificates = [
+            [
+                tlv(0x8F, &[CA_INDEX]),
+                tlv(0x90, &issuer_certificate),
+                tlv(0x92, issuer_remainder),
+                tlv(0x9F32, &[0x03]),
+            ]
+            .concat(),
+            [
+                tlv(0x93, &signed_static_data),
+                tlv(0x9F49, authentication::DEFAULT_DDOL),
+            ]
+            .concat(),
+            [
+                tlv(0x9F46, &icc_certificate),
+                tlv(0x9F47, ICC_EXPONENT),
+                tlv(0x9F48, icc_remainder),
+            ]
+            .concat(),
+        ];
+
+        let [sfi_1, sfi_11] = authenticated;
+        let [first, second, third] = certificates.map(|content| tlv(0x70, &content));
+        Self {
+            records: vec![
+                (1, 1, sfi_1),
+                (11, 1, sfi_11),
+                (2, 1, first),
+                (2, 2, second),
+                (2, 3, third),
+            ],
+            pdol_data: Vec::new(),
+            atc: 0,
+        }
+    }
+
+    /// Signs ICC dynamic data over `terminal_data` with the ICC key.
+    fn sign_dynamic(dynamic_data: &[u8], terminal_data: &[u8]) -> Vec<u8> {
+        let data = [&[0x05, 0x01, dynamic_data.len() as u8], dynamic_data].concat();
+        sign(
+            ICC_MODULUS,
+            ICC_PRIVATE_EXPONENT,
+            &pad(data, 96 - 22),
+            terminal_data,
+        )
+    }
+}
+
+impl Applet for OdaApplet {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let proprietary = tlv(0x9F38, PDOL);
+        let fci = [tlv(0x84, VISA), tlv(0xA5, &proprietary)].concat();
+        response.extend(tlv(0x6F, &fci));
+        status::OK
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let proprietary = command.class_byte() & 0x80 != 0;
+
+        match (proprietary, command.instruction(), command.parameters()) {
+            (true, 0xA8, (0x00, 0x00)) => {
+                self.pdol_data = command.data()[2..].to_vec();
+                let content = [tlv(0x82, &Self::AIP), tlv(0x94, &Self::AFL)].concat();
+                response.extend(tlv(0x77, &content));
+                status::OK
+            }
+            (false, 0xB2, (number, reference)) => {
+                let record = self
+                    .records
+                    .iter()
+                    .find(|(sfi, record, _)| (*sfi, *record) == (reference >> 3, number));
+                match record {
+                    Some((_, _, record)) => {
+                        response.extend(record);
+                        status::OK
+                    }
+                    None => status::RECORD_NOT_FOUND,
+                }
+            }
+            (false, 0x88, (0x00, 0x00)) => {
+                let dynamic_data = [&[0x02][..], &Self::DYNAMIC_NUMBER].concat();
+                let signed = Self::sign_dynamic(&dynamic_data, command.data());
+                response.extend(tlv(0x80, &signed));
+                status::OK
+            }
+            (true, 0xAE, (reference, 0x00)) if reference & 0x10 != 0 => {
+                self.atc += 1;
+                let cid = reference & 0xC0;
+                let cryptogram = [0xAC; 8];
+                let objects = [
+                    tlv(0x9F27, &[cid]),
+                    tlv(0x9F36, &self.atc.to_be_bytes()),
+                    tlv(0x9F10, &[0x06, 0x01, 0x0A, 0x03, 0xA0, 0x00, 0x00]),
+                ];
+
+                let cdol_data = command.data();
+                let hash = sha1(&[&self.pdol_data, cdol_data, &objects.concat()]);
+                let dynamic_data = [
+                    &[0x02][..],
+                    &Self::DYNAMIC_NUMBER,
+                    &[cid],
+                    &cryptogram,
+                    &hash,
+                ]
+                .concat();
+                let signed = Self::sign_dynamic(&dynamic_data, &cdol_data[cdol_data.len() - 4..]);
+
+                let content = [
+                    &objects[0][..],
+                    &objects[1],
+                    &tlv(0x9F4B, &signed),
+                    &objects[2],
+                ]
+                .concat();
+                response.extend(tlv(0x77, &content));
+                status::OK
+            }
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+fn terminal() -> TerminalData {
+    TerminalData::new()
+        .with_amount(1234)
+        .with(tag::TRANSACTION_DATE, &TODAY)
+        .with_unpredictable_number(UNPREDICTABLE_NUMBER)
+}
+
+fn ca_keys() -> CaKeyStore {
+    CaKeyStore::new().with_key(CaPublicKey::new(
+        RID,
+        CA_INDEX,
+        &modulus(CA_MODULUS),
+        &[0x03],
+    ))
+}
+
+fn read(applet: OdaApplet) -> (Emv<VirtualCard>, Application) {
+    let card = VirtualCard::new(FileSystem::new()).with_applet(VISA, applet);
+    let mut emv = Emv::new(card);
+    let application = block_on(emv.read_application(VISA, &terminal())).unwrap();
+    (emv, application)
+}
+
+#[test]
+fn verifies_static_data() {
+    let (_, application) = read(OdaApplet::new([0x12, 0x30]));
+    assert!(
+        application
+            .processing_options
+            .supports(ProcessingOptions::SDA)
+    );
+
+    let ca_keys = ca_keys();
+    let verifier = Verifier::new(&SoftwareCrypto, &ca_keys, TODAY);
+    let data = &application.data;
+
+    let issuer = verifier.issuer_key(VISA, data).unwrap();
+    assert_eq!(
+        issuer,
+        PublicKey {
+            modulus: modulus(ISSUER_MODULUS),
+            exponent: vec![0x03],
+        }
+    );
+
+    let static_data = authentication::static_data(&application.records, data).unwrap();
+    assert_eq!(static_data.len(), 30 + 7 + 2);
+    assert_eq!(
+        verifier.verify_sda(&issuer, data, &static_data),
+        Ok([0xDA, 0xC0])
+    );
+
+    let icc = verifier.icc_key(&issuer, data, &static_data).unwrap();
+    assert_eq!(icc.modulus, modulus(ICC_MODULUS));
+    assert_eq!(icc.exponent, ICC_EXPONENT);
+}
+
+#[test]
+fn verifies_dynamic_signatures() {
+    let (mut emv, application) = read(OdaApplet::new([0x12, 0x30]));
+    let ca_keys = ca_keys();
+    let verifier = Verifier::new(&SoftwareCrypto, &ca_keys, TODAY);
+    let data = &application.data;
+
+    let issuer = verifier.issuer_key(VISA, data).unwrap();
+    let static_data = authentication::static_data(&application.records, data).unwrap();
+    let icc = verifier.icc_key(&issuer, data, &static_data).unwrap();
+
+    let terminal = terminal();
+    let ddol_data = terminal.fill(data.get(tag::DDOL).unwrap()).unwrap();
+    assert_eq!(ddol_data, UNPREDICTABLE_NUMBER);
+    let signed = block_on(emv.internal_authenticate(&ddol_data)).unwrap();
+    assert_eq!(
+        verifier.verify_dda(&icc, &signed, &ddol_data),
+        Ok(OdaApplet::DYNAMIC_NUMBER.to_vec())
+    );
+    assert_eq!(
+        verifier.verify_dda(&icc, &signed, &[0x00; 4]),
+        Err(OdaError::HashMismatch)
+    );
+
+    let cdol_data = terminal.fill(data.get(tag::CDOL1).unwrap()).unwrap();
+    let mut cryptogram = block_on(emv.generate_ac(CryptogramType::Arqc, true, &cdol_data)).unwrap();
+    assert_eq!(
+        verifier.verify_cda(
+            &icc,
+            &cryptogram,
+            &UNPREDICTABLE_NUMBER,
+            &application.pdol_data,
+            &cdol_data
+        ),
+        Ok([0xAC; 8])
+    );
+    assert_eq!(
+        verifier.verify_cda(
+            &icc,
+            &cryptogram,
+            &UNPREDICTABLE_NUMBER,
+            &application.pdol_data,
+            &cdol_data[1..]
+        ),
+        Err(OdaError::HashMismatch)
+    );
+
+    cryptogram.cid = CryptogramType::Tc.to_u8();
+    assert_eq!(
+        verifier.verify_cda(
+            &icc,
+            &cryptogram,
+            &UNPREDICTABLE_NUMBER,
+            &application.pdol_data,
+            &cdol_data
+        ),
+        Err(OdaError::CryptogramMismatch)
+    );
+}
+
+#[test]
+fn rejects_what_does_not_verify() {
+    let (_, mut application) = read(OdaApplet::new([0x12, 0x30]));
+    let ca_keys = ca_keys();
+    let crypto = SoftwareCrypto;
+
+    let unknown = CaKeyStore::new().with_key(CaPublicKey::new(
+        RID,
+        CA_INDEX + 1,
+        &modulus(CA_MODULUS),
+        &[0x03],
+    ));
+    assert_eq!(
+        Verifier::new(&crypto, &unknown, TODAY).issuer_key(VISA, &application.data),
+        Err(OdaError::UnknownCaKey)
+    );
+    assert_eq!(
+        Verifier::new(&crypto, &ca_keys, [0x31, 0x01, 0x01]).issuer_key(VISA, &application.data),
+        Err(OdaError::Expired)
+    );
+
+    let verifier = Verifier::new(&crypto, &ca_keys, TODAY);
+    let issuer = verifier.issuer_key(VISA, &application.data).unwrap();
+
+    application
+        .data
+        .insert(tag::ISSUER_PUBLIC_KEY_EXPONENT, &[0x01, 0x00, 0x01]);
+    assert_eq!(
+        verifier.issuer_key(VISA, &application.data),
+        Err(OdaError::HashMismatch)
+    );
+    application
+        .data
+        .insert(tag::ISSUER_PUBLIC_KEY_EXPONENT, &[0x03]);
+    application
+        .data
+        .insert(tag::PAN, &[0x51, 0x61, 0x73, 0x90, 0x01, 0x01, 0x00, 0x10]);
+    assert_eq!(
+        verifier.issuer_key(VISA, &application.data),
+        Err(OdaError::PanMismatch)
+    );
+    application.data.insert(tag::PAN, PAN);
+
+    let last = application.records[0].data.len() - 1;
+    application.records[0].data[last] ^= 0x01;
+    let static_data = authentication::static_data(&application.records, &application.data).unwrap();
+    assert_eq!(
+        verifier.verify_sda(&issuer, &application.data, &static_data),
+        Err(OdaError::HashMismatch)
+    );
+    assert_eq!(
+        verifier.icc_key(&issuer, &application.data, &static_data),
+        Err(OdaError::HashMismatch)
+    );
+    assert_eq!(
+        verifier.verify_sda(&issuer, &application.data, &static_data[1..]),
+        Err(OdaError::HashMismatch)
+    );
+    assert_eq!(
+        verifier.verify_dda(&issuer, &[0x00; 112], &[]),
+        Err(OdaError::Malformed)
+    );
+
+    // The issuer certificate expires at the end of December 2025.
+    let (_, application) = read(OdaApplet::new([0x12, 0x25]));
+    assert_eq!(
+        verifier.issuer_key(VISA, &application.data),
+        Err(OdaError::Expired)
+    );
+}
+
+#[test]
+fn checks_published_ca_keys() {
+    let key = CaPublicKey::new(RID, 0x92, &hex(VISA_TEST_CA_92), &[0x03]);
+    assert_eq!(key.key.modulus.len(), 176);
+    assert_eq!(
+        key.checksum(&SoftwareCrypto)[..],
+        hex(VISA_TEST_CA_92_CHECKSUM)
+    );
+
+    let mut miscopied = key.clone();
+    miscopied.key.modulus[0] ^= 0x01;
+    assert_ne!(
+        miscopied.checksum(&SoftwareCrypto)[..],
+        hex(VISA_TEST_CA_92_CHECKSUM)
+    );
+
+    let ca_keys = ca_keys().with_key(key.clone());
+    assert_eq!(ca_keys.find(&RID, 0x92), Some(&key));
+    assert_eq!(ca_keys.find(&RID, 0x94), None);
+}
+
+#[test]
+fn recovers_known_certificates() {
+    let issuer_modulus = modulus(ISSUER_MODULUS);
+    let icc_modulus = modulus(ICC_MODULUS);
+
+    let mut data = EmvData::new();
+    data.insert(tag::PAN, PAN);
+    data.insert(tag::CA_PUBLIC_KEY_INDEX, &[CA_INDEX]);
+    data.insert(tag::ISSUER_PUBLIC_KEY_CERTIFICATE, &hex(ISSUER_CERTIFICATE));
+    data.insert(tag::ISSUER_PUBLIC_KEY_REMAINDER, &issuer_modulus[92..]);
+    data.insert(tag::ISSUER_PUBLIC_KEY_EXPONENT, &[0x03]);
+    data.insert(tag::ICC_PUBLIC_KEY_CERTIFICATE, &hex(ICC_CERTIFICATE));
+    data.insert(tag::ICC_PUBLIC_KEY_REMAINDER, &icc_modulus[70..]);
+    data.insert(tag::ICC_PUBLIC_KEY_EXPONENT, ICC_EXPONENT);
+
+    let ca_keys = ca_keys();
+    let verifier = Verifier::new(&SoftwareCrypto, &ca_keys, TODAY);
+
+    let issuer = verifier.issuer_key(VISA, &data).unwrap();
+    assert_eq!(issuer.modulus, issuer_modulus);
+    assert_eq!(issuer.exponent, [0x03]);
+
+    let icc = verifier
+        .icc_key(&issuer, &data, &hex(CERTIFIED_STATIC_DATA))
+        .unwrap();
+    assert_eq!(icc.modulus, icc_modulus);
+    assert_eq!(icc.exponent, ICC_EXPONENT);
+
+    assert_eq!(
+        Verifier::new(&SoftwareCrypto, &ca_keys, [0x29, 0x01, 0x01]).icc_key(
+            &issuer,
+            &data,
+            &hex(CERTIFIED_STATIC_DATA)
+        ),
+        Err(OdaError::Expired)
+    );
+}
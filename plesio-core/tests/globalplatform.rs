@@ -0,0 +1,313 @@
+use std::{cell::RefCell, rc::Rc};
+
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        globalplatform::{
+            card_manager::{CardManager, CardManagerError, ISSUER_SECURITY_DOMAIN},
+            operation::{
+                delete::Delete,
+                get_data::KeyInformation,
+                get_status::{GetStatus, RegistryEntry, StatusFormat, StatusScope},
+                install::Install,
+                load::Load,
+                set_status::{SetStatus, SetStatusScope},
+            },
+        },
+        iso_7816::status,
+        status::ApduStatus,
+    },
+    card::{applet::Applet, command::CardCommand, file::FileSystem, virtual_card::VirtualCard},
+};
+
+mod common;
+
+use common::tlv;
+
+const PACKAGE_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x06, 0x47, 0x2F, 0x00, 0x01];
+const APPLET_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x06, 0x47, 0x2F, 0x00, 0x01, 0x01];
+
+/// A command as the security domain received it: class, instruction,
+/// parameters and data.
+type Received = (u8, u8, u8, u8, Vec<u8>);
+
+fn registry_entry(aid: &[u8], life_cycle: u8) -> Vec<u8> {
+    [
+        tlv(0x4F, aid),
+        tlv(0x9F70, &[life_cycle]),
+        tlv(0xC5, &[0x00, 0x00, 0x00]),
+        tlv(0xC4, PACKAGE_AID),
+    ]
+    .concat()
+}
+
+#[derive(Default)]
+struct SecurityDomain {
+    received: Rc<RefCell<Vec<Received>>>,
+}
+
+impl Applet for SecurityDomain {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let proprietary = [
+            tlv(
+                0x73,
+                &tlv(0x06, &[0x2A, 0x86, 0x48, 0x86, 0xFC, 0x6B, 0x01]),
+            ),
+            tlv(0x9F65, &[0x01, 0x00]),
+        ]
+        .concat();
+        let fci = [tlv(0x84, ISSUER_SECURITY_DOMAIN), tlv(0xA5, &proprietary)].concat();
+        response.extend(tlv(0x6F, &fci));
+        status::OK
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let (p1, p2) = command.parameters();
+        self.received.borrow_mut().push((
+            command.class_byte(),
+            command.instruction(),
+            p1,
+            p2,
+            command.data().to_vec(),
+        ));
+
+        match (command.instruction(), p1, p2) {
+            (0xF2, 0x40, 0x02) => {
+                for index in 1..=2 {
+                    response.extend(tlv(0xE3, &registry_entry(&[0xA0, index], 0x07)));
+                }
+                ApduStatus::new(0x63, 0x10)
+            }
+            (0xF2, 0x40, 0x03) => {
+                response.extend(tlv(0xE3, &registry_entry(APPLET_AID, 0x0F)));
+                status::OK
+            }
+            (0xF2, 0x10, 0x00) => {
+                response.extend([0x02, 0xA0, 0x01, 0x01, 0x00, 0x02]);
+                response.extend([0x02, 0xA0, 0x02, 0x01, 0xA1]);
+                status::OK
+            }
+            (0xCA, 0x9F, 0x7F) => {
+                response.extend(tlv(0x9F7F, &(0..42).collect::<Vec<u8>>()));
+                status::OK
+            }
+            (0xCA, 0x00, 0x66) => {
+                let gp = [0x2A, 0x86, 0x48, 0x86, 0xFC, 0x6B];
+                let oid = |arc: &[u8]| tlv(0x06, &[&gp[..], arc].concat());
+                let template = [
+                    oid(&[0x01]),
+                    tlv(0x60, &oid(&[0x02, 0x02, 0x02, 0x01])),
+                    tlv(0x63, &oid(&[0x03])),
+                    tlv(0x64, &oid(&[0x04, 0x03, 0x70])),
+                    tlv(0x64, &oid(&[0x04, 0x02, 0x55])),
+                ]
+                .concat();
+                response.extend(tlv(0x66, &tlv(0x73, &template)));
+                status::OK
+            }
+            (0xCA, 0x00, 0xE0) => {
+                let keys = [
+                    tlv(0xC0, &[0x01, 0x30, 0x88, 0x10]),
+                    tlv(0xC0, &[0x02, 0x30, 0x88, 0x10]),
+                    tlv(0xC0, &[0x03, 0x30, 0x88, 0x10]),
+                ]
+                .concat();
+                response.extend(tlv(0xE0, &keys));
+                status::OK
+            }
+            (0xE6 | 0xE8, _, _) => {
+                response.push(0x00);
+                status::OK
+            }
+            (0xE4, _, _) => match command.data() {
+                [0x4F, _, aid @ ..] if aid == PACKAGE_AID => {
+                    response.push(0x00);
+                    status::OK
+                }
+                _ => ApduStatus::new(0x6A, 0x88),
+            },
+            (0xF0, _, _) => status::OK,
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+fn card_manager(max_payload_size: usize) -> (CardManager<VirtualCard>, Rc<RefCell<Vec<Received>>>) {
+    let security_domain = SecurityDomain::default();
+    let received = security_domain.received.clone();
+    let card = VirtualCard::new(FileSystem::new())
+        .with_applet(ISSUER_SECURITY_DOMAIN, security_domain)
+        .with_max_payload_size(max_payload_size);
+
+    let mut manager = CardManager::new(card);
+    let info = block_on(manager.select(ISSUER_SECURITY_DOMAIN)).unwrap();
+    assert_eq!(info.aid, ISSUER_SECURITY_DOMAIN);
+    assert_eq!(info.max_command_length, Some(256));
+    assert!(info.management_data.is_some());
+
+    (manager, received)
+}
+
+#[test]
+fn get_status_follows_more_data() {
+    let (mut manager, received) = card_manager(255);
+
+    let entries = block_on(manager.get_status(GetStatus::new(StatusScope::Applications))).unwrap();
+    let aids: Vec<_> = entries.iter().map(|entry| entry.aid.as_slice()).collect();
+    assert_eq!(aids, [&[0xA0, 0x01][..], &[0xA0, 0x02], APPLET_AID]);
+    assert_eq!(entries[2].life_cycle, 0x0F);
+    assert_eq!(entries[2].load_file.as_deref(), Some(PACKAGE_AID));
+
+    let received = received.borrow();
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0], (0x80, 0xF2, 0x40, 0x02, vec![0x4F, 0x00]));
+    assert_eq!(received[1].3, 0x03);
+}
+
+#[test]
+fn get_status_parses_legacy_load_files_and_modules() {
+    let (mut manager, _) = card_manager(255);
+
+    let operation = GetStatus::new(StatusScope::LoadFilesAndModules)
+        .with_format(StatusFormat::Legacy)
+        .with_aid_prefix(&[])
+        .unwrap();
+    let entries = block_on(manager.get_status(operation)).unwrap();
+
+    assert_eq!(
+        entries[0],
+        RegistryEntry {
+            aid: vec![0xA0, 0x01],
+            life_cycle: 0x01,
+            privileges: [0x00, 0x00, 0x00],
+            modules: vec![vec![0xA0, 0x02], vec![0xA1]],
+            ..RegistryEntry::default()
+        }
+    );
+    assert_eq!(entries.len(), 1);
+}
+
+#[test]
+fn loads_and_installs_in_numbered_blocks() {
+    let (mut manager, received) = card_manager(64);
+    let load_file: Vec<u8> = (0..150).map(|index| index as u8).collect();
+
+    let install = Install::for_load(PACKAGE_AID, ISSUER_SECURITY_DOMAIN).unwrap();
+    block_on(manager.install(install)).unwrap();
+    block_on(manager.load(&load_file)).unwrap();
+    let install = Install::for_install(PACKAGE_AID, APPLET_AID, APPLET_AID)
+        .and_then(|install| install.with_parameters(&[0x01, 0x02]))
+        .unwrap();
+    block_on(manager.install(install)).unwrap();
+
+    let received = received.borrow();
+    let mut install_for_load = vec![PACKAGE_AID.len() as u8];
+    install_for_load.extend_from_slice(PACKAGE_AID);
+    install_for_load.push(ISSUER_SECURITY_DOMAIN.len() as u8);
+    install_for_load.extend_from_slice(ISSUER_SECURITY_DOMAIN);
+    install_for_load.extend([0x00, 0x00, 0x00]);
+    assert_eq!(received[0], (0x80, 0xE6, 0x02, 0x00, install_for_load));
+
+    let blocks = &received[1..4];
+    assert_eq!(
+        blocks
+            .iter()
+            .map(|&(class, instruction, p1, p2, _)| (class, instruction, p1, p2))
+            .collect::<Vec<_>>(),
+        [
+            (0x80, 0xE8, 0x00, 0x00),
+            (0x80, 0xE8, 0x00, 0x01),
+            (0x80, 0xE8, 0x80, 0x02),
+        ]
+    );
+    let payload: Vec<u8> = blocks.iter().flat_map(|block| block.4.clone()).collect();
+    assert_eq!(payload[..3], [0xC4, 0x81, 150]);
+    assert_eq!(payload[3..], load_file);
+
+    let (_, _, p1, _, data) = &received[4];
+    assert_eq!(*p1, 0x0C);
+    assert!(data.ends_with(&[0x01, 0x00, 0x04, 0xC9, 0x02, 0x01, 0x02, 0x00]));
+}
+
+#[test]
+fn rejects_load_file_beyond_block_count() {
+    let (mut manager, received) = card_manager(16);
+
+    let result = block_on(manager.load(&[0u8; 16 * 256]));
+    assert!(matches!(result, Err(CardManagerError::LoadFileTooLarge)));
+    assert!(received.borrow().is_empty());
+
+    assert!(Load::blocks(&[0x01, 0x02], 0).is_none());
+}
+
+#[test]
+fn rejects_fields_longer_than_their_length_byte() {
+    let long = [0xA0; 256];
+
+    assert!(Install::for_load(&long, ISSUER_SECURITY_DOMAIN).is_none());
+    assert!(Install::for_personalization(&long).is_none());
+
+    let install = Install::for_install(PACKAGE_AID, APPLET_AID, APPLET_AID).unwrap();
+    assert!(install.with_parameters(&long[..254]).is_none());
+    let install = Install::for_install(PACKAGE_AID, APPLET_AID, APPLET_AID).unwrap();
+    assert!(install.with_parameters(&long[..253]).is_some());
+    let install = Install::for_load(PACKAGE_AID, &[]).unwrap();
+    assert!(install.with_token(&long).is_none());
+
+    assert!(Delete::new(&long).is_none());
+    assert!(
+        GetStatus::new(StatusScope::Applications)
+            .with_aid_prefix(&long)
+            .is_none()
+    );
+}
+
+#[test]
+fn deletes_and_sets_status() {
+    let (mut manager, received) = card_manager(255);
+
+    block_on(manager.delete(Delete::new(PACKAGE_AID).unwrap().with_related_objects())).unwrap();
+    block_on(manager.set_status(SetStatus::new(
+        SetStatusScope::Application,
+        APPLET_AID,
+        0x83,
+    )))
+    .unwrap();
+
+    let result = block_on(manager.delete(Delete::new(APPLET_AID).unwrap()));
+    assert!(matches!(
+        result,
+        Err(CardManagerError::Rejected(status)) if status == ApduStatus::new(0x6A, 0x88)
+    ));
+
+    let received = received.borrow();
+    let mut delete = vec![0x4F, PACKAGE_AID.len() as u8];
+    delete.extend_from_slice(PACKAGE_AID);
+    assert_eq!(received[0], (0x80, 0xE4, 0x00, 0x80, delete));
+    assert_eq!(received[1], (0x80, 0xF0, 0x40, 0x83, APPLET_AID.to_vec()));
+}
+
+#[test]
+fn reads_cplc_key_information_and_card_recognition_data() {
+    let (mut manager, _) = card_manager(255);
+
+    let cplc = block_on(manager.cplc()).unwrap();
+    assert_eq!(cplc.ic_fabricator, [0x00, 0x01]);
+    assert_eq!(cplc.ic_serial_number, [0x0C, 0x0D, 0x0E, 0x0F]);
+    assert_eq!(cplc.ic_personalization_equipment, [0x26, 0x27, 0x28, 0x29]);
+
+    let keys = block_on(manager.key_information()).unwrap();
+    assert_eq!(keys.len(), 3);
+    assert_eq!(
+        keys[2],
+        KeyInformation {
+            identifier: 0x03,
+            version: 0x30,
+            components: vec![(0x88, 0x10)],
+        }
+    );
+
+    let recognition = block_on(manager.card_recognition_data()).unwrap();
+    assert_eq!(recognition.version, [0x02, 0x02, 0x01]);
+    assert_eq!(recognition.secure_channels, [(0x03, 0x70), (0x02, 0x55)]);
+}
@@ -0,0 +1,602 @@
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        iso_7816::{
+            status,
+            tlv::ber::{BerTlv, BerTlvIterator},
+        },
+        openpgp::{
+            KeySlot, OPENPGP_AID, Password,
+            client::{OpenPgp, OpenPgpError},
+            object::{AlgorithmAttributes, Kdf, PublicKey},
+        },
+        status::ApduStatus,
+    },
+    card::{applet::Applet, command::CardCommand, file::FileSystem, virtual_card::VirtualCard},
+};
+use sha2::{Digest, Sha256};
+
+mod common;
+
+use common::tlv;
+
+const FULL_AID: &[u8] = &[
+    0xD2, 0x76, 0x00, 0x01, 0x24, 0x01, 0x03, 0x04, 0x00, 0x06, 0x12, 0x34, 0x56, 0x78, 0x00, 0x00,
+];
+const RSA_2048: &[u8] = &[0x01, 0x08, 0x00, 0x00, 0x11, 0x00];
+/// ECDH over Curve25519.
+const CV25519: &[u8] = &[
+    0x12, 0x2B, 0x06, 0x01, 0x04, 0x01, 0x97, 0x55, 0x01, 0x05, 0x01,
+];
+const TERMINATED: ApduStatus = ApduStatus::new(0x62, 0x85);
+
+fn reversed(data: &[u8]) -> Vec<u8> {
+    data.iter().rev().copied().collect()
+}
+
+struct Counter {
+    value: Vec<u8>,
+    retries: u8,
+}
+
+impl Counter {
+    fn new(value: &[u8]) -> Self {
+        Self {
+            value: value.to_vec(),
+            retries: 3,
+        }
+    }
+
+    fn check(&mut self, presented: &[u8]) -> ApduStatus {
+        if self.retries == 0 {
+            return status::AUTHENTICATION_METHOD_BLOCKED;
+        }
+
+        if presented != self.value {
+            self.retries -= 1;
+            return status::verification_failed(self.retries);
+        }
+
+        self.retries = 3;
+        status::OK
+    }
+}
+
+/// The card side of an OpenPGP application with PW1 `123456` and PW3
+/// `12345678`. The private key operations answer with their input
+/// reversed.
+struct OpenPgpApplet {
+    pw1: Counter,
+    pw3: Counter,
+    reset_code: Option<Counter>,
+    /// Whether PW1 is verified for signing, PW1 for the other operations,
+    /// and PW3.
+    verified: [bool; 3],
+    terminated: bool,
+    objects: Vec<(u16, Vec<u8>)>,
+    attributes: [Vec<u8>; 3],
+    keys: [Option<Vec<u8>>; 3],
+    fingerprints: [[u8; 20]; 3],
+    dates: [[u8; 4]; 3],
+    signatures: u32,
+}
+
+impl OpenPgpApplet {
+    fn new() -> Self {
+        Self {
+            pw1: Counter::new(b"123456"),
+            pw3: Counter::new(b"12345678"),
+            reset_code: None,
+            verified: [false; 3],
+            terminated: false,
+            objects: Vec::new(),
+            attributes: [RSA_2048.to_vec(), CV25519.to_vec(), RSA_2048.to_vec()],
+            keys: [None, None, None],
+            fingerprints: [[0; 20]; 3],
+            dates: [[0; 4]; 3],
+            signatures: 0,
+        }
+    }
+
+    fn object(&self, tag: u16) -> Option<&[u8]> {
+        self.objects
+            .iter()
+            .find(|(object, _)| *object == tag)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    fn application_related_data(&self) -> Vec<u8> {
+        let retries = [
+            self.pw1.retries,
+            self.reset_code.as_ref().map_or(0, |code| code.retries),
+            self.pw3.retries,
+        ];
+        let key_information: Vec<u8> = (0..3)
+            .flat_map(|index| [index as u8 + 1, self.keys[index].is_some() as u8])
+            .collect();
+
+        let discretionary = [
+            tlv(
+                0xC0,
+                &[0x7D, 0x00, 0x00, 0xFF, 0x08, 0x00, 0x00, 0xFF, 0x00, 0x00],
+            ),
+            tlv(0xC1, &self.attributes[0]),
+            tlv(0xC2, &self.attributes[1]),
+            tlv(0xC3, &self.attributes[2]),
+            tlv(
+                0xC4,
+                &[[0x00, 0x7F, 0x7F, 0x7F].as_slice(), &retries].concat(),
+            ),
+            tlv(0xC5, &self.fingerprints.concat()),
+            tlv(0xC6, &[0; 60]),
+            tlv(0xCD, &self.dates.concat()),
+            tlv(0xDE, &key_information),
+        ]
+        .concat();
+
+        let template = [
+            tlv(0x4F, FULL_AID),
+            tlv(0x5F52, &[0x00, 0x73, 0x00, 0x00, 0xE0, 0x05, 0x90, 0x00]),
+            tlv(0x73, &discretionary),
+        ]
+        .concat();
+        tlv(0x6E, &template)
+    }
+
+    fn get_data(&self, tag: u16, response: &mut Vec<u8>) -> ApduStatus {
+        let data = match tag {
+            0x6E => self.application_related_data(),
+            0x65 => {
+                let field = |tag: u16| tlv(tag as u32, self.object(tag).unwrap_or_default());
+                let fields = [field(0x5B), field(0x5F2D), field(0x5F35)];
+                tlv(0x65, &fields.concat())
+            }
+            0x7A => tlv(0x7A, &tlv(0x93, &self.signatures.to_be_bytes()[1..])),
+            _ => match self.object(tag) {
+                Some(value) => value.to_vec(),
+                None => return ApduStatus::new(0x6A, 0x88),
+            },
+        };
+
+        response.extend(data);
+        status::OK
+    }
+
+    fn put_data(&mut self, tag: u16, data: &[u8]) -> ApduStatus {
+        if !self.verified[2] {
+            return status::SECURITY_STATUS_NOT_SATISFIED;
+        }
+
+        match tag {
+            0xC1..=0xC3 => self.attributes[tag as usize - 0xC1] = data.to_vec(),
+            0xC7..=0xC9 => self.fingerprints[tag as usize - 0xC7].copy_from_slice(data),
+            0xCE..=0xD0 => self.dates[tag as usize - 0xCE].copy_from_slice(data),
+            0xD3 => self.reset_code = Some(Counter::new(data)),
+            0xF9 => {
+                let objects = BerTlvIterator::new(data);
+                if let Some(pw1) = objects.get(0x87) {
+                    self.pw1 = Counter::new(pw1.value());
+                }
+                if let Some(pw3) = objects.get(0x88) {
+                    self.pw3 = Counter::new(pw3.value());
+                }
+                self.verified = [false; 3];
+                self.objects.push((tag, tlv(0xF9, data)));
+            }
+            _ => {
+                self.objects.retain(|(object, _)| *object != tag);
+                self.objects.push((tag, data.to_vec()));
+            }
+        }
+
+        status::OK
+    }
+
+    fn verify(&mut self, reference: u8, data: &[u8]) -> ApduStatus {
+        let index = reference as usize - 0x81;
+        let counter = if reference == 0x83 {
+            &mut self.pw3
+        } else {
+            &mut self.pw1
+        };
+
+        if data.is_empty() {
+            return match self.verified[index] {
+                true => status::OK,
+                false if counter.retries == 0 => status::AUTHENTICATION_METHOD_BLOCKED,
+                false => status::verification_failed(counter.retries),
+            };
+        }
+
+        let status = counter.check(data);
+        self.verified[index] = status == status::OK;
+        status
+    }
+
+    fn generate(&mut self, p1: u8, data: &[u8], response: &mut Vec<u8>) -> ApduStatus {
+        let index = match data[0] {
+            0xB6 => 0,
+            0xB8 => 1,
+            _ => 2,
+        };
+
+        if p1 == 0x80 {
+            if !self.verified[2] {
+                return status::SECURITY_STATUS_NOT_SATISFIED;
+            }
+
+            let public_key = match self.attributes[index][0] {
+                0x01 => [tlv(0x81, &[0xC5; 256]), tlv(0x82, &[0x01, 0x00, 0x01])].concat(),
+                _ => tlv(0x86, &[0x40 + index as u8; 32]),
+            };
+            self.keys[index] = Some(tlv(0x7F49, &public_key));
+        }
+
+        match &self.keys[index] {
+            Some(key) => {
+                response.extend_from_slice(key);
+                status::OK
+            }
+            None => ApduStatus::new(0x6A, 0x88),
+        }
+    }
+
+    fn private_key(&mut self, index: usize, input: &[u8], response: &mut Vec<u8>) -> ApduStatus {
+        if !self.verified[index] {
+            return status::SECURITY_STATUS_NOT_SATISFIED;
+        }
+
+        response.extend(reversed(input));
+        status::OK
+    }
+}
+
+impl Applet for OpenPgpApplet {
+    fn select(&mut self, _command: &CardCommand<'_>, _response: &mut Vec<u8>) -> ApduStatus {
+        self.verified = [false; 3];
+
+        match self.terminated {
+            true => TERMINATED,
+            false => status::OK,
+        }
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let data = command.data();
+        let (p1, p2) = command.parameters();
+
+        if self.terminated {
+            if command.instruction() == 0x44 {
+                *self = Self::new();
+                return status::OK;
+            }
+            return TERMINATED;
+        }
+
+        match (command.instruction(), p1, p2) {
+            (0xCA, _, _) => self.get_data(u16::from_be_bytes([p1, p2]), response),
+            (0xDA, _, _) => self.put_data(u16::from_be_bytes([p1, p2]), data),
+            (0x20, 0x00, reference @ 0x81..=0x83) => self.verify(reference, data),
+            (0x24, 0x00, reference @ (0x81 | 0x83)) => {
+                let counter = if reference == 0x83 {
+                    &mut self.pw3
+                } else {
+                    &mut self.pw1
+                };
+                let (old, new) = data.split_at(counter.value.len());
+                match counter.check(old) {
+                    status::OK => {
+                        counter.value = new.to_vec();
+                        status::OK
+                    }
+                    status => status,
+                }
+            }
+            (0x2C, 0x00, 0x81) => {
+                let Some(code) = &mut self.reset_code else {
+                    return status::AUTHENTICATION_METHOD_BLOCKED;
+                };
+                let (presented, new) = data.split_at(code.value.len());
+                match code.check(presented) {
+                    status::OK => {
+                        self.pw1 = Counter::new(new);
+                        status::OK
+                    }
+                    status => status,
+                }
+            }
+            (0x2C, 0x02, 0x81) if self.verified[2] => {
+                self.pw1 = Counter::new(data);
+                status::OK
+            }
+            (0x2A, 0x9E, 0x9A) => {
+                let status = self.private_key(0, data, response);
+                if status == status::OK {
+                    self.signatures += 1;
+                    self.verified[0] = false;
+                }
+                status
+            }
+            (0x2A, 0x80, 0x86) => match data {
+                [0x00, cryptogram @ ..] => self.private_key(1, cryptogram, response),
+                _ => {
+                    let (cipher, _) = BerTlv::next(data).unwrap();
+                    assert_eq!(cipher.tag(), 0xA6);
+                    let (public_key, _) = BerTlv::next(cipher.value()).unwrap();
+                    let point = public_key.children().get(0x86).unwrap();
+                    self.private_key(1, point.value(), response)
+                }
+            },
+            (0x88, 0x00, 0x00) => self.private_key(1, data, response),
+            (0x47, 0x80 | 0x81, 0x00) => self.generate(p1, data, response),
+            (0xE6, 0x00, 0x00) => {
+                if self.verified[2] || (self.pw1.retries == 0 && self.pw3.retries == 0) {
+                    self.terminated = true;
+                    status::OK
+                } else {
+                    status::SECURITY_STATUS_NOT_SATISFIED
+                }
+            }
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+fn openpgp() -> OpenPgp<VirtualCard> {
+    let card = VirtualCard::new(FileSystem::new()).with_applet(FULL_AID, OpenPgpApplet::new());
+    let mut openpgp = OpenPgp::new(card);
+    block_on(openpgp.select()).unwrap();
+    openpgp
+}
+
+#[test]
+fn parses_application_related_data() {
+    let mut openpgp = openpgp();
+    assert!(FULL_AID.starts_with(OPENPGP_AID));
+
+    let related = block_on(openpgp.application_related_data()).unwrap();
+    assert_eq!(related.version(), (3, 4));
+    assert_eq!(related.manufacturer(), 0x0006);
+    assert_eq!(related.serial_number(), 0x12345678);
+    assert!(related.extended_capabilities.supports_key_import());
+    assert!(
+        related
+            .extended_capabilities
+            .changeable_algorithm_attributes()
+    );
+    assert!(related.extended_capabilities.supports_kdf());
+    assert_eq!(related.extended_capabilities.max_challenge_length, 0xFF);
+
+    assert_eq!(
+        related.algorithm_attributes[0],
+        Some(AlgorithmAttributes::Rsa {
+            modulus_bits: 2048,
+            exponent_bits: 17,
+            import_format: 0x00,
+        })
+    );
+    let Some(AlgorithmAttributes::Ecc { algorithm, oid, .. }) = &related.algorithm_attributes[1]
+    else {
+        panic!("expected ECC attributes");
+    };
+    assert_eq!(*algorithm, 0x12);
+    assert_eq!(oid, &CV25519[1..]);
+    assert_eq!(
+        related.algorithm_attributes[1].as_ref().unwrap().to_bytes(),
+        CV25519
+    );
+
+    assert_eq!(related.password_status.max_pw1_length, 0x7F);
+    assert_eq!(related.password_status.pw1_retries, 3);
+    assert_eq!(related.password_status.reset_code_retries, 0);
+    assert!(!related.password_status.pw1_valid_for_multiple_signatures);
+    assert_eq!(related.fingerprint(KeySlot::Signature), None);
+    assert_eq!(related.key_information, [(1, 0), (2, 0), (3, 0)]);
+}
+
+#[test]
+fn manages_cardholder_data_and_passwords() {
+    let mut openpgp = openpgp();
+
+    assert!(matches!(
+        block_on(openpgp.set_name(b"Doe<<Alex")),
+        Err(OpenPgpError::Rejected(status)) if status == status::SECURITY_STATUS_NOT_SATISFIED
+    ));
+
+    block_on(openpgp.verify(Password::Admin, b"12345678")).unwrap();
+    block_on(openpgp.set_name(b"Doe<<Alex")).unwrap();
+    block_on(openpgp.set_language(b"ende")).unwrap();
+    block_on(openpgp.set_sex(b'9')).unwrap();
+    block_on(openpgp.set_url(b"https://keys.example/alex.asc")).unwrap();
+
+    let cardholder = block_on(openpgp.cardholder_data()).unwrap();
+    assert_eq!(cardholder.name, b"Doe<<Alex");
+    assert_eq!(cardholder.language, b"ende");
+    assert_eq!(cardholder.sex, Some(b'9'));
+    assert_eq!(
+        block_on(openpgp.url()).unwrap(),
+        b"https://keys.example/alex.asc"
+    );
+    assert!(matches!(
+        block_on(openpgp.kdf()),
+        Err(OpenPgpError::NotFound)
+    ));
+
+    assert!(matches!(
+        block_on(openpgp.verify(Password::User, b"000000")),
+        Err(OpenPgpError::WrongPassword { retries: 2 })
+    ));
+    block_on(openpgp.change_password(Password::User, b"123456", b"654321")).unwrap();
+    assert_eq!(block_on(openpgp.retries(Password::User)).unwrap(), Some(3));
+
+    block_on(openpgp.set_reset_code(b"reset-me")).unwrap();
+    for _ in 0..3 {
+        let _ = block_on(openpgp.verify(Password::User, b"000000"));
+    }
+    assert!(matches!(
+        block_on(openpgp.verify(Password::User, b"654321")),
+        Err(OpenPgpError::Blocked)
+    ));
+
+    block_on(openpgp.reset_retry_counter(b"reset-me", b"111111")).unwrap();
+    block_on(openpgp.verify(Password::User, b"111111")).unwrap();
+    assert_eq!(block_on(openpgp.retries(Password::User)).unwrap(), None);
+
+    block_on(openpgp.reset_retry_counter_as_admin(b"222222")).unwrap();
+    block_on(openpgp.verify(Password::Signing, b"222222")).unwrap();
+}
+
+#[test]
+fn signs_deciphers_and_authenticates() {
+    let mut openpgp = openpgp();
+    let digest_info = [0x30, 0x31, 0x30, 0x0D];
+
+    assert!(matches!(
+        block_on(openpgp.sign(&digest_info)),
+        Err(OpenPgpError::Rejected(status)) if status == status::SECURITY_STATUS_NOT_SATISFIED
+    ));
+
+    block_on(openpgp.verify(Password::Signing, b"123456")).unwrap();
+    assert_eq!(
+        block_on(openpgp.sign(&digest_info)).unwrap(),
+        reversed(&digest_info)
+    );
+    assert!(block_on(openpgp.sign(&digest_info)).is_err());
+    assert_eq!(block_on(openpgp.signature_counter()).unwrap(), 1);
+
+    block_on(openpgp.verify(Password::User, b"123456")).unwrap();
+    let cryptogram = vec![0x3C; 256];
+    assert_eq!(
+        block_on(openpgp.decipher_rsa(&cryptogram)).unwrap(),
+        cryptogram
+    );
+    let point: Vec<u8> = (0..32).collect();
+    assert_eq!(
+        block_on(openpgp.decipher_ecdh(&point)).unwrap(),
+        reversed(&point)
+    );
+    assert_eq!(
+        block_on(openpgp.internal_authenticate(&[0x01, 0x02])).unwrap(),
+        [0x02, 0x01]
+    );
+}
+
+#[test]
+fn generates_keys_and_stores_fingerprints() {
+    let mut openpgp = openpgp();
+
+    assert!(matches!(
+        block_on(openpgp.public_key(KeySlot::Signature)),
+        Err(OpenPgpError::NotFound)
+    ));
+
+    block_on(openpgp.verify(Password::Admin, b"12345678")).unwrap();
+    let ed25519 =
+        AlgorithmAttributes::parse(&[0x16, 0x2B, 0x06, 0x01, 0x04, 0x01, 0xDA, 0x47, 0x0F, 0x01])
+            .unwrap();
+    block_on(openpgp.set_algorithm_attributes(KeySlot::Authentication, &ed25519)).unwrap();
+
+    let PublicKey::Rsa { modulus, exponent } =
+        block_on(openpgp.generate_key(KeySlot::Signature)).unwrap()
+    else {
+        panic!("expected an RSA key");
+    };
+    assert_eq!(modulus.len(), 256);
+    assert_eq!(exponent, [0x01, 0x00, 0x01]);
+
+    let generated = block_on(openpgp.generate_key(KeySlot::Authentication)).unwrap();
+    assert_eq!(
+        generated,
+        PublicKey::Ecc {
+            point: vec![0x42; 32]
+        }
+    );
+    assert_eq!(
+        block_on(openpgp.public_key(KeySlot::Authentication)).unwrap(),
+        generated
+    );
+
+    block_on(openpgp.set_fingerprint(KeySlot::Authentication, &[0xAB; 20])).unwrap();
+    block_on(openpgp.set_generation_date(KeySlot::Authentication, 1_700_000_000)).unwrap();
+
+    let related = block_on(openpgp.application_related_data()).unwrap();
+    assert_eq!(
+        related.fingerprint(KeySlot::Authentication),
+        Some(&[0xAB; 20])
+    );
+    assert_eq!(related.generation_dates, [0, 0, 1_700_000_000]);
+    assert_eq!(related.algorithm_attributes[2], Some(ed25519));
+    assert_eq!(related.key_information, [(1, 1), (2, 0), (3, 1)]);
+}
+
+#[test]
+fn derives_passwords_with_kdf() {
+    let mut openpgp = openpgp();
+
+    let mut kdf = Kdf {
+        algorithm: Kdf::ITERATED_SALTED_S2K,
+        hash_algorithm: Some(0x08),
+        iterations: 42,
+        pw1_salt: vec![0x11; 8],
+        pw3_salt: Some(vec![0x33; 8]),
+        ..Kdf::default()
+    };
+    kdf.initial_pw1_hash = Some(kdf.derive::<Sha256>(Password::User, b"123456"));
+    kdf.initial_pw3_hash = Some(kdf.derive::<Sha256>(Password::Admin, b"12345678"));
+
+    let salted = [[0x11; 8].as_slice(), b"123456"].concat();
+    assert_eq!(
+        kdf.initial_pw1_hash.as_deref(),
+        Some(&Sha256::digest(salted.repeat(3))[..])
+    );
+
+    block_on(openpgp.verify(Password::Admin, b"12345678")).unwrap();
+    block_on(openpgp.set_kdf(&kdf)).unwrap();
+    assert_eq!(block_on(openpgp.kdf()).unwrap(), kdf);
+
+    assert!(block_on(openpgp.verify(Password::User, b"123456")).is_err());
+    let derived = block_on(openpgp.kdf())
+        .unwrap()
+        .derive::<Sha256>(Password::User, b"123456");
+    block_on(openpgp.verify(Password::User, &derived)).unwrap();
+
+    // A card's F9 without a salt, and an empty password.
+    let unsalted = Kdf::parse(&[
+        0xF9, 0x09, 0x81, 0x01, 0x03, 0x83, 0x04, 0x00, 0x01, 0x00, 0x00,
+    ])
+    .unwrap();
+    assert!(unsalted.pw1_salt.is_empty());
+    assert_eq!(
+        unsalted.derive::<Sha256>(Password::User, b""),
+        Sha256::digest(b"")[..]
+    );
+
+    let none = Kdf::default();
+    assert_eq!(none.to_bytes(), [0x81, 0x01, 0x00]);
+    assert_eq!(none.derive::<Sha256>(Password::User, b"123456"), b"123456");
+}
+
+#[test]
+fn factory_reset_blocks_passwords_and_reactivates() {
+    let mut openpgp = openpgp();
+
+    block_on(openpgp.verify(Password::Admin, b"12345678")).unwrap();
+    block_on(openpgp.change_password(Password::Admin, b"12345678", b"forgotten")).unwrap();
+    block_on(openpgp.generate_key(KeySlot::Decryption)).unwrap();
+    block_on(openpgp.set_fingerprint(KeySlot::Decryption, &[0xCD; 20])).unwrap();
+
+    let card = openpgp.into_inner();
+    let mut openpgp = OpenPgp::new(card);
+    block_on(openpgp.select()).unwrap();
+    assert!(matches!(
+        block_on(openpgp.terminate()),
+        Err(OpenPgpError::Rejected(status)) if status == status::SECURITY_STATUS_NOT_SATISFIED
+    ));
+
+    block_on(openpgp.factory_reset()).unwrap();
+
+    let related = block_on(openpgp.application_related_data()).unwrap();
+    assert_eq!(related.fingerprint(KeySlot::Decryption), None);
+    assert_eq!(related.password_status.pw3_retries, 3);
+    block_on(openpgp.verify(Password::Admin, b"12345678")).unwrap();
+}
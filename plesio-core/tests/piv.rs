@@ -0,0 +1,545 @@
+use std::{collections::HashMap, io::Write};
+
+use aes::{Aes128, Aes192, Aes256};
+use des::{
+    TdesEde3,
+    cipher::{BlockEncrypt, KeyInit, generic_array::GenericArray},
+};
+use flate2::{Compression, write::GzEncoder};
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        iso_7816::{
+            status,
+            tlv::ber::{BerTlv, BerTlvIterator},
+        },
+        piv::{
+            Algorithm, KeyReference, PIV_AID, Slot,
+            client::{Piv, PivError},
+            key::{PinPolicy, PrivateKey, PublicKey, TouchPolicy},
+            management::{DEFAULT_MANAGEMENT_KEY, ManagementKey},
+            object::tag,
+            operation::{GenerateKeyPair, ImportKey},
+        },
+        status::ApduStatus,
+    },
+    card::{applet::Applet, command::CardCommand, file::FileSystem, virtual_card::VirtualCard},
+};
+
+mod common;
+
+use common::tlv;
+
+const FULL_AID: &[u8] = &[
+    0xA0, 0x00, 0x00, 0x03, 0x08, 0x00, 0x00, 0x10, 0x00, 0x01, 0x00,
+];
+
+fn certificate_object(certificate: &[u8], info: u8) -> Vec<u8> {
+    let content = [tlv(0x70, certificate), tlv(0x71, &[info]), tlv(0xFE, &[])].concat();
+    tlv(0x53, &content)
+}
+
+fn certificate() -> Vec<u8> {
+    (0..600).map(|index| (index % 251) as u8).collect()
+}
+
+/// Encrypts a block under the card's management key.
+fn management_encrypt(algorithm: u8, key: &[u8], block: &mut [u8]) {
+    match algorithm {
+        0x03 => TdesEde3::new_from_slice(key)
+            .unwrap()
+            .encrypt_block(GenericArray::from_mut_slice(block)),
+        0x08 => Aes128::new_from_slice(key)
+            .unwrap()
+            .encrypt_block(GenericArray::from_mut_slice(block)),
+        0x0A => Aes192::new_from_slice(key)
+            .unwrap()
+            .encrypt_block(GenericArray::from_mut_slice(block)),
+        0x0C => Aes256::new_from_slice(key)
+            .unwrap()
+            .encrypt_block(GenericArray::from_mut_slice(block)),
+        _ => panic!("unsupported management key algorithm {algorithm:02X}"),
+    }
+}
+
+struct Authenticator {
+    value: [u8; 8],
+    retries: u8,
+}
+
+/// The card side of a PIV application with a PIN `123456` and PUK
+/// `12345678`. GENERAL AUTHENTICATE answers with its input reversed, and
+/// needs the PIN except for the card authentication key. Key generation
+/// and import need the management key, and only generated keys can be
+/// attested, by a "certificate" wrapping the generation request.
+struct PivApplet {
+    objects: HashMap<Vec<u8>, Vec<u8>>,
+    pin: Authenticator,
+    puk: Authenticator,
+    verified: bool,
+    management_key: (u8, Vec<u8>),
+    witness: Option<Vec<u8>>,
+    administrator: bool,
+    /// The algorithm of each slot's key and the command data that created
+    /// it.
+    keys: HashMap<u8, (u8, Vec<u8>)>,
+}
+
+impl PivApplet {
+    fn new() -> Self {
+        let compressed = {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&certificate()).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        let chuid = [
+            tlv(0x30, &[0xD4; 25]),
+            tlv(0x34, &[0x11; 16]),
+            tlv(0x35, b"20301231"),
+            tlv(0x3E, &[]),
+            tlv(0xFE, &[]),
+        ]
+        .concat();
+
+        let objects = [
+            (vec![0x5F, 0xC1, 0x02], tlv(0x53, &chuid)),
+            (
+                vec![0x5F, 0xC1, 0x05],
+                certificate_object(&certificate(), 0x00),
+            ),
+            (
+                vec![0x5F, 0xC1, 0x0D],
+                certificate_object(&compressed, 0x01),
+            ),
+            (
+                vec![0x5F, 0xC1, 0x0C],
+                tlv(
+                    0x53,
+                    &[tlv(0xC1, &[0x01]), tlv(0xC2, &[0x00]), tlv(0xF3, &[])].concat(),
+                ),
+            ),
+            (
+                vec![0x7E],
+                tlv(
+                    0x7E,
+                    &[tlv(0x4F, FULL_AID), tlv(0x5F2F, &[0x60, 0x20])].concat(),
+                ),
+            ),
+        ];
+
+        Self {
+            objects: objects.into_iter().collect(),
+            pin: Authenticator {
+                value: *b"123456\xFF\xFF",
+                retries: 3,
+            },
+            puk: Authenticator {
+                value: *b"12345678",
+                retries: 3,
+            },
+            verified: false,
+            management_key: (0x03, DEFAULT_MANAGEMENT_KEY.to_vec()),
+            witness: None,
+            administrator: false,
+            keys: HashMap::new(),
+        }
+    }
+
+    fn with_management_key(mut self, algorithm: u8, key: &[u8]) -> Self {
+        self.management_key = (algorithm, key.to_vec());
+        self
+    }
+
+    fn authenticate(&mut self, algorithm: u8, data: &[u8], response: &mut Vec<u8>) -> ApduStatus {
+        let (key_algorithm, key) = &self.management_key;
+
+        if algorithm != *key_algorithm {
+            return ApduStatus::new(0x6A, 0x86);
+        }
+
+        let block_size = if algorithm == 0x03 { 8 } else { 16 };
+        let (template, _) = BerTlv::next(data).unwrap();
+        let objects = template.children();
+        let witness = objects.get(0x80).unwrap().value();
+
+        if witness.is_empty() {
+            let mut encrypted = vec![0x3C; block_size];
+            self.witness = Some(encrypted.clone());
+            management_encrypt(algorithm, key, &mut encrypted);
+            response.extend(tlv(0x7C, &tlv(0x80, &encrypted)));
+            return status::OK;
+        }
+
+        if self.witness.take().as_deref() != Some(witness) {
+            return status::SECURITY_STATUS_NOT_SATISFIED;
+        }
+
+        let mut challenge = objects.get(0x81).unwrap().value().to_vec();
+        management_encrypt(algorithm, key, &mut challenge);
+        response.extend(tlv(0x7C, &tlv(0x82, &challenge)));
+        self.administrator = true;
+        status::OK
+    }
+
+    fn generate(&mut self, slot: u8, data: &[u8], response: &mut Vec<u8>) -> ApduStatus {
+        let (template, _) = BerTlv::next(data).unwrap();
+        let algorithm = template.children().get(0x80).unwrap().value()[0];
+
+        let public_key = match algorithm {
+            0x07 => [tlv(0x81, &[0xC5; 256]), tlv(0x82, &[0x01, 0x00, 0x01])].concat(),
+            0x11 => tlv(0x86, &[[0x04].as_slice(), &[0x22; 64]].concat()),
+            _ => return ApduStatus::new(0x6A, 0x80),
+        };
+
+        self.keys.insert(slot, (algorithm, data.to_vec()));
+        response.extend(tlv(0x7F49, &public_key));
+        status::OK
+    }
+
+    fn import(&mut self, algorithm: u8, slot: u8, data: &[u8]) -> ApduStatus {
+        let tags: Vec<u32> = BerTlvIterator::new(data)
+            .map(|object| object.tag())
+            .collect();
+        let components: &[u32] = match algorithm {
+            0x07 => &[0x01, 0x02, 0x03, 0x04, 0x05],
+            0x11 => &[0x06],
+            _ => return ApduStatus::new(0x6A, 0x80),
+        };
+
+        if !tags.starts_with(components) {
+            return ApduStatus::new(0x6A, 0x80);
+        }
+
+        self.keys.insert(slot, (algorithm, data.to_vec()));
+        status::OK
+    }
+
+    fn check(authenticator: &mut Authenticator, presented: &[u8]) -> ApduStatus {
+        if authenticator.retries == 0 {
+            return status::AUTHENTICATION_METHOD_BLOCKED;
+        }
+
+        if presented != authenticator.value {
+            authenticator.retries -= 1;
+            return status::verification_failed(authenticator.retries);
+        }
+
+        authenticator.retries = 3;
+        status::OK
+    }
+}
+
+impl Applet for PivApplet {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let property = [
+            tlv(0x4F, FULL_AID),
+            tlv(0x79, &tlv(0x4F, PIV_AID)),
+            tlv(0x50, b"Test PIV"),
+            tlv(0xAC, &[tlv(0x80, &[0x11]), tlv(0x80, &[0x07])].concat()),
+        ]
+        .concat();
+        response.extend(tlv(0x61, &property));
+        status::OK
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let data = command.data();
+
+        match (command.instruction(), command.parameters()) {
+            (0xCB, (0x3F, 0xFF)) => match self.objects.get(&data[2..]) {
+                Some(object) if data[0] == 0x5C => {
+                    response.extend_from_slice(object);
+                    status::OK
+                }
+                _ => status::FILE_NOT_FOUND,
+            },
+            (0x20, (0x00, 0x80)) if data.is_empty() => match self.verified {
+                true => status::OK,
+                false => status::verification_failed(self.pin.retries),
+            },
+            (0x20, (0x00, 0x80)) => {
+                let status = Self::check(&mut self.pin, data);
+                self.verified = status == status::OK;
+                status
+            }
+            (0x24, (0x00, 0x80)) => match Self::check(&mut self.pin, &data[..8]) {
+                status::OK => {
+                    self.pin.value.copy_from_slice(&data[8..]);
+                    status::OK
+                }
+                status => status,
+            },
+            (0x2C, (0x00, 0x80)) => match Self::check(&mut self.puk, &data[..8]) {
+                status::OK => {
+                    self.pin = Authenticator {
+                        value: data[8..].try_into().unwrap(),
+                        retries: 3,
+                    };
+                    status::OK
+                }
+                status => status,
+            },
+            (0x87, (algorithm, 0x9B)) => self.authenticate(algorithm, data, response),
+            (0x47 | 0xFE, _) if !self.administrator => status::SECURITY_STATUS_NOT_SATISFIED,
+            (0x47, (0x00, slot)) => self.generate(slot, data, response),
+            (0xFE, (algorithm, slot)) => self.import(algorithm, slot, data),
+            (0xF9, (slot, 0x00)) => match self.keys.get(&slot) {
+                Some((_, request)) if request[0] == 0xAC => {
+                    response.extend(tlv(0x30, request));
+                    status::OK
+                }
+                Some(_) => ApduStatus::new(0x6A, 0x80),
+                None => status::REFERENCED_DATA_NOT_FOUND,
+            },
+            (0x87, (_, slot)) => {
+                if slot != 0x9E && !self.verified {
+                    return status::SECURITY_STATUS_NOT_SATISFIED;
+                }
+
+                let (template, _) = BerTlv::next(data).unwrap();
+                assert!(template.children().get(0x82).unwrap().value().is_empty());
+                let input = template
+                    .children()
+                    .find(|object| matches!(object.tag(), 0x81 | 0x85))
+                    .unwrap();
+                let result: Vec<u8> = input.value().iter().rev().copied().collect();
+                response.extend(tlv(0x7C, &tlv(0x82, &result)));
+                status::OK
+            }
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+fn piv() -> Piv<VirtualCard> {
+    piv_with(PivApplet::new())
+}
+
+fn piv_with(applet: PivApplet) -> Piv<VirtualCard> {
+    let card = VirtualCard::new(FileSystem::new()).with_applet(FULL_AID, applet);
+    let mut piv = Piv::new(card);
+
+    let property = block_on(piv.select()).unwrap();
+    assert_eq!(property.aid, FULL_AID);
+    assert_eq!(property.label.as_deref(), Some(&b"Test PIV"[..]));
+    assert_eq!(property.algorithms, [0x11, 0x07]);
+
+    piv
+}
+
+#[test]
+fn reads_data_objects() {
+    let mut piv = piv();
+
+    let chuid = block_on(piv.chuid()).unwrap();
+    assert_eq!(chuid.fasc_n, [0xD4; 25]);
+    assert_eq!(chuid.guid.as_deref(), Some(&[0x11; 16][..]));
+    assert_eq!(chuid.expiration.as_deref(), Some(&b"20301231"[..]));
+
+    let discovery = block_on(piv.discovery()).unwrap();
+    assert_eq!(discovery.aid, FULL_AID);
+    assert!(discovery.accepts_global_pin());
+    assert!(discovery.prefers_global_pin());
+
+    let history = block_on(piv.key_history()).unwrap();
+    assert_eq!(history.on_card_certificates, 1);
+    assert_eq!(history.off_card_url, None);
+
+    assert!(matches!(
+        block_on(piv.card_capability_container()),
+        Err(PivError::NotFound)
+    ));
+    assert!(matches!(
+        block_on(piv.read_object(tag::PRINTED_INFORMATION)),
+        Err(PivError::NotFound)
+    ));
+}
+
+#[test]
+fn reads_plain_and_compressed_certificates() {
+    let mut piv = piv();
+
+    assert_eq!(
+        block_on(piv.certificate(Slot::Authentication)).unwrap(),
+        certificate()
+    );
+    assert_eq!(
+        block_on(piv.certificate(Slot::Retired(1))).unwrap(),
+        certificate()
+    );
+    assert!(matches!(
+        block_on(piv.certificate(Slot::Signature)),
+        Err(PivError::NotFound)
+    ));
+}
+
+#[test]
+fn manages_pin_and_puk() {
+    let mut piv = piv();
+
+    assert_eq!(block_on(piv.retries(KeyReference::Pin)).unwrap(), Some(3));
+    assert!(matches!(
+        block_on(piv.verify(KeyReference::Pin, b"000000")),
+        Err(PivError::WrongPin { retries: 2 })
+    ));
+    assert!(matches!(
+        block_on(piv.verify(KeyReference::Pin, b"123456789")),
+        Err(PivError::InvalidPin)
+    ));
+
+    block_on(piv.change_reference_data(KeyReference::Pin, b"123456", b"654321")).unwrap();
+
+    for retries in [2, 1, 0] {
+        assert!(matches!(
+            block_on(piv.verify(KeyReference::Pin, b"123456")),
+            Err(PivError::WrongPin { retries: left }) if left == retries
+        ));
+    }
+    assert!(matches!(
+        block_on(piv.verify(KeyReference::Pin, b"654321")),
+        Err(PivError::Blocked)
+    ));
+    assert_eq!(block_on(piv.retries(KeyReference::Pin)).unwrap(), Some(0));
+
+    block_on(piv.reset_retry_counter(b"12345678", b"111111")).unwrap();
+    block_on(piv.verify(KeyReference::Pin, b"111111")).unwrap();
+    assert_eq!(block_on(piv.retries(KeyReference::Pin)).unwrap(), None);
+}
+
+#[test]
+fn signs_and_agrees_keys_with_general_authenticate() {
+    let mut piv = piv();
+    let digest = [0x01, 0x02, 0x03, 0x04];
+
+    assert_eq!(
+        block_on(piv.authenticate_card(Algorithm::EccP256, &digest)).unwrap(),
+        [0x04, 0x03, 0x02, 0x01]
+    );
+    assert!(matches!(
+        block_on(piv.sign(Algorithm::EccP256, Slot::Signature, &digest)),
+        Err(PivError::Rejected(status)) if status == status::SECURITY_STATUS_NOT_SATISFIED
+    ));
+
+    block_on(piv.verify(KeyReference::Pin, b"123456")).unwrap();
+
+    let padded = vec![0x5A; 256];
+    let signature = block_on(piv.sign(Algorithm::Rsa2048, Slot::Signature, &padded)).unwrap();
+    assert_eq!(signature, padded);
+
+    let point = [[0x04].as_slice(), &[0x22; 64]].concat();
+    let secret =
+        block_on(piv.key_agreement(Algorithm::EccP256, Slot::KeyManagement, &point)).unwrap();
+    assert_eq!(secret[..64], [0x22; 64]);
+    assert_eq!(secret[64], 0x04);
+}
+
+#[test]
+fn authenticates_with_management_key() {
+    let mut piv = piv();
+    let challenge = [0x5A; 16];
+
+    let wrong = ManagementKey::new(Algorithm::TripleDes, &[0x11; 24]).unwrap();
+    assert!(matches!(
+        block_on(piv.authenticate_management_key(&wrong, &challenge)),
+        Err(PivError::Rejected(status)) if status == status::SECURITY_STATUS_NOT_SATISFIED
+    ));
+    assert!(matches!(
+        block_on(piv.generate_key_pair(GenerateKeyPair::new(Slot::Authentication, Algorithm::EccP256))),
+        Err(PivError::Rejected(status)) if status == status::SECURITY_STATUS_NOT_SATISFIED
+    ));
+
+    let default = ManagementKey::new(Algorithm::TripleDes, &DEFAULT_MANAGEMENT_KEY).unwrap();
+    block_on(piv.authenticate_management_key(&default, &challenge)).unwrap();
+
+    assert!(ManagementKey::new(Algorithm::Aes128, &DEFAULT_MANAGEMENT_KEY).is_none());
+    assert!(ManagementKey::new(Algorithm::Aes256, &[0x2B; 16]).is_none());
+    assert!(ManagementKey::new(Algorithm::EccP256, &[0x2B; 16]).is_none());
+
+    for (algorithm, reference, key) in [
+        (Algorithm::Aes128, 0x08, [0x2B; 16].as_slice()),
+        (Algorithm::Aes192, 0x0A, &[0x3C; 24]),
+        (Algorithm::Aes256, 0x0C, &[0x4D; 32]),
+    ] {
+        let mut piv = piv_with(PivApplet::new().with_management_key(reference, key));
+
+        let aes = ManagementKey::new(algorithm, key).unwrap();
+        assert_eq!(aes.block_size(), 16);
+        block_on(piv.authenticate_management_key(&aes, &challenge)).unwrap();
+    }
+}
+
+#[test]
+fn generates_imports_and_attests_keys() {
+    let mut piv = piv();
+    let key = ManagementKey::new(Algorithm::TripleDes, &DEFAULT_MANAGEMENT_KEY).unwrap();
+    block_on(piv.authenticate_management_key(&key, &[0x01; 16])).unwrap();
+
+    let generate = GenerateKeyPair::new(Slot::Signature, Algorithm::Rsa2048)
+        .with_pin_policy(PinPolicy::Always)
+        .with_touch_policy(TouchPolicy::Cached);
+    let PublicKey::Rsa { modulus, exponent } = block_on(piv.generate_key_pair(generate)).unwrap()
+    else {
+        panic!("expected an RSA key");
+    };
+    assert_eq!(modulus, [0xC5; 256]);
+    assert_eq!(exponent, [0x01, 0x00, 0x01]);
+
+    let public_key = block_on(piv.generate_key_pair(GenerateKeyPair::new(
+        Slot::Authentication,
+        Algorithm::EccP256,
+    )))
+    .unwrap();
+    assert_eq!(
+        public_key,
+        PublicKey::Ec {
+            point: [[0x04].as_slice(), &[0x22; 64]].concat()
+        }
+    );
+
+    let attestation = block_on(piv.attest(Slot::Signature)).unwrap();
+    assert_eq!(
+        attestation,
+        [
+            0x30, 0x0B, 0xAC, 0x09, 0x80, 0x01, 0x07, 0xAA, 0x01, 0x03, 0xAB, 0x01, 0x03
+        ]
+    );
+
+    let scalar = PrivateKey::Ec {
+        scalar: vec![0x77; 32],
+    };
+    assert!(!format!("{scalar:?}").contains("119"));
+    block_on(
+        piv.import_key(
+            ImportKey::new(Slot::KeyManagement, Algorithm::EccP256, &scalar)
+                .with_pin_policy(PinPolicy::Once),
+        ),
+    )
+    .unwrap();
+
+    let rsa = PrivateKey::Rsa {
+        p: vec![0x01; 128],
+        q: vec![0x02; 128],
+        dp: vec![0x03; 128],
+        dq: vec![0x04; 128],
+        qinv: vec![0x05; 128],
+    };
+    block_on(piv.import_key(ImportKey::new(Slot::Retired(2), Algorithm::Rsa2048, &rsa))).unwrap();
+    assert!(matches!(
+        block_on(piv.import_key(ImportKey::new(
+            Slot::Retired(3),
+            Algorithm::Rsa2048,
+            &scalar
+        ))),
+        Err(PivError::Rejected(_))
+    ));
+
+    assert!(matches!(
+        block_on(piv.attest(Slot::KeyManagement)),
+        Err(PivError::Rejected(status)) if status == ApduStatus::new(0x6A, 0x80)
+    ));
+    assert!(matches!(
+        block_on(piv.attest(Slot::CardAuthentication)),
+        Err(PivError::NotFound)
+    ));
+}
@@ -0,0 +1,265 @@
+use std::time::Duration;
+
+use plesio_core::apdu::{
+    blocking::block_on,
+    iso_7816::{
+        class::Iso7816Class,
+        operation::Iso7816Command,
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    mock::{MockError, MockTransport},
+    record::{
+        FormatError, RecordedError, Recording, RecordingTransport, ReplayError, ReplayTransport,
+    },
+    transport::ApduTransport,
+};
+
+fn command(instruction: u8, parameters: (u8, u8), data: &[u8]) -> Iso7816Command<'_> {
+    Iso7816Command::new(Iso7816Class::default(), instruction, parameters, data)
+}
+
+fn session<T: ApduTransport>(transport: &mut Iso7816Transport<T>, pin: &[u8]) -> Vec<u8> {
+    let verify = block_on(transport.execute_owned(command(0x20, (0x00, 0x81), pin))).unwrap();
+    assert_eq!(verify.status().code1(), 0x90);
+
+    let data = block_on(transport.execute_owned(command(0xCA, (0x00, 0x6E), &[]))).unwrap();
+    data.data().to_vec()
+}
+
+fn record() -> Recording {
+    let card = MockTransport::new()
+        .expect(
+            &[0x00, 0x20, 0x00, 0x81, 0x04, 0x31, 0x32, 0x33, 0x34, 0x00],
+            &[0x90, 0x00],
+        )
+        .expect(
+            &[0x00, 0xCA, 0x00, 0x6E, 0x00],
+            &[0x6E, 0x02, 0x4F, 0x00, 0x90, 0x00],
+        );
+    let mut transport = Iso7816Transport::new(RecordingTransport::new(card));
+
+    assert_eq!(session(&mut transport, b"1234"), [0x6E, 0x02, 0x4F, 0x00]);
+
+    let (card, recording) = transport.into_inner().into_parts();
+    card.assert_finished();
+    recording
+}
+
+#[test]
+fn records_and_replays_session() {
+    let recording = record();
+    assert_eq!(recording.exchanges.len(), 2);
+    assert_eq!(
+        recording.exchanges[1].command,
+        [0x00, 0xCA, 0x00, 0x6E, 0x00]
+    );
+    assert_eq!(
+        recording.exchanges[1].reply,
+        Ok(vec![0x6E, 0x02, 0x4F, 0x00, 0x90, 0x00])
+    );
+
+    let mut transport = Iso7816Transport::new(ReplayTransport::new(recording));
+    assert_eq!(session(&mut transport, b"1234"), [0x6E, 0x02, 0x4F, 0x00]);
+    assert!(transport.inner().is_finished());
+}
+
+#[test]
+fn round_trips_through_json_lines() {
+    let mut recording = record();
+    recording.exchanges[0].reply = Err(RecordedError::PayloadTooLarge { max_size: 255 });
+    recording.exchanges[1].reply = Err(RecordedError::Other("Failure(\"unplugged\")".into()));
+
+    // Times are saved with microsecond resolution.
+    for (index, exchange) in recording.exchanges.iter_mut().enumerate() {
+        exchange.started = Duration::from_micros(1500 * index as u64);
+        exchange.elapsed = Duration::from_micros(1200);
+    }
+
+    let mut saved = Vec::new();
+    recording.write_jsonl(&mut saved).unwrap();
+
+    let text = String::from_utf8(saved.clone()).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with(r#"{"format":"plesio-apdu","version":1,"#));
+    assert!(lines[1].starts_with(r#"{"command":"00200081043132333400","payload_too_large":255,"#));
+
+    assert_eq!(Recording::read_jsonl(saved.as_slice()).unwrap(), recording);
+}
+
+#[test]
+fn rejects_malformed_lines() {
+    let header = r#"{"format":"plesio-apdu","version":1,"max_payload_size":255}"#;
+    let read = |text: &str| Recording::read_jsonl(text.as_bytes()).unwrap_err();
+
+    let odd_hex = format!(
+        "{header}\n\n{}",
+        r#"{"command":"00B","response":"9000","started_us":0,"elapsed_us":0}"#
+    );
+    assert!(matches!(
+        read(&odd_hex),
+        FormatError::Malformed { position: 3, .. }
+    ));
+
+    let both = format!(
+        "{header}\n{}",
+        r#"{"command":"00B0","response":"9000","error":"x","started_us":0,"elapsed_us":0}"#
+    );
+    assert!(matches!(
+        read(&both),
+        FormatError::Malformed { position: 2, .. }
+    ));
+
+    assert!(matches!(
+        read(r#"{"format":"other","version":1,"max_payload_size":255}"#),
+        FormatError::Malformed { position: 1, .. }
+    ));
+}
+
+#[test]
+fn replay_checks_commands() {
+    let mut transport = Iso7816Transport::new(ReplayTransport::new(record()));
+    let error =
+        block_on(transport.execute_owned(command(0x20, (0x00, 0x81), b"9999"))).unwrap_err();
+
+    assert!(matches!(
+        error,
+        Iso7816TransportError::Transport(ReplayError::Mismatch { index: 0, .. })
+    ));
+    assert_eq!(transport.inner().position(), 0);
+}
+
+#[test]
+fn replay_ignores_volatile_data() {
+    let replay = ReplayTransport::new(record()).ignore_data(0x20);
+    let mut transport = Iso7816Transport::new(replay);
+
+    assert_eq!(session(&mut transport, b"9999"), [0x6E, 0x02, 0x4F, 0x00]);
+
+    let error = block_on(transport.execute_owned(command(0xCA, (0x00, 0x6E), &[]))).unwrap_err();
+    assert!(matches!(
+        error,
+        Iso7816TransportError::Transport(ReplayError::Exhausted)
+    ));
+}
+
+#[test]
+fn replays_recorded_errors() {
+    let card = MockTransport::new().expect_error(
+        &[0x00, 0xB0, 0x00, 0x00, 0x00],
+        MockError::Failure("reader unplugged"),
+    );
+    let mut transport = Iso7816Transport::new(RecordingTransport::new(card));
+    assert!(block_on(transport.execute_owned(command(0xB0, (0x00, 0x00), &[]))).is_err());
+
+    let (_, recording) = transport.into_inner().into_parts();
+    let mut transport = Iso7816Transport::new(ReplayTransport::new(recording));
+    let error = block_on(transport.execute_owned(command(0xB0, (0x00, 0x00), &[]))).unwrap_err();
+
+    assert!(matches!(
+        error,
+        Iso7816TransportError::Transport(ReplayError::Recorded(message)) if message.contains("reader unplugged")
+    ));
+}
+
+#[test]
+fn round_trips_through_pcapng() {
+    let mut recording = record();
+    recording.exchanges.push(recording.exchanges[1].clone());
+    recording.exchanges[1].reply = Err(RecordedError::PayloadTooLarge { max_size: 255 });
+    recording.exchanges[2].reply = Err(RecordedError::Other("Failure(\"unplugged\")".into()));
+
+    for (index, exchange) in recording.exchanges.iter_mut().enumerate() {
+        exchange.started = Duration::from_micros(1500 * index as u64);
+        exchange.elapsed = Duration::from_micros(1200);
+    }
+    recording.exchanges[1].elapsed = Duration::ZERO;
+    recording.exchanges[2].elapsed = Duration::ZERO;
+
+    let mut saved = Vec::new();
+    recording.write_pcapng(&mut saved).unwrap();
+
+    assert_eq!(saved[..4], [0x0A, 0x0D, 0x0D, 0x0A]);
+    let interface = u32::from_le_bytes(saved[4..8].try_into().unwrap()) as usize;
+    assert_eq!(saved[interface..interface + 4], [0x01, 0x00, 0x00, 0x00]);
+    assert_eq!(saved[interface + 8..interface + 10], 264u16.to_le_bytes());
+
+    assert_eq!(Recording::read_pcapng(saved.as_slice()).unwrap(), recording);
+}
+
+/// Builds a big-endian pcapng block.
+fn block(kind: u32, body: &[u8]) -> Vec<u8> {
+    let length = (body.len() + 12) as u32;
+    let mut block = kind.to_be_bytes().to_vec();
+    block.extend_from_slice(&length.to_be_bytes());
+    block.extend_from_slice(body);
+    block.extend_from_slice(&length.to_be_bytes());
+    block
+}
+
+fn packet(timestamp: u64, data: &[u8], flags: Option<u32>) -> Vec<u8> {
+    let mut body = 0u32.to_be_bytes().to_vec();
+    body.extend_from_slice(&((timestamp >> 32) as u32).to_be_bytes());
+    body.extend_from_slice(&(timestamp as u32).to_be_bytes());
+    body.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    body.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    body.extend_from_slice(data);
+    body.resize(body.len().next_multiple_of(4), 0);
+
+    if let Some(flags) = flags {
+        body.extend_from_slice(&[0x00, 0x02, 0x00, 0x04]);
+        body.extend_from_slice(&flags.to_be_bytes());
+        body.extend_from_slice(&[0x00; 4]);
+    }
+
+    block(6, &body)
+}
+
+#[test]
+fn reads_foreign_pcapng_captures() {
+    let mut capture = block(
+        0x0A0D_0D0A,
+        &[
+            0x1A, 0x2B, 0x3C, 0x4D, 0x00, 0x01, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xFF, 0xFF,
+        ],
+    );
+    // ISO 7816 link type with nanosecond timestamps.
+    capture.extend(block(
+        1,
+        &[
+            0x01, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x00, 0x01, 0x09, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ],
+    ));
+    capture.extend(packet(1_000, &[0x3B, 0x80, 0x80, 0x01, 0x01], Some(0b01)));
+    capture.extend(packet(2_000, &[0x00, 0xCA, 0x00, 0x6E, 0x00], None));
+    capture.extend(block(0x0000_0BAD, &[0x00; 4]));
+    capture.extend(packet(
+        2_503_000,
+        &[0x6E, 0x02, 0x4F, 0x00, 0x90, 0x00],
+        None,
+    ));
+
+    let recording = Recording::read_pcapng(capture.as_slice()).unwrap();
+    assert_eq!(recording.max_payload_size, 255);
+    assert_eq!(recording.exchanges.len(), 1);
+    assert_eq!(recording.exchanges[0].started, Duration::from_micros(2));
+    assert_eq!(recording.exchanges[0].elapsed, Duration::from_micros(2501));
+
+    let mut transport = Iso7816Transport::new(ReplayTransport::new(recording));
+    let data = block_on(transport.execute_owned(command(0xCA, (0x00, 0x6E), &[]))).unwrap();
+    assert_eq!(data.data(), [0x6E, 0x02, 0x4F, 0x00]);
+}
+
+#[test]
+fn rejects_malformed_pcapng_blocks() {
+    assert!(matches!(
+        Recording::read_pcapng(block(1, &[0x00; 8]).as_slice()),
+        Err(FormatError::Malformed { position: 1, .. })
+    ));
+    assert!(matches!(
+        Recording::read_pcapng(&[0x0A, 0x0D, 0x0D, 0x0A, 0x00][..]),
+        Err(FormatError::Io(_))
+    ));
+}
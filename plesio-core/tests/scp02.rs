@@ -0,0 +1,500 @@
+use std::{cell::RefCell, rc::Rc};
+
+use des::{
+    Des, TdesEde2,
+    cipher::{BlockDecrypt, BlockEncrypt, KeyInit, generic_array::GenericArray},
+};
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        globalplatform::{
+            SecurityLevel,
+            card_manager::{CardManager, CardManagerError},
+            operation::put_key::{KeyData, KeyType, PutKey},
+            scp02::{self, Scp02Error, Scp02Keys, Scp02Options, Scp02Transport},
+        },
+        iso_7816::status,
+        mock::MockTransport,
+        owned::OwnedCommand,
+        status::ApduStatus,
+        transport::ApduTransport,
+    },
+    card::{
+        applet::Applet,
+        command::CardCommand,
+        file::FileSystem,
+        virtual_card::{VirtualCard, VirtualCardError},
+    },
+};
+
+mod common;
+
+use common::hex;
+
+const ISD_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x01, 0x51, 0x00, 0x00, 0x00];
+
+const ENC: [u8; 16] = [
+    0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4A, 0x4B, 0x4C, 0x4D, 0x4E, 0x4F,
+];
+const MAC: [u8; 16] = [
+    0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x5B, 0x5C, 0x5D, 0x5E, 0x5F,
+];
+const DEK: [u8; 16] = [0x60; 16];
+
+const HOST_CHALLENGE: [u8; 8] = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+const SEQUENCE_COUNTER: [u8; 2] = [0x00, 0x2A];
+const CARD_CHALLENGE: [u8; 6] = [0xA1, 0xB2, 0xC3, 0xD4, 0xE5, 0xF6];
+
+fn pad(data: &[u8]) -> Vec<u8> {
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    padded.resize(padded.len().next_multiple_of(8), 0x00);
+    padded
+}
+
+/// Two-key 3DES in CBC mode with a zero IV.
+fn tdes_cbc(key: &[u8], data: &mut [u8], encrypt: bool) {
+    let cipher = TdesEde2::new_from_slice(key).unwrap();
+    let mut chain = [0u8; 8];
+
+    for block in data.chunks_mut(8) {
+        let input: [u8; 8] = block.try_into().unwrap();
+
+        if encrypt {
+            block.iter_mut().zip(chain).for_each(|(b, c)| *b ^= c);
+            cipher.encrypt_block(GenericArray::from_mut_slice(block));
+            chain.copy_from_slice(block);
+        } else {
+            cipher.decrypt_block(GenericArray::from_mut_slice(block));
+            block.iter_mut().zip(chain).for_each(|(b, c)| *b ^= c);
+            chain = input;
+        }
+    }
+}
+
+/// ISO 9797-1 MAC algorithm 3 with padding method 2.
+fn retail_mac(key: &[u8], icv: [u8; 8], data: &[u8]) -> [u8; 8] {
+    let k1 = Des::new_from_slice(&key[..8]).unwrap();
+    let k2 = Des::new_from_slice(&key[8..]).unwrap();
+    let mut chain = GenericArray::from(icv);
+
+    for block in pad(data).chunks(8) {
+        chain.iter_mut().zip(block).for_each(|(c, b)| *c ^= b);
+        k1.encrypt_block(&mut chain);
+    }
+
+    k2.decrypt_block(&mut chain);
+    k1.encrypt_block(&mut chain);
+    chain.into()
+}
+
+fn full_mac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut padded = pad(data);
+    tdes_cbc(key, &mut padded, true);
+    padded[padded.len() - 8..].to_vec()
+}
+
+fn session_key(key: &[u8], constant: [u8; 2]) -> Vec<u8> {
+    let mut derivation = [0u8; 16];
+    derivation[..2].copy_from_slice(&constant);
+    derivation[2..4].copy_from_slice(&SEQUENCE_COUNTER);
+    tdes_cbc(key, &mut derivation, true);
+    derivation.to_vec()
+}
+
+struct Session {
+    enc: Vec<u8>,
+    mac: Vec<u8>,
+    dek: Vec<u8>,
+    level: Option<u8>,
+    last_mac: Option<[u8; 8]>,
+}
+
+/// The card side of SCP02 on a security domain with a single key set and
+/// the options `i`. Once the channel is open, INS EE echoes the command
+/// data, INS E6 fails with 6A88 and PUT KEY answers with the check values
+/// of the keys it decrypts.
+struct SecurityDomain {
+    options: u8,
+    session: Option<Session>,
+    received: Rc<RefCell<Vec<Vec<u8>>>>,
+}
+
+impl SecurityDomain {
+    fn new(options: u8) -> Self {
+        Self {
+            options,
+            session: None,
+            received: Rc::default(),
+        }
+    }
+
+    /// Checks the C-MAC of `command` and returns its plain data.
+    fn unwrap(&self, session: &mut Session, command: &CardCommand<'_>) -> Option<Vec<u8>> {
+        let data = command.data();
+        let (data, mac) = data.split_at_checked(data.len().checked_sub(8)?)?;
+        let mut data = data.to_vec();
+
+        if session.level.is_some_and(|level| level & 0x02 != 0) && !data.is_empty() {
+            tdes_cbc(&session.enc, &mut data, false);
+            data.truncate(data.iter().rposition(|&byte| byte == 0x80)?);
+        }
+
+        let (p1, p2) = command.parameters();
+        let mut authenticated = match self.options & 0x02 {
+            0 => vec![command.class_byte(), command.instruction(), p1, p2],
+            _ => vec![command.class_byte() & !0x04, command.instruction(), p1, p2],
+        };
+        authenticated.push(match self.options & 0x02 {
+            0 => data.len() as u8 + 8,
+            _ => data.len() as u8,
+        });
+        authenticated.extend_from_slice(&data);
+
+        let icv = match session.last_mac {
+            None => [0; 8],
+            Some(last) if self.options & 0x10 != 0 => {
+                let mut icv = GenericArray::from(last);
+                Des::new_from_slice(&session.mac[..8])
+                    .unwrap()
+                    .encrypt_block(&mut icv);
+                icv.into()
+            }
+            Some(last) => last,
+        };
+
+        let expected = retail_mac(&session.mac, icv, &authenticated);
+        session.last_mac = Some(expected);
+        (expected == *mac).then_some(data)
+    }
+}
+
+impl Applet for SecurityDomain {
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        if command.class_byte() == 0x80 && command.instruction() == 0x50 {
+            let enc = session_key(&ENC, [0x01, 0x82]);
+            let card_cryptogram = full_mac(
+                &enc,
+                &[command.data(), &SEQUENCE_COUNTER, &CARD_CHALLENGE].concat(),
+            );
+
+            response.extend_from_slice(&[0x00; 10]);
+            response.extend_from_slice(&[0x20, 0x02]);
+            response.extend_from_slice(&SEQUENCE_COUNTER);
+            response.extend_from_slice(&CARD_CHALLENGE);
+            response.extend_from_slice(&card_cryptogram);
+
+            self.session = Some(Session {
+                enc,
+                mac: session_key(&MAC, [0x01, 0x01]),
+                dek: session_key(&DEK, [0x01, 0x81]),
+                level: None,
+                last_mac: None,
+            });
+            return status::OK;
+        }
+
+        let Some(mut session) = self.session.take() else {
+            return ApduStatus::new(0x69, 0x85);
+        };
+
+        if command.class_byte() & 0x04 == 0 {
+            return ApduStatus::new(0x69, 0x82);
+        }
+
+        let Some(data) = self.unwrap(&mut session, command) else {
+            return ApduStatus::new(0x69, 0x82);
+        };
+
+        let Some(_) = session.level else {
+            let host_cryptogram = full_mac(
+                &session.enc,
+                &[&SEQUENCE_COUNTER[..], &CARD_CHALLENGE, &HOST_CHALLENGE].concat(),
+            );
+
+            if command.instruction() != 0x82 || data != host_cryptogram {
+                return ApduStatus::new(0x63, 0x00);
+            }
+
+            session.level = Some(command.parameters().0);
+            self.session = Some(session);
+            return status::OK;
+        };
+
+        let dek = session.dek.clone();
+        self.session = Some(session);
+        self.received.borrow_mut().push(data.clone());
+
+        match command.instruction() {
+            0xEE => {
+                response.extend_from_slice(&data);
+                status::OK
+            }
+            0xD8 => {
+                response.push(data[0]);
+
+                for key in data[1..].chunks(22) {
+                    assert_eq!(key[..2], [0x80, 0x10]);
+                    let mut plain = key[2..18].to_vec();
+                    plain
+                        .chunks_mut(8)
+                        .for_each(|block| tdes_cbc(&dek, block, false));
+
+                    let mut check = [0u8; 8];
+                    tdes_cbc(&plain, &mut check, true);
+                    response.extend_from_slice(&check[..3]);
+                }
+                status::OK
+            }
+            0xE6 => ApduStatus::new(0x6A, 0x88),
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+fn keys() -> Scp02Keys {
+    Scp02Keys::new(ENC, MAC, DEK)
+}
+
+fn card(security_domain: SecurityDomain) -> VirtualCard {
+    let mut card = VirtualCard::new(FileSystem::new()).with_applet(ISD_AID, security_domain);
+
+    let select = OwnedCommand::new(0x00, 0xA4, (0x04, 0x00), ISD_AID.to_vec());
+    let mut reply = [0u8; 258];
+    let response = block_on(card.execute(select, &mut reply)).unwrap();
+    assert_eq!(response.status(), status::OK);
+
+    card
+}
+
+fn open(
+    card: VirtualCard,
+    options: Scp02Options,
+    level: SecurityLevel,
+) -> Result<Scp02Transport<VirtualCard>, Scp02Error<VirtualCardError>> {
+    block_on(Scp02Transport::open(
+        card,
+        &keys(),
+        options,
+        level,
+        HOST_CHALLENGE,
+    ))
+}
+
+fn echo(transport: &mut impl ApduTransport, data: &[u8]) -> (Vec<u8>, ApduStatus) {
+    let command = OwnedCommand::new(0x80, 0xEE, (0x00, 0x00), data.to_vec());
+    let mut reply = [0u8; 258];
+    let response = block_on(transport.execute(command, &mut reply)).unwrap();
+    (response.data().to_vec(), response.status())
+}
+
+// The known answers below were computed apart from this crate, with
+// OpenSSL's DES and 3DES and the derivation, cryptograms and retail MAC as
+// Appendix E of the Card Specification lays them out, for the default
+// static keys 40..4F.
+
+const DEFAULT_KEY: [u8; 16] = [
+    0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4A, 0x4B, 0x4C, 0x4D, 0x4E, 0x4F,
+];
+
+#[test]
+fn derives_known_session_keys() {
+    let keys = Scp02Keys::new(DEFAULT_KEY, DEFAULT_KEY, DEFAULT_KEY);
+    let session_keys = keys.session_keys(SEQUENCE_COUNTER);
+
+    assert_eq!(
+        session_keys.enc[..],
+        hex("7AA8DE1A36F4F51AFBC7E1579F778B44")
+    );
+    assert_eq!(
+        session_keys.mac[..],
+        hex("2983BA77D709C2DAA1E6000ABCCAC951")
+    );
+    assert_eq!(
+        session_keys.dek[..],
+        hex("91B1A7BA4BF14C3672CB1DA9D47CA01A")
+    );
+}
+
+#[test]
+fn exchanges_known_cryptograms_and_wrapped_apdus() {
+    let initialize_update = [&hex("8050000008")[..], &HOST_CHALLENGE, &[0x00]].concat();
+    // Key diversification data, key information, sequence counter, card
+    // challenge and card cryptogram.
+    let card_response = [
+        &[0x00; 10][..],
+        &[0x01, 0x02],
+        &SEQUENCE_COUNTER,
+        &CARD_CHALLENGE,
+        &hex("A05C5918994FD9BD"),
+        &[0x90, 0x00],
+    ]
+    .concat();
+
+    // The host cryptogram B2DE615049B9DAD0 with its C-MAC, then "plesio"
+    // encrypted, with a C-MAC over the plain command whose ICV is the
+    // previous C-MAC encrypted.
+    let mock = MockTransport::new()
+        .expect(&initialize_update, &card_response)
+        .expect(
+            &hex("8482030010B2DE615049B9DAD0F6BCD1927654FF9B"),
+            &[0x90, 0x00],
+        )
+        .expect(
+            &hex("84EE000010B0AECE5EB7E7F1A2F926FF38106D33C900"),
+            &[0x90, 0x00],
+        );
+
+    let keys = Scp02Keys::new(DEFAULT_KEY, DEFAULT_KEY, DEFAULT_KEY);
+    let mut transport = block_on(Scp02Transport::open(
+        mock,
+        &keys,
+        Scp02Options::I_15,
+        SecurityLevel::C_DECRYPTION,
+        HOST_CHALLENGE,
+    ))
+    .unwrap();
+    assert_eq!(
+        transport.session_dek()[..],
+        hex("91B1A7BA4BF14C3672CB1DA9D47CA01A")
+    );
+
+    assert_eq!(echo(&mut transport, b"plesio"), (Vec::new(), status::OK));
+    transport.inner().assert_finished();
+}
+
+#[test]
+fn opens_channel_with_each_option_and_level() {
+    let options = [
+        Scp02Options::I_15,
+        Scp02Options::I_55,
+        Scp02Options::I_15 | Scp02Options::UNMODIFIED_APDU_MAC,
+    ];
+
+    for options in options {
+        for level in [SecurityLevel::C_MAC, SecurityLevel::C_DECRYPTION] {
+            let security_domain = SecurityDomain::new(options.to_u8());
+            let received = security_domain.received.clone();
+            let mut transport = open(card(security_domain), options, level).unwrap();
+
+            for message in [&b"first"[..], b"", &[0x5A; 47]] {
+                assert_eq!(
+                    echo(&mut transport, message),
+                    (message.to_vec(), status::OK)
+                );
+            }
+
+            assert_eq!(received.borrow().len(), 3);
+            assert_eq!(received.borrow()[2], [0x5A; 47]);
+        }
+    }
+}
+
+#[test]
+fn chains_icv_as_configured() {
+    // The card encrypts the ICV, the host does not.
+    let options = Scp02Options::THREE_KEYS | Scp02Options::EXPLICIT;
+    let mut transport = open(
+        card(SecurityDomain::new(0x15)),
+        options,
+        SecurityLevel::C_MAC,
+    )
+    .unwrap();
+
+    assert_eq!(echo(&mut transport, b"data").1, ApduStatus::new(0x69, 0x82));
+}
+
+#[test]
+fn limits_payload_to_wrapped_capacity() {
+    let transport = open(
+        card(SecurityDomain::new(0x55)),
+        Scp02Options::I_55,
+        SecurityLevel::C_MAC,
+    )
+    .unwrap();
+    assert_eq!(transport.max_payload_size(), 247);
+
+    let mut transport = open(
+        card(SecurityDomain::new(0x55)),
+        Scp02Options::I_55,
+        SecurityLevel::C_DECRYPTION,
+    )
+    .unwrap();
+    assert_eq!(transport.max_payload_size(), 239);
+
+    let command = OwnedCommand::new(0x80, 0xEE, (0x00, 0x00), vec![0x00; 240]);
+    let mut reply = [0u8; 258];
+    let error = block_on(transport.execute(command, &mut reply)).unwrap_err();
+    assert!(matches!(
+        error,
+        Scp02Error::PayloadTooLarge { max_size: 239 }
+    ));
+
+    assert_eq!(echo(&mut transport, &[0x33; 239]).0, [0x33; 239]);
+}
+
+#[test]
+fn rejects_wrong_keys_and_unsupported_options() {
+    let wrong = Scp02Keys::new(MAC, ENC, DEK);
+    let result = block_on(Scp02Transport::open(
+        card(SecurityDomain::new(0x55)),
+        &wrong,
+        Scp02Options::I_55,
+        SecurityLevel::C_MAC,
+        HOST_CHALLENGE,
+    ));
+    assert!(matches!(result, Err(Scp02Error::InvalidCardCryptogram)));
+
+    let implicit = Scp02Options::from_u8(0x1A);
+    let result = open(
+        card(SecurityDomain::new(0x55)),
+        implicit,
+        SecurityLevel::C_MAC,
+    );
+    assert!(matches!(
+        result,
+        Err(Scp02Error::UnsupportedProtocol { options: 0x1A, .. })
+    ));
+
+    let level = SecurityLevel::C_MAC | SecurityLevel::R_MAC;
+    let result = open(card(SecurityDomain::new(0x55)), Scp02Options::I_55, level);
+    assert!(matches!(
+        result,
+        Err(Scp02Error::UnsupportedSecurityLevel(_))
+    ));
+}
+
+#[test]
+fn replaces_keys_with_put_key() {
+    assert_eq!(scp02::key_check_value(&ENC), [0x8B, 0xAF, 0x47]);
+
+    let security_domain = SecurityDomain::new(0x15);
+    let received = security_domain.received.clone();
+    let transport = open(
+        card(security_domain),
+        Scp02Options::I_15,
+        SecurityLevel::C_DECRYPTION,
+    )
+    .unwrap();
+
+    let new_keys = [[0x11; 16], [0x22; 16], [0x33; 16]];
+    let keys: Vec<_> = new_keys
+        .iter()
+        .map(|key| transport.encrypt_key(key))
+        .collect();
+    assert_eq!(keys[0].check_value(), scp02::key_check_value(&[0x11; 16]));
+
+    let mut manager = CardManager::new(transport);
+    block_on(manager.put_key(PutKey::new(0x02, &keys).replacing(0x01))).unwrap();
+
+    let sent = received.borrow()[0].clone();
+    assert_eq!(sent.len(), 1 + 3 * 22);
+    assert_eq!(sent[0], 0x02);
+    assert_ne!(sent[3..19], [0x11; 16]);
+
+    let tampered = KeyData::new(KeyType::Des, 16, vec![0x00; 16], keys[0].check_value());
+    assert!(matches!(
+        block_on(manager.put_key(PutKey::new(0x02, &[tampered]))),
+        Err(CardManagerError::KeyCheckValueMismatch)
+    ));
+}
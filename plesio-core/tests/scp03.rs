@@ -0,0 +1,496 @@
+use std::{cell::RefCell, rc::Rc};
+
+use aes::{
+    Aes128,
+    cipher::{BlockDecrypt, BlockEncrypt, KeyInit, generic_array::GenericArray},
+};
+use cmac::{Cmac, Mac};
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        globalplatform::{
+            SecurityLevel,
+            card_manager::CardManager,
+            operation::put_key::PutKey,
+            scp03::{self, Scp03Error, Scp03Keys, Scp03Transport},
+        },
+        iso_7816::{
+            class::Iso7816Class, operation::Iso7816Command, status, transport::Iso7816Transport,
+        },
+        mock::MockTransport,
+        owned::OwnedCommand,
+        status::ApduStatus,
+        transport::ApduTransport,
+    },
+    card::{applet::Applet, command::CardCommand, file::FileSystem, virtual_card::VirtualCard},
+};
+
+mod common;
+
+use common::hex;
+
+const ISD_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x01, 0x51, 0x00, 0x00, 0x00];
+
+const ENC: [u8; 16] = [
+    0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49, 0x4A, 0x4B, 0x4C, 0x4D, 0x4E, 0x4F,
+];
+const MAC: [u8; 16] = [
+    0x50, 0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x5B, 0x5C, 0x5D, 0x5E, 0x5F,
+];
+const DEK: [u8; 16] = [0x60; 16];
+
+const HOST_CHALLENGE: [u8; 8] = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+const CARD_CHALLENGE: [u8; 8] = [0xA1, 0xB2, 0xC3, 0xD4, 0xE5, 0xF6, 0x07, 0x18];
+
+fn cmac(key: &[u8], data: &[u8]) -> [u8; 16] {
+    let mut mac = <Cmac<Aes128> as KeyInit>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+fn derive(key: &[u8], constant: u8, context: &[u8], bits: u16) -> Vec<u8> {
+    let mut input = vec![0; 11];
+    input.push(constant);
+    input.push(0x00);
+    input.extend_from_slice(&bits.to_be_bytes());
+    input.push(0x01);
+    input.extend_from_slice(context);
+
+    cmac(key, &input)[..bits as usize / 8].to_vec()
+}
+
+fn encrypt_block(key: &[u8], block: [u8; 16]) -> [u8; 16] {
+    let mut block = GenericArray::from(block);
+    Aes128::new_from_slice(key)
+        .unwrap()
+        .encrypt_block(&mut block);
+    block.into()
+}
+
+fn cbc(key: &[u8], icv: [u8; 16], data: &mut [u8], encrypt: bool) {
+    let cipher = Aes128::new_from_slice(key).unwrap();
+    let mut chain = icv;
+
+    for block in data.chunks_mut(16) {
+        let input: [u8; 16] = block.try_into().unwrap();
+
+        if encrypt {
+            block.iter_mut().zip(chain).for_each(|(b, c)| *b ^= c);
+            cipher.encrypt_block(GenericArray::from_mut_slice(block));
+            chain.copy_from_slice(block);
+        } else {
+            cipher.decrypt_block(GenericArray::from_mut_slice(block));
+            block.iter_mut().zip(chain).for_each(|(b, c)| *b ^= c);
+            chain = input;
+        }
+    }
+}
+
+struct Session {
+    enc: Vec<u8>,
+    mac: Vec<u8>,
+    rmac: Vec<u8>,
+    level: Option<u8>,
+    chaining: [u8; 16],
+    counter: u128,
+}
+
+/// The card side of SCP03 on a security domain with a single AES-128 key
+/// set. Once the channel is open, INS EE echoes the command data, or as
+/// many bytes as P1-P2 ask for, INS E6 fails with 6A88 and PUT KEY answers
+/// with the check values of the keys it decrypts.
+#[derive(Default)]
+struct SecurityDomain {
+    session: Option<Session>,
+    received: Rc<RefCell<Vec<Vec<u8>>>>,
+    corrupt_rmac: bool,
+}
+
+impl SecurityDomain {
+    fn initialize_update(
+        &mut self,
+        command: &CardCommand<'_>,
+        response: &mut Vec<u8>,
+    ) -> ApduStatus {
+        let mut context = command.data().to_vec();
+        context.extend_from_slice(&CARD_CHALLENGE);
+
+        let mac = derive(&MAC, 0x06, &context, 128);
+
+        response.extend_from_slice(&[0x00; 10]);
+        response.extend_from_slice(&[0x30, 0x03, 0x70]);
+        response.extend_from_slice(&CARD_CHALLENGE);
+        response.extend_from_slice(&derive(&mac, 0x00, &context, 64));
+        response.extend_from_slice(&[0x00, 0x00, 0x2A]);
+
+        self.session = Some(Session {
+            enc: derive(&ENC, 0x04, &context, 128),
+            rmac: derive(&MAC, 0x07, &context, 128),
+            mac,
+            level: None,
+            chaining: [0; 16],
+            counter: 1,
+        });
+
+        status::OK
+    }
+
+    /// Checks the C-MAC of `command` and returns its data without it.
+    fn verify_mac(session: &mut Session, command: &CardCommand<'_>) -> Option<Vec<u8>> {
+        let data = command.data();
+        let (data, mac) = data.split_at_checked(data.len().checked_sub(8)?)?;
+        let (p1, p2) = command.parameters();
+
+        let mut input = session.chaining.to_vec();
+        input.extend_from_slice(&[command.class_byte(), command.instruction(), p1, p2]);
+        input.push((data.len() + 8) as u8);
+        input.extend_from_slice(data);
+
+        let chaining = cmac(&session.mac, &input);
+        (chaining[..8] == *mac).then(|| {
+            session.chaining = chaining;
+            data.to_vec()
+        })
+    }
+}
+
+impl Applet for SecurityDomain {
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        if command.class_byte() == 0x80 && command.instruction() == 0x50 {
+            return self.initialize_update(command, response);
+        }
+
+        let Some(session) = self.session.as_mut() else {
+            return ApduStatus::new(0x69, 0x85);
+        };
+
+        if command.class_byte() & 0x04 == 0 {
+            return ApduStatus::new(0x69, 0x82);
+        }
+
+        let Some(mut data) = Self::verify_mac(session, command) else {
+            self.session = None;
+            return ApduStatus::new(0x69, 0x82);
+        };
+
+        let Some(level) = session.level else {
+            let context = [HOST_CHALLENGE, CARD_CHALLENGE].concat();
+
+            if command.instruction() != 0x82 || data != derive(&session.mac, 0x01, &context, 64) {
+                self.session = None;
+                return ApduStatus::new(0x63, 0x00);
+            }
+
+            session.level = Some(command.parameters().0);
+            return status::OK;
+        };
+
+        let counter = session.counter;
+        session.counter += 1;
+
+        if level & 0x02 != 0 && !data.is_empty() {
+            cbc(
+                &session.enc,
+                encrypt_block(&session.enc, counter.to_be_bytes()),
+                &mut data,
+                false,
+            );
+            let end = data.iter().rposition(|&byte| byte == 0x80).unwrap();
+            data.truncate(end);
+        }
+
+        self.received.borrow_mut().push(data.clone());
+
+        let status = match command.instruction() {
+            0xEE => {
+                let (p1, p2) = command.parameters();
+                match u16::from_be_bytes([p1, p2]) {
+                    0 => response.extend_from_slice(&data),
+                    length => response.extend((0..length).map(|index| index as u8)),
+                }
+                status::OK
+            }
+            0xE6 => return ApduStatus::new(0x6A, 0x88),
+            0xD8 => {
+                response.push(data[0]);
+                let mut rest = &data[1..];
+
+                while let [0x88, _, length, key @ ..] = rest {
+                    let mut plain = key[..16].to_vec();
+                    cbc(&DEK, [0; 16], &mut plain, false);
+                    plain.truncate(*length as usize);
+                    response.extend_from_slice(&encrypt_block(&plain, [0x01; 16])[..3]);
+                    rest = &key[16 + 4..];
+                }
+                status::OK
+            }
+            _ => return status::INSTRUCTION_NOT_SUPPORTED,
+        };
+
+        if level & 0x20 != 0 && !response.is_empty() {
+            let mut icv = counter.to_be_bytes();
+            icv[0] = 0x80;
+            response.push(0x80);
+            response.resize(response.len().next_multiple_of(16), 0x00);
+            cbc(
+                &session.enc,
+                encrypt_block(&session.enc, icv),
+                response,
+                true,
+            );
+        }
+
+        if level & 0x10 != 0 {
+            let mut input = session.chaining.to_vec();
+            input.extend_from_slice(response);
+            input.extend_from_slice(&[status.code1(), status.code2()]);
+
+            let mut rmac = cmac(&session.rmac, &input);
+            if self.corrupt_rmac {
+                rmac[0] ^= 0x01;
+            }
+            response.extend_from_slice(&rmac[..8]);
+        }
+
+        status
+    }
+}
+
+fn keys() -> Scp03Keys {
+    Scp03Keys::new(&ENC, &MAC, &DEK).unwrap()
+}
+
+fn card(security_domain: SecurityDomain) -> VirtualCard {
+    let mut card = VirtualCard::new(FileSystem::new()).with_applet(ISD_AID, security_domain);
+
+    let select = OwnedCommand::new(0x00, 0xA4, (0x04, 0x00), ISD_AID.to_vec());
+    let mut reply = [0u8; 258];
+    let response = block_on(card.execute(select, &mut reply)).unwrap();
+    assert_eq!(response.status(), status::OK);
+
+    card
+}
+
+fn open(card: VirtualCard, level: SecurityLevel) -> Scp03Transport<VirtualCard> {
+    block_on(Scp03Transport::open(card, &keys(), level, HOST_CHALLENGE)).unwrap()
+}
+
+fn echo(transport: &mut impl ApduTransport, data: &[u8]) -> (Vec<u8>, ApduStatus) {
+    let command = OwnedCommand::new(0x80, 0xEE, (0x00, 0x00), data.to_vec());
+    let mut reply = [0u8; 258];
+    let response = block_on(transport.execute(command, &mut reply)).unwrap();
+    (response.data().to_vec(), response.status())
+}
+
+// The known answers below were computed apart from this crate, with
+// OpenSSL's AES and AES-CMAC and the KDF input laid out as Amendment D
+// section 6.2.2 gives it.
+
+#[test]
+fn derives_known_session_keys() {
+    let session_keys = keys().session_keys(&HOST_CHALLENGE, &CARD_CHALLENGE);
+    assert_eq!(session_keys.enc, hex("CCC86DCEE3463FBADB68F4096F484A15"));
+    assert_eq!(session_keys.mac, hex("0BEB433C46C28389DF28BF1F9644C91A"));
+    assert_eq!(session_keys.rmac, hex("D667D20CF2701A7478F9018302FA7A71"));
+
+    // 256-bit keys take two rounds of the KDF.
+    let enc: Vec<u8> = (0x40..0x60).collect();
+    let mac: Vec<u8> = (0x60..0x80).collect();
+    let session_keys = Scp03Keys::new(&enc, &mac, &[0x00; 32])
+        .unwrap()
+        .session_keys(&HOST_CHALLENGE, &CARD_CHALLENGE);
+    assert_eq!(
+        session_keys.enc,
+        hex("DB41C6B6574F373B64C4ACA9FE635A33D3C7898A00035FDA676BDCCD49031D92")
+    );
+    assert_eq!(
+        session_keys.mac,
+        hex("1472486E553D70BBF371AB8257819DA50E7A8A08F22BB369613CB4335A985008")
+    );
+    assert_eq!(
+        session_keys.rmac,
+        hex("364CF260611E5F1CA93C3E957311E2342103D68722EF1FD7BC782992863CEC62")
+    );
+}
+
+#[test]
+fn exchanges_known_cryptograms_and_wrapped_apdus() {
+    let initialize_update = [&hex("8050000008")[..], &HOST_CHALLENGE, &hex("00")].concat();
+    // Key diversification data, key information, card challenge, card
+    // cryptogram and sequence counter.
+    let card_response = [
+        &[0x00; 10][..],
+        &[0x30, 0x03, 0x70],
+        &CARD_CHALLENGE,
+        &hex("B22EAAB279B7E329"),
+        &[0x00, 0x00, 0x2A],
+        &[0x90, 0x00],
+    ]
+    .concat();
+
+    // The host cryptogram 812A073ACD515E2A with its C-MAC, then "plesio"
+    // encrypted and MACed, answered by "secure channel" encrypted and
+    // R-MACed.
+    let mock = MockTransport::new()
+        .expect(&initialize_update, &card_response)
+        .expect(
+            &hex("8482330010812A073ACD515E2ADF12FA32EFD5CF76"),
+            &[0x90, 0x00],
+        )
+        .expect(
+            &hex("84EE000018826B808664AE70127BF507FB442249267C928902B76C50FA00"),
+            &hex("597CA07ECB5925C72E474E114888E19097A571B632E1A0559000"),
+        );
+
+    let level = SecurityLevel::C_DECRYPTION | SecurityLevel::R_ENCRYPTION;
+    let mut transport =
+        block_on(Scp03Transport::open(mock, &keys(), level, HOST_CHALLENGE)).unwrap();
+
+    assert_eq!(
+        echo(&mut transport, b"plesio"),
+        (b"secure channel".to_vec(), status::OK)
+    );
+    transport.inner().assert_finished();
+}
+
+#[test]
+fn opens_channel_at_each_security_level() {
+    let levels = [
+        SecurityLevel::NONE,
+        SecurityLevel::C_MAC,
+        SecurityLevel::C_DECRYPTION,
+        SecurityLevel::C_MAC | SecurityLevel::R_MAC,
+        SecurityLevel::C_DECRYPTION | SecurityLevel::R_MAC,
+        SecurityLevel::C_DECRYPTION | SecurityLevel::R_ENCRYPTION,
+    ];
+
+    for level in levels {
+        let security_domain = SecurityDomain::default();
+        let received = security_domain.received.clone();
+        let mut transport = open(card(security_domain), level);
+        assert_eq!(transport.security_level().to_u8(), level.to_u8());
+
+        if level == SecurityLevel::NONE {
+            let (_, status) = echo(&mut transport, b"plain");
+            assert_eq!(status, ApduStatus::new(0x69, 0x82));
+            continue;
+        }
+
+        for message in [&b"first"[..], b"", &[0x5A; 47]] {
+            assert_eq!(
+                echo(&mut transport, message),
+                (message.to_vec(), status::OK)
+            );
+        }
+
+        assert_eq!(received.borrow().len(), 3);
+        assert_eq!(received.borrow()[2], [0x5A; 47]);
+    }
+}
+
+#[test]
+fn serves_long_protected_responses() {
+    let level = SecurityLevel::C_DECRYPTION | SecurityLevel::R_ENCRYPTION;
+    let mut transport = Iso7816Transport::new(open(card(SecurityDomain::default()), level));
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xEE, (0x01, 0x2C), &[]);
+    let response = block_on(transport.execute_owned(command)).unwrap();
+
+    assert_eq!(response.status(), status::OK);
+    assert_eq!(response.data().len(), 300);
+    assert!(
+        response
+            .data()
+            .iter()
+            .enumerate()
+            .all(|(index, &byte)| byte == index as u8)
+    );
+}
+
+#[test]
+fn passes_unprotected_errors_through() {
+    let level = SecurityLevel::C_MAC | SecurityLevel::R_MAC;
+    let mut transport = open(card(SecurityDomain::default()), level);
+
+    let command = OwnedCommand::new(0x80, 0xE6, (0x00, 0x00), Vec::new());
+    let mut reply = [0u8; 258];
+    let response = block_on(transport.execute(command, &mut reply)).unwrap();
+    assert_eq!(response.status(), ApduStatus::new(0x6A, 0x88));
+
+    assert_eq!(echo(&mut transport, b"after").0, b"after");
+}
+
+#[test]
+fn limits_payload_to_wrapped_capacity() {
+    let mut transport = open(card(SecurityDomain::default()), SecurityLevel::C_DECRYPTION);
+    assert_eq!(transport.max_payload_size(), 239);
+
+    let command = OwnedCommand::new(0x80, 0xEE, (0x00, 0x00), vec![0x00; 240]);
+    let mut reply = [0u8; 258];
+    let error = block_on(transport.execute(command, &mut reply)).unwrap_err();
+    assert!(matches!(
+        error,
+        Scp03Error::PayloadTooLarge { max_size: 239 }
+    ));
+
+    let (data, _) = echo(&mut transport, &[0x33; 239]);
+    assert_eq!(data, [0x33; 239]);
+}
+
+#[test]
+fn rejects_wrong_keys() {
+    let keys = Scp03Keys::new(&MAC, &ENC, &DEK).unwrap();
+    let card = card(SecurityDomain::default());
+    let result = block_on(Scp03Transport::open(
+        card,
+        &keys,
+        SecurityLevel::C_MAC,
+        HOST_CHALLENGE,
+    ));
+
+    assert!(matches!(result, Err(Scp03Error::InvalidCardCryptogram)));
+    assert!(Scp03Keys::new(&ENC, &MAC, &[0x60; 24]).is_none());
+}
+
+#[test]
+fn rejects_invalid_response_mac() {
+    let security_domain = SecurityDomain {
+        corrupt_rmac: true,
+        ..Default::default()
+    };
+    let level = SecurityLevel::C_MAC | SecurityLevel::R_MAC;
+    let mut transport = open(card(security_domain), level);
+
+    let command = OwnedCommand::new(0x80, 0xEE, (0x00, 0x00), b"data".to_vec());
+    let mut reply = [0u8; 258];
+    let error = block_on(transport.execute(command, &mut reply)).unwrap_err();
+    assert!(matches!(error, Scp03Error::InvalidMac));
+}
+
+#[test]
+fn replaces_keys_with_put_key() {
+    assert_eq!(scp03::key_check_value(&[0x40; 15]), None);
+
+    let security_domain = SecurityDomain::default();
+    let received = security_domain.received.clone();
+    let transport = open(card(security_domain), SecurityLevel::C_DECRYPTION);
+
+    let new_keys = [[0x11; 16], [0x22; 16], [0x33; 16]];
+    let keys: Vec<_> = new_keys
+        .iter()
+        .map(|key| keys().encrypt_key(key).unwrap())
+        .collect();
+    assert_eq!(
+        keys[0].check_value(),
+        encrypt_block(&[0x11; 16], [0x01; 16])[..3]
+    );
+
+    let mut manager = CardManager::new(transport);
+    block_on(manager.put_key(PutKey::new(0x30, &keys).replacing(0x01))).unwrap();
+
+    let sent = received.borrow()[0].clone();
+    assert_eq!(sent.len(), 1 + 3 * 23);
+    assert_eq!(sent[..4], [0x30, 0x88, 0x11, 0x10]);
+    assert_eq!(sent[20], 0x03);
+    assert_eq!(sent[21..24], keys[0].check_value());
+}
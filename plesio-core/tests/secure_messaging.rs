@@ -0,0 +1,417 @@
+use std::convert::Infallible;
+
+use des::{
+    Des, TdesEde2,
+    cipher::{BlockDecrypt, BlockEncrypt, KeyInit, generic_array::GenericArray},
+};
+use plesio_core::apdu::{
+    blocking::block_on,
+    class::ApduClass,
+    command::ApduCommand,
+    iso_7816::{
+        class::Iso7816Class,
+        operation::Iso7816Command,
+        secure_messaging::{SecureMessagingError, SecureMessagingTransport, SmCipher, pad, unpad},
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    mock::{MockError, MockTransport},
+    response::ApduResponse,
+    status::ApduStatus,
+    transport::ApduTransport,
+};
+
+mod common;
+
+use common::hex;
+
+/// ICAO 9303 3DES secure messaging: two-key 3DES in CBC mode with a zero
+/// IV, and the ISO 9797-1 retail MAC over the counter and data.
+struct Icao3Des {
+    encryption: TdesEde2,
+    mac_keys: (Des, Des),
+}
+
+impl Icao3Des {
+    fn new(encryption: &str, mac: &str) -> Self {
+        let mac = hex(mac);
+
+        Self {
+            encryption: TdesEde2::new_from_slice(&hex(encryption)).unwrap(),
+            mac_keys: (
+                Des::new_from_slice(&mac[..8]).unwrap(),
+                Des::new_from_slice(&mac[8..]).unwrap(),
+            ),
+        }
+    }
+}
+
+impl SmCipher for Icao3Des {
+    type Error = Infallible;
+
+    fn block_size(&self) -> usize {
+        8
+    }
+
+    fn encrypt(&mut self, _counter: &[u8], data: &mut [u8]) -> Result<(), Infallible> {
+        let mut chain = [0u8; 8];
+
+        for block in data.chunks_exact_mut(8) {
+            block
+                .iter_mut()
+                .zip(chain)
+                .for_each(|(byte, iv)| *byte ^= iv);
+            self.encryption
+                .encrypt_block(GenericArray::from_mut_slice(block));
+            chain.copy_from_slice(block);
+        }
+
+        Ok(())
+    }
+
+    fn decrypt(&mut self, _counter: &[u8], data: &mut [u8]) -> Result<(), Infallible> {
+        let mut chain = [0u8; 8];
+
+        for block in data.chunks_exact_mut(8) {
+            let cryptogram: [u8; 8] = block.try_into().unwrap();
+            self.encryption
+                .decrypt_block(GenericArray::from_mut_slice(block));
+            block
+                .iter_mut()
+                .zip(chain)
+                .for_each(|(byte, iv)| *byte ^= iv);
+            chain = cryptogram;
+        }
+
+        Ok(())
+    }
+
+    fn mac(&mut self, counter: &[u8], data: &[u8]) -> Result<[u8; 8], Infallible> {
+        let mut state = [0u8; 8];
+
+        for block in counter.chunks(8).chain(data.chunks(8)) {
+            state
+                .iter_mut()
+                .zip(block)
+                .for_each(|(byte, data)| *byte ^= data);
+            self.mac_keys
+                .0
+                .encrypt_block(GenericArray::from_mut_slice(&mut state));
+        }
+
+        self.mac_keys
+            .1
+            .decrypt_block(GenericArray::from_mut_slice(&mut state));
+        self.mac_keys
+            .0
+            .encrypt_block(GenericArray::from_mut_slice(&mut state));
+
+        Ok(state)
+    }
+}
+
+/// The session keys and counter of the ICAO 9303 part 11 worked example.
+fn icao_cipher() -> Icao3Des {
+    Icao3Des::new(
+        "979EC13B1CBFE9DCD01AB0FED307EAE5",
+        "F1CB1F1FB5ADF208806B89DC579DC1F8",
+    )
+}
+
+const ICAO_COUNTER: &str = "887022120C06C226";
+
+#[test]
+fn protects_icao_worked_example() {
+    let card = MockTransport::new()
+        .expect(
+            &hex("0CA4020C158709016375432908C044F68E08BF8B92D635FF24F800"),
+            &hex("990290008E08FA855A5D4C50A8ED9000"),
+        )
+        .expect(
+            &hex("0CB000000D9701048E08ED6705417E96BA5500"),
+            &hex("8709019FF0EC34F9922651990290008E08AD55CC17140B2DED9000"),
+        );
+    let transport =
+        SecureMessagingTransport::new(card, icao_cipher()).with_counter(&hex(ICAO_COUNTER));
+    let mut transport = Iso7816Transport::new(transport);
+
+    let select = Iso7816Command::new(Iso7816Class::default(), 0xA4, (0x02, 0x0C), &[0x01, 0x1E]);
+    let mut reply = [0u8; 2];
+    let response = block_on(transport.transmit(select, &mut reply)).unwrap();
+    assert_eq!(response.status(), ApduStatus::new(0x90, 0x00));
+
+    let read = Iso7816Command::new(Iso7816Class::default(), 0xB0, (0x00, 0x00), &[]);
+    let mut reply = [0u8; 6];
+    let response = block_on(transport.transmit(read, &mut reply)).unwrap();
+    assert_eq!(response.data(), hex("60145F01"));
+
+    assert_eq!(transport.inner().counter(), hex("887022120C06C22A"));
+    transport.into_inner().into_inner().assert_finished();
+}
+
+fn object(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut object = vec![tag];
+
+    match value.len() {
+        length @ 0..=0x7F => object.push(length as u8),
+        length @ 0x80..=0xFF => object.extend([0x81, length as u8]),
+        length => object.extend([0x82, (length >> 8) as u8, length as u8]),
+    }
+
+    object.extend_from_slice(value);
+    object
+}
+
+fn objects(mut data: &[u8]) -> Vec<(u8, &[u8], usize)> {
+    let mut objects = Vec::new();
+    let mut offset = 0;
+
+    while !data.is_empty() {
+        let (length, header) = match data[1] {
+            0x81 => (data[2] as usize, 3),
+            0x82 => (u16::from_be_bytes([data[2], data[3]]) as usize, 4),
+            length => (length as usize, 2),
+        };
+
+        objects.push((data[0], &data[header..header + length], offset));
+        data = &data[header + length..];
+        offset += header + length;
+    }
+
+    objects
+}
+
+/// A card speaking ICAO 9303 secure messaging, recording the class and
+/// unprotected data of every command, and answering each with `response`
+/// or `unprotected_error`. `extra_objects` are added to every protected
+/// response just before its MAC.
+struct Card {
+    cipher: Icao3Des,
+    counter: u64,
+    max_payload_size: usize,
+    response: Vec<u8>,
+    tamper: bool,
+    extra_objects: Vec<u8>,
+    unprotected_error: Option<[u8; 2]>,
+    pending: Vec<u8>,
+    received: Vec<(u8, Vec<u8>)>,
+}
+
+impl Card {
+    fn new(response: &[u8]) -> Self {
+        Self {
+            cipher: icao_cipher(),
+            counter: 0,
+            max_payload_size: 255,
+            response: response.to_vec(),
+            tamper: false,
+            extra_objects: Vec::new(),
+            unprotected_error: None,
+            pending: Vec::new(),
+            received: Vec::new(),
+        }
+    }
+
+    fn send_pending<'r>(&mut self, reply: &'r mut [u8]) -> ApduResponse<'r> {
+        let length = self.pending.len().min(reply.len() - 2);
+        reply[..length].copy_from_slice(&self.pending[..length]);
+        self.pending.drain(..length);
+
+        let status = match self.pending.len() {
+            0 => [0x90, 0x00],
+            remaining => [0x61, remaining.min(0xFF) as u8],
+        };
+        reply[length..length + 2].copy_from_slice(&status);
+
+        ApduResponse::parse(&reply[..length + 2]).unwrap()
+    }
+
+    fn respond<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, MockError> {
+        if command.data().len() > self.max_payload_size {
+            return Err(MockError::PayloadTooLarge {
+                max_size: self.max_payload_size,
+            });
+        }
+
+        if command.instruction() == 0xC0 {
+            assert_eq!(command.class().to_u8(), 0x00);
+            return Ok(self.send_pending(reply));
+        }
+
+        let class = command.class().to_u8();
+        let (p1, p2) = command.parameters();
+        let received = objects(command.data());
+        let (_, mac, mac_offset) = *received.last().unwrap();
+
+        self.counter += 1;
+        let counter = self.counter.to_be_bytes();
+        let mut authenticated = vec![class, command.instruction(), p1, p2];
+        pad(&mut authenticated, 8);
+        authenticated.extend_from_slice(&command.data()[..mac_offset]);
+        pad(&mut authenticated, 8);
+        assert_eq!(self.cipher.mac(&counter, &authenticated).unwrap(), mac);
+
+        let mut data = Vec::new();
+        if let Some((_, cryptogram, _)) = received.iter().find(|(tag, _, _)| *tag == 0x87) {
+            data = cryptogram[1..].to_vec();
+            self.cipher.decrypt(&counter, &mut data).unwrap();
+            data = unpad(&data).unwrap().to_vec();
+        }
+        self.received.push((class, data));
+
+        if let Some([code1, code2]) = self.unprotected_error {
+            self.counter += 1;
+            reply[..2].copy_from_slice(&[code1, code2]);
+            return Ok(ApduResponse::parse(&reply[..2]).unwrap());
+        }
+
+        self.counter += 1;
+        let counter = self.counter.to_be_bytes();
+        let mut protected = Vec::new();
+        if !self.response.is_empty() {
+            let mut cryptogram = self.response.clone();
+            pad(&mut cryptogram, 8);
+            self.cipher.encrypt(&counter, &mut cryptogram).unwrap();
+            cryptogram.insert(0, 0x01);
+            protected.extend(object(0x87, &cryptogram));
+        }
+        protected.extend(object(0x99, &[0x90, 0x00]));
+        protected.extend_from_slice(&self.extra_objects);
+
+        let mut authenticated = protected.clone();
+        pad(&mut authenticated, 8);
+        let mut mac = self.cipher.mac(&counter, &authenticated).unwrap();
+        if self.tamper {
+            mac[7] ^= 0x01;
+        }
+        protected.extend(object(0x8E, &mac));
+
+        self.pending = protected;
+        Ok(self.send_pending(reply))
+    }
+}
+
+impl ApduTransport for Card {
+    type TransportError = MockError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> impl Future<Output = Result<ApduResponse<'r>, MockError>> {
+        std::future::ready(self.respond(command, reply_buffer))
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.max_payload_size
+    }
+}
+
+type Session = Iso7816Transport<SecureMessagingTransport<Card, Icao3Des>>;
+
+fn session(card: Card) -> Session {
+    Iso7816Transport::new(SecureMessagingTransport::new(card, icao_cipher()))
+}
+
+fn command(instruction: u8, data: &[u8]) -> Iso7816Command<'_> {
+    Iso7816Command::new(Iso7816Class::default(), instruction, (0x00, 0x00), data)
+}
+
+#[test]
+fn chains_protected_commands() {
+    let mut card = Card::new(&[]);
+    card.max_payload_size = 64;
+    let mut transport = session(card);
+
+    // 64 bytes fit 1 + 40 bytes of cryptogram after DO 97 and DO 8E.
+    assert_eq!(transport.inner().max_payload_size(), 39);
+
+    let data: Vec<u8> = (0..100).collect();
+    let response = block_on(transport.execute_owned(command(0xDA, &data))).unwrap();
+    assert_eq!(response.status(), ApduStatus::new(0x90, 0x00));
+
+    let card = transport.into_inner().into_inner();
+    let classes: Vec<u8> = card.received.iter().map(|(class, _)| *class).collect();
+    assert_eq!(classes, [0x1C, 0x1C, 0x0C]);
+    let received: Vec<u8> = card
+        .received
+        .iter()
+        .flat_map(|(_, data)| data.clone())
+        .collect();
+    assert_eq!(received, data);
+    assert_eq!(card.counter, 6);
+}
+
+#[test]
+fn collects_long_protected_responses() {
+    let data: Vec<u8> = (0..300).map(|byte| byte as u8).collect();
+    let mut transport = session(Card::new(&data));
+
+    let response = block_on(transport.execute_owned(command(0xB0, &[]))).unwrap();
+    assert_eq!(response.data(), data);
+    assert_eq!(transport.inner().counter(), 2u64.to_be_bytes());
+}
+
+#[test]
+fn rejects_invalid_response_mac() {
+    let mut card = Card::new(&[0x01, 0x02]);
+    card.tamper = true;
+    let mut transport = session(card);
+
+    let error = block_on(transport.execute_owned(command(0xB0, &[]))).unwrap_err();
+    assert!(matches!(
+        error,
+        Iso7816TransportError::Transport(SecureMessagingError::InvalidMac)
+    ));
+}
+
+#[test]
+fn rejects_objects_after_the_mac() {
+    let card = MockTransport::new().expect(
+        &hex("0CA4020C158709016375432908C044F68E08BF8B92D635FF24F800"),
+        &hex("990290008E08FA855A5D4C50A8ED8103DEAD019000"),
+    );
+    let transport =
+        SecureMessagingTransport::new(card, icao_cipher()).with_counter(&hex(ICAO_COUNTER));
+    let mut transport = Iso7816Transport::new(transport);
+
+    let select = Iso7816Command::new(Iso7816Class::default(), 0xA4, (0x02, 0x0C), &[0x01, 0x1E]);
+    let mut reply = [0u8; 2];
+    let error = block_on(transport.transmit(select, &mut reply)).unwrap_err();
+    assert!(matches!(
+        error,
+        Iso7816TransportError::Transport(SecureMessagingError::MalformedResponse)
+    ));
+}
+
+#[test]
+fn rejects_duplicate_objects() {
+    for (response, extra_objects) in [
+        (&[0x01, 0x02][..], object(0x99, &[0x62, 0x82])),
+        (&[], [object(0x81, &[0x01]), object(0x81, &[0x02])].concat()),
+    ] {
+        let mut card = Card::new(response);
+        card.extra_objects = extra_objects;
+        let mut transport = session(card);
+
+        let error = block_on(transport.execute_owned(command(0xB0, &[]))).unwrap_err();
+        assert!(matches!(
+            error,
+            Iso7816TransportError::Transport(SecureMessagingError::MalformedResponse)
+        ));
+    }
+}
+
+#[test]
+fn passes_unprotected_errors_through() {
+    let mut card = Card::new(&[]);
+    card.unprotected_error = Some([0x69, 0x88]);
+    let mut transport = session(card);
+
+    let response = block_on(transport.execute_owned(command(0xB0, &[]))).unwrap();
+    assert_eq!(response.status(), ApduStatus::new(0x69, 0x88));
+    assert_eq!(transport.inner().counter(), 2u64.to_be_bytes());
+}
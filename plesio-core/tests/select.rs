@@ -0,0 +1,102 @@
+use plesio_core::apdu::{
+    blocking::block_on,
+    iso_7816::{
+        channel::Iso7816Channel,
+        class::Iso7816Class,
+        operation::{
+            Iso7816Command,
+            select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        },
+        transport::Iso7816Transport,
+    },
+    mock::MockTransport,
+    status::ApduStatus,
+};
+
+const PIV_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x03, 0x08];
+
+#[test]
+fn select_by_aid_returns_fci() {
+    let card = MockTransport::new().expect(
+        &[
+            0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0x10,
+        ],
+        &[0x4F, 0x02, 0x12, 0x34, 0x90, 0x00],
+    );
+    let mut transport = Iso7816Transport::new(card);
+    let mut response = [0u8; 18];
+
+    let select = Iso7816Select::new(
+        Iso7816SelectResolution::ByApplicationIdentifier(PIV_AID),
+        &mut response,
+    );
+    let fci = block_on(transport.execute(select)).unwrap().unwrap();
+
+    assert_eq!(fci.get(0x4F).unwrap().value(), &[0x12, 0x34]);
+    transport.into_inner().assert_finished();
+}
+
+#[test]
+fn select_follows_get_response() {
+    let card = MockTransport::new()
+        .expect(
+            &[
+                0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0x10,
+            ],
+            &[0x61, 0x04],
+        )
+        .expect(
+            &[0x00, 0xC0, 0x00, 0x00, 0x04],
+            &[0x4F, 0x02, 0x12, 0x34, 0x90, 0x00],
+        );
+    let mut transport = Iso7816Transport::new(card);
+    let mut response = [0u8; 18];
+
+    let select = Iso7816Select::new(
+        Iso7816SelectResolution::ByApplicationIdentifier(PIV_AID),
+        &mut response,
+    );
+    let fci = block_on(transport.execute(select)).unwrap().unwrap();
+
+    assert_eq!(fci.get(0x4F).unwrap().value(), &[0x12, 0x34]);
+    transport.into_inner().assert_finished();
+}
+
+#[test]
+fn select_failure_returns_response() {
+    let card =
+        MockTransport::new().expect(&[0x00, 0xA4, 0x00, 0x00, 0x02, 0x3F, 0x00], &[0x6A, 0x82]);
+    let mut transport = Iso7816Transport::new(card);
+
+    let select = Iso7816Select::new(Iso7816SelectResolution::ByFileId([0x3F, 0x00]), &mut []);
+    let failure = block_on(transport.execute(select)).unwrap().unwrap_err();
+
+    assert_eq!(failure.status(), ApduStatus::new(0x6A, 0x82));
+}
+
+#[test]
+fn select_uses_transport_class() {
+    let card =
+        MockTransport::new().expect(&[0x02, 0xA4, 0x00, 0x00, 0x02, 0x3F, 0x00], &[0x90, 0x00]);
+    let class = Iso7816Class::for_channel(Iso7816Channel::Basic(2)).unwrap();
+    let mut transport = Iso7816Transport::new(card).with_class(class);
+
+    let select = Iso7816Select::new(Iso7816SelectResolution::ByFileId([0x3F, 0x00]), &mut []);
+    block_on(transport.execute(select)).unwrap().unwrap();
+
+    assert_eq!(transport.class(), class);
+}
+
+#[test]
+fn transmit_sends_raw_command() {
+    let card =
+        MockTransport::new().expect(&[0x00, 0xCA, 0x9F, 0x7F, 0x00], &[0x01, 0x02, 0x90, 0x00]);
+    let mut transport = Iso7816Transport::new(card);
+    let mut reply = [0u8; 258];
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xCA, (0x9F, 0x7F), &[]);
+    let response = block_on(transport.transmit(command, &mut reply)).unwrap();
+
+    assert_eq!(response.data(), &[0x01, 0x02]);
+    assert_eq!(transport.inner_mut().transcript().len(), 1);
+}
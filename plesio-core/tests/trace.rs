@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use plesio_core::apdu::{
+    blocking::block_on,
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{
+            Iso7816Command,
+            select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        },
+        transport::Iso7816Transport,
+    },
+    mock::{MockError, MockTransport},
+    openpgp::{object::tag, operation::PutData},
+    piv::{Algorithm, Slot, key::PrivateKey, operation::ImportKey},
+    trace::{
+        TracingTransport,
+        describe::{ClassSummary, TlvDump},
+    },
+};
+
+thread_local! {
+    static LINES: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Collects log lines per thread, so tests running in parallel do not see
+/// each other's exchanges.
+struct Capture;
+
+impl Log for Capture {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == "plesio::apdu"
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            LINES.with_borrow_mut(|lines| lines.push((record.level(), record.args().to_string())));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static CAPTURE: Capture = Capture;
+
+fn captured(run: impl FnOnce()) -> Vec<(Level, String)> {
+    let _ = log::set_logger(&CAPTURE);
+    log::set_max_level(LevelFilter::Trace);
+
+    LINES.with_borrow_mut(Vec::clear);
+    run();
+    LINES.with_borrow_mut(std::mem::take)
+}
+
+#[test]
+fn logs_decoded_exchange() {
+    let aid = [0xA0, 0x00, 0x00, 0x03, 0x08];
+    let card = MockTransport::new().expect(
+        &[
+            0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x03, 0x08, 0x0E,
+        ],
+        &[
+            0x6F, 0x09, 0x84, 0x02, 0xA0, 0x00, 0xA5, 0x03, 0x88, 0x01, 0x01, 0x90, 0x00,
+        ],
+    );
+    let mut transport = Iso7816Transport::new(TracingTransport::new(card));
+    let mut reply = [0u8; 16];
+
+    let lines = captured(|| {
+        let select = Iso7816Select::new(
+            Iso7816SelectResolution::ByApplicationIdentifier(&aid),
+            &mut reply,
+        );
+        block_on(transport.execute(select)).unwrap().unwrap();
+    });
+
+    assert_eq!(lines[0].0, Level::Debug);
+    assert_eq!(
+        lines[0].1,
+        "> 00A40400 SELECT (by DF name) [channel 0] Lc=5 A000000308 Le=14"
+    );
+    assert!(lines[1].1.starts_with("< 9000 success, 11 bytes"));
+    assert_eq!(lines[2].0, Level::Trace);
+    assert_eq!(
+        lines[2].1,
+        "< 6F098402A000A503880101\n  6F\n    84 A000\n    A5\n      88 01"
+    );
+}
+
+#[test]
+fn redacts_reference_data() {
+    let verify = [0x00, 0x20, 0x00, 0x81, 0x04, 0x31, 0x32, 0x33, 0x34, 0x00];
+    let run = |redact| {
+        let card = MockTransport::new().expect(&verify, &[0x63, 0xC2]);
+        let mut transport =
+            Iso7816Transport::new(TracingTransport::new(card).with_redaction(redact));
+
+        captured(|| {
+            let command = Iso7816Command::new(Iso7816Class::default(), 0x20, (0x00, 0x81), b"1234");
+            block_on(transport.execute_owned(command)).unwrap();
+        })
+    };
+
+    let redacted = run(true);
+    assert_eq!(
+        redacted[0].1,
+        "> 00200081 VERIFY [channel 0] Lc=4 <redacted> Le=256"
+    );
+    assert!(
+        redacted[1]
+            .1
+            .starts_with("< 63C2 verification failed, 0 bytes")
+    );
+
+    let shown = run(false);
+    assert_eq!(
+        shown[0].1,
+        "> 00200081 VERIFY [channel 0] Lc=4 31323334 Le=256"
+    );
+}
+
+#[test]
+fn redacts_keys_and_secret_data_objects() {
+    let scalar = [0x5A; 32];
+    let import = [
+        &[0x00, 0xFE, 0x11, 0x9A, 0x22, 0x06, 0x20][..],
+        &scalar,
+        &[0x00],
+    ]
+    .concat();
+    let reset_code = [
+        0x00, 0xDA, 0x00, 0xD3, 0x08, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x00,
+    ];
+    let url = [0x00, 0xDA, 0x5F, 0x50, 0x03, 0x61, 0x62, 0x63, 0x00];
+
+    let card = MockTransport::new()
+        .expect(&import, &[0x90, 0x00])
+        .expect(&reset_code, &[0x90, 0x00])
+        .expect(&url, &[0x90, 0x00]);
+    let mut transport = Iso7816Transport::new(TracingTransport::new(card));
+
+    let lines = captured(|| {
+        let key = PrivateKey::Ec {
+            scalar: scalar.to_vec(),
+        };
+        let import = ImportKey::new(Slot::Authentication, Algorithm::EccP256, &key);
+        block_on(transport.execute_owned(import)).unwrap();
+        block_on(transport.execute_owned(PutData::new(tag::RESET_CODE, b"12345678"))).unwrap();
+        block_on(transport.execute_owned(PutData::new(tag::URL, b"abc"))).unwrap();
+    });
+
+    assert!(lines[0].1.starts_with("> 00FE119A "));
+    assert!(lines[0].1.ends_with(" Lc=34 <redacted> Le=256"));
+    assert!(lines[2].1.ends_with(" Lc=8 <redacted> Le=256"));
+    assert!(lines[4].1.ends_with(" Lc=3 616263 Le=256"));
+    assert!(lines.iter().all(|(_, line)| !line.contains("5A5A5A5A")));
+}
+
+#[test]
+fn logs_transport_errors() {
+    let card = MockTransport::new().expect_error(
+        &[0x00, 0xB0, 0x00, 0x00],
+        MockError::Failure("reader unplugged"),
+    );
+    let mut transport = Iso7816Transport::new(TracingTransport::new(card));
+
+    let lines = captured(|| {
+        let command = Iso7816Command::new(Iso7816Class::default(), 0xB0, (0x00, 0x00), &[]);
+        let mut reply = [0u8; 2];
+        let _ = block_on(transport.transmit(command, &mut reply));
+    });
+
+    assert_eq!(lines[1].0, Level::Warn);
+    assert!(lines[1].1.contains("reader unplugged"));
+}
+
+#[test]
+fn describes_class_bytes_and_tlv() {
+    assert_eq!(ClassSummary(0x13).to_string(), "channel 3, chaining");
+    assert_eq!(ClassSummary(0x41).to_string(), "channel 5");
+    assert_eq!(
+        ClassSummary(0x8C).to_string(),
+        "proprietary, channel 0, SM with header"
+    );
+    assert_eq!(ClassSummary(0x24).to_string(), "invalid class");
+
+    assert!(!TlvDump(&[0x90, 0x05, 0x01]).is_tlv());
+    assert_eq!(TlvDump(&[0x90, 0x05, 0x01]).to_string(), "");
+
+    let fci = [
+        0x6F, 0x10, 0x84, 0x02, 0xA0, 0x00, 0xA5, 0x0A, 0x9F, 0x38, 0x02, 0x9F, 0x66, 0x5F, 0x2D,
+        0x02, 0x65, 0x6E,
+    ];
+    assert_eq!(
+        TlvDump(&fci).to_string(),
+        "\n  6F\n    84 A000\n    A5\n      9F38 9F66\n      5F2D 656E"
+    );
+
+    let public_key = [0x7F, 0x49, 0x03, 0x86, 0x01, 0x04, 0x5F, 0xC1, 0x02, 0x00];
+    assert_eq!(
+        TlvDump(&public_key).to_string(),
+        "\n  7F49\n    86 04\n  5FC102 "
+    );
+}
@@ -0,0 +1,240 @@
+use plesio_core::apdu::{
+    blocking::block_on,
+    iso_7816::{
+        class::Iso7816Class,
+        operation::{
+            Iso7816Command,
+            read_record::ReadRecord,
+            select::{Iso7816Select, resolution::Iso7816SelectResolution},
+        },
+        transport::{Iso7816Transport, Iso7816TransportError},
+    },
+    mock::{MockError, MockTransport},
+    status::ApduStatus,
+};
+
+const AID: &[u8] = &[0xA0, 0x00, 0x00, 0x00, 0x01];
+
+fn response(data: &[u8], status: [u8; 2]) -> Vec<u8> {
+    [data, &status].concat()
+}
+
+#[test]
+fn chains_commands_rejected_as_too_large() {
+    let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+
+    let full = [
+        &[0x00, 0xDB, 0x3F, 0xFF, 0x00, 0x01, 0x2C][..],
+        &data,
+        &[0x01, 0x00],
+    ]
+    .concat();
+    let first = [&[0x10, 0xDB, 0x3F, 0xFF, 0xFF][..], &data[..255]].concat();
+    let last = [&[0x00, 0xDB, 0x3F, 0xFF, 0x2D][..], &data[255..], &[0x00]].concat();
+
+    let card = MockTransport::new()
+        .expect_payload_too_large(&full, 255)
+        .expect(&first, &[0x90, 0x00])
+        .expect(&last, &[0x90, 0x00]);
+    let mut transport = Iso7816Transport::new(card);
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xDB, (0x3F, 0xFF), &data);
+    let response = block_on(transport.execute_owned(command)).unwrap();
+
+    assert_eq!(response.status(), ApduStatus::new(0x90, 0x00));
+    transport.into_inner().assert_finished();
+}
+
+#[test]
+fn chaining_stops_at_first_error() {
+    let data = [0xAA; 300];
+
+    let full = [
+        &[0x00, 0xDB, 0x3F, 0xFF, 0x00, 0x01, 0x2C][..],
+        &data,
+        &[0x01, 0x00],
+    ]
+    .concat();
+    let first = [&[0x10, 0xDB, 0x3F, 0xFF, 0xFF][..], &data[..255]].concat();
+
+    let card = MockTransport::new()
+        .expect_payload_too_large(&full, 255)
+        .expect(&first, &[0x6A, 0x80]);
+    let mut transport = Iso7816Transport::new(card);
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xDB, (0x3F, 0xFF), &data);
+    let response = block_on(transport.execute_owned(command)).unwrap();
+
+    assert_eq!(response.status(), ApduStatus::new(0x6A, 0x80));
+    assert!(response.data().is_empty());
+    transport.into_inner().assert_finished();
+}
+
+#[test]
+fn executes_typed_operations_owned() {
+    let record: Vec<u8> = (0..272).map(|i| i as u8).collect();
+
+    let card = MockTransport::new()
+        .expect(
+            &[
+                0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x00, 0x01, 0x00,
+            ],
+            &response(&[0x6F, 0x00], [0x90, 0x00]),
+        )
+        .expect(
+            &[0x00, 0xB2, 0x02, 0x0C, 0x00],
+            &response(&record[..256], [0x61, 0x10]),
+        )
+        .expect(
+            &[0x00, 0xC0, 0x00, 0x00, 0x10],
+            &response(&record[256..], [0x90, 0x00]),
+        );
+    let mut transport = Iso7816Transport::new(card);
+
+    let select = Iso7816Select::new(
+        Iso7816SelectResolution::ByApplicationIdentifier(AID),
+        &mut [],
+    );
+    let fci = block_on(transport.execute_owned(select)).unwrap();
+    assert_eq!(fci.data(), &[0x6F, 0x00]);
+
+    let response = block_on(transport.execute_owned(ReadRecord::new(1, 2))).unwrap();
+    assert_eq!(response.status(), ApduStatus::new(0x90, 0x00));
+    assert_eq!(response.into_data(), record);
+    transport.into_inner().assert_finished();
+}
+
+#[test]
+fn streams_get_response_rounds_into_sink() {
+    let certificate: Vec<u8> = (0..528).map(|i| i as u8).collect();
+
+    let card = MockTransport::new()
+        .expect(
+            &[0x00, 0xCA, 0x01, 0x02, 0x00],
+            &response(&certificate[..256], [0x61, 0x00]),
+        )
+        .expect(
+            &[0x00, 0xC0, 0x00, 0x00, 0x00],
+            &response(&certificate[256..512], [0x61, 0x10]),
+        )
+        .expect(
+            &[0x00, 0xC0, 0x00, 0x00, 0x10],
+            &response(&certificate[512..], [0x90, 0x00]),
+        );
+    let mut transport = Iso7816Transport::new(card);
+    let mut sink = heapless::Vec::<u8, 1024>::new();
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xCA, (0x01, 0x02), &[]);
+    let response = block_on(transport.execute_streaming(command, &mut sink)).unwrap();
+
+    assert_eq!(response.status(), ApduStatus::new(0x90, 0x00));
+    assert_eq!(response.data(), &certificate[..]);
+    transport.into_inner().assert_finished();
+}
+
+#[test]
+fn streaming_reports_full_sink() {
+    let card = MockTransport::new().expect(
+        &[0x00, 0xCA, 0x01, 0x02, 0x00],
+        &response(&[0x55; 16], [0x90, 0x00]),
+    );
+    let mut transport = Iso7816Transport::new(card);
+    let mut sink = heapless::Vec::<u8, 8>::new();
+
+    let command = Iso7816Command::new(Iso7816Class::default(), 0xCA, (0x01, 0x02), &[]);
+    let error = block_on(transport.execute_streaming(command, &mut sink)).unwrap_err();
+
+    assert!(matches!(
+        error,
+        Iso7816TransportError::ResponseBufferTooSmall {
+            expected: 16,
+            hint: 8
+        }
+    ));
+}
+
+#[test]
+fn resends_with_corrected_length() {
+    let card = MockTransport::new()
+        .expect(
+            &[
+                0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x00, 0x01, 0x20,
+            ],
+            &[0x6C, 0x04],
+        )
+        .expect(
+            &[
+                0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x00, 0x01, 0x04,
+            ],
+            &[0x4F, 0x02, 0x12, 0x34, 0x90, 0x00],
+        );
+    let mut transport = Iso7816Transport::new(card);
+    let mut reply = [0u8; 34];
+
+    let select = Iso7816Select::new(
+        Iso7816SelectResolution::ByApplicationIdentifier(AID),
+        &mut reply,
+    );
+    let fci = block_on(transport.execute(select)).unwrap().unwrap();
+
+    assert_eq!(fci.get(0x4F).unwrap().value(), &[0x12, 0x34]);
+    transport.into_inner().assert_finished();
+}
+
+#[test]
+fn reports_reply_buffer_too_small() {
+    let card = MockTransport::new().expect(
+        &[
+            0x00, 0xA4, 0x04, 0x00, 0x05, 0xA0, 0x00, 0x00, 0x00, 0x01, 0x04,
+        ],
+        &[0x61, 0x10],
+    );
+    let mut transport = Iso7816Transport::new(card);
+    let mut reply = [0u8; 6];
+
+    let select = Iso7816Select::new(
+        Iso7816SelectResolution::ByApplicationIdentifier(AID),
+        &mut reply,
+    );
+    let error = block_on(transport.execute(select)).unwrap_err();
+
+    assert!(matches!(
+        error,
+        Iso7816TransportError::ResponseBufferTooSmall {
+            expected: 18,
+            hint: 6
+        }
+    ));
+}
+
+#[test]
+fn propagates_transport_errors() {
+    let card = MockTransport::new().expect_error(
+        &[0x00, 0xA4, 0x00, 0x00, 0x02, 0x3F, 0x00],
+        MockError::Failure("card removed"),
+    );
+    let mut transport = Iso7816Transport::new(card);
+
+    let select = Iso7816Select::new(Iso7816SelectResolution::ByFileId([0x3F, 0x00]), &mut []);
+    let error = block_on(transport.execute(select)).unwrap_err();
+
+    assert!(matches!(
+        error,
+        Iso7816TransportError::Transport(MockError::Failure("card removed"))
+    ));
+    assert_eq!(
+        transport.inner().transcript()[0].reply,
+        Err(MockError::Failure("card removed"))
+    );
+}
+
+#[test]
+#[should_panic(expected = "mock transport expected command")]
+fn mock_panics_on_unexpected_command() {
+    let card =
+        MockTransport::new().expect(&[0x00, 0xA4, 0x00, 0x00, 0x02, 0x3F, 0x00], &[0x90, 0x00]);
+    let mut transport = Iso7816Transport::new(card);
+
+    let select = Iso7816Select::new(Iso7816SelectResolution::ByFileId([0x2F, 0x00]), &mut []);
+    let _ = block_on(transport.execute(select));
+}
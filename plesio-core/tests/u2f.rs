@@ -0,0 +1,218 @@
+use std::{cell::Cell, rc::Rc};
+
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        fido::{
+            FIDO_AID,
+            u2f::{AuthenticateMode, RegistrationResponse, U2F_V2, U2f, U2fError},
+        },
+        iso_7816::status,
+        status::ApduStatus,
+    },
+    card::{applet::Applet, command::CardCommand, file::FileSystem, virtual_card::VirtualCard},
+};
+
+const CHALLENGE: [u8; 32] = [0xC1; 32];
+const APPLICATION: [u8; 32] = [0xA9; 32];
+
+fn certificate() -> Vec<u8> {
+    let mut certificate = vec![0x30, 0x82, 0x01, 0xF4];
+    certificate.extend((0..500).map(|index| index as u8));
+    certificate
+}
+
+fn signature(seed: u8) -> Vec<u8> {
+    let mut signature = vec![0x30, 0x45];
+    signature.extend([seed; 71 - 2]);
+    signature
+}
+
+/// The card side of a U2F authenticator. The key handle it issues is the
+/// application parameter reversed and padded to `key_handle_length` bytes,
+/// and the user is present while `touched` is set.
+struct U2fApplet {
+    key_handle_length: usize,
+    touched: Rc<Cell<bool>>,
+    counter: u32,
+}
+
+impl U2fApplet {
+    fn key_handle(&self, application: &[u8]) -> Vec<u8> {
+        let mut key_handle: Vec<u8> = application.iter().rev().copied().collect();
+        key_handle.resize(self.key_handle_length, 0x6B);
+        key_handle
+    }
+}
+
+impl Applet for U2fApplet {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        response.extend_from_slice(U2F_V2);
+        status::OK
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        let data = command.data();
+
+        match (command.instruction(), command.parameters()) {
+            (0x01, (0x00, 0x00)) => {
+                if !self.touched.take() {
+                    return status::CONDITIONS_OF_USE_NOT_SATISFIED;
+                }
+
+                let key_handle = self.key_handle(&data[32..64]);
+                response.push(0x05);
+                response.push(0x04);
+                response.extend([0x11; 64]);
+                response.push(key_handle.len() as u8);
+                response.extend(key_handle);
+                response.extend(certificate());
+                response.extend(signature(0x5A));
+                status::OK
+            }
+            (0x02, (mode, 0x00)) => {
+                let (application, key_handle) = (&data[32..64], &data[65..]);
+                assert_eq!(data[64] as usize, key_handle.len());
+
+                if key_handle != self.key_handle(application) {
+                    return status::WRONG_DATA;
+                }
+
+                let present = match mode {
+                    0x07 => return status::CONDITIONS_OF_USE_NOT_SATISFIED,
+                    0x03 if !self.touched.take() => {
+                        return status::CONDITIONS_OF_USE_NOT_SATISFIED;
+                    }
+                    0x03 => true,
+                    _ => false,
+                };
+
+                self.counter += 1;
+                response.push(present as u8);
+                response.extend(self.counter.to_be_bytes());
+                response.extend(signature(0x3C));
+                status::OK
+            }
+            (0x03, (0x00, 0x00)) => {
+                response.extend_from_slice(U2F_V2);
+                status::OK
+            }
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+fn u2f(key_handle_length: usize, max_payload_size: usize) -> (U2f<VirtualCard>, Rc<Cell<bool>>) {
+    let touched = Rc::new(Cell::new(false));
+    let applet = U2fApplet {
+        key_handle_length,
+        touched: touched.clone(),
+        counter: 0,
+    };
+    let card = VirtualCard::new(FileSystem::new())
+        .with_applet(FIDO_AID, applet)
+        .with_max_payload_size(max_payload_size);
+
+    let mut u2f = U2f::new(card);
+    assert_eq!(block_on(u2f.select()).unwrap(), U2F_V2);
+    assert_eq!(block_on(u2f.version()).unwrap(), U2F_V2);
+
+    (u2f, touched)
+}
+
+#[test]
+fn registers_after_user_presence() {
+    let (mut u2f, touched) = u2f(64, 255);
+
+    assert!(matches!(
+        block_on(u2f.register(&CHALLENGE, &APPLICATION)),
+        Err(U2fError::UserPresenceRequired)
+    ));
+
+    touched.set(true);
+    let registration = block_on(u2f.register(&CHALLENGE, &APPLICATION)).unwrap();
+    assert_eq!(registration.public_key[0], 0x04);
+    assert_eq!(registration.key_handle.len(), 64);
+    assert_eq!(registration.attestation_certificate, certificate());
+    assert_eq!(registration.signature, signature(0x5A));
+
+    let signed = registration.signed_data(&CHALLENGE, &APPLICATION);
+    assert_eq!(signed[0], 0x00);
+    assert_eq!(signed[1..33], APPLICATION);
+    assert_eq!(signed[33..65], CHALLENGE);
+    assert_eq!(signed.len(), 1 + 32 + 32 + 64 + 65);
+}
+
+#[test]
+fn authenticates_in_each_mode() {
+    let (mut u2f, touched) = u2f(64, 255);
+    touched.set(true);
+    let key_handle = block_on(u2f.register(&CHALLENGE, &APPLICATION))
+        .unwrap()
+        .key_handle;
+
+    assert!(block_on(u2f.is_registered(&CHALLENGE, &APPLICATION, &key_handle)).unwrap());
+    assert!(!block_on(u2f.is_registered(&CHALLENGE, &[0x00; 32], &key_handle)).unwrap());
+
+    let enforce = AuthenticateMode::EnforceUserPresence;
+    assert!(matches!(
+        block_on(u2f.authenticate(enforce, &CHALLENGE, &APPLICATION, &key_handle)),
+        Err(U2fError::UserPresenceRequired)
+    ));
+
+    touched.set(true);
+    let response =
+        block_on(u2f.authenticate(enforce, &CHALLENGE, &APPLICATION, &key_handle)).unwrap();
+    assert!(response.user_present);
+    assert_eq!(response.counter, 1);
+    assert_eq!(response.signature, signature(0x3C));
+
+    let signed = response.signed_data(&CHALLENGE, &APPLICATION);
+    assert_eq!(signed[32..37], [0x01, 0x00, 0x00, 0x00, 0x01]);
+    assert_eq!(signed[37..], CHALLENGE);
+
+    let response = block_on(u2f.authenticate(
+        AuthenticateMode::DontEnforceUserPresence,
+        &CHALLENGE,
+        &APPLICATION,
+        &key_handle,
+    ))
+    .unwrap();
+    assert!(!response.user_present);
+    assert_eq!(response.counter, 2);
+}
+
+#[test]
+fn sends_long_key_handles_extended_or_chained() {
+    for max_payload_size in [255, 1024] {
+        let (mut u2f, touched) = u2f(255, max_payload_size);
+        touched.set(true);
+        let key_handle = block_on(u2f.register(&CHALLENGE, &APPLICATION))
+            .unwrap()
+            .key_handle;
+        assert_eq!(key_handle.len(), 255);
+
+        assert!(block_on(u2f.is_registered(&CHALLENGE, &APPLICATION, &key_handle)).unwrap());
+    }
+
+    let (mut u2f, _) = u2f(255, 1024);
+    assert!(matches!(
+        block_on(u2f.is_registered(&CHALLENGE, &APPLICATION, &[0x01; 256])),
+        Err(U2fError::KeyHandleTooLong)
+    ));
+}
+
+#[test]
+fn rejects_malformed_registration() {
+    let mut data = vec![0x05];
+    data.extend([0x04; 65]);
+    data.push(4);
+    data.extend([0x01; 4]);
+    data.extend(certificate());
+
+    assert_eq!(RegistrationResponse::parse(&data), None);
+    data.extend(signature(0x01));
+    assert!(RegistrationResponse::parse(&data).is_some());
+    data[0] = 0x04;
+    assert_eq!(RegistrationResponse::parse(&data), None);
+}
@@ -0,0 +1,251 @@
+use std::{cell::Cell, rc::Rc};
+
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        iso_7816::{
+            channel::Iso7816Channel,
+            class::Iso7816Class,
+            operation::{
+                Iso7816Command,
+                select::{Iso7816Select, resolution::Iso7816SelectResolution},
+            },
+            status,
+            transport::Iso7816Transport,
+        },
+        owned::OwnedResponse,
+        status::ApduStatus,
+    },
+    card::{
+        applet::Applet,
+        command::CardCommand,
+        file::{AccessCondition, FileSystem},
+        pin::PinObject,
+        virtual_card::VirtualCard,
+    },
+};
+
+const DF_NAME: &[u8] = &[0xD2, 0x76, 0x00, 0x00, 0x01];
+const ECHO_AID: &[u8] = &[0xA0, 0x00, 0x00, 0x00, 0x62, 0x03, 0x01];
+
+/// Echoes the command data back for INS 01.
+struct Echo {
+    deselected: Rc<Cell<bool>>,
+}
+
+impl Applet for Echo {
+    fn select(&mut self, _command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        response.extend_from_slice(&[0x6F, 0x00]);
+        status::OK
+    }
+
+    fn deselect(&mut self) {
+        self.deselected.set(true);
+    }
+
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        match command.instruction() {
+            0x01 => {
+                response.extend_from_slice(command.data());
+                status::OK
+            }
+            _ => status::INSTRUCTION_NOT_SUPPORTED,
+        }
+    }
+}
+
+fn card() -> (VirtualCard, Rc<Cell<bool>>) {
+    let mut files = FileSystem::new();
+    let df = files.add_dedicated(files.master_file(), 0x5000, Some(DF_NAME));
+    let contents: Vec<u8> = (0..600).map(|i| i as u8).collect();
+    files.add_elementary(df, 0x5001, &contents);
+    let protected = files.add_elementary(df, 0x5002, b"secret");
+    files.set_access(
+        protected,
+        AccessCondition::Pin(0x81),
+        AccessCondition::Never,
+    );
+
+    let deselected = Rc::new(Cell::new(false));
+    let card = VirtualCard::new(files)
+        .with_pin(PinObject::new(0x81, b"123456", 3).with_resetting_code(0x82))
+        .with_pin(PinObject::new(0x82, b"12345678", 3))
+        .with_applet(
+            ECHO_AID,
+            Echo {
+                deselected: deselected.clone(),
+            },
+        );
+
+    (card, deselected)
+}
+
+fn send(
+    transport: &mut Iso7816Transport<VirtualCard>,
+    instruction: u8,
+    parameters: (u8, u8),
+    data: &[u8],
+) -> OwnedResponse {
+    let command = Iso7816Command::new(transport.class(), instruction, parameters, data);
+    block_on(transport.execute_owned(command)).unwrap()
+}
+
+#[test]
+fn selects_by_path_and_reads_binary() {
+    let mut transport = Iso7816Transport::new(card().0);
+
+    let fcp = send(
+        &mut transport,
+        0xA4,
+        (0x08, 0x04),
+        &[0x50, 0x00, 0x50, 0x01],
+    );
+    assert_eq!(fcp.status(), status::OK);
+    assert_eq!(
+        fcp.data(),
+        &[
+            0x62, 0x0E, 0x80, 0x02, 0x02, 0x58, 0x82, 0x01, 0x01, 0x83, 0x02, 0x50, 0x01, 0x8A,
+            0x01, 0x05
+        ]
+    );
+
+    let first = send(&mut transport, 0xB0, (0x00, 0x00), &[]);
+    assert_eq!(first.status(), status::OK);
+    assert_eq!(first.data().len(), 256);
+
+    let last = send(&mut transport, 0xB0, (0x02, 0x00), &[]);
+    assert_eq!(last.status(), status::END_OF_FILE);
+    assert_eq!(
+        last.data(),
+        &(512..600).map(|i| i as u8).collect::<Vec<_>>()[..]
+    );
+}
+
+#[test]
+fn selects_df_by_name_through_select_operation() {
+    let mut transport = Iso7816Transport::new(card().0);
+    let mut response = [0u8; 64];
+
+    let select = Iso7816Select::new(
+        Iso7816SelectResolution::ByApplicationIdentifier(DF_NAME),
+        &mut response,
+    );
+    let fci = block_on(transport.execute(select)).unwrap().unwrap();
+
+    assert_eq!(
+        fci.get(0x6F).unwrap().value()[..4],
+        [0x82, 0x01, 0x38, 0x83]
+    );
+    assert_eq!(
+        send(&mut transport, 0xA4, (0x00, 0x0C), &[0x50, 0x01]).status(),
+        status::OK
+    );
+}
+
+#[test]
+fn protected_file_requires_pin() {
+    let mut transport = Iso7816Transport::new(card().0);
+
+    send(
+        &mut transport,
+        0xA4,
+        (0x08, 0x0C),
+        &[0x50, 0x00, 0x50, 0x02],
+    );
+
+    let denied = send(&mut transport, 0xB0, (0x00, 0x00), &[]);
+    assert_eq!(denied.status(), status::SECURITY_STATUS_NOT_SATISFIED);
+
+    let verify = send(&mut transport, 0x20, (0x00, 0x81), b"123456");
+    assert_eq!(verify.status(), status::OK);
+
+    let read = send(&mut transport, 0xB0, (0x00, 0x00), &[]);
+    assert_eq!(read.data(), b"secret");
+}
+
+#[test]
+fn pin_blocks_and_is_reset_with_resetting_code() {
+    let mut transport = Iso7816Transport::new(card().0);
+
+    for retries in (0..3).rev() {
+        let verify = send(&mut transport, 0x20, (0x00, 0x81), b"000000");
+        assert_eq!(verify.status(), status::verification_failed(retries));
+    }
+
+    let blocked = send(&mut transport, 0x20, (0x00, 0x81), b"123456");
+    assert_eq!(blocked.status(), status::AUTHENTICATION_METHOD_BLOCKED);
+
+    let reset = send(&mut transport, 0x2C, (0x00, 0x81), b"12345678654321");
+    assert_eq!(reset.status(), status::OK);
+    assert_eq!(transport.inner().pin(0x81).unwrap().retries(), 3);
+
+    let verify = send(&mut transport, 0x20, (0x00, 0x81), b"654321");
+    assert_eq!(verify.status(), status::OK);
+
+    let query = send(&mut transport, 0x20, (0x00, 0x81), &[]);
+    assert_eq!(query.status(), status::OK);
+}
+
+#[test]
+fn logical_channels_keep_separate_state() {
+    let mut transport = Iso7816Transport::new(card().0);
+
+    let open = send(&mut transport, 0x70, (0x00, 0x00), &[]);
+    assert_eq!(open.data(), &[0x01]);
+
+    send(&mut transport, 0x20, (0x00, 0x81), b"123456");
+
+    let channel = Iso7816Class::for_channel(Iso7816Channel::Basic(1)).unwrap();
+    transport.set_class(channel);
+
+    send(
+        &mut transport,
+        0xA4,
+        (0x08, 0x0C),
+        &[0x50, 0x00, 0x50, 0x02],
+    );
+    let denied = send(&mut transport, 0xB0, (0x00, 0x00), &[]);
+    assert_eq!(denied.status(), status::SECURITY_STATUS_NOT_SATISFIED);
+
+    let close = send(&mut transport, 0x70, (0x80, 0x00), &[]);
+    assert_eq!(close.status(), status::OK);
+
+    let closed = send(&mut transport, 0xA4, (0x00, 0x0C), &[]);
+    assert_eq!(closed.status(), status::LOGICAL_CHANNEL_NOT_SUPPORTED);
+}
+
+#[test]
+fn applet_receives_reassembled_chain_and_long_response() {
+    let (card, deselected) = card();
+    let mut transport = Iso7816Transport::new(card);
+
+    let select = send(&mut transport, 0xA4, (0x04, 0x00), &ECHO_AID[..5]);
+    assert_eq!(select.data(), &[0x6F, 0x00]);
+
+    let data: Vec<u8> = (0..700).map(|i| (i * 7) as u8).collect();
+    let echo = send(&mut transport, 0x01, (0x00, 0x00), &data);
+
+    assert_eq!(echo.status(), status::OK);
+    assert_eq!(echo.data(), &data[..]);
+
+    transport.inner_mut().reset();
+    assert!(deselected.get());
+
+    let unknown = send(&mut transport, 0x01, (0x00, 0x00), &data[..4]);
+    assert_eq!(unknown.status(), status::INSTRUCTION_NOT_SUPPORTED);
+}
+
+#[test]
+fn reports_the_largest_transparent_ef_size() {
+    let mut files = FileSystem::new();
+    let file = files.add_elementary(files.master_file(), 0x0101, &vec![0; 0xFFFF]);
+
+    assert_eq!(files.fcp(file)[2..6], [0x80, 0x02, 0xFF, 0xFF]);
+}
+
+#[test]
+#[should_panic(expected = "transparent EF larger than 65535 bytes")]
+fn rejects_transparent_efs_over_64_kib() {
+    let mut files = FileSystem::new();
+    files.add_elementary(files.master_file(), 0x0101, &vec![0; 0x10000]);
+}
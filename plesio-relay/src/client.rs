@@ -0,0 +1,168 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+use hmac::Mac;
+use plesio_core::apdu::{
+    blocking::{BlockingAdapter, BlockingTransport},
+    command::ApduCommand,
+    encoding,
+    response::ApduResponse,
+};
+
+use crate::{CHALLENGE_LENGTH, Channel, RelayError, Role, authentication_tag, kind, session_key};
+
+/// A [`BlockingTransport`] forwarding commands to a
+/// [`RelayServer`](crate::server::RelayServer).
+pub struct RelayClient<S> {
+    channel: Channel<S>,
+    max_payload_size: usize,
+}
+
+impl RelayClient<TcpStream> {
+    /// Connects to a relay server, authenticating with `key` if the server
+    /// asks for it.
+    pub fn connect(address: impl ToSocketAddrs, key: Option<&[u8]>) -> Result<Self, RelayError> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+
+        Self::new(stream, key)
+    }
+}
+
+impl<S: Read + Write> RelayClient<S> {
+    /// Performs the handshake over an established stream.
+    pub fn new(stream: S, key: Option<&[u8]>) -> Result<Self, RelayError> {
+        let mut channel = Channel::new(stream);
+        let (kind, payload) = channel.receive()?.ok_or(RelayError::Closed)?;
+
+        let (max_payload_size, challenge) = match kind {
+            kind::WELCOME => match payload.split_first_chunk::<4>() {
+                Some((max_payload_size, challenge))
+                    if challenge.is_empty() || challenge.len() == CHALLENGE_LENGTH =>
+                {
+                    (u32::from_be_bytes(*max_payload_size) as usize, challenge)
+                }
+                _ => return Err(RelayError::MalformedFrame("welcome")),
+            },
+            kind => return Err(RelayError::UnexpectedFrame { kind }),
+        };
+
+        if challenge.is_empty() {
+            channel.send(kind::AUTHENTICATE, &[])?;
+        } else {
+            let key = key.ok_or(RelayError::AuthenticationFailed)?;
+
+            let mut nonce = [0u8; CHALLENGE_LENGTH];
+            getrandom::fill(&mut nonce).map_err(|error| RelayError::Remote(error.to_string()))?;
+            let tag = authentication_tag(key, challenge, &nonce)
+                .finalize()
+                .into_bytes();
+
+            channel.send(kind::AUTHENTICATE, &[&nonce[..], &tag].concat())?;
+            channel.start_session(session_key(key, challenge, &nonce), Role::Client);
+        }
+
+        // With a key, a server that does not know it cannot produce a
+        // reply that verifies.
+        match channel.receive() {
+            Ok(Some((kind::OK, _))) => {}
+            Ok(Some((kind::BUSY, _))) => return Err(RelayError::Busy),
+            Ok(Some((kind::ERROR, _))) | Err(RelayError::InvalidMac) => {
+                return Err(RelayError::AuthenticationFailed);
+            }
+            Ok(Some((kind, _))) => return Err(RelayError::UnexpectedFrame { kind }),
+            Ok(None) => return Err(RelayError::Closed),
+            Err(error) => return Err(error),
+        }
+
+        Ok(Self {
+            channel,
+            max_payload_size,
+        })
+    }
+
+    /// Wraps the client for use with the async `Iso7816Transport` API.
+    pub fn into_async(self) -> BlockingAdapter<Self> {
+        BlockingAdapter::new(self)
+    }
+
+    pub fn into_inner(self) -> S {
+        self.channel.stream
+    }
+
+    /// Sends an encoded command and returns the encoded response, including
+    /// the status bytes.
+    pub fn transmit(&mut self, command: &[u8]) -> Result<Vec<u8>, RelayError> {
+        match self.request(kind::COMMAND, command)? {
+            (kind::RESPONSE, response) if response.len() >= 2 => Ok(response),
+            (kind::RESPONSE, _) => Err(RelayError::MalformedFrame("response")),
+            (kind, _) => Err(RelayError::UnexpectedFrame { kind }),
+        }
+    }
+
+    /// The ATR of the relayed card.
+    pub fn atr(&mut self) -> Result<Vec<u8>, RelayError> {
+        match self.request(kind::GET_ATR, &[])? {
+            (kind::ATR, atr) => Ok(atr),
+            (kind, _) => Err(RelayError::UnexpectedFrame { kind }),
+        }
+    }
+
+    /// Resets the relayed card.
+    pub fn reset(&mut self) -> Result<(), RelayError> {
+        match self.request(kind::RESET, &[])? {
+            (kind::OK, _) => Ok(()),
+            (kind, _) => Err(RelayError::UnexpectedFrame { kind }),
+        }
+    }
+
+    /// Sends a request and reads its reply, turning error replies into
+    /// errors.
+    fn request(&mut self, kind: u8, payload: &[u8]) -> Result<(u8, Vec<u8>), RelayError> {
+        self.channel.send(kind, payload)?;
+
+        match self.channel.receive()?.ok_or(RelayError::Closed)? {
+            (kind::ERROR, message) => Err(RelayError::Remote(
+                String::from_utf8_lossy(&message).into_owned(),
+            )),
+            (kind::PAYLOAD_TOO_LARGE, max_size) => match <[u8; 4]>::try_from(max_size) {
+                Ok(max_size) => Err(RelayError::PayloadTooLarge {
+                    max_size: u32::from_be_bytes(max_size) as usize,
+                }),
+                Err(_) => Err(RelayError::MalformedFrame("payload too large")),
+            },
+            reply => Ok(reply),
+        }
+    }
+}
+
+impl<S: Read + Write> BlockingTransport for RelayClient<S> {
+    type TransportError = RelayError;
+
+    fn execute<'r>(
+        &mut self,
+        command: impl ApduCommand,
+        reply_buffer: &'r mut [u8],
+    ) -> Result<ApduResponse<'r>, Self::TransportError> {
+        let expected_length = encoding::expected_length(reply_buffer.len());
+        let length =
+            encoding::encoded_length(&command, expected_length).ok_or(RelayError::Unencodable)?;
+        let mut encoded = vec![0u8; length];
+        encoding::encode(&command, expected_length, &mut encoded).ok_or(RelayError::Unencodable)?;
+
+        let response = self.transmit(&encoded)?;
+
+        let reply = reply_buffer
+            .get_mut(..response.len())
+            .ok_or(RelayError::MalformedFrame("response"))?;
+        reply.copy_from_slice(&response);
+
+        ApduResponse::parse(reply).ok_or(RelayError::MalformedFrame("response"))
+    }
+
+    fn max_payload_size(&self) -> usize {
+        self.max_payload_size
+    }
+}
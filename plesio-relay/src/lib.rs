@@ -0,0 +1,252 @@
+//! Drives a card attached to one machine from another over a byte stream.
+//!
+//! A [`server::RelayServer`] wraps a local [`BlockingTransport`] and serves
+//! one client at a time; a [`client::RelayClient`] is an [`ApduTransport`]
+//! that forwards commands to it. Both work over any `Read + Write` stream, so TLS can be
+//! layered underneath by wrapping a `TcpStream` before handing it over.
+//!
+//! Every message is a frame of a one byte kind, a four byte big-endian
+//! payload length and the payload. On connection the server sends
+//! `WELCOME`, carrying its maximum command payload size and, if a
+//! pre-shared key is configured, a 32 byte challenge. The client answers
+//! with `AUTHENTICATE`: empty without a key, or a 32 byte nonce of its own
+//! and the HMAC-SHA256 of both under the key. Only then does the server
+//! claim the card, replying `OK`, or `BUSY` if another session holds it.
+//!
+//! With a key, both sides derive a session key from the challenge and the
+//! nonce, and every frame from the server's reply on carries the
+//! HMAC-SHA256 of its sender, sequence number, kind and payload under it,
+//! so frames cannot be forged, altered, replayed or reordered.
+//!
+//! [`ApduTransport`]: plesio_core::apdu::transport::ApduTransport
+//! [`BlockingTransport`]: plesio_core::apdu::blocking::BlockingTransport
+
+use std::io::{self, Read, Write};
+
+use hmac::{Hmac, Mac};
+use plesio_core::apdu::{
+    encoding,
+    transport::{PayloadTooLarge, TransportError},
+};
+use sha2::Sha256;
+
+pub mod client;
+pub mod server;
+
+/// The port used when none is given.
+pub const DEFAULT_PORT: u16 = 35964;
+
+const CHALLENGE_LENGTH: usize = 32;
+
+const MAC_LENGTH: usize = 32;
+
+/// Larger frames are rejected without reading their payload.
+const MAX_FRAME_LENGTH: usize = encoding::MAX_ENCODED_LENGTH + MAC_LENGTH;
+
+/// The sender of a frame, part of its MAC so a frame cannot be reflected
+/// back to its sender.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Client = 0x00,
+    Server = 0x01,
+}
+
+mod kind {
+    // Client to server.
+    pub const COMMAND: u8 = 0x01;
+    pub const RESET: u8 = 0x02;
+    pub const GET_ATR: u8 = 0x03;
+    pub const AUTHENTICATE: u8 = 0x04;
+
+    // Server to client.
+    pub const WELCOME: u8 = 0x80;
+    pub const OK: u8 = 0x81;
+    pub const RESPONSE: u8 = 0x82;
+    pub const ERROR: u8 = 0x83;
+    pub const BUSY: u8 = 0x84;
+    pub const ATR: u8 = 0x85;
+    pub const PAYLOAD_TOO_LARGE: u8 = 0x86;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RelayError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("the card is in use by another session")]
+    Busy,
+    #[error("pre-shared key authentication failed")]
+    AuthenticationFailed,
+    #[error("frame failed its integrity check")]
+    InvalidMac,
+    #[error("unexpected frame of kind {kind:#04X}")]
+    UnexpectedFrame { kind: u8 },
+    #[error("frame of {length} bytes exceeds the maximum size")]
+    FrameTooLarge { length: usize },
+    #[error("malformed {0} frame")]
+    MalformedFrame(&'static str),
+    #[error("connection closed by peer")]
+    Closed,
+    #[error("relayed card failed: {0}")]
+    Remote(String),
+    #[error("command payload exceeds {max_size} bytes")]
+    PayloadTooLarge { max_size: usize },
+    #[error("command cannot be encoded as an APDU")]
+    Unencodable,
+}
+
+impl TransportError for RelayError {
+    fn is_payload_too_large(&self) -> Option<PayloadTooLarge> {
+        match self {
+            Self::PayloadTooLarge { max_size } => Some(PayloadTooLarge {
+                max_size: *max_size,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn write_frame(stream: &mut impl Write, kind: u8, payload: &[u8]) -> Result<(), RelayError> {
+    let length = u32::try_from(payload.len())
+        .ok()
+        .filter(|&length| length as usize <= MAX_FRAME_LENGTH)
+        .ok_or(RelayError::FrameTooLarge {
+            length: payload.len(),
+        })?;
+
+    let mut frame = Vec::with_capacity(5 + payload.len());
+    frame.push(kind);
+    frame.extend_from_slice(&length.to_be_bytes());
+    frame.extend_from_slice(payload);
+
+    stream.write_all(&frame)?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+/// Reads one frame, or `None` if the stream ended between frames.
+fn read_frame(stream: &mut impl Read) -> Result<Option<(u8, Vec<u8>)>, RelayError> {
+    let mut header = [0u8; 5];
+
+    match stream.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    }
+
+    let [kind, length @ ..] = header;
+    let length = u32::from_be_bytes(length) as usize;
+
+    if length > MAX_FRAME_LENGTH {
+        return Err(RelayError::FrameTooLarge { length });
+    }
+
+    let mut payload = vec![0u8; length];
+    stream.read_exact(&mut payload)?;
+
+    Ok(Some((kind, payload)))
+}
+
+/// The HMAC-SHA256 of `parts` under `key`.
+fn authenticator(key: &[u8], parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+/// The tag a client answers `challenge` with, along with its `nonce`.
+fn authentication_tag(key: &[u8], challenge: &[u8], nonce: &[u8]) -> Hmac<Sha256> {
+    authenticator(key, &[b"plesio-relay authenticate", challenge, nonce])
+}
+
+fn session_key(key: &[u8], challenge: &[u8], nonce: &[u8]) -> [u8; MAC_LENGTH] {
+    authenticator(key, &[b"plesio-relay session", challenge, nonce])
+        .finalize()
+        .into_bytes()
+        .into()
+}
+
+struct Session {
+    key: [u8; MAC_LENGTH],
+    role: Role,
+    sent: u64,
+    received: u64,
+}
+
+impl Session {
+    fn mac(&self, sender: Role, sequence: u64, kind: u8, payload: &[u8]) -> Hmac<Sha256> {
+        authenticator(
+            &self.key,
+            &[&[sender as u8], &sequence.to_be_bytes(), &[kind], payload],
+        )
+    }
+}
+
+/// A stream of frames, authenticated once a session key is agreed.
+struct Channel<S> {
+    stream: S,
+    session: Option<Session>,
+}
+
+impl<S: Read + Write> Channel<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream,
+            session: None,
+        }
+    }
+
+    /// Authenticates every later frame under `key`.
+    fn start_session(&mut self, key: [u8; MAC_LENGTH], role: Role) {
+        self.session = Some(Session {
+            key,
+            role,
+            sent: 0,
+            received: 0,
+        });
+    }
+
+    fn send(&mut self, kind: u8, payload: &[u8]) -> Result<(), RelayError> {
+        let Some(session) = &mut self.session else {
+            return write_frame(&mut self.stream, kind, payload);
+        };
+
+        let mac = session.mac(session.role, session.sent, kind, payload);
+        session.sent += 1;
+
+        let mut frame = payload.to_vec();
+        frame.extend_from_slice(&mac.finalize().into_bytes());
+        write_frame(&mut self.stream, kind, &frame)
+    }
+
+    /// Reads one frame, or `None` if the stream ended between frames.
+    fn receive(&mut self) -> Result<Option<(u8, Vec<u8>)>, RelayError> {
+        let Some((kind, mut payload)) = read_frame(&mut self.stream)? else {
+            return Ok(None);
+        };
+
+        let Some(session) = &mut self.session else {
+            return Ok(Some((kind, payload)));
+        };
+
+        let length = payload
+            .len()
+            .checked_sub(MAC_LENGTH)
+            .ok_or(RelayError::InvalidMac)?;
+        let sender = match session.role {
+            Role::Client => Role::Server,
+            Role::Server => Role::Client,
+        };
+
+        session
+            .mac(sender, session.received, kind, &payload[..length])
+            .verify_slice(&payload[length..])
+            .map_err(|_| RelayError::InvalidMac)?;
+        session.received += 1;
+
+        payload.truncate(length);
+        Ok(Some((kind, payload)))
+    }
+}
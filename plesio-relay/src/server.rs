@@ -0,0 +1,253 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex, TryLockError},
+    thread,
+    time::Duration,
+};
+
+use hmac::Mac;
+use plesio_core::apdu::{
+    blocking::BlockingTransport, encoding, owned::OwnedCommand, transport::TransportError,
+};
+
+use crate::{CHALLENGE_LENGTH, Channel, RelayError, Role, authentication_tag, kind, session_key};
+
+type ResetHandler<T> = Box<dyn FnMut(&mut T) -> Result<(), String> + Send>;
+
+/// Serves a local [`BlockingTransport`] to
+/// [`RelayClient`](crate::client::RelayClient)s, one session at a time.
+///
+/// Each command is executed on the session's thread, which blocks until the
+/// card replies.
+///
+/// ISO 7816 state such as the selected applet and security status lives on
+/// the card, so only one client may hold a session; others are turned away
+/// as busy until it disconnects. A client claims the card only once it has
+/// authenticated, so a connection that never does cannot lock others out.
+pub struct RelayServer<T> {
+    transport: T,
+    atr: Option<Vec<u8>>,
+    reset: Option<ResetHandler<T>>,
+    key: Option<Vec<u8>>,
+    handshake_timeout: Duration,
+}
+
+impl<T: BlockingTransport> RelayServer<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            atr: None,
+            reset: None,
+            key: None,
+            handshake_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// The ATR reported to clients. Without one, ATR queries fail.
+    pub fn with_atr(mut self, atr: &[u8]) -> Self {
+        self.atr = Some(atr.to_vec());
+        self
+    }
+
+    /// Handles reset requests. Without a handler, reset requests fail.
+    pub fn with_reset(
+        mut self,
+        reset: impl FnMut(&mut T) -> Result<(), String> + Send + 'static,
+    ) -> Self {
+        self.reset = Some(Box::new(reset));
+        self
+    }
+
+    /// Requires clients to prove knowledge of `key` before they may send
+    /// anything, and authenticates every frame of the session under a key
+    /// derived from it.
+    pub fn with_key(mut self, key: &[u8]) -> Self {
+        self.key = Some(key.to_vec());
+        self
+    }
+
+    /// How long [`listen`](Self::listen) waits for a client to
+    /// authenticate before dropping it. Ten seconds by default.
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+
+    /// Serves a single session over `stream` until the client disconnects.
+    pub fn serve(&mut self, stream: impl Read + Write) -> Result<(), RelayError> {
+        let channel = handshake(
+            stream,
+            self.key.as_deref(),
+            self.transport.max_payload_size(),
+        )?;
+        self.run(channel)
+    }
+
+    fn run(&mut self, mut channel: Channel<impl Read + Write>) -> Result<(), RelayError> {
+        channel.send(kind::OK, &[])?;
+
+        while let Some((kind, payload)) = channel.receive()? {
+            let (kind, payload) = self.handle(kind, &payload);
+            channel.send(kind, &payload)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, request: u8, payload: &[u8]) -> (u8, Vec<u8>) {
+        match request {
+            kind::COMMAND => self.execute(payload),
+            kind::GET_ATR => match &self.atr {
+                Some(atr) => (kind::ATR, atr.clone()),
+                None => (kind::ERROR, b"ATR not available".to_vec()),
+            },
+            kind::RESET => match &mut self.reset {
+                Some(reset) => match reset(&mut self.transport) {
+                    Ok(()) => (kind::OK, Vec::new()),
+                    Err(message) => (kind::ERROR, message.into_bytes()),
+                },
+                None => (kind::ERROR, b"reset not supported".to_vec()),
+            },
+            kind => (
+                kind::ERROR,
+                format!("unsupported request {kind:#04X}").into_bytes(),
+            ),
+        }
+    }
+
+    fn execute(&mut self, encoded: &[u8]) -> (u8, Vec<u8>) {
+        let Some(decoded) = encoding::decode(encoded) else {
+            return (kind::ERROR, b"malformed command APDU".to_vec());
+        };
+
+        let [class, instruction, p1, p2] = decoded.header;
+        let command = OwnedCommand::new(class, instruction, (p1, p2), decoded.data.to_vec());
+        let mut reply = vec![0u8; decoded.expected_length + 2];
+
+        match self.transport.execute(command, &mut reply) {
+            Ok(response) => {
+                let length = response.data().len() + 2;
+                reply.truncate(length);
+                (kind::RESPONSE, reply)
+            }
+            Err(error) => match error.is_payload_too_large() {
+                Some(too_large) => (
+                    kind::PAYLOAD_TOO_LARGE,
+                    u32::try_from(too_large.max_size)
+                        .unwrap_or(u32::MAX)
+                        .to_be_bytes()
+                        .to_vec(),
+                ),
+                None => (kind::ERROR, format!("{error:?}").into_bytes()),
+            },
+        }
+    }
+}
+
+impl<T: BlockingTransport + Send + 'static> RelayServer<T> {
+    /// Accepts connections on `listener` forever, serving each on its own
+    /// thread while turning away clients that arrive during a session.
+    pub fn listen(self, listener: TcpListener) -> Result<(), RelayError> {
+        let key: Option<Arc<[u8]>> = self.key.as_deref().map(Arc::from);
+        let max_payload_size = self.transport.max_payload_size();
+        let timeout = self.handshake_timeout;
+        let server = Arc::new(Mutex::new(self));
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = server.clone();
+            let key = key.clone();
+
+            thread::spawn(move || {
+                Self::session(&server, key.as_deref(), max_payload_size, timeout, stream)
+            });
+        }
+
+        Ok(())
+    }
+
+    fn session(
+        server: &Mutex<Self>,
+        key: Option<&[u8]>,
+        max_payload_size: usize,
+        timeout: Duration,
+        stream: TcpStream,
+    ) -> Result<(), RelayError> {
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(timeout))?;
+
+        let mut channel = handshake(stream, key, max_payload_size)?;
+        channel.stream.set_read_timeout(None)?;
+
+        let mut server = match server.try_lock() {
+            Ok(server) => server,
+            // A session that panicked leaves the card in an unknown state,
+            // but it is still the best we have.
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return channel.send(kind::BUSY, &[]),
+        };
+
+        server.run(channel)
+    }
+}
+
+/// Welcomes a client and checks its answer, without touching the card.
+fn handshake<S: Read + Write>(
+    stream: S,
+    key: Option<&[u8]>,
+    max_payload_size: usize,
+) -> Result<Channel<S>, RelayError> {
+    let mut channel = Channel::new(stream);
+
+    let max_payload_size = u32::try_from(max_payload_size).unwrap_or(u32::MAX);
+    let mut welcome = max_payload_size.to_be_bytes().to_vec();
+
+    let mut challenge = [0u8; CHALLENGE_LENGTH];
+    if key.is_some() {
+        getrandom::fill(&mut challenge).map_err(|error| RelayError::Remote(error.to_string()))?;
+        welcome.extend_from_slice(&challenge);
+    }
+
+    channel.send(kind::WELCOME, &welcome)?;
+
+    let answer = match channel.receive()? {
+        Some((kind::AUTHENTICATE, answer)) => answer,
+        Some((kind, _)) => return Err(RelayError::UnexpectedFrame { kind }),
+        None => return Err(RelayError::Closed),
+    };
+
+    let authenticated = match key {
+        Some(key) => match answer.split_first_chunk::<CHALLENGE_LENGTH>() {
+            Some((nonce, tag))
+                if authentication_tag(key, &challenge, nonce)
+                    .verify_slice(tag)
+                    .is_ok() =>
+            {
+                channel.start_session(session_key(key, &challenge, nonce), Role::Server);
+                true
+            }
+            _ => false,
+        },
+        None => answer.is_empty(),
+    };
+
+    if !authenticated {
+        channel.send(kind::ERROR, b"authentication failed")?;
+        return Err(RelayError::AuthenticationFailed);
+    }
+
+    Ok(channel)
+}
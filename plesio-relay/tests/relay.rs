@@ -0,0 +1,214 @@
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use plesio_core::{
+    apdu::{
+        blocking::block_on,
+        iso_7816::{operation::Iso7816Command, status, transport::Iso7816Transport},
+        mock::MockTransport,
+    },
+    card::{file::FileSystem, virtual_card::VirtualCard},
+};
+use plesio_relay::{RelayError, client::RelayClient, server::RelayServer};
+
+const KEY: &[u8] = b"correct horse battery staple";
+
+/// Serves a single session with a virtual card holding one EF, 3F00/0101.
+fn spawn_card_server(
+    key: Option<&'static [u8]>,
+) -> (SocketAddr, JoinHandle<Result<(), RelayError>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+        let mut files = FileSystem::new();
+        files.add_elementary(files.master_file(), 0x0101, &[0u8; 400]);
+
+        let card = VirtualCard::new(files);
+        let atr = card.atr().to_vec();
+        let mut server =
+            RelayServer::new(card)
+                .with_atr(&atr)
+                .with_reset(|card: &mut VirtualCard| {
+                    card.reset();
+                    Ok(())
+                });
+
+        if let Some(key) = key {
+            server = server.with_key(key);
+        }
+
+        server.serve(listener.accept()?.0)
+    });
+
+    (address, server)
+}
+
+#[test]
+fn relays_commands_to_remote_card() {
+    let (address, server) = spawn_card_server(Some(KEY));
+
+    let mut client = RelayClient::connect(address, Some(KEY)).unwrap();
+    assert_eq!(client.atr().unwrap(), &[0x3B, 0x80, 0x80, 0x01, 0x01]);
+    assert_eq!(
+        client
+            .transmit(&[0x00, 0xA4, 0x00, 0x0C, 0x02, 0x01, 0x01])
+            .unwrap(),
+        &[0x90, 0x00]
+    );
+
+    let mut transport = Iso7816Transport::new(client.into_async());
+    let class = transport.class();
+
+    // 300 bytes exceed the card's short APDU limit, so the command is
+    // rejected remotely and resent as a chain.
+    let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+    let update = Iso7816Command::new(class, 0xD6, (0x00, 0x10), &data);
+    let response = block_on(transport.execute_owned(update)).unwrap();
+    assert_eq!(response.status(), status::OK);
+
+    let read = Iso7816Command::new(class, 0xB0, (0x00, 0x10), &[]);
+    let response = block_on(transport.execute_owned(read)).unwrap();
+    assert_eq!(response.data(), &data[..256]);
+
+    let mut client = transport.into_inner().into_inner();
+    client.reset().unwrap();
+    assert_eq!(
+        client.transmit(&[0x00, 0xB0, 0x00, 0x00, 0x00]).unwrap(),
+        &[0x69, 0x86]
+    );
+
+    drop(client);
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn rejects_client_with_wrong_key() {
+    let (address, server) = spawn_card_server(Some(KEY));
+
+    let error = RelayClient::connect(address, Some(b"wrong")).err().unwrap();
+    assert!(matches!(error, RelayError::AuthenticationFailed));
+
+    let error = server.join().unwrap().unwrap_err();
+    assert!(matches!(error, RelayError::AuthenticationFailed));
+}
+
+#[test]
+fn reports_unsupported_requests_as_remote_errors() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+        RelayServer::new(MockTransport::new()).serve(listener.accept().unwrap().0)
+    });
+
+    let mut client = RelayClient::connect(address, None).unwrap();
+    assert!(matches!(client.atr(), Err(RelayError::Remote(_))));
+    assert!(matches!(client.reset(), Err(RelayError::Remote(_))));
+    assert!(matches!(
+        client.transmit(&[0x00, 0xA4]),
+        Err(RelayError::Remote(_))
+    ));
+
+    drop(client);
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn allows_one_session_at_a_time() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    thread::spawn(move || RelayServer::new(MockTransport::new()).listen(listener));
+
+    let first = RelayClient::connect(address, None).unwrap();
+    let error = RelayClient::connect(address, None).err().unwrap();
+    assert!(matches!(error, RelayError::Busy));
+
+    drop(first);
+
+    // The session ends once the server notices the disconnect.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        match RelayClient::connect(address, None) {
+            Ok(_) => break,
+            Err(RelayError::Busy) if Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(10))
+            }
+            Err(error) => panic!("reconnecting failed: {error}"),
+        }
+    }
+}
+
+#[test]
+fn claims_the_card_only_after_authentication() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        RelayServer::new(MockTransport::new())
+            .with_key(KEY)
+            .with_handshake_timeout(Duration::from_millis(200))
+            .listen(listener)
+    });
+
+    // A client that never authenticates does not keep others out...
+    let mut silent = TcpStream::connect(address).unwrap();
+    let first = RelayClient::connect(address, Some(KEY)).unwrap();
+    assert!(matches!(
+        RelayClient::connect(address, Some(KEY)),
+        Err(RelayError::Busy)
+    ));
+    drop(first);
+
+    // ...and is dropped once the handshake times out.
+    silent
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut welcome = Vec::new();
+    silent.read_to_end(&mut welcome).unwrap();
+    assert_eq!(welcome[0], 0x80);
+}
+
+/// Flips the last byte of the first command frame written through it.
+struct Tampering(TcpStream);
+
+impl Read for Tampering {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for Tampering {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match buf.first() {
+            Some(0x01) => {
+                let mut frame = buf.to_vec();
+                *frame.last_mut().unwrap() ^= 0x01;
+                self.0.write_all(&frame)?;
+                Ok(buf.len())
+            }
+            _ => self.0.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[test]
+fn rejects_tampered_frames() {
+    let (address, server) = spawn_card_server(Some(KEY));
+
+    let stream = Tampering(TcpStream::connect(address).unwrap());
+    let mut client = RelayClient::new(stream, Some(KEY)).unwrap();
+    assert!(client.transmit(&[0x00, 0xB0, 0x00, 0x00, 0x00]).is_err());
+
+    let error = server.join().unwrap().unwrap_err();
+    assert!(matches!(error, RelayError::InvalidMac));
+}
@@ -0,0 +1,127 @@
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+use plesio_core::card::host::SimulatedCard;
+
+/// The port vpcd listens on for the first reader slot.
+pub const DEFAULT_PORT: u16 = 35963;
+
+const POWER_OFF: u8 = 0x00;
+const POWER_ON: u8 = 0x01;
+const RESET: u8 = 0x02;
+const GET_ATR: u8 = 0x04;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VpcdError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("response of {length} bytes does not fit in a vpcd frame")]
+    FrameTooLarge { length: usize },
+}
+
+/// Connects a [`SimulatedCard`] to vpcd, the virtual reader driver of
+/// vsmartcard, so that PC/SC applications can talk to it as if it were
+/// inserted in a reader.
+///
+/// vpcd sends frames with a two byte big-endian length prefix. A single
+/// byte frame is a control code for power off, power on, reset or an ATR
+/// request; anything longer is a command APDU. Commands and ATR requests are
+/// answered with a frame of the same shape, control codes are not.
+pub struct VpcdClient<C> {
+    card: C,
+    powered: bool,
+}
+
+impl<C: SimulatedCard> VpcdClient<C> {
+    pub fn new(card: C) -> Self {
+        Self {
+            card,
+            powered: false,
+        }
+    }
+
+    pub fn card(&self) -> &C {
+        &self.card
+    }
+
+    pub fn card_mut(&mut self) -> &mut C {
+        &mut self.card
+    }
+
+    pub fn into_card(self) -> C {
+        self.card
+    }
+
+    /// Whether vpcd last powered the card on.
+    pub fn is_powered(&self) -> bool {
+        self.powered
+    }
+
+    /// Connects to vpcd at `address` and serves it until it disconnects.
+    pub fn connect(&mut self, address: impl ToSocketAddrs) -> Result<(), VpcdError> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+
+        self.serve(stream)
+    }
+
+    /// Serves frames read from `stream` until it is closed.
+    pub fn serve(&mut self, mut stream: impl Read + Write) -> Result<(), VpcdError> {
+        while let Some(frame) = read_frame(&mut stream)? {
+            if let Some(reply) = self.handle(&frame) {
+                let length = u16::try_from(reply.len()).map_err(|_| VpcdError::FrameTooLarge {
+                    length: reply.len(),
+                })?;
+
+                stream.write_all(&length.to_be_bytes())?;
+                stream.write_all(&reply)?;
+                stream.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles the payload of one frame, returning the payload to reply
+    /// with, if any.
+    pub fn handle(&mut self, frame: &[u8]) -> Option<Vec<u8>> {
+        match *frame {
+            [POWER_OFF] => {
+                self.powered = false;
+                None
+            }
+            [POWER_ON] => {
+                self.card.reset();
+                self.powered = true;
+                None
+            }
+            [RESET] => {
+                self.card.reset();
+                None
+            }
+            [GET_ATR] => Some(self.card.atr().to_vec()),
+            // Unknown control codes are ignored, as vicc does.
+            [_] => None,
+            _ => Some(self.card.transmit(frame)),
+        }
+    }
+}
+
+/// Reads one length-prefixed frame, or `None` if the stream ended between
+/// frames.
+fn read_frame(stream: &mut impl Read) -> Result<Option<Vec<u8>>, VpcdError> {
+    let mut length = [0u8; 2];
+
+    match stream.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error.into()),
+    }
+
+    let mut frame = vec![0u8; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut frame)?;
+
+    Ok(Some(frame))
+}
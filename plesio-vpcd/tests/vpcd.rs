@@ -0,0 +1,88 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use plesio_core::{
+    apdu::{iso_7816::status, status::ApduStatus},
+    card::{applet::Applet, command::CardCommand, host::AppletHost},
+};
+use plesio_vpcd::VpcdClient;
+
+const AID: &[u8] = &[0xF0, 0x01, 0x02, 0x03, 0x04];
+
+struct Echo;
+
+impl Applet for Echo {
+    fn process(&mut self, command: &CardCommand<'_>, response: &mut Vec<u8>) -> ApduStatus {
+        response.extend_from_slice(command.data());
+        status::OK
+    }
+}
+
+/// The vpcd side of the connection.
+struct Reader(TcpStream);
+
+impl Reader {
+    fn send(&mut self, frame: &[u8]) {
+        self.0
+            .write_all(&(frame.len() as u16).to_be_bytes())
+            .unwrap();
+        self.0.write_all(frame).unwrap();
+    }
+
+    fn receive(&mut self) -> Vec<u8> {
+        let mut length = [0u8; 2];
+        self.0.read_exact(&mut length).unwrap();
+
+        let mut frame = vec![0u8; u16::from_be_bytes(length) as usize];
+        self.0.read_exact(&mut frame).unwrap();
+        frame
+    }
+}
+
+#[test]
+fn serves_vpcd_over_loopback() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut client = VpcdClient::new(AppletHost::new(AID, Echo));
+        client.connect(address).map(|()| client.is_powered())
+    });
+
+    let mut reader = Reader(listener.accept().unwrap().0);
+
+    reader.send(&[0x01]);
+    reader.send(&[0x04]);
+    assert_eq!(reader.receive(), &[0x3B, 0x80, 0x80, 0x01, 0x01]);
+
+    reader.send(&[0x80, 0x10, 0x00, 0x00, 0x02, 0xCA, 0xFE, 0x00]);
+    assert_eq!(reader.receive(), &[0xCA, 0xFE, 0x90, 0x00]);
+
+    reader.send(&[0x80, 0x10]);
+    assert_eq!(reader.receive(), &[0x67, 0x00]);
+
+    reader.send(&[0x02]);
+    reader.send(&[0x00]);
+    drop(reader);
+
+    let powered = client.join().unwrap().unwrap();
+    assert!(!powered);
+}
+
+#[test]
+fn handles_control_codes_without_reply() {
+    let mut client = VpcdClient::new(AppletHost::new(AID, Echo));
+
+    assert_eq!(client.handle(&[0x01]), None);
+    assert!(client.is_powered());
+    assert_eq!(client.handle(&[0x02]), None);
+    assert_eq!(client.handle(&[0x00]), None);
+    assert!(!client.is_powered());
+    assert_eq!(
+        client.handle(&[0x04]).as_deref(),
+        Some(&[0x3B, 0x80, 0x80, 0x01, 0x01][..])
+    );
+}